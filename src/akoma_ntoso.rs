@@ -0,0 +1,267 @@
+//! 既存の出力をAkoma Ntoso（OASIS LegalDocML）の判決文書にする`export-akoma-ntoso`サブコマンド
+//!
+//! 裁判例ごとに`{dir}/{ファイル名}.xml`を書き出す。要素の対応は次のとおり。
+//!
+//! - `<meta>`の`<identification>`：FRBRの作品・表現・体現形のIRIと裁判年月日・裁判所・事件番号
+//! - `<meta>`の`<references>`：裁判所と、このツールを`TLCOrganization`として置く
+//! - `<meta>`の`<proprietary>`：`lawsuit_id`・詳細ページや全文PDFのリンクなど、Akoma Ntosoに対応する要素が無いもの。
+//!   スキーマに合わせて[`TOOL_NAMESPACE`]の名前空間（接頭辞`lp`）の要素にする
+//! - `<header>`：事件番号・事件名・裁判所名・裁判年月日
+//! - `<judgmentBody>`：判示事項の要旨と裁判要旨を`<introduction>`に、判決文の本文を行ごとに`<motivation>`の段落にする
+//!
+//! IRIは`/akn/jp/judgment/{trial_type}/{裁判年月日}/{lawsuit_id}`とする。
+//! 月日が分からない裁判年月日は、XMLの日付の型に合わせて足りない部分を1として書く。
+//! 西暦に直せない元号の裁判例は日付を書けないので、警告を出して書き出さない。
+
+use crate::{dataset, permissions};
+use anyhow::Result;
+use jplaw_data_types::listup::PrecedentData;
+use listup_precedent_lib::date::{Date, OrdDate};
+use serde_json::Value;
+use std::fmt::Write;
+use tracing::*;
+
+/// Akoma Ntoso 3.0の名前空間
+pub const NAMESPACE: &str = "http://docs.oasis-open.org/legaldocml/ns/akn/3.0";
+
+const SOURCE: &str = "listup_precedent";
+
+/// `<proprietary>`の中の要素の名前空間
+pub const TOOL_NAMESPACE: &str = "https://github.com/japanese-law-analysis/listup_precedent";
+
+/// XMLの文字データ・属性値として書けるようにする
+fn escape(s: &str) -> String {
+  let mut escaped = String::with_capacity(s.len());
+  for c in s.chars() {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&apos;"),
+      // XML 1.0で書けない制御文字は落とす
+      c if (c as u32) < 0x20 && !matches!(c, '\t' | '\n' | '\r') => {}
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+/// `xsd:date`の形の日付。扱えない元号では`None`
+fn iso_date(date: &Date) -> Option<String> {
+  let date = OrdDate::of(date)?;
+  Some(format!(
+    "{:04}-{:02}-{:02}",
+    date.year,
+    date.month.max(1),
+    date.day.max(1)
+  ))
+}
+
+/// 1件の裁判例をAkoma Ntosoの文書にする。`date`は裁判年月日を[`iso_date`]にしたもの
+fn to_xml(trial_type: &str, data: &PrecedentData, date: &str) -> Result<String> {
+  let work = format!(
+    "/akn/jp/judgment/{}/{}/{}",
+    escape(&trial_type.to_lowercase()),
+    date,
+    escape(&data.lawsuit_id)
+  );
+  let expression = format!("{work}/jpn@");
+  let generated = chrono::Utc::now().format("%Y-%m-%d");
+  let case_number = escape(&data.case_number);
+  let case_name = escape(&data.case_name);
+  let court_name = escape(&data.court_name);
+
+  let mut xml = String::new();
+  writeln!(xml, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+  writeln!(xml, r#"<akomaNtoso xmlns="{NAMESPACE}">"#)?;
+  writeln!(xml, r#"  <judgment name="{}">"#, escape(trial_type))?;
+  writeln!(xml, "    <meta>")?;
+  writeln!(xml, r##"      <identification source="#{SOURCE}">"##)?;
+  writeln!(xml, "        <FRBRWork>")?;
+  writeln!(xml, r#"          <FRBRthis value="{work}/!main"/>"#)?;
+  writeln!(xml, r#"          <FRBRuri value="{work}"/>"#)?;
+  writeln!(
+    xml,
+    r#"          <FRBRdate date="{date}" name="judgment"/>"#
+  )?;
+  writeln!(xml, r##"          <FRBRauthor href="#court"/>"##)?;
+  writeln!(xml, r#"          <FRBRcountry value="jp"/>"#)?;
+  writeln!(xml, r#"          <FRBRnumber value="{case_number}"/>"#)?;
+  writeln!(xml, r#"          <FRBRname value="{case_name}"/>"#)?;
+  writeln!(xml, "        </FRBRWork>")?;
+  writeln!(xml, "        <FRBRExpression>")?;
+  writeln!(xml, r#"          <FRBRthis value="{expression}/!main"/>"#)?;
+  writeln!(xml, r#"          <FRBRuri value="{expression}"/>"#)?;
+  writeln!(
+    xml,
+    r#"          <FRBRdate date="{date}" name="judgment"/>"#
+  )?;
+  writeln!(xml, r##"          <FRBRauthor href="#court"/>"##)?;
+  writeln!(xml, r#"          <FRBRlanguage language="jpn"/>"#)?;
+  writeln!(xml, "        </FRBRExpression>")?;
+  writeln!(xml, "        <FRBRManifestation>")?;
+  writeln!(
+    xml,
+    r#"          <FRBRthis value="{expression}/!main.xml"/>"#
+  )?;
+  writeln!(xml, r#"          <FRBRuri value="{expression}.akn"/>"#)?;
+  writeln!(
+    xml,
+    r#"          <FRBRdate date="{generated}" name="generation"/>"#
+  )?;
+  writeln!(xml, r##"          <FRBRauthor href="#{SOURCE}"/>"##)?;
+  writeln!(xml, "        </FRBRManifestation>")?;
+  writeln!(xml, "      </identification>")?;
+  writeln!(xml, r##"      <references source="#{SOURCE}">"##)?;
+  writeln!(
+    xml,
+    r#"        <TLCOrganization eId="court" href="/ontology/organization/jp/{}" showAs="{court_name}"/>"#,
+    escape(&data.court_name.replace(' ', "_"))
+  )?;
+  writeln!(
+    xml,
+    r#"        <TLCOrganization eId="{SOURCE}" href="{TOOL_NAMESPACE}" showAs="{SOURCE}"/>"#
+  )?;
+  writeln!(xml, "      </references>")?;
+  writeln!(
+    xml,
+    r##"      <proprietary source="#{SOURCE}" xmlns:lp="{TOOL_NAMESPACE}">"##
+  )?;
+  let trial_type = trial_type.to_string();
+  let proprietary = [
+    ("lawsuitId", Some(&data.lawsuit_id)),
+    ("trialType", Some(&trial_type)),
+    ("detailPageLink", Some(&data.detail_page_link)),
+    ("fullPdfLink", Some(&data.full_pdf_link)),
+    ("rightType", data.right_type.as_ref()),
+    ("lawsuitType", data.lawsuit_type.as_ref()),
+    ("resultType", data.result_type.as_ref()),
+    ("result", data.result.as_ref()),
+    ("articleInfo", data.article_info.as_ref()),
+    ("field", data.field.as_ref()),
+    ("refLaw", data.ref_law.as_ref()),
+    ("originalCourtName", data.original_court_name.as_ref()),
+    ("originalCaseNumber", data.original_case_number.as_ref()),
+    ("originalResult", data.original_result.as_ref()),
+  ];
+  for (name, value) in proprietary {
+    if let Some(value) = value {
+      writeln!(xml, "        <lp:{name}>{}</lp:{name}>", escape(value))?;
+    }
+  }
+  if let Some(original_date) = data.original_date.as_ref().and_then(iso_date) {
+    writeln!(
+      xml,
+      "        <lp:originalDate>{original_date}</lp:originalDate>"
+    )?;
+  }
+  writeln!(xml, "      </proprietary>")?;
+  writeln!(xml, "    </meta>")?;
+  writeln!(xml, "    <header>")?;
+  writeln!(xml, "      <p><docNumber>{case_number}</docNumber></p>")?;
+  writeln!(xml, "      <p><docTitle>{case_name}</docTitle></p>")?;
+  writeln!(
+    xml,
+    r##"      <p><courtType refersTo="#court">{court_name}</courtType></p>"##
+  )?;
+  writeln!(
+    xml,
+    r#"      <p><docDate date="{date}">{date}</docDate></p>"#
+  )?;
+  writeln!(xml, "    </header>")?;
+  writeln!(xml, "    <judgmentBody>")?;
+  let summaries = [("gist", &data.gist), ("caseGist", &data.case_gist)];
+  if summaries.iter().any(|(_, s)| s.is_some()) {
+    writeln!(xml, "      <introduction>")?;
+    for (class, summary) in summaries {
+      if let Some(summary) = summary {
+        writeln!(xml, r#"        <p class="{class}">{}</p>"#, escape(summary))?;
+      }
+    }
+    writeln!(xml, "      </introduction>")?;
+  }
+  writeln!(xml, "      <motivation>")?;
+  let lines = data
+    .contents
+    .iter()
+    .flat_map(|contents| contents.lines())
+    .map(str::trim)
+    .filter(|line| !line.is_empty())
+    .collect::<Vec<_>>();
+  if lines.is_empty() {
+    // 本文の無い裁判例でも、`judgmentBody`が空にならないようにする
+    writeln!(xml, "        <p/>")?;
+  }
+  for line in lines {
+    writeln!(xml, "        <p>{}</p>", escape(line))?;
+  }
+  writeln!(xml, "      </motivation>")?;
+  writeln!(xml, "    </judgmentBody>")?;
+  writeln!(xml, "  </judgment>")?;
+  writeln!(xml, "</akomaNtoso>")?;
+  Ok(xml)
+}
+
+/// `index`の一覧と`output`の裁判例のJSONを、`dir`に1件ずつAkoma NtosoのXMLで書き出す。書き出した件数を返す
+pub async fn export(index: &str, output: &str, dir: &str) -> Result<usize> {
+  let rows = dataset::load_dataset(index, output).await?;
+  tokio::fs::create_dir_all(dir).await?;
+  let mut written = 0;
+  for row in &rows {
+    let Some(data) = &row.data else {
+      continue;
+    };
+    let trial_type = row
+      .entry
+      .get("trial_type")
+      .and_then(Value::as_str)
+      .unwrap_or_default();
+    let Some(date) = iso_date(&data.date) else {
+      warn!(
+        "akoma ntoso: skipped {} (the era of the date is not supported)",
+        data.lawsuit_id
+      );
+      continue;
+    };
+    let path = format!(
+      "{dir}/{}.xml",
+      crate::partition::record_name(&row.info).replace('/', "_")
+    );
+    tokio::fs::write(&path, to_xml(trial_type, data, &date)?).await?;
+    permissions::apply(&path).await?;
+    written += 1;
+  }
+  info!("akoma ntoso: {} ({} documents)", dir, written);
+  Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use listup_precedent_lib::examples;
+
+  #[test]
+  fn sample_document() {
+    let mut data = examples::sample_data();
+    data.case_name = "損害賠償請求事件<A&B>".to_string();
+    let date = iso_date(&data.date).unwrap();
+    assert_eq!(date, "2022-03-24");
+    let xml = to_xml("SupremeCourt", &data, &date).unwrap();
+
+    assert!(xml.contains(&format!(r#"<akomaNtoso xmlns="{NAMESPACE}">"#)));
+    assert!(xml.contains(&format!(r#"xmlns:lp="{TOOL_NAMESPACE}""#)));
+    assert!(xml.contains("<lp:lawsuitId>99999</lp:lawsuitId>"));
+    assert!(xml.contains("<lp:originalDate>2021-01-28</lp:originalDate>"));
+    assert!(!xml.contains("<lawsuitId>"));
+    assert!(xml.contains(r#"<FRBRdate date="2022-03-24" name="judgment"/>"#));
+    assert!(xml.contains("<p><docTitle>損害賠償請求事件&lt;A&amp;B&gt;</docTitle></p>"));
+    assert!(xml.contains(r#"<FRBRname value="損害賠償請求事件&lt;A&amp;B&gt;"/>"#));
+  }
+
+  #[test]
+  fn escapes_markup_and_control_characters() {
+    assert_eq!(escape(r#"a<b>&"c'd"#), "a&lt;b&gt;&amp;&quot;c&apos;d");
+    assert_eq!(escape("甲\u{0}乙\n"), "甲乙\n");
+  }
+}
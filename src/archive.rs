@@ -0,0 +1,20 @@
+//! 判決文のPDFが消えてしまったときに、アーカイブサービスから代わりに取得するためのURL生成
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PdfFallback {
+  /// Internet ArchiveのWayback Machine
+  Wayback,
+}
+
+impl PdfFallback {
+  /// 元のURLに対応するアーカイブ上のURL
+  pub fn archive_url(&self, url: &str) -> String {
+    match self {
+      // タイムスタンプに`2`を与えると最も近いスナップショットにリダイレクトされ、
+      // `id_`を付けるとWayback Machineによる書き換えの無い元のファイルが返る
+      PdfFallback::Wayback => format!("https://web.archive.org/web/2id_/{url}"),
+    }
+  }
+}
@@ -0,0 +1,196 @@
+//! 一覧ファイルと裁判例のデータのApache Arrow IPC（Feather v2）形式での出力
+//!
+//! PythonのpyarrowやRのarrowパッケージからゼロコピーで読み込めるように、
+//! 一覧ファイルの各項目を1行とする表にして書き出す。裁判年月日は`Date32`型の`date`列にし、
+//! 元号・元号の年・西暦の年・月・日の列も残す。`trial_type`と`era`は値の種類が少ないので辞書型にする。
+//!
+//! `export-arrow`サブコマンドでは、一覧の列に裁判例のJSONの事件名・要旨・本文などの列を加えたデータセットを書き出す。
+
+use crate::{
+  compat,
+  dataset::{self, DatasetRow},
+  output, permissions,
+};
+use anyhow::{anyhow, Result};
+use arrow::{
+  array::{ArrayRef, Date32Builder, StringBuilder, StringDictionaryBuilder, UInt32Builder},
+  datatypes::{DataType, Field, Int8Type, Schema},
+  ipc::writer::FileWriter,
+  record_batch::RecordBatch,
+};
+use chrono::NaiveDate;
+use jplaw_data_types::{law::Date, listup::PrecedentData};
+use serde_json::Value;
+use std::{fs::File, sync::Arc};
+use tracing::*;
+
+fn dictionary_type() -> DataType {
+  DataType::Dictionary(Box::new(DataType::Int8), Box::new(DataType::Utf8))
+}
+
+fn schema() -> Schema {
+  Schema::new(vec![
+    Field::new("lawsuit_id", DataType::Utf8, false),
+    Field::new("uuid", DataType::Utf8, true),
+    Field::new("case_number", DataType::Utf8, false),
+    Field::new("court_name", DataType::Utf8, false),
+    Field::new("trial_type", dictionary_type(), false),
+    Field::new("date", DataType::Date32, true),
+    Field::new("era", dictionary_type(), false),
+    Field::new("era_year", DataType::UInt32, false),
+    Field::new("year", DataType::UInt32, false),
+    Field::new("month", DataType::UInt32, true),
+    Field::new("day", DataType::UInt32, true),
+  ])
+}
+
+/// 文字列のフィールドはそのまま、それ以外はJSONとして文字列にする
+fn field_text(value: &Value) -> String {
+  match value {
+    Value::String(s) => s.clone(),
+    v => v.to_string(),
+  }
+}
+
+fn str_field(entry: &Value, key: &str) -> Result<String> {
+  entry
+    .get(key)
+    .map(field_text)
+    .ok_or_else(|| anyhow!("一覧の項目に{key}がありません：{entry}"))
+}
+
+/// 1970年1月1日からの日数。月日が無いか日付として正しくなければ`None`
+fn days_since_epoch(year: usize, month: Option<usize>, day: Option<usize>) -> Option<i32> {
+  let date = NaiveDate::from_ymd_opt(year as i32, month? as u32, day? as u32)?;
+  let epoch = NaiveDate::from_ymd_opt(1970, 1, 1)?;
+  Some((date - epoch).num_days() as i32)
+}
+
+/// 一覧の項目を表にする
+pub fn to_record_batch(entries: &[Value]) -> Result<RecordBatch> {
+  let mut lawsuit_id = StringBuilder::new();
+  let mut uuid = StringBuilder::new();
+  let mut case_number = StringBuilder::new();
+  let mut court_name = StringBuilder::new();
+  let mut trial_type = StringDictionaryBuilder::<Int8Type>::new();
+  let mut date_days = Date32Builder::new();
+  let mut era = StringDictionaryBuilder::<Int8Type>::new();
+  let mut era_year = UInt32Builder::new();
+  let mut year = UInt32Builder::new();
+  let mut month = UInt32Builder::new();
+  let mut day = UInt32Builder::new();
+  for entry in entries {
+    let date: Date = serde_json::from_value(
+      entry
+        .get("date")
+        .cloned()
+        .ok_or_else(|| anyhow!("一覧の項目にdateがありません：{entry}"))?,
+    )?;
+    let ad_year = compat::era_to_ad_year(&date.era, date.year);
+    lawsuit_id.append_value(str_field(entry, "lawsuit_id")?);
+    uuid.append_option(entry.get("uuid").and_then(|v| v.as_str()));
+    case_number.append_value(str_field(entry, "case_number")?);
+    court_name.append_value(str_field(entry, "court_name")?);
+    trial_type.append(str_field(entry, "trial_type")?)?;
+    date_days.append_option(days_since_epoch(ad_year, date.month, date.day));
+    era.append(field_text(&serde_json::to_value(&date.era)?))?;
+    era_year.append_value(date.year as u32);
+    year.append_value(ad_year as u32);
+    month.append_option(date.month.map(|m| m as u32));
+    day.append_option(date.day.map(|d| d as u32));
+  }
+  let columns: Vec<ArrayRef> = vec![
+    Arc::new(lawsuit_id.finish()),
+    Arc::new(uuid.finish()),
+    Arc::new(case_number.finish()),
+    Arc::new(court_name.finish()),
+    Arc::new(trial_type.finish()),
+    Arc::new(date_days.finish()),
+    Arc::new(era.finish()),
+    Arc::new(era_year.finish()),
+    Arc::new(year.finish()),
+    Arc::new(month.finish()),
+    Arc::new(day.finish()),
+  ];
+  Ok(RecordBatch::try_new(Arc::new(schema()), columns)?)
+}
+
+/// 一覧の列に加える、裁判例のJSONから取る列
+const DATA_COLUMNS: &[&str] = &[
+  "case_name",
+  "lawsuit_type",
+  "result_type",
+  "result",
+  "field",
+  "gist",
+  "case_gist",
+  "ref_law",
+  "detail_page_link",
+  "full_pdf_link",
+  "contents",
+];
+
+fn data_column<'a>(data: &'a PrecedentData, column: &str) -> Option<&'a str> {
+  match column {
+    "case_name" => Some(&data.case_name),
+    "lawsuit_type" => data.lawsuit_type.as_deref(),
+    "result_type" => data.result_type.as_deref(),
+    "result" => data.result.as_deref(),
+    "field" => data.field.as_deref(),
+    "gist" => data.gist.as_deref(),
+    "case_gist" => data.case_gist.as_deref(),
+    "ref_law" => data.ref_law.as_deref(),
+    "detail_page_link" => Some(&data.detail_page_link),
+    "full_pdf_link" => Some(&data.full_pdf_link),
+    "contents" => data.contents.as_deref(),
+    _ => None,
+  }
+}
+
+/// 一覧の列に裁判例のデータの列を加えた表にする
+pub fn to_dataset_batch(rows: &[&DatasetRow]) -> Result<RecordBatch> {
+  let entries = rows.iter().map(|r| r.entry.clone()).collect::<Vec<_>>();
+  let index_batch = to_record_batch(&entries)?;
+  let mut fields = index_batch
+    .schema()
+    .fields()
+    .iter()
+    .map(|f| f.as_ref().clone())
+    .collect::<Vec<_>>();
+  let mut columns = index_batch.columns().to_vec();
+  for column in DATA_COLUMNS {
+    let mut builder = StringBuilder::new();
+    for row in rows {
+      builder.append_option(row.data.as_ref().and_then(|d| data_column(d, column)));
+    }
+    fields.push(Field::new(*column, DataType::Utf8, true));
+    columns.push(Arc::new(builder.finish()) as ArrayRef);
+  }
+  Ok(RecordBatch::try_new(
+    Arc::new(Schema::new(fields)),
+    columns,
+  )?)
+}
+
+fn write_batch(path: &str, batch: &RecordBatch) -> Result<()> {
+  let file = File::create(path)?;
+  let mut writer = FileWriter::try_new(file, &batch.schema())?;
+  writer.write(batch)?;
+  writer.finish()?;
+  Ok(())
+}
+
+/// `index`の一覧ファイルを読んで、`path`にArrow IPC形式で書き出す。書き出した件数を返す
+pub async fn write(index: &str, path: &str) -> Result<usize> {
+  let entries = output::read_value_lst(index).await?;
+  write_batch(path, &to_record_batch(&entries)?)?;
+  Ok(entries.len())
+}
+
+/// `index`の一覧と`output`の裁判例のデータを、`path`にArrow IPC形式のデータセットとして書き出す。書き出した件数を返す
+pub async fn export(index: &str, output: &str, path: &str) -> Result<usize> {
+  let rows = dataset::load_dataset(index, output).await?;
+  write_batch(path, &to_dataset_batch(&rows.iter().collect::<Vec<_>>())?)?;
+  permissions::apply(path).await?;
+  Ok(rows.len())
+}
@@ -0,0 +1,194 @@
+//! 取得したURLを順に記録する監査ログと、そのログをなぞって取得し直す`replay`サブコマンド
+//!
+//! `--audit-log`を与えると、HTMLとPDFを取得するたびに1行1件のJSONを追記する。
+//! 各行は通し番号・UNIX時間（ミリ秒）・種類（`html`・`pdf`）・URLと、結果（`ok`・`not_modified`・`error`）を持つ。
+//! `--cache-dir`に保存してあって実際にはリクエストを送らなかったものも記録する。
+//!
+//! ```json
+//! {"seq":0,"timestamp":1700000000000,"kind":"html","url":"https://www.courts.go.jp/app/hanrei_jp/detail2?id=89000","outcome":"ok","bytes":31234}
+//! ```
+//!
+//! `replay --audit-log`は記録したURLを同じ順序で1件ずつ取得し直し、詳細ページは解析までやり直す。
+//! 記録と結果が食い違ったものを警告に出すので、不具合が起きたときの実行を再現して調べられる。
+//! 出力フォルダや一覧ファイルには書き出さない。
+
+use crate::{events::Events, fetch::Fetcher, shutdown, Args};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{
+  fs::{File, OpenOptions},
+  io::Write,
+  sync::{
+    atomic::{AtomicUsize, Ordering},
+    Mutex,
+  },
+  time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditKind {
+  Html,
+  Pdf,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+  Ok,
+  /// 条件付きリクエストで更新されていなかった（304）
+  NotModified,
+  Error,
+}
+
+/// 監査ログの1行
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+  pub seq: usize,
+  pub timestamp: u64,
+  pub kind: AuditKind,
+  pub url: String,
+  pub outcome: Outcome,
+  /// 取得した本文のバイト数
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub bytes: Option<usize>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
+
+pub struct AuditLog {
+  file: Mutex<File>,
+  seq: AtomicUsize,
+}
+
+impl AuditLog {
+  /// `path`に監査ログを作る。既にあれば追記する
+  pub fn open(path: &str) -> Result<Self> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    Ok(AuditLog {
+      file: Mutex::new(file),
+      seq: AtomicUsize::new(0),
+    })
+  }
+
+  /// 取得の結果を1行追記する。書き込みに失敗しても取得処理自体は続ける
+  pub fn record(&self, kind: AuditKind, url: &str, result: &Result<Option<usize>>) {
+    let (outcome, bytes, error) = match result {
+      Ok(Some(bytes)) => (Outcome::Ok, Some(*bytes), None),
+      Ok(None) => (Outcome::NotModified, None, None),
+      Err(e) => (Outcome::Error, None, Some(format!("{e:#}"))),
+    };
+    let entry = AuditEntry {
+      seq: self.seq.fetch_add(1, Ordering::Relaxed),
+      timestamp: SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default(),
+      kind,
+      url: url.to_string(),
+      outcome,
+      bytes,
+      error,
+    };
+    let Ok(line) = serde_json::to_string(&entry) else {
+      return;
+    };
+    let mut file = self.file.lock().unwrap();
+    if let Err(e) = writeln!(file, "{line}") {
+      warn!("failed to write audit log: {e}");
+    }
+  }
+}
+
+/// 監査ログを読む。壊れた行は読み飛ばす
+async fn read_entries(path: &str) -> Result<Vec<AuditEntry>> {
+  let text = tokio::fs::read_to_string(path).await?;
+  let mut entries = Vec::new();
+  for (i, line) in text.lines().enumerate() {
+    if line.trim().is_empty() {
+      continue;
+    }
+    match serde_json::from_str::<AuditEntry>(line) {
+      Ok(entry) => entries.push(entry),
+      Err(e) => warn!("skip broken audit log line {}: {e}", i + 1),
+    }
+  }
+  Ok(entries)
+}
+
+/// 1件を取得し直し、詳細ページなら解析もする。取得した本文のバイト数を返す
+///
+/// PDFのアーカイブへのフォールバックも別の行に記録されているので、ここではフォールバックしない
+async fn replay_one(fetcher: &Fetcher, entry: &AuditEntry) -> Result<Option<usize>> {
+  match entry.kind {
+    AuditKind::Pdf => {
+      let bytes = fetcher.get_pdf_if_modified(&entry.url, false).await?;
+      Ok(bytes.map(|b| b.len()))
+    }
+    AuditKind::Html => {
+      let html = fetcher.get_text(&entry.url).await?;
+      if entry.url.contains("/app/hanrei_jp/detail") {
+        crate::parse_detail_page(
+          &html,
+          crate::trial_type_from_link(&entry.url)?,
+          crate::get_lawsuit_id(&entry.url).await?,
+          entry.url.clone(),
+        )
+        .await?;
+      }
+      Ok(Some(html.len()))
+    }
+  }
+}
+
+/// `audit_log`に記録したURLを同じ順序で取得し直す。食い違いがあった件数を返す
+pub async fn replay(args: &Args, events: &Events, audit_log: &str) -> Result<usize> {
+  if args.audit_log.as_deref() == Some(audit_log) {
+    return Err(anyhow!(
+      "再実行の記録を読み込む監査ログと同じファイルには書き出せません：{audit_log}"
+    ));
+  }
+  let entries = read_entries(audit_log).await?;
+  let fetcher = crate::build_fetcher(args).await?;
+  events.emit("run_started", json!({ "replay": entries.len() }));
+  info!("[START] replay: {} requests", entries.len());
+  let mut differed = 0;
+  for entry in &entries {
+    if shutdown::requested() {
+      warn!("interrupted before seq {}", entry.seq);
+      events.emit("interrupted", json!({ "next_link": &entry.url }));
+      break;
+    }
+    let result = replay_one(&fetcher, entry).await;
+    let outcome = match &result {
+      Ok(Some(_)) => Outcome::Ok,
+      Ok(None) => Outcome::NotModified,
+      Err(_) => Outcome::Error,
+    };
+    match result {
+      Err(e) if crate::fetch::is_circuit_open(&e) => return Err(e),
+      Err(e) if outcome != entry.outcome => {
+        differed += 1;
+        warn!(
+          "replay: seq {} {}: recorded {:?} but failed: {e:#}",
+          entry.seq, &entry.url, entry.outcome
+        );
+      }
+      Err(e) => info!(
+        "replay: seq {} {}: failed again: {e:#}",
+        entry.seq, &entry.url
+      ),
+      Ok(_) if outcome != entry.outcome => {
+        differed += 1;
+        warn!(
+          "replay: seq {} {}: recorded {:?} but got {:?}",
+          entry.seq, &entry.url, entry.outcome, outcome
+        );
+      }
+      Ok(_) => debug!("replay: seq {} {}: ok", entry.seq, &entry.url),
+    }
+  }
+  Ok(differed)
+}
@@ -0,0 +1,211 @@
+//! `--archive`による、1回の実行で書き出すファイルの1つのアーカイブへのまとめ書き
+//!
+//! 裁判例ごとのファイルは出力フォルダに作らず、書き出すたびにアーカイブに追記する。
+//! 実行の最後に一覧ファイルと、収めたファイルの名前・大きさ・SHA-256を並べた`manifest.json`を加えて閉じる。
+//! 形式はpathの拡張子で決める（`.tar`・`.tar.gz`（`.tgz`）・`.tar.zst`・`.zip`）。
+//! 途中で落ちた場合、`.tar.gz`・`.tar.zst`・`.zip`は閉じられないので読めないことがある。
+
+use crate::{permissions, response_cache::to_hex};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::{
+  fs::File,
+  io::Write,
+  path::Path,
+  sync::{Mutex, OnceLock},
+  time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::*;
+
+/// tarを書き込む先
+enum TarSink {
+  Plain(File),
+  Gzip(flate2::write::GzEncoder<File>),
+  Zstd(zstd::Encoder<'static, File>),
+}
+
+impl Write for TarSink {
+  fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+    match self {
+      TarSink::Plain(w) => w.write(buf),
+      TarSink::Gzip(w) => w.write(buf),
+      TarSink::Zstd(w) => w.write(buf),
+    }
+  }
+
+  fn flush(&mut self) -> std::io::Result<()> {
+    match self {
+      TarSink::Plain(w) => w.flush(),
+      TarSink::Gzip(w) => w.flush(),
+      TarSink::Zstd(w) => w.flush(),
+    }
+  }
+}
+
+impl TarSink {
+  fn finish(self) -> Result<()> {
+    match self {
+      TarSink::Plain(mut w) => w.flush()?,
+      TarSink::Gzip(w) => w.finish()?.flush()?,
+      TarSink::Zstd(w) => w.finish()?.flush()?,
+    }
+    Ok(())
+  }
+}
+
+enum Writer {
+  Tar(tar::Builder<TarSink>),
+  Zip(zip::ZipWriter<File>),
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct ManifestEntry {
+  name: String,
+  size: usize,
+  sha256: String,
+}
+
+struct Bundle {
+  path: String,
+  writer: Writer,
+  entries: Vec<ManifestEntry>,
+}
+
+static BUNDLE: OnceLock<Mutex<Option<Bundle>>> = OnceLock::new();
+
+fn now_secs() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0, |d| d.as_secs())
+}
+
+impl Bundle {
+  fn create(path: &str) -> Result<Self> {
+    let file = File::create(path)?;
+    let writer = if path.ends_with(".zip") {
+      Writer::Zip(zip::ZipWriter::new(file))
+    } else if path.ends_with(".tar.zst") {
+      Writer::Tar(tar::Builder::new(TarSink::Zstd(zstd::Encoder::new(
+        file, 3,
+      )?)))
+    } else if path.ends_with(".tar.gz") || path.ends_with(".tgz") {
+      Writer::Tar(tar::Builder::new(TarSink::Gzip(
+        flate2::write::GzEncoder::new(file, flate2::Compression::default()),
+      )))
+    } else if path.ends_with(".tar") {
+      Writer::Tar(tar::Builder::new(TarSink::Plain(file)))
+    } else {
+      return Err(anyhow!(
+        "アーカイブの拡張子は.tar・.tar.gz・.tgz・.tar.zst・.zipのいずれかにしてください：{path}"
+      ));
+    };
+    Ok(Bundle {
+      path: path.to_string(),
+      writer,
+      entries: Vec::new(),
+    })
+  }
+
+  fn append(&mut self, name: &str, bytes: &[u8]) -> Result<()> {
+    match &mut self.writer {
+      Writer::Tar(builder) => {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(bytes.len() as u64);
+        header.set_mode(0o644);
+        header.set_mtime(now_secs());
+        header.set_cksum();
+        builder.append_data(&mut header, name, bytes)?;
+      }
+      Writer::Zip(zip) => {
+        let options = zip::write::SimpleFileOptions::default()
+          .compression_method(zip::CompressionMethod::Deflated)
+          .large_file(bytes.len() as u64 >= u32::MAX as u64);
+        zip.start_file(name, options)?;
+        zip.write_all(bytes)?;
+      }
+    }
+    self.entries.push(ManifestEntry {
+      name: name.to_string(),
+      size: bytes.len(),
+      sha256: to_hex(&Sha256::digest(bytes)),
+    });
+    Ok(())
+  }
+
+  fn finish(mut self) -> Result<String> {
+    let manifest = json!({
+      "tool": env!("CARGO_PKG_NAME"),
+      "version": env!("CARGO_PKG_VERSION"),
+      "created_at": now_secs(),
+      "files": &self.entries,
+    });
+    self.append("manifest.json", &serde_json::to_vec_pretty(&manifest)?)?;
+    match self.writer {
+      Writer::Tar(builder) => builder.into_inner()?.finish()?,
+      Writer::Zip(zip) => {
+        zip.finish()?.flush()?;
+      }
+    }
+    Ok(self.path)
+  }
+}
+
+fn bundle() -> &'static Mutex<Option<Bundle>> {
+  BUNDLE.get_or_init(|| Mutex::new(None))
+}
+
+/// `path`にアーカイブを作り、以降に書き出す裁判例のファイルをそこに収める
+pub fn open(path: &str) -> Result<()> {
+  let mut bundle = bundle()
+    .lock()
+    .map_err(|_| anyhow!("アーカイブのロックが壊れています"))?;
+  *bundle = Some(Bundle::create(path)?);
+  info!("archive: {}", path);
+  Ok(())
+}
+
+/// アーカイブに書き出しているかどうか
+pub fn is_open() -> bool {
+  bundle().lock().is_ok_and(|b| b.is_some())
+}
+
+/// `name`というファイルとしてアーカイブに追記する
+pub fn append(name: &str, bytes: &[u8]) -> Result<()> {
+  let mut bundle = bundle()
+    .lock()
+    .map_err(|_| anyhow!("アーカイブのロックが壊れています"))?;
+  let bundle = bundle
+    .as_mut()
+    .ok_or_else(|| anyhow!("アーカイブが開かれていません"))?;
+  bundle.append(name, bytes)
+}
+
+/// `files`（一覧ファイルなど）と`manifest.json`を加えてアーカイブを閉じる。アーカイブを開いていなければ何もしない
+pub async fn finish(files: &[String]) -> Result<()> {
+  let mut contents = Vec::new();
+  for path in files.iter().filter(|p| Path::new(p).exists()) {
+    let name = Path::new(path)
+      .file_name()
+      .map_or_else(|| path.clone(), |n| n.to_string_lossy().to_string());
+    contents.push((name, tokio::fs::read(path).await?));
+  }
+  let path = {
+    let mut bundle = bundle()
+      .lock()
+      .map_err(|_| anyhow!("アーカイブのロックが壊れています"))?;
+    let Some(mut bundle) = bundle.take() else {
+      return Ok(());
+    };
+    for (name, bytes) in &contents {
+      bundle.append(name, bytes)?;
+    }
+    let len = bundle.entries.len();
+    let path = bundle.finish()?;
+    info!("archived {} file(s) into {}", len, &path);
+    path
+  };
+  permissions::apply(&path).await?;
+  Ok(())
+}
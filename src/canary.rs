@@ -0,0 +1,192 @@
+//! 裁判所のホームページの仕様変更を少ないリクエストで検知する`canary`サブコマンド
+//!
+//! `--fixtures`のファイルに並べた既知の判例の詳細ページを取得して解析し、記録してある期待値と比べる。
+//! 加えて最近の判例の一覧ページから詳細ページへのリンクが読み取れるかも確かめる。
+//! どれかが食い違えばエラーで終わるので、定期実行の前段に置けば、ページの構造が変わったまま全件を取得し直すのを防げる。
+//!
+//! フィクスチャはJSONの配列で、各項目は`detail_page_link`と、フィールド名から期待値への`expected`を持つ。
+//! `--update`を与えると、現在のページから解析した値で`expected`を書き直す。
+//!
+//! `--sample`を与えると、サーバーにはリクエストを送らず、ライブラリの`examples::sample_data()`が
+//! 同梱のサンプルのHTMLを今の解析処理にかけた結果と一致するかだけを確かめる。
+//!
+//! ```json
+//! [{ "detail_page_link": "https://www.courts.go.jp/app/hanrei_jp/detail2?id=89000", "expected": { "case_number": "..." } }]
+//! ```
+
+use crate::{fetch::Fetcher, list_only, Args, COURTS_DOMEIN, RECENT_LIST_PATH};
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::fs;
+use tracing::*;
+
+/// `--update`で記録するフィールド。本文はPDFから取るので含めない
+const CHECKED_FIELDS: &[&str] = &[
+  "case_number",
+  "case_name",
+  "court_name",
+  "date",
+  "trial_type",
+  "lawsuit_type",
+  "result_type",
+  "result",
+  "field",
+  "gist",
+  "case_gist",
+  "ref_law",
+  "full_pdf_link",
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Fixture {
+  pub detail_page_link: String,
+  #[serde(default)]
+  pub expected: Map<String, Value>,
+}
+
+/// 期待値と異なったフィールド
+#[derive(Debug, Clone)]
+struct Mismatch {
+  field: String,
+  expected: Value,
+  actual: Value,
+}
+
+/// 詳細ページを取得して解析した結果を、フィールド名から値へのマップにする
+async fn parse(fetcher: &Fetcher, detail_page_link: &str) -> Result<Map<String, Value>> {
+  let html = fetcher.get_text(detail_page_link).await?;
+  let data = crate::parse_detail_page(
+    &html,
+    crate::trial_type_from_link(detail_page_link)?,
+    crate::get_lawsuit_id(detail_page_link).await?,
+    detail_page_link.to_string(),
+  )
+  .await?;
+  match serde_json::to_value(data)? {
+    Value::Object(obj) => Ok(obj),
+    v => Err(anyhow!("解析結果がオブジェクトではありません：{v}")),
+  }
+}
+
+fn compare(fixture: &Fixture, actual: &Map<String, Value>) -> Vec<Mismatch> {
+  fixture
+    .expected
+    .iter()
+    .filter_map(|(field, expected)| {
+      let actual = actual.get(field).cloned().unwrap_or(Value::Null);
+      (actual != *expected).then(|| Mismatch {
+        field: field.clone(),
+        expected: expected.clone(),
+        actual,
+      })
+    })
+    .collect()
+}
+
+/// 同梱のサンプルのHTMLを解析し、`examples::sample_data()`と比べる
+pub async fn check_sample() -> Result<()> {
+  use listup_precedent_lib::examples;
+  let link = examples::SAMPLE_DETAIL_PAGE_LINK;
+  let data = crate::parse_detail_page(
+    examples::SAMPLE_DETAIL_HTML,
+    crate::trial_type_from_link(link)?,
+    crate::get_lawsuit_id(link).await?,
+    link.to_string(),
+  )
+  .await?;
+  let Value::Object(actual) = serde_json::to_value(data)? else {
+    return Err(anyhow!("解析結果がオブジェクトではありません"));
+  };
+  let Value::Object(expected) = serde_json::to_value(examples::sample_data())? else {
+    return Err(anyhow!("サンプルのデータがオブジェクトではありません"));
+  };
+  let fixture = Fixture {
+    detail_page_link: link.to_string(),
+    expected,
+  };
+  let mismatches = compare(&fixture, &actual);
+  if mismatches.is_empty() {
+    info!("canary: sample data ok");
+    return Ok(());
+  }
+  for m in &mismatches {
+    error!(
+      "canary: sample: {} expected {} but got {}",
+      m.field, m.expected, m.actual
+    );
+  }
+  Err(anyhow!(
+    "サンプルのデータが解析結果と{}件食い違っています。examples::sample_data()を更新してください",
+    mismatches.len()
+  ))
+}
+
+/// 最近の判例の一覧ページから詳細ページへのリンクを読み取れるか確かめる
+async fn check_list_page(fetcher: &Fetcher) -> Result<()> {
+  let url = format!("{COURTS_DOMEIN}{RECENT_LIST_PATH}");
+  let html = fetcher.get_text(&url).await?;
+  let entries = list_only::parse_list_page(&html).await?;
+  if entries.is_empty() {
+    return Err(anyhow!("一覧ページから判例のリンクを読み取れません：{url}"));
+  }
+  info!("canary: list page ok ({} entries)", entries.len());
+  Ok(())
+}
+
+pub async fn run(args: &Args, fixtures_path: &str, update: bool) -> Result<()> {
+  let fetcher = crate::build_fetcher(args).await?;
+  let mut fixtures: Vec<Fixture> = serde_json::from_str(&fs::read_to_string(fixtures_path).await?)?;
+  if fixtures.is_empty() {
+    return Err(anyhow!("フィクスチャがありません：{fixtures_path}"));
+  }
+  let mut failures = 0;
+  if let Err(e) = check_list_page(&fetcher).await {
+    error!("canary: {e:#}");
+    failures += 1;
+  }
+  for fixture in &mut fixtures {
+    let actual = match parse(&fetcher, &fixture.detail_page_link).await {
+      Ok(actual) => actual,
+      Err(e) => {
+        error!(
+          "canary: failed to parse {}: {e:#}",
+          &fixture.detail_page_link
+        );
+        failures += 1;
+        continue;
+      }
+    };
+    if update {
+      fixture.expected = CHECKED_FIELDS
+        .iter()
+        .filter_map(|field| Some((field.to_string(), actual.get(*field)?.clone())))
+        .collect();
+      info!("canary: updated {}", &fixture.detail_page_link);
+      continue;
+    }
+    let mismatches = compare(fixture, &actual);
+    if mismatches.is_empty() {
+      info!("canary: ok {}", &fixture.detail_page_link);
+    } else {
+      failures += 1;
+      for m in mismatches {
+        error!(
+          "canary: {}: {} expected {} but got {}",
+          &fixture.detail_page_link, m.field, m.expected, m.actual
+        );
+      }
+    }
+  }
+  if update {
+    fs::write(fixtures_path, serde_json::to_string_pretty(&fixtures)?).await?;
+    info!("canary: fixtures written: {fixtures_path}");
+  }
+  if failures > 0 {
+    return Err(anyhow!(
+      "カナリアチェックで{failures}件の食い違いがありました。ページの構造が変わった可能性があります"
+    ));
+  }
+  info!("canary: all {} fixture(s) passed", fixtures.len());
+  Ok(())
+}
@@ -0,0 +1,135 @@
+//! 事件番号の事件記号（`令和3(オ)123`の`オ`など）の意味の辞書
+//!
+//! 事件記号は裁判所と事件の種類ごとに事件記録符号規程で決められている。
+//! 判例の検索でよく見るものを内蔵し、事件番号から取り出した記号の意味を`case_mark_meaning`として付ける。
+//! 辞書に無い記号は`case_mark`だけを付ける。
+
+use regex::Regex;
+use serde::Serialize;
+use std::sync::OnceLock;
+
+/// 事件記号とその意味
+const CASE_MARKS: &[(&str, &str)] = &[
+  // 最高裁判所
+  ("オ", "民事上告事件"),
+  ("受", "民事上告受理事件"),
+  ("ク", "民事特別抗告事件"),
+  ("許", "民事許可抗告事件"),
+  ("テ", "民事特別上告事件"),
+  ("行ツ", "行政上告事件"),
+  ("行ヒ", "行政上告受理事件"),
+  ("行ト", "行政特別抗告事件"),
+  ("行フ", "行政許可抗告事件"),
+  ("あ", "刑事上告事件"),
+  ("し", "刑事特別抗告事件"),
+  ("さ", "刑事非常上告事件"),
+  ("す", "刑事雑事件"),
+  // 高等裁判所
+  ("ネ", "民事控訴事件"),
+  ("ラ", "民事抗告事件"),
+  ("ツ", "民事上告事件（高等裁判所）"),
+  ("行コ", "行政控訴事件"),
+  ("行ス", "行政抗告事件"),
+  ("行ケ", "行政第一審訴訟事件（高等裁判所）"),
+  ("う", "刑事控訴事件"),
+  ("く", "刑事抗告事件"),
+  // 地方裁判所
+  ("ワ", "民事第一審通常訴訟事件"),
+  ("レ", "民事控訴事件（簡易裁判所の判決に対するもの）"),
+  ("ソ", "民事抗告事件（簡易裁判所の決定に対するもの）"),
+  ("ヨ", "民事保全命令事件"),
+  ("モ", "民事雑事件"),
+  ("フ", "破産事件"),
+  ("ヌ", "民事執行事件"),
+  ("ケ", "担保権の実行としての競売等事件"),
+  ("行ウ", "行政第一審訴訟事件"),
+  ("行ク", "行政雑事件"),
+  ("わ", "刑事公判請求事件"),
+  ("む", "刑事雑事件"),
+  // 簡易裁判所
+  ("ハ", "民事第一審通常訴訟事件（簡易裁判所）"),
+  // 家庭裁判所
+  ("家", "家事審判事件"),
+  ("家イ", "家事調停事件"),
+  ("家ホ", "人事訴訟事件"),
+  ("少", "少年保護事件"),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CaseMark {
+  pub mark: String,
+  /// 辞書に無い記号では`None`
+  pub meaning: Option<&'static str>,
+}
+
+fn mark_re() -> &'static Regex {
+  static RE: OnceLock<Regex> = OnceLock::new();
+  RE.get_or_init(|| Regex::new(r"[(（]([^)）]+)[)）]").unwrap())
+}
+
+/// 事件記号の意味。辞書に無ければ`None`
+pub fn meaning(mark: &str) -> Option<&'static str> {
+  CASE_MARKS
+    .iter()
+    .find(|(m, _)| *m == mark)
+    .map(|(_, meaning)| *meaning)
+}
+
+/// 事件番号から最初の事件記号を取り出す
+pub fn find(case_number: &str) -> Option<CaseMark> {
+  let mark = mark_re().captures(case_number)?[1].trim().to_string();
+  Some(CaseMark {
+    meaning: meaning(&mark),
+    mark,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn finds_known_marks() {
+    assert_eq!(
+      find("令和3(受)1234"),
+      Some(CaseMark {
+        mark: "受".to_string(),
+        meaning: Some("民事上告受理事件"),
+      })
+    );
+    assert_eq!(
+      find("平成30年（行ヒ）56"),
+      Some(CaseMark {
+        mark: "行ヒ".to_string(),
+        meaning: Some("行政上告受理事件"),
+      })
+    );
+  }
+
+  #[test]
+  fn takes_the_first_mark() {
+    assert_eq!(
+      find("令和2(オ)10、令和2(受)11").map(|m| m.mark),
+      Some("オ".to_string())
+    );
+  }
+
+  #[test]
+  fn keeps_unknown_marks_without_meaning() {
+    assert_eq!(
+      find("令和4(ゆ)1"),
+      Some(CaseMark {
+        mark: "ゆ".to_string(),
+        meaning: None,
+      })
+    );
+    assert_eq!(find("令和4年1234号"), None);
+  }
+
+  #[test]
+  fn marks_are_unique() {
+    for (i, (mark, _)) in CASE_MARKS.iter().enumerate() {
+      assert!(CASE_MARKS[i + 1..].iter().all(|(m, _)| m != mark), "{mark}");
+    }
+  }
+}
@@ -0,0 +1,57 @@
+//! HTMLの文字コードの判定とデコード
+//!
+//! 裁判所のホームページは今はUTF-8で返すが、Shift_JISやEUC-JPのページが返ってきたときに
+//! 文字化けしたまま保存しないよう、BOM・Content-Typeヘッダーのcharset・`<meta>`タグの順に文字コードを判定してデコードする。
+//! どれも無ければUTF-8とみなす。
+
+use encoding_rs::{Encoding, UTF_8};
+use regex::bytes::Regex;
+use std::sync::OnceLock;
+use tracing::*;
+
+/// `<meta>`タグを探す範囲（HTMLの仕様に合わせて先頭の1024バイト）
+const META_SCAN_LIMIT: usize = 1024;
+
+fn meta_charset_re() -> &'static Regex {
+  static RE: OnceLock<Regex> = OnceLock::new();
+  RE.get_or_init(|| Regex::new(r#"(?i)<meta[^>]+charset\s*=\s*["']?([A-Za-z0-9_:.-]+)"#).unwrap())
+}
+
+/// Content-Typeヘッダーの値から`charset`を取り出す
+fn content_type_charset(content_type: &str) -> Option<&str> {
+  content_type.split(';').skip(1).find_map(|param| {
+    let (name, value) = param.split_once('=')?;
+    name
+      .trim()
+      .eq_ignore_ascii_case("charset")
+      .then(|| value.trim().trim_matches('"'))
+  })
+}
+
+/// `<meta charset="...">`・`<meta http-equiv="Content-Type" content="...; charset=...">`の文字コード
+fn meta_charset(bytes: &[u8]) -> Option<&'static Encoding> {
+  let head = &bytes[..bytes.len().min(META_SCAN_LIMIT)];
+  let caps = meta_charset_re().captures(head)?;
+  Encoding::for_label(caps.get(1)?.as_bytes())
+}
+
+/// HTMLの本文を判定した文字コードでデコードする。`content_type`はレスポンスのContent-Typeヘッダーの値
+pub fn decode_html(bytes: &[u8], content_type: Option<&str>, url: &str) -> String {
+  let encoding = content_type
+    .and_then(content_type_charset)
+    .and_then(|label| Encoding::for_label(label.as_bytes()))
+    .or_else(|| meta_charset(bytes))
+    .unwrap_or(UTF_8);
+  // BOMがあればそちらを優先する
+  let (text, used, had_errors) = encoding.decode(bytes);
+  if used != UTF_8 {
+    info!("decoded as {}: {url}", used.name());
+  }
+  if had_errors {
+    warn!(
+      "invalid byte sequences for {} were replaced: {url}",
+      used.name()
+    );
+  }
+  text.into_owned()
+}
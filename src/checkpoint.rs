@@ -0,0 +1,233 @@
+//! 落ちたり止められたりした実行を、その続きから再開するためのチェックポイントファイル
+//!
+//! 1件書き出すたびに、現在のページと、それまでに処理した判例の`lawsuit_id`の集合を保存する。
+//! 再開するときは集合に含まれる判例を飛ばすので、並行して取得して書き出す順が一覧と入れ替わっても、
+//! 一覧ページの並びが前回から変わっても、取りこぼしや二重の取得が起きない。
+//! `lawsuit_id`は連番に近いので、集合は連続する番号を区間にまとめて小さく保つ。
+//!
+//! 書き込み途中で落ちても壊れないよう、一時ファイルに書いてから名前を変える。
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeSet, path::Path};
+use tokio::fs;
+
+/// 処理済みの`lawsuit_id`の集合
+///
+/// 数字の`lawsuit_id`は、連続する番号を`[最初, 最後]`の区間にまとめて昇順に持つ
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessedIds {
+  /// 重ならず、隣り合わない区間を昇順に並べたもの
+  ranges: Vec<(u64, u64)>,
+  /// 数字でない`lawsuit_id`
+  #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+  others: BTreeSet<String>,
+}
+
+impl ProcessedIds {
+  pub fn is_empty(&self) -> bool {
+    self.ranges.is_empty() && self.others.is_empty()
+  }
+
+  /// 集合に含まれる`lawsuit_id`の数
+  pub fn len(&self) -> u64 {
+    let numbers: u64 = self
+      .ranges
+      .iter()
+      .map(|&(first, last)| last - first + 1)
+      .sum();
+    numbers + self.others.len() as u64
+  }
+
+  pub fn contains(&self, lawsuit_id: &str) -> bool {
+    match lawsuit_id.parse::<u64>() {
+      Ok(n) => {
+        let i = self.ranges.partition_point(|&(_, last)| last < n);
+        self.ranges.get(i).is_some_and(|&(first, _)| first <= n)
+      }
+      Err(_) => self.others.contains(lawsuit_id),
+    }
+  }
+
+  pub fn insert(&mut self, lawsuit_id: &str) {
+    let Ok(n) = lawsuit_id.parse::<u64>() else {
+      self.others.insert(lawsuit_id.to_string());
+      return;
+    };
+    // `n`を含むか、`n`のすぐ後で終わるか、`n`より後にある最初の区間
+    let i = self
+      .ranges
+      .partition_point(|&(_, last)| last.saturating_add(1) < n);
+    match self.ranges.get(i).copied() {
+      Some((first, last)) if first <= n && n <= last => {}
+      Some((first, last)) if last.saturating_add(1) == n => {
+        self.ranges[i] = (first, n);
+        // 次の区間とつながったらまとめる
+        if let Some(&(next_first, next_last)) = self.ranges.get(i + 1) {
+          if next_first == n + 1 {
+            self.ranges[i] = (first, next_last);
+            self.ranges.remove(i + 1);
+          }
+        }
+      }
+      Some((first, last)) if first == n + 1 => self.ranges[i] = (n, last),
+      _ => self.ranges.insert(i, (n, n)),
+    }
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+  pub start: Option<String>,
+  pub end: Option<String>,
+  pub recent: bool,
+  #[serde(default)]
+  pub since_id: Option<u64>,
+  #[serde(default)]
+  pub until_id: Option<u64>,
+  /// 処理中のページ
+  pub page: usize,
+  /// 最後に書き出した判例の`lawsuit_id`
+  pub last_lawsuit_id: Option<String>,
+  /// 処理した判例の`lawsuit_id`。これが無い以前の形式のチェックポイントでは`last_lawsuit_id`で再開する
+  #[serde(default)]
+  pub processed: ProcessedIds,
+}
+
+impl Checkpoint {
+  pub async fn load(path: &str) -> Result<Option<Self>> {
+    if !Path::new(path).exists() {
+      return Ok(None);
+    }
+    let s = fs::read_to_string(path).await?;
+    Ok(Some(serde_json::from_str(&s)?))
+  }
+
+  pub async fn save(&self, path: &str) -> Result<()> {
+    let tmp = format!("{path}.tmp");
+    fs::write(&tmp, serde_json::to_string(self)?).await?;
+    fs::rename(&tmp, path).await?;
+    Ok(())
+  }
+
+  pub async fn remove(path: &str) -> Result<()> {
+    if Path::new(path).exists() {
+      fs::remove_file(path).await?;
+    }
+    Ok(())
+  }
+
+  /// `page`の`lawsuit_id`の判例を処理したことを記録する
+  pub fn mark_processed(&mut self, page: usize, lawsuit_id: &str) {
+    self.page = page;
+    self.last_lawsuit_id = Some(lawsuit_id.to_string());
+    self.processed.insert(lawsuit_id);
+  }
+
+  /// `page`の判例をすべて処理したことを記録する
+  pub fn mark_page_done(&mut self, page: usize) {
+    self.page = page + 1;
+    self.last_lawsuit_id = None;
+  }
+
+  /// `lawsuit_id`の判例を前回までに処理したかどうか
+  pub fn is_processed(&self, lawsuit_id: &str) -> bool {
+    self.processed.contains(lawsuit_id)
+  }
+
+  /// `expected`と同じ取得条件の実行のチェックポイントかどうかを確かめる
+  pub fn ensure_same_range(&self, expected: &Checkpoint) -> Result<()> {
+    if self.start != expected.start
+      || self.end != expected.end
+      || self.recent != expected.recent
+      || self.since_id != expected.since_id
+      || self.until_id != expected.until_id
+    {
+      return Err(anyhow!(
+        "チェックポイントの取得条件（start: {:?}, end: {:?}, recent: {}, since_id: {:?}, until_id: {:?}）が今回の指定と異なります",
+        self.start,
+        self.end,
+        self.recent,
+        self.since_id,
+        self.until_id
+      ));
+    }
+    Ok(())
+  }
+}
+
+/// `links`（リンクと`lawsuit_id`の組）から、処理済みのものを取り除く。取り除いた件数を返す
+pub fn remove_processed(links: &mut Vec<(String, String)>, processed: &ProcessedIds) -> usize {
+  let before = links.len();
+  links.retain(|(_, id)| !processed.contains(id));
+  before - links.len()
+}
+
+/// `links`（リンクと`lawsuit_id`の組）から、`last_lawsuit_id`までの処理済みのものを取り除く。
+/// 見つからなかった場合はページの内容が変わったとみなして、何も取り除かずに`false`を返す
+pub fn skip_processed(links: &mut Vec<(String, String)>, last_lawsuit_id: &str) -> bool {
+  match links.iter().position(|(_, id)| id == last_lawsuit_id) {
+    Some(pos) => {
+      links.drain(..=pos);
+      true
+    }
+    None => false,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ids(list: &[&str]) -> ProcessedIds {
+    let mut ids = ProcessedIds::default();
+    for id in list {
+      ids.insert(id);
+    }
+    ids
+  }
+
+  #[test]
+  fn merges_adjacent_ids() {
+    assert_eq!(ids(&["10", "11", "12"]).ranges, [(10, 12)]);
+    assert_eq!(ids(&["12", "11", "10"]).ranges, [(10, 12)]);
+    assert_eq!(
+      ids(&["10", "12", "20"]).ranges,
+      [(10, 10), (12, 12), (20, 20)]
+    );
+    assert_eq!(ids(&["10", "11", "11", "10"]).ranges, [(10, 11)]);
+  }
+
+  #[test]
+  fn bridges_two_ranges() {
+    let mut processed = ids(&["10", "11", "13", "14"]);
+    assert_eq!(processed.ranges, [(10, 11), (13, 14)]);
+    processed.insert("12");
+    assert_eq!(processed.ranges, [(10, 14)]);
+    assert_eq!(processed.len(), 5);
+  }
+
+  #[test]
+  fn contains_numbers_and_other_ids() {
+    let processed = ids(&["10", "11", "20", "H1-abc"]);
+    assert!(processed.contains("10"));
+    assert!(processed.contains("11"));
+    assert!(processed.contains("20"));
+    assert!(!processed.contains("9"));
+    assert!(!processed.contains("12"));
+    assert!(!processed.contains("21"));
+    assert!(processed.contains("H1-abc"));
+    assert!(!processed.contains("H1-abd"));
+    assert_eq!(processed.len(), 4);
+    assert!(ProcessedIds::default().is_empty());
+  }
+
+  #[test]
+  fn serializes_as_ranges() {
+    let processed = ids(&["1", "2", "3", "7"]);
+    assert_eq!(
+      serde_json::to_value(&processed).unwrap(),
+      serde_json::json!({ "ranges": [[1, 3], [7, 7]] })
+    );
+  }
+}
@@ -0,0 +1,217 @@
+//! `--format chunks`による、本文を区切った裁判例ごとのJSON Linesの書き出し
+//!
+//! RAGなどの前処理を省けるよう、本文を`--chunk-size`文字以内の塊に区切り、1行に1つの塊を書く。
+//! 各行には本文以外のフィールドをすべて付けるので、塊ごとにそのまま検索用のデータベースに登録できる。
+//! 塊の切れ目はなるべく`。`か改行の直後にし、隣り合う塊は`--chunk-overlap`文字ずつ重ねる。
+//!
+//! | フィールド | 内容 |
+//! |---|---|
+//! | `text` | 塊の本文 |
+//! | `chunk_index` | 0から始まる塊の番号 |
+//! | `chunk_count` | 塊の数。本文が無ければ0で、`text`が空の1行だけを書く |
+//! | `chunk_start`・`chunk_end` | 本文の中の塊の位置（文字数） |
+//!
+//! 読むときは重なりを除いて塊をつなぎ直すので、元の本文に戻せる。
+
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+use std::sync::OnceLock;
+
+const CHUNK_FIELDS: &[&str] = &[
+  "text",
+  "chunk_index",
+  "chunk_count",
+  "chunk_start",
+  "chunk_end",
+];
+
+#[derive(Debug, Clone, Copy)]
+struct ChunkSize {
+  size: usize,
+  overlap: usize,
+}
+
+static CHUNK_SIZE: OnceLock<ChunkSize> = OnceLock::new();
+
+/// 塊の文字数と重なりの文字数を設定する。2回目以降の呼び出しでは何もしない
+pub fn install(size: usize, overlap: usize) {
+  let size = size.max(1);
+  let _ = CHUNK_SIZE.set(ChunkSize {
+    size,
+    overlap: overlap.min(size - 1),
+  });
+}
+
+fn chunk_size() -> ChunkSize {
+  CHUNK_SIZE.get().copied().unwrap_or(ChunkSize {
+    size: 1000,
+    overlap: 100,
+  })
+}
+
+/// 本文の中の塊の位置（文字数）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+  pub start: usize,
+  pub end: usize,
+}
+
+/// 本文を塊に区切る
+pub fn split(chars: &[char]) -> Vec<Span> {
+  split_with(chars, chunk_size())
+}
+
+fn split_with(chars: &[char], ChunkSize { size, overlap }: ChunkSize) -> Vec<Span> {
+  let mut spans = Vec::new();
+  let mut start = 0;
+  while start < chars.len() {
+    let mut end = (start + size).min(chars.len());
+    if end < chars.len() {
+      // 塊の後半に文の切れ目があれば、そこで切る
+      if let Some(pos) = chars[start + size / 2..end]
+        .iter()
+        .rposition(|c| *c == '。' || *c == '\n')
+      {
+        end = start + size / 2 + pos + 1;
+      }
+    }
+    spans.push(Span { start, end });
+    if end == chars.len() {
+      break;
+    }
+    start = end.saturating_sub(overlap).max(start + 1);
+  }
+  spans
+}
+
+/// 裁判例のJSONを、本文の塊ごとのJSON Linesにする
+pub fn encode(value: &Value) -> Result<Vec<u8>> {
+  let mut meta = value
+    .as_object()
+    .cloned()
+    .ok_or_else(|| anyhow!("裁判例のJSONがオブジェクトではありません"))?;
+  let contents = meta.remove("contents");
+  let chars = contents
+    .as_ref()
+    .and_then(|v| v.as_str())
+    .unwrap_or_default()
+    .chars()
+    .collect::<Vec<_>>();
+  let spans = split(&chars);
+  let mut lines = Vec::new();
+  let mut write_line = |index: usize, span: Span| -> Result<()> {
+    let mut line = meta.clone();
+    line.insert(
+      "text".to_string(),
+      chars[span.start..span.end]
+        .iter()
+        .collect::<String>()
+        .into(),
+    );
+    line.insert("chunk_index".to_string(), index.into());
+    line.insert("chunk_count".to_string(), spans.len().into());
+    line.insert("chunk_start".to_string(), span.start.into());
+    line.insert("chunk_end".to_string(), span.end.into());
+    lines.extend(serde_json::to_vec(&line)?);
+    lines.push(b'\n');
+    Ok(())
+  };
+  if spans.is_empty() {
+    write_line(0, Span { start: 0, end: 0 })?;
+  }
+  for (index, span) in spans.iter().enumerate() {
+    write_line(index, *span)?;
+  }
+  Ok(lines)
+}
+
+fn usize_field(line: &Map<String, Value>, key: &str) -> Result<usize> {
+  line
+    .get(key)
+    .and_then(|v| v.as_u64())
+    .map(|n| n as usize)
+    .ok_or_else(|| anyhow!("塊に{key}がありません"))
+}
+
+/// 塊ごとのJSON Linesから、本文をつなぎ直した裁判例のJSONに戻す
+pub fn decode(bytes: &[u8]) -> Result<Value> {
+  let mut record: Option<Map<String, Value>> = None;
+  let mut contents = Vec::<char>::new();
+  let mut chunk_count = 0;
+  for line in std::str::from_utf8(bytes)?
+    .lines()
+    .filter(|l| !l.trim().is_empty())
+  {
+    let line: Map<String, Value> = serde_json::from_str(line)?;
+    chunk_count = usize_field(&line, "chunk_count")?;
+    let start = usize_field(&line, "chunk_start")?;
+    let text = line
+      .get("text")
+      .and_then(|v| v.as_str())
+      .unwrap_or_default();
+    // 前の塊と重なっている部分を除いてつなぐ
+    contents.extend(text.chars().skip(contents.len().saturating_sub(start)));
+    if record.is_none() {
+      let mut meta = line;
+      for field in CHUNK_FIELDS {
+        meta.remove(*field);
+      }
+      record = Some(meta);
+    }
+  }
+  let mut record = record.ok_or_else(|| anyhow!("塊が1つもありません"))?;
+  let contents = if chunk_count == 0 {
+    Value::Null
+  } else {
+    contents.into_iter().collect::<String>().into()
+  };
+  record.insert("contents".to_string(), contents);
+  Ok(Value::Object(record))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn spans(text: &str, size: usize, overlap: usize) -> Vec<(usize, usize)> {
+    let chars = text.chars().collect::<Vec<_>>();
+    split_with(&chars, ChunkSize { size, overlap })
+      .into_iter()
+      .map(|span| (span.start, span.end))
+      .collect()
+  }
+
+  #[test]
+  fn overlaps_adjacent_chunks() {
+    let text = "あ".repeat(25);
+    assert_eq!(spans(&text, 10, 3), [(0, 10), (7, 17), (14, 24), (21, 25)]);
+    assert_eq!(spans(&text, 10, 0), [(0, 10), (10, 20), (20, 25)]);
+    assert_eq!(spans("", 10, 3), []);
+  }
+
+  #[test]
+  fn cuts_after_sentence_end() {
+    // 後半に句点があればその直後で切る。前半の句点では切らない
+    assert_eq!(
+      spans("あいうえお。かきくけこさしすせそ", 10, 2),
+      [(0, 6), (4, 14), (12, 16)]
+    );
+    assert_eq!(
+      spans("あ。いうえおかきくけこさ", 10, 0),
+      [(0, 10), (10, 12)]
+    );
+  }
+
+  #[test]
+  fn round_trips_contents() {
+    let contents = "主文\n本件上告を棄却する。".repeat(200);
+    let value = json!({ "lawsuit_id": "99999", "contents": contents });
+    let bytes = encode(&value).unwrap();
+    assert!(bytes.iter().filter(|b| **b == b'\n').count() > 1);
+    assert_eq!(decode(&bytes).unwrap(), value);
+
+    let value = json!({ "lawsuit_id": "99999", "contents": null });
+    assert_eq!(decode(&encode(&value).unwrap()).unwrap(), value);
+  }
+}
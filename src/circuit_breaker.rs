@@ -0,0 +1,71 @@
+//! 再試行しても失敗するリクエストが続いたときに一定時間取得を止め、それでも回復しなければ中断するための仕組み
+
+use std::time::Duration;
+
+/// 失敗を記録したあとに取るべき行動
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakerAction {
+  /// そのまま続ける
+  Continue,
+  /// 指定された時間だけ止めてから続ける
+  Pause(Duration),
+  /// 回復の見込みが無いので中断する（理由の要約を持つ）
+  Abort(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+  /// 何件のリクエストが続けて失敗したら止めるか
+  threshold: usize,
+  /// 止める時間
+  cooldown: Duration,
+  /// 何回止めても回復しなければ中断するか
+  max_pauses: usize,
+  consecutive_failures: usize,
+  pauses: usize,
+  total_failures: usize,
+}
+
+impl CircuitBreaker {
+  pub fn new(threshold: usize, cooldown: Duration, max_pauses: usize) -> Self {
+    CircuitBreaker {
+      threshold: threshold.max(1),
+      cooldown,
+      max_pauses,
+      consecutive_failures: 0,
+      pauses: 0,
+      total_failures: 0,
+    }
+  }
+
+  pub fn record_success(&mut self) {
+    self.consecutive_failures = 0;
+    self.pauses = 0;
+  }
+
+  /// 再試行しても失敗したリクエストを1件記録する
+  pub fn record_failure(&mut self) -> BreakerAction {
+    self.consecutive_failures += 1;
+    self.total_failures += 1;
+    if self.consecutive_failures < self.threshold {
+      return BreakerAction::Continue;
+    }
+    if self.pauses >= self.max_pauses {
+      return BreakerAction::Abort(format!(
+        "{}件続けてリクエストに失敗し、{}回（各{}秒）休止しても回復しなかったため中断します（累計失敗回数：{}）",
+        self.threshold,
+        self.pauses,
+        self.cooldown.as_secs(),
+        self.total_failures
+      ));
+    }
+    self.pauses += 1;
+    self.consecutive_failures = 0;
+    BreakerAction::Pause(self.cooldown)
+  }
+
+  /// これまでに休止した回数（最後に成功してから）
+  pub fn pauses(&self) -> usize {
+    self.pauses
+  }
+}
@@ -0,0 +1,137 @@
+//! 実行の終了時に、CKAN互換のデータカタログAPIへデータセットのメタデータを登録・更新する
+//!
+//! `--ckan-url`と`--ckan-dataset`を与えると、実行がエラー無く終わったときに`package_show`でデータセットの有無を確かめ、
+//! 無ければ`package_create`で作り、あれば`package_patch`で件数・取得範囲・更新日時などを更新する。
+//! `--ckan-resource-base-url`を与えると、そのURLの下に一覧ファイルを公開しているものとしてリソースにも登録する。
+//! APIキーはコマンドライン引数に残らないよう、環境変数`LISTUP_CKAN_API_KEY`から読む。
+
+use crate::{compress, fetch, notify, output, summary::RunSummary, Args};
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use std::path::Path;
+use tracing::*;
+
+/// APIキーを読む環境変数
+const KEY_ENV: &str = "LISTUP_CKAN_API_KEY";
+
+struct Client {
+  http: reqwest::Client,
+  url: String,
+  key: String,
+}
+
+impl Client {
+  fn new(url: &str) -> Result<Self> {
+    Ok(Client {
+      http: reqwest::Client::builder()
+        .user_agent(fetch::USER_AGENT)
+        .build()?,
+      url: url.trim_end_matches('/').to_string(),
+      key: std::env::var(KEY_ENV)
+        .map_err(|_| anyhow!("CKANのAPIキーを環境変数{KEY_ENV}に設定してください"))?,
+    })
+  }
+
+  /// `action`を呼び、`result`を返す。`package_show`でデータセットが無いときは`None`
+  async fn call(&self, action: &str, body: &Value) -> Result<Option<Value>> {
+    let res = self
+      .http
+      .post(format!("{}/api/3/action/{action}", self.url))
+      .header(reqwest::header::AUTHORIZATION, &self.key)
+      .header(reqwest::header::CONTENT_TYPE, "application/json")
+      .body(serde_json::to_vec(body)?)
+      .send()
+      .await?;
+    let status = res.status();
+    let bytes = res.bytes().await?;
+    if status == reqwest::StatusCode::NOT_FOUND {
+      return Ok(None);
+    }
+    let value: Value = serde_json::from_slice(&bytes).map_err(|_| {
+      anyhow!(
+        "CKANの応答を読めません：{action}: {status}: {}",
+        String::from_utf8_lossy(&bytes)
+      )
+    })?;
+    if !status.is_success() || value.get("success") != Some(&Value::Bool(true)) {
+      return Err(anyhow!(
+        "CKANがエラーを返しました：{action}: {status}: {}",
+        value.get("error").unwrap_or(&value)
+      ));
+    }
+    Ok(value.get("result").cloned())
+  }
+}
+
+fn extra(key: &str, value: impl ToString) -> Value {
+  json!({ "key": key, "value": value.to_string() })
+}
+
+/// 登録するデータセットのメタデータ
+async fn package(args: &Args, dataset: &str, summary: &RunSummary) -> Result<Value> {
+  let records = output::read_value_lst(&args.index).await?.len();
+  let range = notify::range_text(args);
+  let mut package = json!({
+    "name": dataset,
+    "title": args.ckan_title,
+    "notes": format!(
+      "裁判所のホームページ（https://www.courts.go.jp）の裁判例検索から取得した裁判例の一覧と各裁判例のデータです。\n\n件数：{records}\n取得範囲：{range}"
+    ),
+    "url": "https://www.courts.go.jp/app/hanrei_jp/search1",
+    "extras": [
+      extra("records", records),
+      extra("range", &range),
+      extra("last_run_status", &summary.status),
+      extra("last_run_records_written", summary.records_written),
+      extra("last_updated", chrono::Local::now().to_rfc3339()),
+      extra("generator", format!("listup_precedent {}", env!("CARGO_PKG_VERSION"))),
+    ],
+  });
+  if let Some(org) = &args.ckan_owner_org {
+    package["owner_org"] = org.clone().into();
+  }
+  if let Some(license) = &args.ckan_license_id {
+    package["license_id"] = license.clone().into();
+  }
+  if let Some(base) = &args.ckan_resource_base_url {
+    let index = compress::existing_path(&args.index);
+    let name = Path::new(&index)
+      .file_name()
+      .map(|s| s.to_string_lossy().to_string())
+      .unwrap_or(index.clone());
+    package["resources"] = json!([{
+      "name": name,
+      "url": format!("{}/{name}", base.trim_end_matches('/')),
+      "description": "裁判例の一覧ファイル",
+    }]);
+  }
+  Ok(package)
+}
+
+/// `--ckan-url`のデータカタログに、`--ckan-dataset`のデータセットのメタデータを登録・更新する
+pub async fn publish(args: &Args, summary: &RunSummary) -> Result<()> {
+  let (Some(url), Some(dataset)) = (&args.ckan_url, &args.ckan_dataset) else {
+    return Ok(());
+  };
+  let client = Client::new(url)?;
+  let package = package(args, dataset, summary).await?;
+  let exists = client
+    .call("package_show", &json!({ "id": dataset }))
+    .await?
+    .is_some();
+  if exists {
+    let mut patch = package;
+    patch["id"] = dataset.clone().into();
+    client.call("package_patch", &patch).await?;
+    info!("updated CKAN dataset: {}/dataset/{}", url, dataset);
+  } else {
+    if args.ckan_owner_org.is_none() {
+      return Err(anyhow!(
+        "CKANにデータセットを作るには--ckan-owner-orgが必要です：{dataset}"
+      ));
+    }
+    client.call("package_create", &package).await?;
+    info!("created CKAN dataset: {}/dataset/{}", url, dataset);
+  }
+  Ok(())
+}
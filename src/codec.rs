@@ -0,0 +1,144 @@
+//! 一覧ファイルの展開と読み込み
+//!
+//! 圧縮の形式（gzip・zstd）も一覧ファイルの形式（JSONの配列・JSON Lines・MessagePack・CBOR）も先頭のバイトで見分けるので、
+//! 書き出したときの`--compress`や`--index-format`の指定によらず読める。
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::Deserialize;
+use serde_json::Value;
+use std::io::Read;
+use tracing::*;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// 先頭のバイトで圧縮の形式を見分けて展開する。圧縮されていなければそのまま返す
+pub fn decompress(bytes: Vec<u8>) -> Result<Vec<u8>> {
+  if bytes.starts_with(ZSTD_MAGIC) {
+    Ok(zstd::decode_all(&bytes[..])?)
+  } else if bytes.starts_with(GZIP_MAGIC) {
+    let mut decoded = Vec::new();
+    flate2::read::GzDecoder::new(&bytes[..]).read_to_end(&mut decoded)?;
+    Ok(decoded)
+  } else {
+    Ok(bytes)
+  }
+}
+
+/// 一覧ファイルの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum IndexFormat {
+  /// 全項目を1つのJSONの配列にする
+  Json,
+  /// 1行に1項目を書くJSON Lines
+  Jsonl,
+  /// 項目のMessagePackを順に並べたもの
+  Msgpack,
+  /// 項目のCBORを順に並べたもの（CBOR Sequence）
+  Cbor,
+}
+
+/// 一覧ファイルの先頭のバイトから形式を見分ける。MessagePack・CBORの項目はマップなので先頭のバイトで区別できる
+pub fn detect_index_format(bytes: &[u8]) -> IndexFormat {
+  match bytes.iter().find(|b| !b.is_ascii_whitespace()) {
+    Some(b'[') => IndexFormat::Json,
+    Some(0x80..=0x8f | 0xde | 0xdf) => IndexFormat::Msgpack,
+    Some(0xa0..=0xbf) => IndexFormat::Cbor,
+    _ => IndexFormat::Jsonl,
+  }
+}
+
+/// JSON Linesの一覧を読む。`lenient`のときは書きかけの最後の行を読み飛ばす
+fn parse_jsonl(s: &str, lenient: bool) -> Result<Vec<Value>> {
+  let lines = s
+    .lines()
+    .filter(|l| !l.trim().is_empty())
+    .collect::<Vec<_>>();
+  let mut lst = Vec::with_capacity(lines.len());
+  for (i, line) in lines.iter().enumerate() {
+    match serde_json::from_str(line) {
+      Ok(value) => lst.push(value),
+      Err(_) if lenient && i + 1 == lines.len() => {
+        warn!("ignored an incomplete last line of the index");
+      }
+      Err(e) => return Err(e.into()),
+    }
+  }
+  Ok(lst)
+}
+
+/// MessagePack・CBORの項目の並びを読む。`lenient`のときは書きかけの最後の項目を読み飛ばす
+fn parse_binary_seq(mut bytes: &[u8], format: IndexFormat, lenient: bool) -> Result<Vec<Value>> {
+  let mut lst = Vec::new();
+  while !bytes.is_empty() {
+    let value = match format {
+      IndexFormat::Msgpack => Value::deserialize(&mut rmp_serde::Deserializer::new(&mut bytes))
+        .map_err(anyhow::Error::from),
+      _ => ciborium::de::from_reader(&mut bytes).map_err(anyhow::Error::from),
+    };
+    match value {
+      Ok(value) => lst.push(value),
+      Err(_) if lenient => {
+        warn!("ignored an incomplete last entry of the index");
+        break;
+      }
+      Err(e) => return Err(e),
+    }
+  }
+  Ok(lst)
+}
+
+/// 閉じられていない・最後の項目が書きかけのJSONの配列を、読める項目までで閉じて読む
+fn parse_json_lenient(bytes: &[u8]) -> Result<Vec<Value>> {
+  let s = String::from_utf8_lossy(bytes);
+  if let Ok(lst) = serde_json::from_str(&s) {
+    return Ok(lst);
+  }
+  // 最後の項目が書きかけの場合に備えて、後ろの`}`から順に切り詰めて閉じてみる
+  for (pos, _) in s.rmatch_indices('}') {
+    if let Ok(lst) = serde_json::from_str(&format!("{}]", &s[..=pos])) {
+      return Ok(lst);
+    }
+  }
+  let trimmed = s.trim_end().trim_end_matches(',');
+  let lst = serde_json::from_str(&format!("{trimmed}]"))?;
+  Ok(lst)
+}
+
+/// 展開済みの一覧ファイルを、先頭のバイトで形式を見分けて読む
+///
+/// `lenient`のときは、書き込みの途中で止まって閉じられていない一覧ファイルも読める項目までを読む
+pub fn parse_index(bytes: &[u8], lenient: bool) -> Result<Vec<Value>> {
+  match (detect_index_format(bytes), lenient) {
+    (IndexFormat::Json, false) => Ok(serde_json::from_slice(bytes)?),
+    (IndexFormat::Json, true) => parse_json_lenient(bytes),
+    (IndexFormat::Jsonl, false) => parse_jsonl(std::str::from_utf8(bytes)?, false),
+    (IndexFormat::Jsonl, true) => parse_jsonl(&String::from_utf8_lossy(bytes), true),
+    (format, lenient) => parse_binary_seq(bytes, format, lenient),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Write;
+
+  #[test]
+  fn reads_compressed_and_unfinished_indexes() {
+    let jsonl = b"{\"lawsuit_id\":\"1\"}\n{\"lawsuit_id\":\"2\"}\n";
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(jsonl).unwrap();
+    let gzip = encoder.finish().unwrap();
+    let zstd = zstd::encode_all(&jsonl[..], 3).unwrap();
+    for bytes in [jsonl.to_vec(), gzip, zstd] {
+      let bytes = decompress(bytes).unwrap();
+      assert_eq!(detect_index_format(&bytes), IndexFormat::Jsonl);
+      assert_eq!(parse_index(&bytes, false).unwrap().len(), 2);
+    }
+
+    let unfinished = b"[\n{\"lawsuit_id\":\"1\"},\n{\"lawsuit_id\":\"2\"},\n{\"lawsu";
+    assert!(parse_index(unfinished, false).is_err());
+    assert_eq!(parse_index(unfinished, true).unwrap().len(), 2);
+  }
+}
@@ -0,0 +1,185 @@
+//! 旧スキーマに依存する下流ツールのための互換出力
+//!
+//! v1は`uuid`や`path`を付け加える前の一覧ファイルで、項目は`PrecedentInfo`をそのままシリアライズしたものである。
+//! 元の版と同じバイト列になるよう、`serde_json::Value`を経由せず（キーが辞書順に並び替わるため）、
+//! 元の版と同じくjplaw_ioで`PrecedentInfo`を書き出す。
+
+use crate::output::{self, commit_tmp, tmp_path};
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use japanese_law_xml_schema::law::Era;
+use jplaw_data_types::listup::PrecedentInfo;
+use jplaw_io::{flush_file_value_lst, gen_file_value_lst, write_value_lst};
+use tokio::fs::File;
+use tracing::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CompatVersion {
+  /// `uuid`などを付け加える前の、`PrecedentInfo`の配列
+  V1,
+}
+
+impl CompatVersion {
+  /// 互換indexのファイル名に付ける接尾辞
+  pub fn suffix(&self) -> &'static str {
+    match self {
+      CompatVersion::V1 => "v1",
+    }
+  }
+}
+
+/// `list.json`に対して`list.v1.json`のような互換indexのpathを生成する
+pub fn gen_compat_index_path(index: &str, version: CompatVersion) -> String {
+  match index.strip_suffix(".json") {
+    Some(stem) => format!("{stem}.{}.json", version.suffix()),
+    None => format!("{index}.{}", version.suffix()),
+  }
+}
+
+/// 元号の年を西暦の年にする
+pub fn era_to_ad_year(era: &Era, era_year: usize) -> usize {
+  listup_precedent_lib::date::era_to_ad_year(era, era_year)
+    .expect("裁判例の日付は昭和・平成・令和のいずれか")
+}
+
+/// 書き出し中の互換一覧ファイル
+///
+/// `.tmp`を付けたファイルに書き、`flush`で閉じたときに元の名前に変える。
+/// `--deterministic`でも並べ直さず、元の版と同じく書き出した順のままにする
+pub struct CompatIndex {
+  path: String,
+  file: Option<File>,
+}
+
+impl CompatIndex {
+  pub async fn create(path: &str) -> Result<Self> {
+    Ok(CompatIndex {
+      path: path.to_string(),
+      file: Some(gen_file_value_lst(&tmp_path(path)).await?),
+    })
+  }
+
+  /// `keep_existing`のときは既にある項目（前回の書きかけがあればその項目）を書き戻しておく
+  pub async fn open(path: &str, keep_existing: bool) -> Result<Self> {
+    let existing = if keep_existing {
+      output::read_value_lst_lenient(&output::unfinished_or(path)).await?
+    } else {
+      Vec::new()
+    };
+    let mut file = CompatIndex::create(path).await?;
+    for value in existing {
+      file.write(&serde_json::from_value(value)?).await?;
+    }
+    Ok(file)
+  }
+
+  pub async fn write(&mut self, info: &PrecedentInfo) -> Result<()> {
+    let file = self
+      .file
+      .as_mut()
+      .ok_or_else(|| anyhow!("一覧ファイルは閉じられています：{}", self.path))?;
+    write_value_lst(file, info).await?;
+    Ok(())
+  }
+
+  /// 一覧ファイルを閉じて元の名前に変える。2回目以降の呼び出しでは何もしない
+  pub async fn flush(&mut self) -> Result<()> {
+    let Some(mut file) = self.file.take() else {
+      return Ok(());
+    };
+    flush_file_value_lst(&mut file).await?;
+    commit_tmp(file, &self.path).await?;
+    crate::permissions::apply(&self.path).await?;
+    Ok(())
+  }
+}
+
+/// `entries`の一覧の項目で互換一覧ファイルを書き直す
+pub async fn rewrite(path: &str, entries: &[serde_json::Value]) -> Result<()> {
+  let mut file = CompatIndex::create(path).await?;
+  for value in entries {
+    file.write(&serde_json::from_value(value.clone())?).await?;
+  }
+  file.flush().await
+}
+
+/// `new_path`の互換一覧の項目を`path`の互換一覧にマージする。`lawsuit_id`が同じ項目は新しいもので置き換える
+pub async fn merge_index(path: &str, new_path: &str) -> Result<()> {
+  let existing = output::read_value_lst(path).await?;
+  let new = output::read_value_lst(new_path).await?;
+  let new_ids = new
+    .iter()
+    .filter_map(|v| v.get("lawsuit_id"))
+    .collect::<Vec<_>>();
+  let merged = existing
+    .iter()
+    .filter(|v| {
+      v.get("lawsuit_id")
+        .map_or(true, |id| !new_ids.contains(&id))
+    })
+    .chain(new.iter())
+    .cloned()
+    .collect::<Vec<_>>();
+  rewrite(path, &merged).await?;
+  info!("merged {} entries into {}", new.len(), path);
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::record::precedent_info_of;
+  use listup_precedent_lib::examples;
+
+  /// 元の版の`main`と同じ手順で一覧ファイルを書き出す
+  async fn write_like_baseline(path: &str, infos: &[PrecedentInfo]) {
+    let mut index_file = gen_file_value_lst(path).await.unwrap();
+    for info in infos {
+      write_value_lst(&mut index_file, info).await.unwrap();
+    }
+    flush_file_value_lst(&mut index_file).await.unwrap();
+  }
+
+  #[tokio::test]
+  async fn v1_index_matches_baseline_bytes() {
+    let dir = std::env::temp_dir().join(format!("listup_precedent_compat_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let mut second = examples::sample_data();
+    second.lawsuit_id = "100000".to_string();
+    second.case_number = "令和3(受)456".to_string();
+    let infos = [
+      precedent_info_of(&examples::sample_data()),
+      precedent_info_of(&second),
+    ];
+
+    let fixture = dir.join("baseline.json").to_string_lossy().to_string();
+    write_like_baseline(&fixture, &infos).await;
+    let path = dir.join("list.v1.json").to_string_lossy().to_string();
+    let mut index = CompatIndex::create(&path).await.unwrap();
+    for info in &infos {
+      index.write(info).await.unwrap();
+    }
+    index.flush().await.unwrap();
+
+    let expected = std::fs::read(&fixture).unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), expected);
+    assert!(!std::path::Path::new(&tmp_path(&path)).exists());
+
+    // 読み直して書き直しても（`--resume`・`retry-failed`・`repair`）同じバイト列になる
+    let entries = output::read_value_lst(&path).await.unwrap();
+    let keys = entries[0].as_object().unwrap().keys().collect::<Vec<_>>();
+    assert_eq!(
+      keys,
+      [
+        "case_number",
+        "court_name",
+        "date",
+        "lawsuit_id",
+        "trial_type"
+      ]
+    );
+    rewrite(&path, &entries).await.unwrap();
+    assert_eq!(std::fs::read(&path).unwrap(), expected);
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,83 @@
+//! 裁判例ごとのファイルと一覧ファイルの圧縮
+//!
+//! `--compress zstd`（または`gzip`）を与えると、裁判例ごとのファイルを`{ファイル名}.json.zst`のように圧縮して書き出し、
+//! 一覧ファイルは書き出し終えたあとに`list.json.zst`のように圧縮する。
+//! 読むときは先頭のバイトで圧縮の形式を見分けるので、`--compress`の指定によらず読める。
+
+use anyhow::Result;
+use clap::ValueEnum;
+pub use listup_precedent_lib::codec::decompress;
+use std::{io::Write, path::Path, sync::OnceLock};
+use tracing::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+  None,
+  Gzip,
+  Zstd,
+}
+
+/// zstdの圧縮レベル。本文の多い裁判例でも書き出しが取得の妨げにならない程度にする
+const ZSTD_LEVEL: i32 = 9;
+
+static COMPRESSION: OnceLock<Compression> = OnceLock::new();
+
+impl Compression {
+  /// 圧縮したファイルの名前の末尾に付ける拡張子
+  pub fn extension(self) -> &'static str {
+    match self {
+      Compression::None => "",
+      Compression::Gzip => ".gz",
+      Compression::Zstd => ".zst",
+    }
+  }
+
+  pub fn compress(self, bytes: &[u8]) -> Result<Vec<u8>> {
+    match self {
+      Compression::None => Ok(bytes.to_vec()),
+      Compression::Gzip => {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(bytes)?;
+        Ok(encoder.finish()?)
+      }
+      Compression::Zstd => Ok(zstd::encode_all(bytes, ZSTD_LEVEL)?),
+    }
+  }
+}
+
+/// 以降に書き出すファイルの圧縮の形式を設定する。2回目以降の呼び出しでは何もしない
+pub fn set_compression(compression: Compression) {
+  let _ = COMPRESSION.set(compression);
+}
+
+/// 書き出すファイルの圧縮の形式。設定されていなければ圧縮しない
+pub fn compression() -> Compression {
+  COMPRESSION.get().copied().unwrap_or(Compression::None)
+}
+
+/// `path`があればそれを、無ければ圧縮した`path.zst`・`path.gz`のうち存在するものを返す
+pub fn existing_path(path: &str) -> String {
+  if Path::new(path).exists() {
+    return path.to_string();
+  }
+  [Compression::Zstd, Compression::Gzip]
+    .iter()
+    .map(|c| format!("{path}{}", c.extension()))
+    .find(|p| Path::new(p).exists())
+    .unwrap_or_else(|| path.to_string())
+}
+
+/// 書き出し終えた`path`のファイルを設定された形式で圧縮し、元のファイルを消す
+pub async fn compress_file(path: &str) -> Result<()> {
+  let compression = compression();
+  if compression == Compression::None || !Path::new(path).exists() {
+    return Ok(());
+  }
+  let compressed_path = format!("{path}{}", compression.extension());
+  let bytes = tokio::fs::read(path).await?;
+  tokio::fs::write(&compressed_path, compression.compress(&bytes)?).await?;
+  crate::permissions::apply(&compressed_path).await?;
+  tokio::fs::remove_file(path).await?;
+  info!("compressed: {}", &compressed_path);
+  Ok(())
+}
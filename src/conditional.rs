@@ -0,0 +1,65 @@
+//! 再取得時に条件付きリクエスト（If-None-Match・If-Modified-Since）を送るための、
+//! URLごとのETag・Last-Modifiedを保存する小さなキャッシュ
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, path::Path, sync::Mutex};
+use tokio::fs;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Validator {
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub etag: Option<String>,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub last_modified: Option<String>,
+  /// 詳細ページの場合、そのページから生成した出力ファイル名
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub file_name: Option<String>,
+}
+
+pub struct ConditionalCache {
+  path: String,
+  entries: Mutex<HashMap<String, Validator>>,
+}
+
+impl ConditionalCache {
+  /// `path`のファイルが存在すれば読み込み、無ければ空のキャッシュを作る
+  pub async fn load(path: &str) -> Result<Self> {
+    let entries = if Path::new(path).exists() {
+      let s = fs::read_to_string(path).await?;
+      serde_json::from_str(&s)?
+    } else {
+      HashMap::new()
+    };
+    Ok(ConditionalCache {
+      path: path.to_string(),
+      entries: Mutex::new(entries),
+    })
+  }
+
+  pub async fn save(&self) -> Result<()> {
+    let s = serde_json::to_string(&*self.entries.lock().unwrap())?;
+    fs::write(&self.path, s).await?;
+    Ok(())
+  }
+
+  pub fn get(&self, url: &str) -> Option<Validator> {
+    self.entries.lock().unwrap().get(url).cloned()
+  }
+
+  pub fn update(&self, url: &str, etag: Option<String>, last_modified: Option<String>) {
+    if etag.is_none() && last_modified.is_none() {
+      return;
+    }
+    let mut entries = self.entries.lock().unwrap();
+    let entry = entries.entry(url.to_string()).or_default();
+    entry.etag = etag;
+    entry.last_modified = last_modified;
+  }
+
+  pub fn set_file_name(&self, url: &str, file_name: &str) {
+    let mut entries = self.entries.lock().unwrap();
+    let entry = entries.entry(url.to_string()).or_default();
+    entry.file_name = Some(file_name.to_string());
+  }
+}
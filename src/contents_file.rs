@@ -0,0 +1,62 @@
+//! `--contents-dir`による、PDFから抽出した本文の別ファイルへの書き出し
+//!
+//! 本文は裁判例のJSONの大半を占めるので、`--contents-dir contents`では本文を`output/contents/{ファイル名}.txt`に書き、
+//! JSONからは`contents`を除いて、代わりに出力フォルダからの相対パスを`contents_path`として書く。
+//! `--contents-gzip`では`.txt.gz`としてgzipで圧縮する。
+//! このツールの`export-*`などが裁判例のJSONを読むときは、`contents_path`のファイルから本文を読み戻す。
+
+use crate::{compress::Compression, output};
+use anyhow::Result;
+use std::{path::Path, sync::OnceLock};
+
+#[derive(Debug, Clone)]
+struct ContentsDir {
+  dir: String,
+  gzip: bool,
+}
+
+static CONTENTS_DIR: OnceLock<ContentsDir> = OnceLock::new();
+
+/// 以降に書き出す本文のフォルダを設定する。2回目以降の呼び出しでは何もしない
+pub fn install(dir: &str, gzip: bool) {
+  let _ = CONTENTS_DIR.set(ContentsDir {
+    dir: dir.trim_end_matches('/').to_string(),
+    gzip,
+  });
+}
+
+/// 本文を別ファイルに書き出すかどうか
+pub fn is_enabled() -> bool {
+  CONTENTS_DIR.get().is_some()
+}
+
+/// `contents_path`を実際のファイルのパスにする。絶対パスでなければ出力フォルダからの相対パス
+fn local_path(output: &str, reference: &str) -> String {
+  if Path::new(reference).is_absolute() {
+    reference.to_string()
+  } else {
+    format!("{output}/{reference}")
+  }
+}
+
+/// `file_name`の裁判例の本文を書き出し、JSONに書く`contents_path`を返す
+pub async fn write(output: &str, file_name: &str, text: &str) -> Result<String> {
+  let Some(contents_dir) = CONTENTS_DIR.get() else {
+    return Ok(String::new());
+  };
+  let (compression, extension) = if contents_dir.gzip {
+    (Compression::Gzip, ".txt.gz")
+  } else {
+    (Compression::None, ".txt")
+  };
+  let reference = format!("{}/{file_name}{extension}", contents_dir.dir);
+  let bytes = compression.compress(text.as_bytes())?;
+  output::write_side_file(output, &reference, bytes).await?;
+  Ok(reference)
+}
+
+/// `contents_path`のファイルから本文を読む
+pub async fn read(output: &str, reference: &str) -> Result<String> {
+  let bytes = tokio::fs::read(local_path(output, reference)).await?;
+  Ok(String::from_utf8(crate::compress::decompress(bytes)?)?)
+}
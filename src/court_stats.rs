@@ -0,0 +1,67 @@
+//! 裁判所の部・法廷ごとの月次件数を時系列のCSVにして書き出す
+//!
+//! `court_name`を「東京地方裁判所」のような裁判所と「民事第8部」のような部・法廷に分け、
+//! 裁判年月日（西暦）の月ごとに件数を数える。列は`court,division,month,count`で、裁判所・部・月の順に並べる。
+//! 文字コードは`--output-encoding`に従う。
+
+use crate::compat;
+use anyhow::Result;
+use jplaw_data_types::law::Date;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// `court_name`を裁判所と部・法廷に分ける
+fn split_court_name(court_name: &str) -> (&str, &str) {
+  match court_name.find("裁判所") {
+    Some(pos) => court_name.split_at(pos + "裁判所".len()),
+    None => (court_name, ""),
+  }
+}
+
+/// CSVの1つの値にする。カンマ・引用符・改行を含むときは引用符で囲む
+fn csv_field(s: &str) -> String {
+  if s.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", s.replace('"', "\"\""))
+  } else {
+    s.to_string()
+  }
+}
+
+/// `index`の一覧ファイルを読んで、部・法廷ごとの月次件数を`path`にCSVで書き出す
+pub async fn write(index: &str, path: &str) -> Result<()> {
+  let entries = crate::output::read_value_lst(index).await?;
+  let mut counts: BTreeMap<(String, String, String), usize> = BTreeMap::new();
+  for entry in &entries {
+    let (Some(court_name), Some(date)) = (
+      entry.get("court_name").and_then(Value::as_str),
+      entry.get("date"),
+    ) else {
+      continue;
+    };
+    let date: Date = serde_json::from_value(date.clone())?;
+    let month = match date.month {
+      Some(month) => format!(
+        "{:04}-{:02}",
+        compat::era_to_ad_year(&date.era, date.year),
+        month
+      ),
+      None => format!("{:04}", compat::era_to_ad_year(&date.era, date.year)),
+    };
+    let (court, division) = split_court_name(court_name.trim());
+    *counts
+      .entry((court.to_string(), division.trim().to_string(), month))
+      .or_default() += 1;
+  }
+  let mut csv = String::from("court,division,month,count\n");
+  for ((court, division, month), count) in counts {
+    csv.push_str(&format!(
+      "{},{},{},{}\n",
+      csv_field(&court),
+      csv_field(&division),
+      month,
+      count
+    ));
+  }
+  crate::text_encoding::write(path, &csv).await?;
+  Ok(())
+}
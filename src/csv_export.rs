@@ -0,0 +1,109 @@
+//! 既存の出力を平らなCSVにする`export-csv`サブコマンド
+//!
+//! 一覧ファイルの各項目について裁判例のJSONを読み、`--columns`で選んだフィールドを1行に並べる。
+//! 日付は西暦の`yyyy-mm-dd`にし、文字列以外の値はJSONのまま書く。
+//! Excelで開いても文字化けしないよう、先頭にBOMを付けたUTF-8で書き出す。
+//! `--output-encoding shift_jis`などを与えるとその文字コードで書き出す（BOMは付けない）。
+
+use crate::{compat, output, permissions, text_encoding, Args};
+use anyhow::Result;
+use jplaw_data_types::{law::Date, listup::PrecedentInfo};
+use serde_json::{Map, Value};
+use tracing::*;
+
+/// `--columns`を省略したときの列
+pub const DEFAULT_COLUMNS: &[&str] = &[
+  "lawsuit_id",
+  "uuid",
+  "case_number",
+  "case_name",
+  "court_name",
+  "date",
+  "trial_type",
+  "lawsuit_type",
+  "result_type",
+  "result",
+  "field",
+  "detail_page_link",
+  "full_pdf_link",
+];
+
+/// CSVの1つの値にする。カンマ・引用符・改行を含むときは引用符で囲む
+pub fn csv_field(s: &str) -> String {
+  if s.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", s.replace('"', "\"\""))
+  } else {
+    s.to_string()
+  }
+}
+
+/// 日付のオブジェクトは西暦の`yyyy-mm-dd`（月日が無ければその部分を省く）にする
+pub fn date_text(value: &Value) -> Option<String> {
+  let date: Date = serde_json::from_value(value.clone()).ok()?;
+  let year = compat::era_to_ad_year(&date.era, date.year);
+  Some(match (date.month, date.day) {
+    (Some(month), Some(day)) => format!("{year:04}-{month:02}-{day:02}"),
+    (Some(month), None) => format!("{year:04}-{month:02}"),
+    _ => format!("{year:04}"),
+  })
+}
+
+fn cell(record: &Map<String, Value>, column: &str) -> String {
+  match record.get(column) {
+    None | Some(Value::Null) => String::new(),
+    Some(Value::String(s)) => s.clone(),
+    Some(v) if column.ends_with("date") => date_text(v).unwrap_or_else(|| v.to_string()),
+    Some(v) => v.to_string(),
+  }
+}
+
+/// `args.index`の一覧と`args.output`の裁判例のJSONから、`columns`の列のCSVを`path`に書き出す
+pub async fn export(args: &Args, path: &str, columns: &[String]) -> Result<()> {
+  let entries = output::read_value_lst(&args.index).await?;
+  let mut csv = String::new();
+  if text_encoding::is_utf8() {
+    csv.push('\u{feff}');
+  }
+  csv.push_str(
+    &columns
+      .iter()
+      .map(|c| csv_field(c))
+      .collect::<Vec<_>>()
+      .join(","),
+  );
+  csv.push('\n');
+  let mut missing = 0;
+  for entry in entries {
+    // 一覧の項目に裁判例のJSONの項目を重ねて、どちらにあるフィールドも列にできるようにする
+    let mut record = match entry {
+      Value::Object(obj) => obj,
+      _ => continue,
+    };
+    let file_name = serde_json::from_value::<PrecedentInfo>(Value::Object(record.clone()))
+      .ok()
+      .map(|info| crate::partition::record_name(&info));
+    match file_name {
+      Some(name) if output::data_exists(&args.output, &name) => {
+        if let Value::Object(data) = output::read_record_value(&args.output, &name).await? {
+          record.extend(data);
+        }
+      }
+      _ => missing += 1,
+    }
+    csv.push_str(
+      &columns
+        .iter()
+        .map(|c| csv_field(&cell(&record, c)))
+        .collect::<Vec<_>>()
+        .join(","),
+    );
+    csv.push('\n');
+  }
+  if missing > 0 {
+    warn!("{missing} record file(s) not found; only index fields were exported for them");
+  }
+  text_encoding::write(path, &csv).await?;
+  permissions::apply(path).await?;
+  info!("[END] export csv: {}", path);
+  Ok(())
+}
@@ -0,0 +1,38 @@
+//! 一覧の項目と、出力フォルダにある裁判例のデータを組にして読み込む
+//!
+//! `export-parquet`・`export-arrow`・`export-akoma-ntoso`・`serve-graphql`など、既存の出力を読み直すサブコマンドが共通して使う。
+
+use crate::output;
+use anyhow::Result;
+use jplaw_data_types::listup::{PrecedentData, PrecedentInfo};
+use serde_json::Value;
+use tracing::*;
+
+/// 一覧の項目と、出力フォルダにあればその裁判例のデータ
+pub struct DatasetRow {
+  pub entry: Value,
+  pub info: PrecedentInfo,
+  pub data: Option<PrecedentData>,
+}
+
+/// `index`の一覧を読み、各項目に`output`の裁判例のデータを添える
+pub async fn load_dataset(index: &str, output: &str) -> Result<Vec<DatasetRow>> {
+  let entries = output::read_value_lst(index).await?;
+  let mut rows = Vec::with_capacity(entries.len());
+  let mut missing = 0;
+  for entry in entries {
+    let info: PrecedentInfo = serde_json::from_value(entry.clone())?;
+    let file_name = crate::partition::record_name(&info);
+    let data = if output::data_exists(output, &file_name) {
+      Some(output::read_data(output, &file_name).await?)
+    } else {
+      missing += 1;
+      None
+    };
+    rows.push(DatasetRow { entry, info, data });
+  }
+  if missing > 0 {
+    warn!("{missing} record file(s) not found; only index fields were exported for them");
+  }
+  Ok(rows)
+}
@@ -0,0 +1,114 @@
+//! 裁判年月日（[`Date`]）の比較・日数の加算・西暦と元号の変換
+//!
+//! [`Date`]は元号と元号の年で日付を持つので、そのままでは元号をまたいで大小を比べられない。
+//! [`DateExt`]で西暦の日付との変換や日数の加算を、[`OrdDate`]で大小の比較をできるようにする。
+//!
+//! ```
+//! use listup_precedent_lib::date::{Date, DateExt};
+//!
+//! let start = Date::gen_from_ad(2019, 4, 1);
+//! let end = Date::gen_from_ad(2019, 5, 31);
+//! let date = Date::gen_from_ad(2019, 5, 1);
+//! assert!(date.is_between(&start, &end));
+//! assert_eq!(date.ad_year(), Some(2019));
+//! assert_eq!(date.add_days(-1).and_then(|d| d.to_naive_date()), chrono::NaiveDate::from_ymd_opt(2019, 4, 30));
+//! ```
+//!
+//! 扱える元号は昭和・平成・令和だけで、それ以外の元号の日付は比較や変換の結果が`None`になる。
+
+use chrono::{Datelike, Days, NaiveDate};
+use std::cmp::Ordering;
+
+pub use japanese_law_xml_schema::law::Era;
+pub use jplaw_data_types::law::Date;
+
+/// 元号の年を西暦の年にする。昭和・平成・令和以外の元号では`None`
+pub fn era_to_ad_year(era: &Era, era_year: usize) -> Option<usize> {
+  match era {
+    Era::Showa => Some(1925 + era_year),
+    Era::Heisei => Some(1988 + era_year),
+    Era::Reiwa => Some(2018 + era_year),
+    _ => None,
+  }
+}
+
+/// 大小を比べられる西暦の日付
+///
+/// 月日が分からない日付は、月日を0として比べる（同じ年・月のどの日付よりも前になる）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct OrdDate {
+  pub year: usize,
+  pub month: usize,
+  pub day: usize,
+}
+
+impl OrdDate {
+  /// 扱えない元号の日付では`None`
+  pub fn of(date: &Date) -> Option<Self> {
+    Some(OrdDate {
+      year: era_to_ad_year(&date.era, date.year)?,
+      month: date.month.unwrap_or(0),
+      day: date.day.unwrap_or(0),
+    })
+  }
+}
+
+/// [`Date`]の比較・加算・変換
+pub trait DateExt: Sized {
+  /// 西暦の年
+  fn ad_year(&self) -> Option<usize>;
+
+  /// 西暦の日付にする。月日が無いか、日付として正しくなければ`None`
+  fn to_naive_date(&self) -> Option<NaiveDate>;
+
+  /// 西暦の日付を元号の日付にする
+  fn from_naive_date(date: NaiveDate) -> Self;
+
+  /// `days`日後（負なら前）の日付。月日が無い日付では`None`
+  fn add_days(&self, days: i64) -> Option<Self>;
+
+  /// 元号をまたいで比べる。どちらかが扱えない元号なら`None`
+  fn compare(&self, other: &Self) -> Option<Ordering>;
+
+  /// `start <= self && self <= end`か。どれかが扱えない元号なら偽
+  fn is_between(&self, start: &Self, end: &Self) -> bool;
+}
+
+impl DateExt for Date {
+  fn ad_year(&self) -> Option<usize> {
+    era_to_ad_year(&self.era, self.year)
+  }
+
+  fn to_naive_date(&self) -> Option<NaiveDate> {
+    NaiveDate::from_ymd_opt(self.ad_year()? as i32, self.month? as u32, self.day? as u32)
+  }
+
+  fn from_naive_date(date: NaiveDate) -> Self {
+    Date::gen_from_ad(
+      date.year() as usize,
+      date.month() as usize,
+      date.day() as usize,
+    )
+  }
+
+  fn add_days(&self, days: i64) -> Option<Self> {
+    let date = self.to_naive_date()?;
+    let moved = if days >= 0 {
+      date.checked_add_days(Days::new(days as u64))?
+    } else {
+      date.checked_sub_days(Days::new(days.unsigned_abs()))?
+    };
+    Some(Self::from_naive_date(moved))
+  }
+
+  fn compare(&self, other: &Self) -> Option<Ordering> {
+    Some(OrdDate::of(self)?.cmp(&OrdDate::of(other)?))
+  }
+
+  fn is_between(&self, start: &Self, end: &Self) -> bool {
+    match (OrdDate::of(start), OrdDate::of(self), OrdDate::of(end)) {
+      (Some(start), Some(date), Some(end)) => start <= date && date <= end,
+      _ => false,
+    }
+  }
+}
@@ -0,0 +1,79 @@
+//! 判決文の本文をdiffで比べやすくした「diff可能なテキスト正規形」
+//!
+//! PDFから抽出した本文は、PDFの版が変わると行の折り返し位置や空白の入り方が変わり、
+//! そのままdiffを取ると内容の変わっていない行まで差分になる。正規形では次のようにして、折り返しに左右されない形にする。
+//!
+//! 1. `- 1 -`のようなページ番号だけの行を取り除く
+//! 2. 改行と空白（全角の空白を含む）をすべて取り除き、全角の英数字を半角にする
+//! 3. 「。」（直後に閉じ括弧が続くときはその閉じ括弧）の後で改行し、1文を1行にする
+//!
+//! `--diff-text`を与えると、正規形を`{出力フォルダ}/{ファイル名}.diff.txt`に書き出す。
+//! 本文が前回の正規形から変わっていたら、前回のものを`{ファイル名}.diff.prev.txt`に残すので、
+//! `diff`コマンドでPDFの差し替えで変わった箇所を確かめられる。
+
+use anyhow::Result;
+use regex::Regex;
+use std::{path::Path, sync::OnceLock};
+use tokio::fs;
+use tracing::*;
+
+/// `- 1 -`のようなページ番号だけの行
+pub fn page_number_line() -> &'static Regex {
+  static RE: OnceLock<Regex> = OnceLock::new();
+  RE.get_or_init(|| Regex::new(r"^[-－‐―ー]?\s*[0-9０-９]+\s*[-－‐―ー]?$").unwrap())
+}
+
+/// 文の終わりの後に続いて、同じ行に含める閉じ括弧
+fn is_closing_bracket(c: char) -> bool {
+  matches!(c, '」' | '』' | '）' | ')' | '】' | '〕')
+}
+
+/// 本文をdiff可能なテキスト正規形にする
+pub fn normalize(text: &str) -> String {
+  let joined = text
+    .lines()
+    .filter(|line| !page_number_line().is_match(line.trim()))
+    .map(crate::stable_id::normalize)
+    .collect::<String>();
+  let mut normalized = String::with_capacity(joined.len());
+  let mut chars = joined.chars().peekable();
+  while let Some(c) = chars.next() {
+    normalized.push(c);
+    if c == '。' {
+      while let Some(&next) = chars.peek() {
+        if !is_closing_bracket(next) {
+          break;
+        }
+        normalized.push(next);
+        chars.next();
+      }
+      normalized.push('\n');
+    }
+  }
+  if !normalized.ends_with('\n') {
+    normalized.push('\n');
+  }
+  normalized
+}
+
+/// 正規形を書き出す。前回の正規形から変わっていたら前回のものを残して`true`を返す
+pub async fn write(output: &str, file_name: &str, contents: &str) -> Result<bool> {
+  let path = format!("{output}/{file_name}.diff.txt");
+  let normalized = normalize(contents);
+  let changed = if Path::new(&path).exists() {
+    let previous = fs::read_to_string(&path).await?;
+    if previous == normalized {
+      return Ok(false);
+    }
+    let prev_path = format!("{output}/{file_name}.diff.prev.txt");
+    fs::write(&prev_path, previous).await?;
+    crate::permissions::apply(&prev_path).await?;
+    info!("contents changed: {}", &path);
+    true
+  } else {
+    false
+  };
+  fs::write(&path, normalized).await?;
+  crate::permissions::apply(&path).await?;
+  Ok(changed)
+}
@@ -0,0 +1,88 @@
+//! 出力先の空き容量と推定必要容量の比較
+
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use tracing::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum DiskCheckPolicy {
+  /// 容量が不足しそうなら警告だけ出して続ける
+  Warn,
+  /// 容量が不足しそうなら取得を始めずに中止する
+  Abort,
+  /// 確認しない
+  Off,
+}
+
+fn format_bytes(bytes: u64) -> String {
+  const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+  let mut value = bytes as f64;
+  let mut unit = 0;
+  while value >= 1024.0 && unit < UNITS.len() - 1 {
+    value /= 1024.0;
+    unit += 1;
+  }
+  format!("{value:.1}{}", UNITS[unit])
+}
+
+/// 1件あたりの出力サイズ（バイト）。`--save-pdf`でPDFも残すときは`avg_pdf_kib`を与えてPDFの分を足す
+pub fn record_bytes(avg_record_kib: u64, avg_pdf_kib: Option<u64>) -> u64 {
+  avg_record_kib
+    .saturating_add(avg_pdf_kib.unwrap_or(0))
+    .saturating_mul(1024)
+}
+
+/// 件数×1件あたりの平均サイズで推定した必要容量と`path`の空き容量を比べる
+pub fn check_disk_space(
+  path: &str,
+  record_count: usize,
+  avg_record_bytes: u64,
+  policy: DiskCheckPolicy,
+) -> Result<()> {
+  if policy == DiskCheckPolicy::Off {
+    return Ok(());
+  }
+  let required = avg_record_bytes.saturating_mul(record_count as u64);
+  let available = match fs2::available_space(path) {
+    Ok(v) => v,
+    Err(e) => {
+      warn!("failed to get available disk space of {path}: {e}");
+      return Ok(());
+    }
+  };
+  info!(
+    "disk space: estimated {} ({} records x {}), available {}",
+    format_bytes(required),
+    record_count,
+    format_bytes(avg_record_bytes),
+    format_bytes(available)
+  );
+  if required <= available {
+    return Ok(());
+  }
+  let message = format!(
+    "出力先の空き容量が不足する可能性があります（推定必要容量：{}、空き容量：{}）",
+    format_bytes(required),
+    format_bytes(available)
+  );
+  match policy {
+    DiskCheckPolicy::Abort => Err(anyhow!(message)),
+    _ => {
+      warn!("{message}");
+      Ok(())
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn saving_pdfs_raises_the_estimate() {
+    assert_eq!(record_bytes(64, None), 64 * 1024);
+    assert_eq!(record_bytes(64, Some(512)), (64 + 512) * 1024);
+    assert!(record_bytes(64, Some(512)) > record_bytes(64, None));
+    assert_eq!(record_bytes(u64::MAX, Some(1)), u64::MAX);
+  }
+}
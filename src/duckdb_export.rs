@@ -0,0 +1,47 @@
+//! 既存の出力をDuckDBで分析するための`export-duckdb`サブコマンド
+//!
+//! `{dir}/parquet`に[`crate::parquet_export`]と同じ年ごとのParquetのデータセットを書き出し、
+//! それを読み込むビューを作るSQLを`{dir}/schema.sql`に書き出す。
+//! 裁判年月日はParquetの時点で`DATE`型の`date`列になっているので、`duckdb analysis.duckdb < schema.sql`のように読み込めば
+//! 期間での絞り込みや月ごとの集計をそのまま書ける。
+
+use crate::{parquet_export, permissions};
+use anyhow::Result;
+use std::path::Path;
+use tokio::fs;
+use tracing::*;
+
+/// SQLの文字列リテラルにする
+fn sql_string(s: &str) -> String {
+  format!("'{}'", s.replace('\'', "''"))
+}
+
+/// `parquet_glob`のParquetを読み込むビューを作るSQL
+fn schema_sql(parquet_glob: &str) -> String {
+  format!(
+    "-- listup_precedent export-duckdb
+CREATE OR REPLACE VIEW precedents AS
+SELECT * FROM read_parquet({}, hive_partitioning = false);
+",
+    sql_string(parquet_glob)
+  )
+}
+
+/// `index`の一覧と`output`の裁判例のJSONから、`dir`にParquetとビューを作るSQLを書き出す。書き出した件数を返す
+pub async fn export(index: &str, output: &str, dir: &str) -> Result<usize> {
+  let parquet_dir = format!("{dir}/parquet");
+  let len = parquet_export::export(index, output, &parquet_dir).await?;
+  // DuckDBをどのディレクトリで起動しても読めるよう、絶対パスで参照する
+  let parquet_dir = match Path::new(&parquet_dir).canonicalize() {
+    Ok(path) => path.to_string_lossy().into_owned(),
+    Err(e) => {
+      warn!("failed to resolve {parquet_dir}: {e}; using the relative path");
+      parquet_dir
+    }
+  };
+  let sql_path = format!("{dir}/schema.sql");
+  fs::write(&sql_path, schema_sql(&format!("{parquet_dir}/*/*.parquet"))).await?;
+  permissions::apply(&sql_path).await?;
+  info!("duckdb schema: {}", &sql_path);
+  Ok(len)
+}
@@ -0,0 +1,175 @@
+//! 最高裁判所の英訳判例ページからの英文要旨の取り込み
+//!
+//! 英訳のある判例は[英訳判例の一覧](https://www.courts.go.jp/app/hanrei_en/list)に載っている。
+//! 一覧を最初に一度だけ全ページ取得し、行に書かれた裁判年月日（西暦）と事件番号の番号部分の組で引けるようにしておく。
+//! 最高裁判所の判例で組が一致するものがあれば、その英訳ページの要旨を`en_summary`として取り込む。
+
+use crate::{compat, fetch::Fetcher, COURTS_DOMEIN};
+use anyhow::Result;
+use jplaw_data_types::listup::PrecedentData;
+use regex::Regex;
+use scraper::{ElementRef, Html, Selector};
+use serde::Serialize;
+use std::{collections::HashMap, sync::OnceLock};
+use tokio::sync::OnceCell;
+use tracing::*;
+
+/// 英訳判例の一覧ページ
+const EN_LIST_PATH: &str = "/app/hanrei_en/list";
+
+/// 英訳判例の一覧を読む最大のページ数
+const MAX_LIST_PAGES: usize = 200;
+
+/// 裁判年月日（`yyyy-mm-dd`）と事件番号の番号部分から英訳ページへのリンクへの対応
+type EnglishIndex = HashMap<(String, String), String>;
+
+static INDEX: OnceCell<EnglishIndex> = OnceCell::const_new();
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EnSummary {
+  pub link: String,
+  pub summary: Option<String>,
+}
+
+fn en_date_re() -> &'static Regex {
+  static RE: OnceLock<Regex> = OnceLock::new();
+  RE.get_or_init(|| {
+    Regex::new(r"(January|February|March|April|May|June|July|August|September|October|November|December)\s+(\d{1,2}),\s*(\d{4})").unwrap()
+  })
+}
+
+fn en_number_re() -> &'static Regex {
+  static RE: OnceLock<Regex> = OnceLock::new();
+  RE.get_or_init(|| Regex::new(r"No\.\s*(\d+)").unwrap())
+}
+
+fn month_number(name: &str) -> usize {
+  [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+  ]
+  .iter()
+  .position(|m| *m == name)
+  .map_or(0, |i| i + 1)
+}
+
+/// 英訳判例の一覧の1行から、裁判年月日と事件番号の番号部分を読み取る
+fn row_key(text: &str) -> Option<(String, String)> {
+  let date = en_date_re().captures(text)?;
+  let number = en_number_re().captures(text)?;
+  Some((
+    format!(
+      "{}-{:02}-{:02}",
+      &date[3],
+      month_number(&date[1]),
+      date[2].parse::<usize>().ok()?
+    ),
+    number[1].trim_start_matches('0').to_string(),
+  ))
+}
+
+async fn load_index(fetcher: &Fetcher) -> Result<EnglishIndex> {
+  let row_selector = Selector::parse("tr").unwrap();
+  let link_selector = Selector::parse("a[href*=\"/app/hanrei_en/detail\"]").unwrap();
+  let mut index = EnglishIndex::new();
+  for page in 1..=MAX_LIST_PAGES {
+    let html = fetcher
+      .get_text(&format!("{COURTS_DOMEIN}{EN_LIST_PATH}?page={page}"))
+      .await?;
+    let document = Html::parse_document(&html);
+    let mut found = false;
+    for row in document.select(&row_selector) {
+      let Some(href) = row
+        .select(&link_selector)
+        .next()
+        .and_then(|a| a.value().attr("href"))
+      else {
+        continue;
+      };
+      found = true;
+      let text = row.text().collect::<Vec<_>>().join(" ");
+      if let Some(key) = row_key(&text) {
+        let link = if href.starts_with("http") {
+          href.to_string()
+        } else {
+          format!("{COURTS_DOMEIN}{href}")
+        };
+        index.insert(key, link);
+      }
+    }
+    if !found {
+      break;
+    }
+  }
+  info!("english judgments: {}", index.len());
+  Ok(index)
+}
+
+/// 判例の裁判年月日（西暦）と事件番号の番号部分
+fn precedent_key(data: &PrecedentData) -> Option<(String, String)> {
+  let case_number = crate::stable_id::normalize(&data.case_number);
+  let number = case_number
+    .trim_end_matches('号')
+    .rsplit(|c: char| !c.is_ascii_digit())
+    .next()
+    .filter(|n| !n.is_empty())?;
+  let date = &data.date;
+  Some((
+    format!(
+      "{:04}-{:02}-{:02}",
+      compat::era_to_ad_year(&date.era, date.year),
+      date.month?,
+      date.day?
+    ),
+    number.trim_start_matches('0').to_string(),
+  ))
+}
+
+/// 英訳ページの「Summary」の見出しに続く本文
+fn parse_summary(html: &str) -> Option<String> {
+  let document = Html::parse_document(html);
+  let dt_selector = Selector::parse("dt, th").unwrap();
+  let dt = document
+    .select(&dt_selector)
+    .find(|e| e.text().collect::<String>().contains("Summary"))?;
+  let dd = dt
+    .next_siblings()
+    .filter_map(ElementRef::wrap)
+    .find(|e| matches!(e.value().name(), "dd" | "td"))?;
+  let text = dd
+    .text()
+    .map(str::trim)
+    .filter(|s| !s.is_empty())
+    .collect::<Vec<_>>()
+    .join("\n");
+  (!text.is_empty()).then_some(text)
+}
+
+/// 最高裁判所の判例で英訳ページがあれば、そのリンクと英文要旨を返す
+pub async fn find(fetcher: &Fetcher, data: &PrecedentData) -> Result<Option<EnSummary>> {
+  if !data.court_name.contains("最高裁判所") {
+    return Ok(None);
+  }
+  let Some(key) = precedent_key(data) else {
+    return Ok(None);
+  };
+  let index = INDEX.get_or_try_init(|| load_index(fetcher)).await?;
+  let Some(link) = index.get(&key) else {
+    return Ok(None);
+  };
+  let html = fetcher.get_text(link).await?;
+  Ok(Some(EnSummary {
+    link: link.clone(),
+    summary: parse_summary(&html),
+  }))
+}
@@ -0,0 +1,216 @@
+//! 既存の出力をElasticsearch・OpenSearchに登録する`export-es`サブコマンド
+//!
+//! インデックスが無ければ、事件名・要旨・本文などの文字列をkuromojiで形態素解析するマッピングで作ってから、
+//! 一覧の項目に裁判例のJSONを重ねたものを`_bulk`APIで`--batch-size`件ずつ登録する。
+//! ドキュメントのIDは`lawsuit_id`なので、同じインデックスに登録し直すと上書きになる。
+//! kuromojiを使うので、Elasticsearchには`analysis-kuromoji`プラグインが入っている必要がある。
+//! 認証が必要な場合のパスワードは環境変数`LISTUP_ES_PASSWORD`から読む。
+
+use crate::{csv_export, fetch, output};
+use anyhow::{anyhow, Result};
+use serde_json::{json, Map, Value};
+use tracing::*;
+
+/// 認証のパスワードを読む環境変数
+const PASSWORD_ENV: &str = "LISTUP_ES_PASSWORD";
+
+/// 形態素解析して全文検索する文字列のフィールド
+const TEXT_FIELDS: &[&str] = &[
+  "case_name",
+  "result",
+  "gist",
+  "case_gist",
+  "ref_law",
+  "contents",
+];
+
+/// 完全一致で絞り込む文字列のフィールド
+const KEYWORD_FIELDS: &[&str] = &[
+  "lawsuit_id",
+  "uuid",
+  "case_number",
+  "trial_type",
+  "lawsuit_type",
+  "result_type",
+  "field",
+  "detail_page_link",
+  "full_pdf_link",
+];
+
+fn index_body() -> Value {
+  let mut properties = Map::new();
+  for field in TEXT_FIELDS {
+    properties.insert(
+      field.to_string(),
+      json!({ "type": "text", "analyzer": "ja" }),
+    );
+  }
+  for field in KEYWORD_FIELDS {
+    properties.insert(field.to_string(), json!({ "type": "keyword" }));
+  }
+  properties.insert(
+    "court_name".to_string(),
+    json!({ "type": "text", "analyzer": "ja", "fields": { "raw": { "type": "keyword" } } }),
+  );
+  properties.insert(
+    "date".to_string(),
+    json!({ "type": "date", "format": "yyyy-MM-dd||yyyy-MM||yyyy" }),
+  );
+  json!({
+    "settings": {
+      "analysis": {
+        "analyzer": {
+          "ja": {
+            "type": "custom",
+            "tokenizer": "kuromoji_tokenizer",
+            "filter": ["kuromoji_baseform", "kuromoji_part_of_speech", "cjk_width", "ja_stop", "kuromoji_stemmer", "lowercase"]
+          }
+        }
+      }
+    },
+    "mappings": { "properties": properties }
+  })
+}
+
+/// Elasticsearchのクライアント
+struct Client {
+  http: reqwest::Client,
+  url: String,
+  user: Option<String>,
+  password: Option<String>,
+}
+
+impl Client {
+  fn new(url: &str, user: Option<&str>) -> Result<Self> {
+    let password = match user {
+      Some(_) => Some(std::env::var(PASSWORD_ENV).map_err(|_| {
+        anyhow!("Elasticsearchのパスワードを環境変数{PASSWORD_ENV}に設定してください")
+      })?),
+      None => None,
+    };
+    Ok(Client {
+      http: reqwest::Client::builder()
+        .user_agent(fetch::USER_AGENT)
+        .build()?,
+      url: url.trim_end_matches('/').to_string(),
+      user: user.map(|u| u.to_string()),
+      password,
+    })
+  }
+
+  fn request(&self, method: reqwest::Method, path: &str) -> reqwest::RequestBuilder {
+    let req = self.http.request(method, format!("{}/{path}", self.url));
+    match &self.user {
+      Some(user) => req.basic_auth(user, self.password.as_ref()),
+      None => req,
+    }
+  }
+
+  /// インデックスが無ければマッピングを設定して作る
+  async fn ensure_index(&self, name: &str) -> Result<()> {
+    let res = self.request(reqwest::Method::HEAD, name).send().await?;
+    if res.status().is_success() {
+      info!("elasticsearch index exists: {name}");
+      return Ok(());
+    }
+    if res.status() != reqwest::StatusCode::NOT_FOUND {
+      return Err(anyhow!(
+        "インデックスを確かめられません：{name}: {}",
+        res.status()
+      ));
+    }
+    self
+      .request(reqwest::Method::PUT, name)
+      .header(reqwest::header::CONTENT_TYPE, "application/json")
+      .body(serde_json::to_vec(&index_body())?)
+      .send()
+      .await?
+      .error_for_status()?;
+    info!("elasticsearch index created: {name}");
+    Ok(())
+  }
+
+  /// `_bulk`APIで登録する。登録に失敗したドキュメントの数を返す
+  async fn bulk(&self, body: String) -> Result<usize> {
+    let bytes = self
+      .request(reqwest::Method::POST, "_bulk")
+      .header(reqwest::header::CONTENT_TYPE, "application/x-ndjson")
+      .body(body)
+      .send()
+      .await?
+      .error_for_status()?
+      .bytes()
+      .await?;
+    let res: Value = serde_json::from_slice(&bytes)?;
+    if res.get("errors").and_then(Value::as_bool) != Some(true) {
+      return Ok(0);
+    }
+    let failed = res
+      .get("items")
+      .and_then(Value::as_array)
+      .into_iter()
+      .flatten()
+      .filter_map(|item| item.get("index")?.get("error"))
+      .inspect(|error| warn!("failed to index document: {error}"))
+      .count();
+    Ok(failed)
+  }
+}
+
+/// 一覧の項目に裁判例のJSONを重ねて、登録するドキュメントにする
+async fn document(entry: Value, output: &str) -> Result<Option<Map<String, Value>>> {
+  let Some(mut doc) = output::read_merged_record(entry, output).await? else {
+    return Ok(None);
+  };
+  // `_`で始まるフィールドはElasticsearchのメタデータと衝突するので登録しない
+  doc.retain(|key, _| !key.starts_with('_'));
+  if let Some(date) = doc.get("date").and_then(csv_export::date_text) {
+    doc.insert("date".to_string(), date.into());
+  }
+  Ok(Some(doc))
+}
+
+/// `index`の一覧と`output`の裁判例のJSONを、`url`のElasticsearchの`es_index`に登録する。登録した件数を返す
+pub async fn export(
+  index: &str,
+  output: &str,
+  url: &str,
+  es_index: &str,
+  batch_size: usize,
+  user: Option<&str>,
+) -> Result<usize> {
+  let client = Client::new(url, user)?;
+  client.ensure_index(es_index).await?;
+  let entries = output::read_value_lst(index).await?;
+  let total = entries.len();
+  let mut indexed = 0;
+  let mut failed = 0;
+  for chunk in entries.chunks(batch_size.max(1)) {
+    let mut body = String::new();
+    let mut len = 0;
+    for entry in chunk {
+      let Some(doc) = document(entry.clone(), output).await? else {
+        continue;
+      };
+      let Some(id) = doc.get("lawsuit_id").and_then(Value::as_str) else {
+        continue;
+      };
+      body.push_str(&json!({ "index": { "_index": es_index, "_id": id } }).to_string());
+      body.push('\n');
+      body.push_str(&Value::Object(doc).to_string());
+      body.push('\n');
+      len += 1;
+    }
+    if len == 0 {
+      continue;
+    }
+    let batch_failed = client.bulk(body).await?;
+    failed += batch_failed;
+    indexed += len - batch_failed;
+    info!("indexed {}/{} documents", indexed + failed, total);
+  }
+  if failed > 0 {
+    warn!("{failed} document(s) failed to be indexed");
+  }
+  Ok(indexed)
+}
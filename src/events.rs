@@ -0,0 +1,96 @@
+//! ラッパースクリプトから進捗を監視するための、1行1イベントのJSONを標準エラーに流す仕組み
+//!
+//! 各行は`{"event":"page_started","timestamp":1700000000000,...}`のように
+//! イベント名とUNIX時間（ミリ秒）とイベント固有のフィールドを持つ。
+//!
+//! JSON-RPCモードでは同じ内容を`event`メソッドの通知として標準出力に流す。
+//!
+//! 出力するかどうかにかかわらず、実行の集計のために件数を数えておく。
+
+use crate::summary::RunSummary;
+use serde_json::{Map, Value};
+use std::{
+  io::Write,
+  sync::Mutex,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+pub struct Events {
+  enabled: bool,
+  rpc: bool,
+  summary: Mutex<RunSummary>,
+}
+
+impl Events {
+  pub fn new(enabled: bool) -> Self {
+    Events {
+      enabled,
+      rpc: false,
+      summary: Mutex::new(RunSummary::default()),
+    }
+  }
+
+  /// JSON-RPCの通知として標準出力に流す
+  pub fn rpc() -> Self {
+    Events {
+      enabled: true,
+      rpc: true,
+      summary: Mutex::new(RunSummary::default()),
+    }
+  }
+
+  /// これまでに流れたイベントの集計
+  pub fn summary(&self) -> RunSummary {
+    self.summary.lock().unwrap().clone()
+  }
+
+  fn count(&self, event: &str, fields: &Value) {
+    let mut summary = self.summary.lock().unwrap();
+    match event {
+      "record_written" => summary.records_written += 1,
+      "record_skipped" => summary.records_skipped += 1,
+      "page_started" => summary.pages += 1,
+      "page_links" => {
+        let count = |key: &str| fields.get(key).and_then(Value::as_u64).unwrap_or(0) as usize;
+        summary.unique_links += count("new_links");
+        summary.duplicate_links += count("duplicate_links");
+      }
+      "error" if fields.get("fatal") == Some(&Value::Bool(false)) => summary.records_failed += 1,
+      _ => {}
+    }
+  }
+
+  /// `fields`はイベント固有のフィールドを持つJSONオブジェクト
+  pub fn emit(&self, event: &str, fields: Value) {
+    self.count(event, &fields);
+    if !self.enabled {
+      return;
+    }
+    let mut obj = Map::new();
+    obj.insert("event".to_string(), Value::from(event));
+    let timestamp = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_millis() as u64)
+      .unwrap_or_default();
+    obj.insert("timestamp".to_string(), Value::from(timestamp));
+    if let Value::Object(fields) = fields {
+      obj.extend(fields);
+    }
+    // 書き込みに失敗しても取得処理自体は続ける
+    if self.rpc {
+      let line = serde_json::json!({
+        "jsonrpc": "2.0",
+        "method": "event",
+        "params": Value::Object(obj),
+      })
+      .to_string();
+      let mut stdout = std::io::stdout().lock();
+      let _ = writeln!(stdout, "{line}");
+      let _ = stdout.flush();
+    } else {
+      let line = Value::Object(obj).to_string();
+      let mut stderr = std::io::stderr().lock();
+      let _ = writeln!(stderr, "{line}");
+    }
+  }
+}
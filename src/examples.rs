@@ -0,0 +1,64 @@
+//! 下流のツールのテストデータに使える、匿名化したサンプルの裁判例
+//!
+//! [`SAMPLE_DETAIL_HTML`]は裁判所のホームページの詳細ページと同じ構造を持つHTMLで、
+//! 事件番号・当事者・リンクなどは実在の判例のものではない。
+//! [`sample_data`]はこのHTMLを`listup_precedent`の詳細ページの解析処理にかけた結果で、
+//! 解析処理を変えたときは`listup_precedent canary --sample`で食い違いが無いかを確かめる。
+//!
+//! ```
+//! let data = listup_precedent_lib::examples::sample_data();
+//! assert_eq!(data.lawsuit_id, "99999");
+//! assert!(data.contents.is_none());
+//! ```
+
+use japanese_law_xml_schema::law::Era;
+use jplaw_data_types::{law::Date, listup::PrecedentData, precedent::TrialType};
+
+/// 匿名化したサンプルの詳細ページのHTML
+pub const SAMPLE_DETAIL_HTML: &str = include_str!("examples/sample_detail.html");
+
+/// サンプルの詳細ページのリンク
+pub const SAMPLE_DETAIL_PAGE_LINK: &str = "https://www.courts.go.jp/app/hanrei_jp/detail2?id=99999";
+
+/// [`SAMPLE_DETAIL_HTML`]から生成した裁判例のデータ
+///
+/// 本文はPDFから取るので、`contents`は`None`になる
+pub fn sample_data() -> PrecedentData {
+  PrecedentData {
+    trial_type: TrialType::SupremeCourt,
+    date: Date {
+      era: Era::Reiwa,
+      year: 4,
+      month: Some(3),
+      day: Some(24),
+    },
+    case_number: "令和3(受)9999".to_string(),
+    case_name: "損害賠償請求事件".to_string(),
+    court_name: "最高裁判所第一小法廷".to_string(),
+    right_type: None,
+    lawsuit_type: None,
+    result_type: Some("判決".to_string()),
+    result: Some("棄却".to_string()),
+    article_info: Some("民集　第76巻3号999頁".to_string()),
+    original_court_name: Some("東京高等裁判所".to_string()),
+    original_case_number: Some("令和2(ネ)9999".to_string()),
+    original_result: None,
+    original_date: Some(Date {
+      era: Era::Reiwa,
+      year: 3,
+      month: Some(1),
+      day: Some(28),
+    }),
+    field: None,
+    gist: Some("甲が乙に対して負う信義則上の説明義務の範囲".to_string()),
+    case_gist: Some(
+      "甲は，契約の締結に先立ち，乙に対して重要な事項を説明すべき信義則上の義務を負う。"
+        .to_string(),
+    ),
+    ref_law: Some("民法1条2項，民法415条".to_string()),
+    lawsuit_id: "99999".to_string(),
+    detail_page_link: SAMPLE_DETAIL_PAGE_LINK.to_string(),
+    contents: None,
+    full_pdf_link: "https://www.courts.go.jp/app/files/hanrei_jp/999/099999_hanrei.pdf".to_string(),
+  }
+}
@@ -0,0 +1,42 @@
+//! `--exclude-court-regex`・`--exclude-case-name-regex`による取得対象の除外
+//!
+//! 詳細ページを解析したあと、PDFをダウンロードする前に判定する。
+//! `--exclude-action drop`では除外した判例は書き出さず、`flag`では書き出したうえで`excluded`フィールドに理由を残す。
+
+use crate::Args;
+use clap::ValueEnum;
+use jplaw_data_types::listup::PrecedentData;
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExcludeAction {
+  /// 書き出さずに捨てる
+  Drop,
+  /// 書き出したうえで`excluded`フィールドを付ける
+  Flag,
+}
+
+/// 除外の理由
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Excluded {
+  /// パターンにマッチしたフィールド
+  pub field: &'static str,
+  pub pattern: String,
+}
+
+/// 除外のパターンにマッチした最初のフィールドを返す
+pub fn check(args: &Args, data: &PrecedentData) -> Option<Excluded> {
+  [
+    ("court_name", &args.exclude_court_regex, &data.court_name),
+    ("case_name", &args.exclude_case_name_regex, &data.case_name),
+  ]
+  .into_iter()
+  .find_map(|(field, re, value)| {
+    re.as_ref()
+      .filter(|re| re.is_match(value))
+      .map(|re| Excluded {
+        field,
+        pattern: re.as_str().to_string(),
+      })
+  })
+}
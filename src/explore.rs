@@ -0,0 +1,76 @@
+//! 検索一覧に現れない判例を探すための、`lawsuit_id`の範囲を総当たりする探索モード
+//!
+//! 詳細ページの存在を確かめるだけで判例の情報は書き出さず、見つかった`lawsuit_id`と詳細ページのリンクだけを
+//! 1行1件のJSON Lines形式で追記していく。サーバーに負荷をかけないよう、リクエスト間隔の下限を大きく取る。
+
+use crate::{checkpoint::Checkpoint, events::Events, fetch, fetch::Fetcher, shutdown, Args};
+use anyhow::Result;
+use serde_json::json;
+use std::{ops::RangeInclusive, time::Duration};
+use tokio::{fs::OpenOptions, io::AsyncWriteExt};
+use tokio_stream::StreamExt;
+use tracing::*;
+
+pub async fn explore_id_range(
+  args: &Args,
+  fetcher: &Fetcher,
+  events: &Events,
+  ids: RangeInclusive<u64>,
+  found_path: &str,
+  resume: Option<Checkpoint>,
+) -> Result<()> {
+  fetcher.raise_min_delay(Duration::from_millis(args.explore_sleep_time));
+  let (first_id, mut progress) = crate::id_range_resume(args, &ids, resume);
+  events.emit(
+    "run_started",
+    json!({ "explore": true, "since_id": ids.start(), "until_id": ids.end() }),
+  );
+  let mut found_file = OpenOptions::new()
+    .create(true)
+    .append(true)
+    .open(found_path)
+    .await?;
+  let checkpoint_path = crate::checkpoint_path(args);
+  let mut found_count = 0;
+  let mut id_stream = tokio_stream::iter(first_id..=*ids.end());
+  while let Some(lawsuit_id) = id_stream.next().await {
+    if progress.is_processed(&lawsuit_id.to_string()) {
+      continue;
+    }
+    if shutdown::requested() {
+      warn!("interrupted before id {lawsuit_id}; re-run with --resume to continue");
+      events.emit("interrupted", json!({ "next_id": lawsuit_id }));
+      return Ok(());
+    }
+    match crate::find_detail_link(fetcher, lawsuit_id).await {
+      Ok(Some(detail_page_link)) => {
+        info!("found: {}", &detail_page_link);
+        found_count += 1;
+        let line = json!({
+          "lawsuit_id": lawsuit_id.to_string(),
+          "detail_page_link": &detail_page_link,
+        });
+        found_file.write_all(format!("{line}\n").as_bytes()).await?;
+        found_file.flush().await?;
+        events.emit("id_found", line);
+      }
+      Ok(None) => info!("not found: {lawsuit_id}"),
+      Err(e) if fetch::is_circuit_open(&e) => return Err(e),
+      Err(e) => {
+        warn!("failed to check {lawsuit_id}: {e:#}");
+        events.emit(
+          "error",
+          json!({ "message": format!("{e:#}"), "fatal": false, "lawsuit_id": lawsuit_id.to_string() }),
+        );
+      }
+    }
+    progress.mark_processed(0, &lawsuit_id.to_string());
+    progress.save(&checkpoint_path).await?;
+  }
+  info!(
+    "explored {} ids, found {}",
+    ids.end() - ids.start() + 1,
+    found_count
+  );
+  Ok(())
+}
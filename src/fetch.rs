@@ -0,0 +1,674 @@
+//! 裁判所のホームページへのHTTPアクセスをまとめて扱う
+
+use crate::audit::{AuditKind, AuditLog};
+use crate::charset;
+use crate::circuit_breaker::{BreakerAction, CircuitBreaker};
+use crate::conditional::ConditionalCache;
+use crate::response_cache::{self, ResponseCache};
+use crate::robots::RobotsPolicy;
+use crate::throttle::{HostThrottles, RequestKind, Throttle};
+use anyhow::{anyhow, Result};
+use std::{
+  collections::HashMap,
+  sync::Mutex,
+  time::{Duration, Instant},
+};
+use tracing::*;
+use url::Url;
+
+/// ネットワークに接続するビルドかどうか。偽のときは`--cache-dir`に保存したレスポンスだけを使う
+const ONLINE: bool = cfg!(feature = "online");
+
+/// robots.txtの照合やサーバー側のログで使われる名前
+pub const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+pub struct Fetcher {
+  client: reqwest::Client,
+  throttle: Mutex<HostThrottles>,
+  breaker: Mutex<CircuitBreaker>,
+  robots: RobotsPolicy,
+  /// robots.txtのポリシーを適用するホスト
+  robots_host: Option<String>,
+  conditional: Option<ConditionalCache>,
+  response_cache: Option<ResponseCache>,
+  /// キャッシュに無いURLにはリクエストを送らずにエラーにする
+  cache_only: bool,
+  /// URLごとの、リクエストを再試行した回数。並行して取得していても判例ごとに数えられるようURLで分ける
+  retries: Mutex<HashMap<String, usize>>,
+  /// 1つのリクエストを再試行する回数の上限
+  max_retries: usize,
+  pdf_limits: BodyLimits,
+  audit: Option<AuditLog>,
+}
+
+/// サーキットブレーカーが回復を諦めたときのエラー。個別のレコードの失敗として扱わず実行を中断する
+#[derive(Debug)]
+pub struct CircuitOpen(pub String);
+
+impl std::fmt::Display for CircuitOpen {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.0)
+  }
+}
+
+impl std::error::Error for CircuitOpen {}
+
+pub fn is_circuit_open(e: &anyhow::Error) -> bool {
+  e.downcast_ref::<CircuitOpen>().is_some()
+}
+
+/// 判決文のPDFのように大きくなりうるレスポンスに課す制限
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BodyLimits {
+  pub timeout: Option<Duration>,
+  /// 本文の最大サイズ（バイト）
+  pub max_size: Option<u64>,
+  /// 取得する前にHEADリクエストでContent-Lengthを確かめ、上限を超えるものや0バイトのものは取得しない
+  pub head_check: bool,
+  /// これを超える本文は今回は取得せず後回しにする（バイト）
+  pub defer_over: Option<u64>,
+}
+
+/// `BodyLimits`を超えたり、リダイレクトが異常だったりして本文を取得できなかったときのエラー
+#[derive(Debug, Clone)]
+pub enum Unavailable {
+  Timeout(Duration),
+  TooLarge {
+    size: u64,
+    max_size: u64,
+  },
+  Empty,
+  /// `defer_over`を超えるので後回しにした
+  Deferred {
+    size: u64,
+    threshold: u64,
+  },
+  /// リダイレクトが循環したか、回数が多すぎた
+  RedirectLoop(String),
+  /// 目的のページではなく検索のトップページにリダイレクトされた
+  RedirectedToTop(String),
+}
+
+impl Unavailable {
+  /// 出力に書き出す理由の名前
+  pub fn reason(&self) -> &'static str {
+    match self {
+      Unavailable::Timeout(_) => "timeout",
+      Unavailable::TooLarge { .. } => "too_large",
+      Unavailable::Empty => "empty",
+      Unavailable::Deferred { .. } => "deferred",
+      Unavailable::RedirectLoop(_) => "redirect_loop",
+      Unavailable::RedirectedToTop(_) => "redirected_to_top",
+    }
+  }
+}
+
+impl std::fmt::Display for Unavailable {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Unavailable::Timeout(timeout) => {
+        write!(f, "{}秒以内に取得できませんでした", timeout.as_secs())
+      }
+      Unavailable::TooLarge { size, max_size } => {
+        write!(
+          f,
+          "サイズ（{size}バイト）が上限（{max_size}バイト）を超えています"
+        )
+      }
+      Unavailable::Empty => write!(f, "本文が空でした"),
+      Unavailable::Deferred { size, threshold } => {
+        write!(
+          f,
+          "サイズ（{size}バイト）が{threshold}バイトを超えるので後回しにしました"
+        )
+      }
+      Unavailable::RedirectLoop(url) => write!(f, "リダイレクトが循環しています：{url}"),
+      Unavailable::RedirectedToTop(url) => {
+        write!(f, "検索のトップページにリダイレクトされました：{url}")
+      }
+    }
+  }
+}
+
+impl std::error::Error for Unavailable {}
+
+/// 取得したレスポンスの本文
+struct Body {
+  bytes: Vec<u8>,
+  /// Content-Typeヘッダーの値。キャッシュから読んだときは`None`
+  content_type: Option<String>,
+}
+
+pub fn unavailable(e: &anyhow::Error) -> Option<&Unavailable> {
+  e.downcast_ref::<Unavailable>()
+}
+
+/// 404 Not Foundによる失敗かどうか
+pub fn is_not_found(e: &anyhow::Error) -> bool {
+  e.downcast_ref::<reqwest::Error>()
+    .and_then(|e| e.status())
+    .map(|status| status == reqwest::StatusCode::NOT_FOUND)
+    .unwrap_or(false)
+}
+
+/// リダイレクトを追う回数の上限
+const MAX_REDIRECTS: usize = 10;
+
+/// リダイレクト先がこれらのパスなら、目的のページが無くトップページに戻されたとみなす
+const SEARCH_TOP_PATHS: &[&str] = &["/", "/index.html", "/app/hanrei_jp/search1"];
+
+/// 循環するリダイレクトと、検索のトップページへのリダイレクトを追わずにエラーにする
+///
+/// そのまま追うと意味の無いHTMLを詳細ページとして解析しようとして失敗するので、取得できないものとして扱う
+fn redirect_policy() -> reqwest::redirect::Policy {
+  reqwest::redirect::Policy::custom(|attempt| {
+    let url = attempt.url().to_string();
+    if attempt.previous().iter().any(|u| u == attempt.url())
+      || attempt.previous().len() >= MAX_REDIRECTS
+    {
+      attempt.error(Unavailable::RedirectLoop(url))
+    } else if SEARCH_TOP_PATHS.contains(&attempt.url().path()) {
+      attempt.error(Unavailable::RedirectedToTop(url))
+    } else {
+      attempt.follow()
+    }
+  })
+}
+
+/// `redirect_policy`がリダイレクトを止めたときのエラー
+fn redirect_trap(e: &reqwest::Error) -> Option<Unavailable> {
+  if !e.is_redirect() {
+    return None;
+  }
+  let mut source = std::error::Error::source(e);
+  while let Some(err) = source {
+    if let Some(trap) = err.downcast_ref::<Unavailable>() {
+      return Some(trap.clone());
+    }
+    source = err.source();
+  }
+  None
+}
+
+/// 時間をおけば回復する見込みのある失敗かどうか
+fn is_transient(e: &reqwest::Error) -> bool {
+  match e.status() {
+    Some(status) => status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS,
+    None => !e.is_builder() && !e.is_redirect(),
+  }
+}
+
+/// HTTPクライアントの接続の設定
+#[derive(Debug, Clone, Copy)]
+pub struct ClientOptions {
+  /// HTTP/2を使わない
+  pub http1_only: bool,
+  /// 使われていない接続をプールに残しておく時間
+  pub pool_idle_timeout: Duration,
+  /// ホストごとにプールに残しておく使われていない接続の数
+  pub pool_max_idle_per_host: usize,
+}
+
+impl Default for ClientOptions {
+  fn default() -> Self {
+    ClientOptions {
+      http1_only: false,
+      pool_idle_timeout: Duration::from_secs(90),
+      pool_max_idle_per_host: 4,
+    }
+  }
+}
+
+impl ClientOptions {
+  /// 並行して取得しても少ない接続を使い回すように設定したクライアント
+  ///
+  /// サーバーが対応していればALPNでHTTP/2を選び、1本の接続に複数のリクエストを多重化する
+  fn build_client(&self) -> Result<reqwest::Client> {
+    let builder = reqwest::Client::builder()
+      .user_agent(USER_AGENT)
+      .redirect(redirect_policy())
+      .pool_idle_timeout(self.pool_idle_timeout)
+      .pool_max_idle_per_host(self.pool_max_idle_per_host)
+      .tcp_keepalive(Duration::from_secs(60));
+    let builder = if self.http1_only {
+      builder.http1_only()
+    } else {
+      builder
+        .http2_adaptive_window(true)
+        .http2_keep_alive_interval(Duration::from_secs(30))
+        .http2_keep_alive_while_idle(true)
+    };
+    Ok(builder.build()?)
+  }
+}
+
+impl Fetcher {
+  pub fn new(throttle: Throttle, breaker: CircuitBreaker, options: ClientOptions) -> Result<Self> {
+    let client = options.build_client()?;
+    Ok(Fetcher {
+      client,
+      throttle: Mutex::new(HostThrottles::new(throttle)),
+      breaker: Mutex::new(breaker),
+      robots: RobotsPolicy::allow_all(),
+      robots_host: None,
+      conditional: None,
+      response_cache: None,
+      cache_only: false,
+      retries: Mutex::new(HashMap::new()),
+      max_retries: 3,
+      pdf_limits: BodyLimits::default(),
+      audit: None,
+    })
+  }
+
+  /// robots.txtを取得する。存在しない場合は`None`を返す
+  ///
+  /// ネットワークに接続しないビルドではリクエストを送らないので、常に`None`を返す
+  pub async fn get_robots_txt(&self, url: &str) -> Result<Option<String>> {
+    if !ONLINE {
+      return Ok(None);
+    }
+    let res = self.client.get(url).send().await?;
+    if res.status() == reqwest::StatusCode::NOT_FOUND {
+      return Ok(None);
+    }
+    let text = res.error_for_status()?.text().await?;
+    Ok(Some(text))
+  }
+
+  /// `robots_url`のホストへのリクエストにrobots.txtのポリシーを適用する。
+  /// Crawl-delayがあればリクエスト間隔の下限にする
+  pub fn set_robots(&mut self, robots_url: &str, robots: RobotsPolicy) -> Result<()> {
+    if let Some(crawl_delay) = robots.crawl_delay() {
+      self.throttle.lock().unwrap().raise_min_delay(crawl_delay);
+    }
+    self.robots_host = Url::parse(robots_url)?.host_str().map(|h| h.to_string());
+    self.robots = robots;
+    Ok(())
+  }
+
+  /// リクエスト間隔の下限を`min_delay`以上にする
+  pub fn raise_min_delay(&self, min_delay: Duration) {
+    self.throttle.lock().unwrap().raise_min_delay(min_delay);
+  }
+
+  /// PDFのリクエストの間隔を、HTMLとは別の`throttle`の設定でホストごとに制御する
+  pub fn set_pdf_throttle(&mut self, throttle: Throttle) {
+    self.throttle.lock().unwrap().set_pdf(throttle);
+  }
+
+  /// 1つのリクエストを再試行する回数の上限。超えたらそのリクエストのエラーを返す
+  pub fn set_max_retries(&mut self, max_retries: usize) {
+    self.max_retries = max_retries;
+  }
+
+  pub fn set_pdf_limits(&mut self, limits: BodyLimits) {
+    self.pdf_limits = limits;
+  }
+
+  pub fn set_conditional_cache(&mut self, cache: ConditionalCache) {
+    self.conditional = Some(cache);
+  }
+
+  pub fn conditional_cache(&self) -> Option<&ConditionalCache> {
+    self.conditional.as_ref()
+  }
+
+  /// 以降に取得したURLを`--audit-log`に記録する
+  pub fn set_audit_log(&mut self, audit: AuditLog) {
+    self.audit = Some(audit);
+  }
+
+  pub fn set_response_cache(&mut self, cache: ResponseCache) {
+    self.response_cache = Some(cache);
+  }
+
+  /// 以降はキャッシュにあるものだけを使い、サーバーにはリクエストを送らない
+  pub fn set_cache_only(&mut self) {
+    self.cache_only = true;
+  }
+
+  /// `urls`へのリクエストを再試行した回数の合計。数えた回数は消すので、同じURLを取得し直せば0から数え直す
+  pub fn take_retries(&self, urls: &[&str]) -> usize {
+    let mut retries = self.retries.lock().unwrap();
+    urls.iter().filter_map(|url| retries.remove(*url)).sum()
+  }
+
+  /// 現在のリクエスト間隔（ミリ秒）。ホストごとの間隔のうち最も長いもの
+  pub fn current_delay_millis(&self) -> u128 {
+    self.throttle.lock().unwrap().max_delay().as_millis()
+  }
+
+  /// `use_validators`が真のときは保存してあるETag・Last-Modifiedを使って条件付きリクエストを送る
+  ///
+  /// 一時的なエラーは`max_retries`回まで再試行し、それでも失敗したらエラーを返して判例ごとの扱いに任せる。
+  /// サーキットブレーカーには再試行しても失敗したリクエストだけを数えるので、
+  /// 1つのURLが失敗し続けるだけでは実行は中断しない
+  async fn send(
+    &self,
+    method: reqwest::Method,
+    url: &str,
+    use_validators: bool,
+    limits: &BodyLimits,
+    kind: RequestKind,
+  ) -> Result<reqwest::Response> {
+    if !ONLINE {
+      return Err(anyhow!(
+        "online featureを無効にしたビルドでは--cache-dirに無いURLは取得できません：{url}"
+      ));
+    }
+    let parsed_url = Url::parse(url)?;
+    let path = match parsed_url.query() {
+      Some(query) => format!("{}?{query}", parsed_url.path()),
+      None => parsed_url.path().to_string(),
+    };
+    if parsed_url.host_str() == self.robots_host.as_deref() && !self.robots.is_allowed(&path) {
+      return Err(anyhow!("robots.txtで禁止されているURLです：{url}"));
+    }
+    let host = parsed_url.host_str().unwrap_or_default();
+    let mut attempts = 0;
+    loop {
+      let wait = self.throttle.lock().unwrap().get(kind, host).reserve();
+      if !wait.is_zero() {
+        tokio::time::sleep(wait).await;
+      }
+      let mut req = self.client.request(method.clone(), url);
+      if let Some(timeout) = limits.timeout {
+        req = req.timeout(timeout);
+      }
+      if use_validators {
+        if let Some(validator) = self.conditional.as_ref().and_then(|c| c.get(url)) {
+          if let Some(etag) = validator.etag {
+            req = req.header(reqwest::header::IF_NONE_MATCH, etag);
+          }
+          if let Some(last_modified) = validator.last_modified {
+            req = req.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+          }
+        }
+      }
+      let start = Instant::now();
+      let res = req.send().await.and_then(|res| res.error_for_status());
+      let latency = start.elapsed();
+      let overloaded = res.as_ref().is_err_and(|e| {
+        e.is_timeout()
+          || e.status().is_some_and(|status| {
+            status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+          })
+      });
+      self
+        .throttle
+        .lock()
+        .unwrap()
+        .get(kind, host)
+        .record(latency, overloaded);
+      let e = match res {
+        Ok(res) => {
+          self.breaker.lock().unwrap().record_success();
+          return Ok(res);
+        }
+        Err(e) => e,
+      };
+      if let Some(trap) = redirect_trap(&e) {
+        warn!("unreachable: {url}: {trap}");
+        return Err(trap.into());
+      }
+      warn!("request failed: {url}: {e}");
+      if let (Some(timeout), true) = (limits.timeout, e.is_timeout()) {
+        return Err(Unavailable::Timeout(timeout).into());
+      }
+      if !is_transient(&e) {
+        return Err(e.into());
+      }
+      if attempts < self.max_retries {
+        attempts += 1;
+        *self
+          .retries
+          .lock()
+          .unwrap()
+          .entry(url.to_string())
+          .or_default() += 1;
+        continue;
+      }
+      let action = self.breaker.lock().unwrap().record_failure();
+      match action {
+        BreakerAction::Continue => {}
+        BreakerAction::Pause(cooldown) => {
+          let pauses = self.breaker.lock().unwrap().pauses();
+          warn!(
+            "circuit breaker: pause {}s (pause count: {})",
+            cooldown.as_secs(),
+            pauses
+          );
+          tokio::time::sleep(cooldown).await;
+        }
+        BreakerAction::Abort(summary) => {
+          error!("circuit breaker: {summary}");
+          return Err(CircuitOpen(format!("{summary}（最後のエラー：{url}: {e}）")).into());
+        }
+      }
+      return Err(e.into());
+    }
+  }
+
+  /// HEADリクエストでContent-Lengthを確かめる。HEADに応じないサーバーもあるので、確かめられなければそのまま取得に進む
+  ///
+  /// HEADもGETと同じくrobots.txtとリクエスト間隔の制御に従う
+  async fn check_content_length(
+    &self,
+    url: &str,
+    limits: &BodyLimits,
+    kind: RequestKind,
+  ) -> Result<()> {
+    let res = match self
+      .send(reqwest::Method::HEAD, url, false, limits, kind)
+      .await
+    {
+      Ok(res) => res,
+      Err(e) if is_circuit_open(&e) || unavailable(&e).is_some() => return Err(e),
+      Err(e) => {
+        debug!("HEAD failed: {url}: {e:#}");
+        return Ok(());
+      }
+    };
+    // HEADのレスポンスには本文が無いので、ヘッダーの値をそのまま読む
+    let size = res
+      .headers()
+      .get(reqwest::header::CONTENT_LENGTH)
+      .and_then(|v| v.to_str().ok())
+      .and_then(|v| v.parse::<u64>().ok());
+    match (size, limits.max_size, limits.defer_over) {
+      (Some(0), _, _) => Err(Unavailable::Empty.into()),
+      (Some(size), Some(max_size), _) if size > max_size => {
+        Err(Unavailable::TooLarge { size, max_size }.into())
+      }
+      (Some(size), _, Some(threshold)) if size > threshold => {
+        Err(Unavailable::Deferred { size, threshold }.into())
+      }
+      _ => Ok(()),
+    }
+  }
+
+  /// レスポンスの本文を取得する。`use_validators`が真で更新されていなければ（304）`None`を返す
+  ///
+  /// 監査ログが設定されていれば、結果をそこに記録する
+  async fn get_body(
+    &self,
+    url: &str,
+    use_validators: bool,
+    limits: &BodyLimits,
+    kind: RequestKind,
+  ) -> Result<Option<Body>> {
+    let body = self.fetch_body(url, use_validators, limits, kind).await;
+    if let Some(audit) = &self.audit {
+      let kind = match kind {
+        RequestKind::Html => AuditKind::Html,
+        RequestKind::Pdf => AuditKind::Pdf,
+      };
+      let result = match &body {
+        Ok(body) => Ok(body.as_ref().map(|b| b.bytes.len())),
+        Err(e) => Err(anyhow!("{e:#}")),
+      };
+      audit.record(kind, url, &result);
+    }
+    body
+  }
+
+  /// キャッシュディレクトリが設定されていれば、キャッシュにあるものはリクエストを送らずにそれを返す
+  ///
+  /// 一覧ページはリクエストを送れる限りキャッシュを使わずに取得し直す
+  async fn fetch_body(
+    &self,
+    url: &str,
+    use_validators: bool,
+    limits: &BodyLimits,
+    kind: RequestKind,
+  ) -> Result<Option<Body>> {
+    let check_size = |size: u64| match (limits.max_size, limits.defer_over) {
+      (Some(max_size), _) if size > max_size => Err(Unavailable::TooLarge { size, max_size }),
+      (_, Some(threshold)) if size > threshold => Err(Unavailable::Deferred { size, threshold }),
+      _ => Ok(()),
+    };
+    let use_cache = !ONLINE || self.cache_only || !response_cache::is_listing(url);
+    if let Some(cache) = self.response_cache.as_ref().filter(|_| use_cache) {
+      if let Some(bytes) = cache.read(url).await {
+        debug!("cache hit: {url}");
+        check_size(bytes.len() as u64)?;
+        return Ok(Some(Body {
+          bytes,
+          content_type: None,
+        }));
+      }
+    }
+    if self.cache_only {
+      return Err(anyhow!("--cache-dirに保存されていないURLです：{url}"));
+    }
+    if limits.head_check {
+      self.check_content_length(url, limits, kind).await?;
+    }
+    let mut res = self
+      .send(reqwest::Method::GET, url, use_validators, limits, kind)
+      .await?;
+    if res.status() == reqwest::StatusCode::NOT_MODIFIED {
+      info!("not modified: {url}");
+      return Ok(None);
+    }
+    if let Some(size) = res.content_length() {
+      check_size(size)?;
+    }
+    let header = |name: reqwest::header::HeaderName| {
+      res
+        .headers()
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.to_string())
+    };
+    let content_type = header(reqwest::header::CONTENT_TYPE);
+    if let Some(cache) = &self.conditional {
+      cache.update(
+        url,
+        header(reqwest::header::ETAG),
+        header(reqwest::header::LAST_MODIFIED),
+      );
+    }
+    // Content-Lengthが無いこともあるので、読みながら上限を確かめる
+    let mut bytes = Vec::new();
+    loop {
+      let chunk = match res.chunk().await {
+        Ok(Some(chunk)) => chunk,
+        Ok(None) => break,
+        Err(e) => match limits.timeout {
+          Some(timeout) if e.is_timeout() => return Err(Unavailable::Timeout(timeout).into()),
+          _ => return Err(e.into()),
+        },
+      };
+      bytes.extend_from_slice(&chunk);
+      check_size(bytes.len() as u64)?;
+    }
+    if limits.head_check && bytes.is_empty() {
+      return Err(Unavailable::Empty.into());
+    }
+    if let Some(cache) = &self.response_cache {
+      cache.write(url, &bytes).await?;
+    }
+    Ok(Some(Body {
+      bytes,
+      content_type,
+    }))
+  }
+
+  async fn get_body_unconditional(&self, url: &str) -> Result<Body> {
+    self
+      .get_body(url, false, &BodyLimits::default(), RequestKind::Html)
+      .await?
+      .ok_or_else(|| anyhow!("条件付きでないリクエストに304が返された：{url}"))
+  }
+
+  /// HTMLを取得し、Content-Typeや`<meta>`タグのcharsetに従ってデコードする
+  pub async fn get_text(&self, url: &str) -> Result<String> {
+    let body = self.get_body_unconditional(url).await?;
+    Ok(charset::decode_html(
+      &body.bytes,
+      body.content_type.as_deref(),
+      url,
+    ))
+  }
+
+  pub async fn get_bytes(&self, url: &str) -> Result<Vec<u8>> {
+    Ok(self.get_body_unconditional(url).await?.bytes)
+  }
+
+  pub async fn get_text_if_modified(
+    &self,
+    url: &str,
+    use_validators: bool,
+  ) -> Result<Option<String>> {
+    let body = self
+      .get_body(
+        url,
+        use_validators,
+        &BodyLimits::default(),
+        RequestKind::Html,
+      )
+      .await?;
+    Ok(body.map(|body| charset::decode_html(&body.bytes, body.content_type.as_deref(), url)))
+  }
+
+  /// 判決文のPDFを`set_pdf_limits`の制限付きで取得する
+  pub async fn get_pdf_if_modified(
+    &self,
+    url: &str,
+    use_validators: bool,
+  ) -> Result<Option<Vec<u8>>> {
+    let body = self
+      .get_body(url, use_validators, &self.pdf_limits, RequestKind::Pdf)
+      .await?;
+    Ok(body.map(|body| body.bytes))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn unavailable_keeps_its_reason_through_anyhow() {
+    let e = anyhow::Error::from(Unavailable::TooLarge {
+      size: 2048,
+      max_size: 1024,
+    })
+    .context("PDFを取得できません");
+    let reason = unavailable(&e).map(Unavailable::reason);
+    assert_eq!(reason, Some("too_large"));
+    assert!(unavailable(&anyhow!("other")).is_none());
+  }
+
+  #[test]
+  fn unavailable_messages() {
+    assert_eq!(
+      Unavailable::Timeout(Duration::from_secs(30)).to_string(),
+      "30秒以内に取得できませんでした"
+    );
+    assert_eq!(Unavailable::Empty.reason(), "empty");
+    assert_eq!(Unavailable::Empty.to_string(), "本文が空でした");
+  }
+}
@@ -0,0 +1,171 @@
+//! [`MemoryIndex`]をC ABIで公開する
+//!
+//! ```c
+//! void *index = listup_index_load("output/list.json");
+//! char *result = listup_index_search(index, "{\"court_name\": \"最高裁判所\", \"limit\": 10}");
+//! /* resultはJSONの配列 */
+//! listup_string_free(result);
+//! listup_index_free(index);
+//! ```
+//!
+//! 失敗した関数はNULL（または0）を返し、`listup_last_error`でその理由を取得できる。
+//! 文字列はすべてUTF-8。
+
+use crate::index::MemoryIndex;
+use std::{
+  cell::RefCell,
+  ffi::{c_char, CStr, CString},
+  ptr,
+};
+
+thread_local! {
+  static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+  let message = CString::new(message).unwrap_or_else(|_| CString::new("error").unwrap());
+  LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+unsafe fn str_arg<'a>(s: *const c_char) -> Option<&'a str> {
+  if s.is_null() {
+    set_last_error("引数がNULLです".to_string());
+    return None;
+  }
+  match CStr::from_ptr(s).to_str() {
+    Ok(s) => Some(s),
+    Err(e) => {
+      set_last_error(e.to_string());
+      None
+    }
+  }
+}
+
+fn into_c_string(s: String) -> *mut c_char {
+  match CString::new(s) {
+    Ok(s) => s.into_raw(),
+    Err(e) => {
+      set_last_error(e.to_string());
+      ptr::null_mut()
+    }
+  }
+}
+
+/// 一覧ファイルを読み込む。失敗したらNULLを返す
+///
+/// # Safety
+///
+/// `path`はNUL終端されたUTF-8文字列を指していなければならない。
+/// 返り値は`listup_index_free`で解放すること。
+#[no_mangle]
+pub unsafe extern "C" fn listup_index_load(path: *const c_char) -> *mut MemoryIndex {
+  let Some(path) = str_arg(path) else {
+    return ptr::null_mut();
+  };
+  match MemoryIndex::load(path) {
+    Ok(index) => Box::into_raw(Box::new(index)),
+    Err(e) => {
+      set_last_error(format!("{e:#}"));
+      ptr::null_mut()
+    }
+  }
+}
+
+/// # Safety
+///
+/// `index`は`listup_index_load`が返したものか、NULLでなければならない。解放後は使えない。
+#[no_mangle]
+pub unsafe extern "C" fn listup_index_free(index: *mut MemoryIndex) {
+  if !index.is_null() {
+    drop(Box::from_raw(index));
+  }
+}
+
+/// 読み込んだ項目の件数
+///
+/// # Safety
+///
+/// `index`は`listup_index_load`が返したものか、NULLでなければならない。
+#[no_mangle]
+pub unsafe extern "C" fn listup_index_len(index: *const MemoryIndex) -> usize {
+  index.as_ref().map(|index| index.len()).unwrap_or(0)
+}
+
+/// JSON文字列の検索条件で検索し、結果をJSONの配列の文字列で返す。失敗したらNULLを返す
+///
+/// # Safety
+///
+/// `index`は`listup_index_load`が返したもの、`query`はNUL終端されたUTF-8文字列でなければならない。
+/// 返り値は`listup_string_free`で解放すること。
+#[no_mangle]
+pub unsafe extern "C" fn listup_index_search(
+  index: *const MemoryIndex,
+  query: *const c_char,
+) -> *mut c_char {
+  let Some(index) = index.as_ref() else {
+    set_last_error("indexがNULLです".to_string());
+    return ptr::null_mut();
+  };
+  let Some(query) = str_arg(query) else {
+    return ptr::null_mut();
+  };
+  match index.search_json(query) {
+    Ok(s) => into_c_string(s),
+    Err(e) => {
+      set_last_error(format!("{e:#}"));
+      ptr::null_mut()
+    }
+  }
+}
+
+/// `lawsuit_id`の項目をJSON文字列で返す。見つからなければNULLを返す
+///
+/// # Safety
+///
+/// `index`は`listup_index_load`が返したもの、`lawsuit_id`はNUL終端されたUTF-8文字列でなければならない。
+/// 返り値は`listup_string_free`で解放すること。
+#[no_mangle]
+pub unsafe extern "C" fn listup_index_get(
+  index: *const MemoryIndex,
+  lawsuit_id: *const c_char,
+) -> *mut c_char {
+  let Some(index) = index.as_ref() else {
+    set_last_error("indexがNULLです".to_string());
+    return ptr::null_mut();
+  };
+  let Some(lawsuit_id) = str_arg(lawsuit_id) else {
+    return ptr::null_mut();
+  };
+  match index.get(lawsuit_id) {
+    Some(value) => into_c_string(value.to_string()),
+    None => {
+      set_last_error(format!("見つかりません：{lawsuit_id}"));
+      ptr::null_mut()
+    }
+  }
+}
+
+/// このライブラリが返した文字列を解放する
+///
+/// # Safety
+///
+/// `s`はこのライブラリの関数が返した文字列か、NULLでなければならない。解放後は使えない。
+#[no_mangle]
+pub unsafe extern "C" fn listup_string_free(s: *mut c_char) {
+  if !s.is_null() {
+    drop(CString::from_raw(s));
+  }
+}
+
+/// このスレッドで最後に失敗した関数のエラーメッセージ。無ければNULLを返す
+///
+/// 返り値は次にこのライブラリの関数を呼ぶまで有効で、解放してはならない。
+#[no_mangle]
+pub extern "C" fn listup_last_error() -> *const c_char {
+  LAST_ERROR.with(|e| {
+    e.borrow()
+      .as_ref()
+      .map(|s| s.as_ptr())
+      .unwrap_or(ptr::null())
+  })
+}
@@ -0,0 +1,188 @@
+//! `--filename-template`による、裁判例ごとのファイル名の指定
+//!
+//! `{trial_type}_{date}_{case_number}`のようにフィールド名を波括弧で囲んだテンプレートから、拡張子を除いたファイル名を作る。
+//! 使えるフィールドは`lawsuit_id`・`trial_type`・`date`（西暦の`yyyy-mm-dd`）・`year`・`era`・`case_number`・`court_name`。
+//! ファイル名に使えない文字（`/`・`\`・`:`・`*`・`?`・`"`・`<`・`>`・`|`と制御文字）は`_`に置き換え、長すぎる名前は切り詰める。
+//! 別の判例と同じ名前にならないよう、テンプレートには`{lawsuit_id}`を含めるのが安全。
+
+use crate::compat;
+use anyhow::{anyhow, Result};
+use jplaw_data_types::listup::PrecedentInfo;
+use serde_json::Value;
+use std::sync::OnceLock;
+use tracing::*;
+
+/// テンプレートで使えるフィールド
+const FIELDS: &[&str] = &[
+  "lawsuit_id",
+  "trial_type",
+  "date",
+  "year",
+  "era",
+  "case_number",
+  "court_name",
+];
+
+/// ファイル名の長さの上限（バイト）。多くのファイルシステムの上限の255バイトから拡張子の分を残す
+const MAX_NAME_BYTES: usize = 200;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+  Literal(String),
+  Field(String),
+}
+
+static TEMPLATE: OnceLock<Vec<Part>> = OnceLock::new();
+
+fn parse(template: &str) -> Result<Vec<Part>> {
+  let mut parts = Vec::new();
+  let mut rest = template;
+  while let Some(start) = rest.find('{') {
+    if start > 0 {
+      parts.push(Part::Literal(rest[..start].to_string()));
+    }
+    let end = rest[start..]
+      .find('}')
+      .ok_or_else(|| anyhow!("テンプレートの波括弧が閉じていません：{template}"))?;
+    let field = &rest[start + 1..start + end];
+    if !FIELDS.contains(&field) {
+      return Err(anyhow!(
+        "テンプレートに使えないフィールドです：{field}（使えるのは{}）",
+        FIELDS.join("・")
+      ));
+    }
+    parts.push(Part::Field(field.to_string()));
+    rest = &rest[start + end + 1..];
+  }
+  if !rest.is_empty() {
+    parts.push(Part::Literal(rest.to_string()));
+  }
+  Ok(parts)
+}
+
+/// テンプレートを読んで設定する。2回目以降の呼び出しでは何もしない
+pub fn install(template: &str) -> Result<()> {
+  let parts = parse(template)?;
+  if !parts.contains(&Part::Field("lawsuit_id".to_string())) {
+    warn!("--filename-template does not contain {{lawsuit_id}}; different records may share a file name");
+  }
+  let _ = TEMPLATE.set(parts);
+  Ok(())
+}
+
+fn text(value: Value) -> String {
+  match value {
+    Value::String(s) => s,
+    v => v.to_string(),
+  }
+}
+
+fn field(info: &PrecedentInfo, name: &str) -> String {
+  let year = compat::era_to_ad_year(&info.date.era, info.date.year);
+  match name {
+    "lawsuit_id" => info.lawsuit_id.clone(),
+    "trial_type" => serde_json::to_value(&info.trial_type)
+      .map(text)
+      .unwrap_or_default(),
+    "date" => match (info.date.month, info.date.day) {
+      (Some(month), Some(day)) => format!("{year:04}-{month:02}-{day:02}"),
+      (Some(month), None) => format!("{year:04}-{month:02}"),
+      _ => format!("{year:04}"),
+    },
+    "year" => year.to_string(),
+    "era" => serde_json::to_value(&info.date.era)
+      .map(text)
+      .unwrap_or_default(),
+    "case_number" => info.case_number.clone(),
+    "court_name" => info.court_name.clone(),
+    _ => String::new(),
+  }
+}
+
+/// ファイル名に使えない文字を`_`に置き換え、長すぎれば切り詰める
+pub fn sanitize(name: &str) -> String {
+  let replaced = name
+    .trim()
+    .chars()
+    .map(|c| match c {
+      '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+      c if c.is_control() => '_',
+      c => c,
+    })
+    .collect::<String>();
+  // 隠しファイルにならないよう、先頭の`.`も置き換える
+  let mut sanitized = match replaced.strip_prefix('.') {
+    Some(rest) => format!("_{rest}"),
+    None => replaced,
+  };
+  if sanitized.len() > MAX_NAME_BYTES {
+    let mut end = MAX_NAME_BYTES;
+    while !sanitized.is_char_boundary(end) {
+      end -= 1;
+    }
+    sanitized.truncate(end);
+  }
+  sanitized
+}
+
+/// 裁判例のファイルの拡張子を除いた名前。テンプレートが設定されていなければ`PrecedentInfo::file_name`
+pub fn file_name(info: &PrecedentInfo) -> String {
+  let Some(parts) = TEMPLATE.get() else {
+    return info.file_name();
+  };
+  let name = parts
+    .iter()
+    .map(|part| match part {
+      Part::Literal(s) => s.clone(),
+      Part::Field(name) => field(info, name),
+    })
+    .collect::<String>();
+  sanitize(&name)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use listup_precedent_lib::examples;
+
+  #[test]
+  fn sanitize_replaces_forbidden_characters() {
+    assert_eq!(sanitize("令和3(受)1/2:3*4?"), "令和3(受)1_2_3_4_");
+    assert_eq!(sanitize(r#"a\b"c<d>e|f"#), "a_b_c_d_e_f");
+    assert_eq!(sanitize(" a\tb\n "), "a_b");
+    assert_eq!(sanitize(".hidden"), "_hidden");
+  }
+
+  #[test]
+  fn sanitize_truncates_on_char_boundary() {
+    let long = "判".repeat(100);
+    let sanitized = sanitize(&long);
+    assert!(sanitized.len() <= MAX_NAME_BYTES);
+    assert_eq!(sanitized, "判".repeat(MAX_NAME_BYTES / 3));
+  }
+
+  #[test]
+  fn parse_template() {
+    assert_eq!(
+      parse("{trial_type}_{date}-x").unwrap(),
+      [
+        Part::Field("trial_type".to_string()),
+        Part::Literal("_".to_string()),
+        Part::Field("date".to_string()),
+        Part::Literal("-x".to_string()),
+      ]
+    );
+    assert!(parse("{unknown}").is_err());
+    assert!(parse("{lawsuit_id").is_err());
+  }
+
+  #[test]
+  fn fields_of_sample() {
+    let info = crate::record::precedent_info_of(&examples::sample_data());
+    assert_eq!(field(&info, "date"), "2022-03-24");
+    assert_eq!(field(&info, "year"), "2022");
+    assert_eq!(field(&info, "era"), "Reiwa");
+    assert_eq!(field(&info, "trial_type"), "SupremeCourt");
+    assert_eq!(field(&info, "lawsuit_id"), "99999");
+  }
+}
@@ -0,0 +1,49 @@
+//! 判決文に含まれる「取消（とりけし）」のような括弧書きの読み仮名の扱い
+//!
+//! 漢字の直後に置かれた、ひらがなだけからなる括弧書きを読み仮名とみなす。
+//! 「（あ）」「（い）」のような項目の番号と区別するため、2文字以上のものだけを対象にする。
+
+use clap::ValueEnum;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FuriganaMode {
+  /// 何もしない
+  Keep,
+  /// 本文から読み仮名を取り除く
+  Remove,
+  /// 本文から読み仮名を取り除き、`readings`フィールドに書き出す
+  Extract,
+}
+
+/// 本文から取り出した読み仮名
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Reading {
+  /// 読み仮名の直前の漢字の並び
+  pub base: String,
+  pub reading: String,
+}
+
+/// 本文を`mode`に従って正規化し、取り除いた読み仮名を返す
+pub fn normalize(text: &str, mode: FuriganaMode) -> (String, Vec<Reading>) {
+  if mode == FuriganaMode::Keep {
+    return (text.to_string(), Vec::new());
+  }
+  let re = Regex::new(r"(?P<base>\p{Han}+)[（(](?P<reading>[\p{Hiragana}ー]{2,})[）)]").unwrap();
+  let mut readings = Vec::new();
+  let normalized = re.replace_all(text, |caps: &regex::Captures| {
+    readings.push(Reading {
+      base: caps["base"].to_string(),
+      reading: caps["reading"].to_string(),
+    });
+    caps["base"].to_string()
+  });
+  let normalized = normalized.into_owned();
+  if mode == FuriganaMode::Extract {
+    (normalized, readings)
+  } else {
+    (normalized, Vec::new())
+  }
+}
@@ -0,0 +1,269 @@
+//! 既存の一覧と裁判例のJSONを読み込み、GraphQLのAPIとして提供する`serve-graphql`サブコマンド
+//!
+//! 起動時に一覧の項目と裁判例のJSONをメモリに読み込み、`POST /graphql`でクエリを受け付ける。
+//! ブラウザで`GET /graphql`を開くとGraphiQLでスキーマを見ながらクエリを試せる。
+//! フィールド名はGraphQLの慣習に合わせてcamelCaseにしている。
+//!
+//! ```graphql
+//! {
+//!   precedents(filter: { courtName: "最高裁判所", yearFrom: 2020, text: "信義則" }, limit: 10) {
+//!     total
+//!     items { lawsuitId caseName date gist }
+//!   }
+//! }
+//! ```
+//!
+//! 読み込みは起動時の1回だけなので、出力を更新したら起動し直す。
+
+use crate::{compat, dataset, shutdown};
+use anyhow::Result;
+use async_graphql::{
+  http::GraphiQLSource, Context, EmptyMutation, EmptySubscription, InputObject, Object, Schema,
+  SimpleObject,
+};
+use async_graphql_axum::GraphQL;
+use axum::{
+  response::{Html, IntoResponse},
+  routing::get,
+  Router,
+};
+use jplaw_data_types::listup::{PrecedentData, PrecedentInfo};
+use serde_json::Value;
+use std::{collections::HashMap, time::Duration};
+use tracing::*;
+
+/// `precedents`で1回に返す件数の上限
+const MAX_LIMIT: usize = 100;
+
+/// 裁判例
+#[derive(Debug, Clone, SimpleObject)]
+pub struct Precedent {
+  lawsuit_id: String,
+  uuid: Option<String>,
+  case_number: String,
+  case_name: Option<String>,
+  court_name: String,
+  trial_type: String,
+  /// 西暦の`yyyy-mm-dd`（月日が無ければその部分を省く）
+  date: String,
+  /// 西暦の年
+  year: i32,
+  lawsuit_type: Option<String>,
+  result_type: Option<String>,
+  result: Option<String>,
+  field: Option<String>,
+  gist: Option<String>,
+  case_gist: Option<String>,
+  ref_law: Option<String>,
+  detail_page_link: Option<String>,
+  full_pdf_link: Option<String>,
+  contents: Option<String>,
+}
+
+fn field_text(value: &Value) -> String {
+  match value {
+    Value::String(s) => s.clone(),
+    v => v.to_string(),
+  }
+}
+
+impl Precedent {
+  fn new(entry: &Value, info: &PrecedentInfo, data: Option<&PrecedentData>) -> Result<Self> {
+    let year = compat::era_to_ad_year(&info.date.era, info.date.year);
+    let date = match (info.date.month, info.date.day) {
+      (Some(month), Some(day)) => format!("{year:04}-{month:02}-{day:02}"),
+      (Some(month), None) => format!("{year:04}-{month:02}"),
+      _ => format!("{year:04}"),
+    };
+    Ok(Precedent {
+      lawsuit_id: info.lawsuit_id.clone(),
+      uuid: entry
+        .get("uuid")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string()),
+      case_number: info.case_number.clone(),
+      case_name: data.map(|d| d.case_name.clone()),
+      court_name: info.court_name.clone(),
+      trial_type: field_text(&serde_json::to_value(&info.trial_type)?),
+      date,
+      year: year as i32,
+      lawsuit_type: data.and_then(|d| d.lawsuit_type.clone()),
+      result_type: data.and_then(|d| d.result_type.clone()),
+      result: data.and_then(|d| d.result.clone()),
+      field: data.and_then(|d| d.field.clone()),
+      gist: data.and_then(|d| d.gist.clone()),
+      case_gist: data.and_then(|d| d.case_gist.clone()),
+      ref_law: data.and_then(|d| d.ref_law.clone()),
+      detail_page_link: data.map(|d| d.detail_page_link.clone()),
+      full_pdf_link: data.map(|d| d.full_pdf_link.clone()),
+      contents: data.and_then(|d| d.contents.clone()),
+    })
+  }
+}
+
+/// `precedents`の絞り込みの条件。指定した条件はすべて満たすものを返す
+#[derive(Debug, Clone, Default, InputObject)]
+pub struct PrecedentFilter {
+  /// 裁判所名に含まれる文字列
+  court_name: Option<String>,
+  /// 裁判の種類（`SupremeCourt`など）
+  trial_type: Option<String>,
+  /// 西暦の年の下限（この年を含む）
+  year_from: Option<i32>,
+  /// 西暦の年の上限（この年を含む）
+  year_to: Option<i32>,
+  lawsuit_type: Option<String>,
+  result_type: Option<String>,
+  field: Option<String>,
+  /// 事件番号に含まれる文字列
+  case_number: Option<String>,
+  /// 事件名・要旨・判示事項の要旨・本文のどれかに含まれる文字列
+  text: Option<String>,
+}
+
+fn contains(value: &Option<String>, pattern: &str) -> bool {
+  value.as_deref().is_some_and(|v| v.contains(pattern))
+}
+
+impl PrecedentFilter {
+  fn matches(&self, p: &Precedent) -> bool {
+    self
+      .court_name
+      .as_deref()
+      .map_or(true, |s| p.court_name.contains(s))
+      && self
+        .trial_type
+        .as_deref()
+        .map_or(true, |s| p.trial_type == s)
+      && self.year_from.map_or(true, |y| p.year >= y)
+      && self.year_to.map_or(true, |y| p.year <= y)
+      && self
+        .lawsuit_type
+        .as_deref()
+        .map_or(true, |s| p.lawsuit_type.as_deref() == Some(s))
+      && self
+        .result_type
+        .as_deref()
+        .map_or(true, |s| p.result_type.as_deref() == Some(s))
+      && self
+        .field
+        .as_deref()
+        .map_or(true, |s| p.field.as_deref() == Some(s))
+      && self
+        .case_number
+        .as_deref()
+        .map_or(true, |s| p.case_number.contains(s))
+      && self.text.as_deref().map_or(true, |s| {
+        contains(&p.case_name, s)
+          || contains(&p.gist, s)
+          || contains(&p.case_gist, s)
+          || contains(&p.contents, s)
+      })
+  }
+}
+
+/// `precedents`の結果
+#[derive(Debug, Clone, SimpleObject)]
+pub struct PrecedentPage {
+  /// 条件に合う件数
+  total: usize,
+  items: Vec<Precedent>,
+}
+
+/// 起動時に読み込んだ裁判例
+struct Store {
+  precedents: Vec<Precedent>,
+  by_id: HashMap<String, usize>,
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+  /// 事件IDで裁判例を1件引く
+  async fn precedent(&self, ctx: &Context<'_>, lawsuit_id: String) -> Option<Precedent> {
+    let store = ctx.data_unchecked::<Store>();
+    store
+      .by_id
+      .get(&lawsuit_id)
+      .map(|i| store.precedents[*i].clone())
+  }
+
+  /// 条件に合う裁判例を、裁判年月日の新しい順に`offset`件飛ばして`limit`件（最大100件）返す
+  async fn precedents(
+    &self,
+    ctx: &Context<'_>,
+    filter: Option<PrecedentFilter>,
+    #[graphql(default = 0)] offset: usize,
+    #[graphql(default = 20)] limit: usize,
+  ) -> PrecedentPage {
+    let store = ctx.data_unchecked::<Store>();
+    let filter = filter.unwrap_or_default();
+    let matched = store
+      .precedents
+      .iter()
+      .filter(|p| filter.matches(p))
+      .collect::<Vec<_>>();
+    PrecedentPage {
+      total: matched.len(),
+      items: matched
+        .into_iter()
+        .skip(offset)
+        .take(limit.min(MAX_LIMIT))
+        .cloned()
+        .collect(),
+    }
+  }
+}
+
+pub type PrecedentSchema = Schema<Query, EmptyMutation, EmptySubscription>;
+
+/// `index`の一覧と`output`の裁判例のJSONを読み込んでスキーマを作る
+pub async fn build_schema(index: &str, output: &str) -> Result<PrecedentSchema> {
+  let rows = dataset::load_dataset(index, output).await?;
+  let mut precedents = rows
+    .iter()
+    .map(|row| Precedent::new(&row.entry, &row.info, row.data.as_ref()))
+    .collect::<Result<Vec<_>>>()?;
+  precedents.sort_by(|a, b| {
+    b.date
+      .cmp(&a.date)
+      .then_with(|| a.lawsuit_id.cmp(&b.lawsuit_id))
+  });
+  let by_id = precedents
+    .iter()
+    .enumerate()
+    .map(|(i, p)| (p.lawsuit_id.clone(), i))
+    .collect();
+  Ok(
+    Schema::build(Query, EmptyMutation, EmptySubscription)
+      .data(Store { precedents, by_id })
+      .finish(),
+  )
+}
+
+async fn graphiql() -> impl IntoResponse {
+  Html(GraphiQLSource::build().endpoint("/graphql").finish())
+}
+
+/// 終了が要求されるまで待つ
+async fn wait_shutdown() {
+  while !shutdown::requested() {
+    tokio::time::sleep(Duration::from_millis(500)).await;
+  }
+}
+
+/// `addr`でGraphQLのAPIを提供する。終了が要求されるまで戻らない
+pub async fn serve(index: &str, output: &str, addr: &str) -> Result<()> {
+  let schema = build_schema(index, output).await?;
+  let app = Router::new().route("/graphql", get(graphiql).post_service(GraphQL::new(schema)));
+  let listener = tokio::net::TcpListener::bind(addr).await?;
+  info!(
+    "[START] serve graphql: http://{}/graphql",
+    listener.local_addr()?
+  );
+  axum::serve(listener, app)
+    .with_graceful_shutdown(wait_shutdown())
+    .await?;
+  Ok(())
+}
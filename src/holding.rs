@@ -0,0 +1,103 @@
+//! 裁判要旨（`case_gist`）から結論にあたる文を取り出す`holding`フィールド
+//!
+//! 裁判要旨は「〜の場合において、〜は、〜と解するのが相当である。」のように結論を末尾に置くことが多いので、
+//! 末尾の文を結論とみなす。「（補足意見がある。）」のような注記や、短すぎる文は飛ばして1つ前の文を使う。
+//! 「１　」「(2)」のような項目の番号は取り除く（「１０年の〜」のように区切りの続かない数字は文の一部として残す）。一覧表示のための、要旨よりさらに短い要約として使う。
+
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// これより短い文は結論とみなさない（文字数）
+const MIN_CHARS: usize = 10;
+
+fn numbering_re() -> &'static Regex {
+  static RE: OnceLock<Regex> = OnceLock::new();
+  RE.get_or_init(|| {
+    // 「１０年の〜」のような数字で始まる文を削らないよう、括弧の無い数字には区切りを必須にする
+    Regex::new(
+      r"^(?:[0-9０-９]+[\s　.．、]+|(?:[(（][0-9０-９一二三四五六七八九十]+[)）]|[⑴-⒇①-⑳])[\s　.．、]*)",
+    )
+    .unwrap()
+  })
+}
+
+/// 括弧で囲まれた注記の文か
+fn is_note(sentence: &str) -> bool {
+  (sentence.starts_with('（') || sentence.starts_with('('))
+    && (sentence.ends_with("）。") || sentence.ends_with(")。") || sentence.ends_with('）'))
+}
+
+/// 裁判要旨の結論にあたる文。見つからなければ`None`
+pub fn extract(case_gist: &str) -> Option<String> {
+  let text = case_gist.split_whitespace().collect::<Vec<_>>().join(" ");
+  // 「（補足意見がある。）」の閉じ括弧は、句点で区切ったあと直前の文に付け直す
+  let mut pieces: Vec<String> = Vec::new();
+  for piece in text.split_inclusive('。') {
+    let piece = piece.trim();
+    match pieces.last_mut() {
+      Some(last) if piece.starts_with('）') || piece.starts_with(')') => last.push_str(piece),
+      _ => pieces.push(piece.to_string()),
+    }
+  }
+  let sentences = pieces
+    .iter()
+    .map(|s| numbering_re().replace(s, "").trim().to_string())
+    .filter(|s| !s.is_empty() && !is_note(s))
+    .collect::<Vec<_>>();
+  sentences
+    .iter()
+    .rev()
+    .find(|s| s.chars().count() >= MIN_CHARS)
+    .or_else(|| sentences.last())
+    .cloned()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn strips_item_numbering() {
+    assert_eq!(
+      extract("１　甲は、乙に対し、不法行為に基づく損害賠償責任を負うと解するのが相当である。")
+        .as_deref(),
+      Some("甲は、乙に対し、不法行為に基づく損害賠償責任を負うと解するのが相当である。")
+    );
+    assert_eq!(
+      extract("（２）当該処分は、裁量権の範囲を逸脱したものとはいえない。").as_deref(),
+      Some("当該処分は、裁量権の範囲を逸脱したものとはいえない。")
+    );
+  }
+
+  #[test]
+  fn keeps_sentence_starting_with_number() {
+    assert_eq!(
+      extract(
+        "１０年の消滅時効期間は、権利を行使することができる時から進行すると解するのが相当である。"
+      )
+      .as_deref(),
+      Some(
+        "１０年の消滅時効期間は、権利を行使することができる時から進行すると解するのが相当である。"
+      )
+    );
+  }
+
+  #[test]
+  fn skips_trailing_note() {
+    assert_eq!(
+      extract("本件規定は、憲法１４条１項に違反しないと解するのが相当である。（裁判官甲野太郎の補足意見がある。）")
+        .as_deref(),
+      Some("本件規定は、憲法１４条１項に違反しないと解するのが相当である。")
+    );
+  }
+
+  #[test]
+  fn falls_back_to_short_sentence() {
+    assert_eq!(
+      extract("原判決は、法令の解釈を誤ったものである。破棄。").as_deref(),
+      Some("原判決は、法令の解釈を誤ったものである。")
+    );
+    assert_eq!(extract("上告棄却。").as_deref(), Some("上告棄却。"));
+    assert_eq!(extract(""), None);
+  }
+}
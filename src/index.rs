@@ -0,0 +1,102 @@
+//! 一覧ファイル（`--index`・`--compat-index`で書き出したもの）のメモリ内インデックス
+
+use crate::codec::{decompress, parse_index};
+use anyhow::{anyhow, Result};
+use serde_json::{Map, Value};
+use std::{collections::HashMap, fs};
+
+pub struct MemoryIndex {
+  entries: Vec<Value>,
+  /// `lawsuit_id`から`entries`の位置への対応
+  by_lawsuit_id: HashMap<String, usize>,
+}
+
+/// フィールドの値を検索用の文字列にする
+fn field_text(value: &Value) -> String {
+  match value {
+    Value::String(s) => s.clone(),
+    v => v.to_string(),
+  }
+}
+
+impl MemoryIndex {
+  pub fn from_entries(entries: Vec<Value>) -> Self {
+    let by_lawsuit_id = entries
+      .iter()
+      .enumerate()
+      .filter_map(|(i, v)| {
+        v.get("lawsuit_id")
+          .and_then(|id| id.as_str())
+          .map(|id| (id.to_string(), i))
+      })
+      .collect();
+    MemoryIndex {
+      entries,
+      by_lawsuit_id,
+    }
+  }
+
+  /// JSONの配列・JSON Lines（`--index-format jsonl`）・MessagePack・CBORのどの形式の一覧ファイルも読み込む
+  ///
+  /// `--compress`でgzipやzstdで圧縮された一覧ファイルは展開してから読む
+  pub fn load(path: &str) -> Result<Self> {
+    let bytes = decompress(fs::read(path)?)?;
+    let entries = parse_index(&bytes, false)?;
+    Ok(MemoryIndex::from_entries(entries))
+  }
+
+  pub fn len(&self) -> usize {
+    self.entries.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.entries.is_empty()
+  }
+
+  pub fn get(&self, lawsuit_id: &str) -> Option<&Value> {
+    self
+      .by_lawsuit_id
+      .get(lawsuit_id)
+      .and_then(|i| self.entries.get(*i))
+  }
+
+  /// `query`の各フィールドの値を含む（部分一致する）項目を返す
+  ///
+  /// `{"court_name": "最高裁判所", "trial_type": "SupremeCourt", "limit": 10}`のように指定する。
+  /// `limit`は返す件数の上限を表す特別なフィールド。
+  pub fn search(&self, query: &Map<String, Value>) -> Vec<&Value> {
+    let limit = query
+      .get("limit")
+      .and_then(|v| v.as_u64())
+      .map(|v| v as usize)
+      .unwrap_or(usize::MAX);
+    let conditions = query
+      .iter()
+      .filter(|(k, _)| k.as_str() != "limit")
+      .map(|(k, v)| (k.as_str(), field_text(v)))
+      .collect::<Vec<_>>();
+    self
+      .entries
+      .iter()
+      .filter(|entry| {
+        conditions.iter().all(|(key, text)| {
+          entry
+            .get(*key)
+            .map(|v| field_text(v).contains(text.as_str()))
+            .unwrap_or(false)
+        })
+      })
+      .take(limit)
+      .collect()
+  }
+
+  /// JSON文字列で与えられた検索条件で検索し、結果をJSONの配列の文字列で返す
+  pub fn search_json(&self, query: &str) -> Result<String> {
+    let query: Value = serde_json::from_str(query)?;
+    let query = query
+      .as_object()
+      .ok_or_else(|| anyhow!("検索条件はJSONのオブジェクトで与えてください"))?;
+    let result = self.search(query);
+    Ok(serde_json::to_string(&result)?)
+  }
+}
@@ -0,0 +1,103 @@
+//! 裁判所のホームページの構造が変わったことを報告するための、GitHub issueの草稿の生成
+//!
+//! 取得中に見つけた想定外の項目やパースに失敗したページを記録しておき、
+//! 実行の最後に対象URLの一覧を含むMarkdownとして書き出す。
+
+use anyhow::Result;
+use std::{collections::BTreeMap, fmt::Write, sync::Mutex};
+use tokio::fs;
+use tracing::*;
+
+/// 1つの項目について草稿に載せるURLの数の上限
+const MAX_URLS: usize = 20;
+
+struct Findings {
+  /// 想定外の項目名ごとの、その項目があった詳細ページのURL
+  unknown_fields: BTreeMap<String, Vec<String>>,
+  /// パースに失敗した詳細ページのURLとエラー
+  parse_errors: Vec<(String, String)>,
+}
+
+static FINDINGS: Mutex<Findings> = Mutex::new(Findings {
+  unknown_fields: BTreeMap::new(),
+  parse_errors: Vec::new(),
+});
+
+fn with_findings<T>(f: impl FnOnce(&mut Findings) -> T) -> T {
+  let mut findings = FINDINGS.lock().unwrap_or_else(|e| e.into_inner());
+  f(&mut findings)
+}
+
+pub fn record_unknown_field(field: &str, url: &str) {
+  with_findings(|findings| {
+    findings
+      .unknown_fields
+      .entry(field.to_string())
+      .or_default()
+      .push(url.to_string())
+  });
+}
+
+pub fn record_parse_error(url: &str, error: &anyhow::Error) {
+  with_findings(|findings| {
+    findings
+      .parse_errors
+      .push((url.to_string(), format!("{error:#}")))
+  });
+}
+
+fn render(findings: &Findings) -> String {
+  let mut s = String::new();
+  let _ = writeln!(s, "# 裁判所のホームページの構造の変化について\n");
+  let _ = writeln!(
+    s,
+    "listup_precedent {}で取得中に、想定していない詳細ページの構造を検出しました。\n",
+    env!("CARGO_PKG_VERSION")
+  );
+  if !findings.unknown_fields.is_empty() {
+    let _ = writeln!(s, "## 想定外の項目\n");
+    for (field, urls) in findings.unknown_fields.iter() {
+      let _ = writeln!(s, "### `{field}`（{}件）\n", urls.len());
+      for url in urls.iter().take(MAX_URLS) {
+        let _ = writeln!(s, "- {url}");
+      }
+      if urls.len() > MAX_URLS {
+        let _ = writeln!(s, "- ほか{}件", urls.len() - MAX_URLS);
+      }
+      let _ = writeln!(s);
+    }
+  }
+  if !findings.parse_errors.is_empty() {
+    let _ = writeln!(
+      s,
+      "## パースに失敗したページ（{}件）\n",
+      findings.parse_errors.len()
+    );
+    for (url, error) in findings.parse_errors.iter().take(MAX_URLS) {
+      let _ = writeln!(s, "- {url}\n  - `{error}`");
+    }
+    if findings.parse_errors.len() > MAX_URLS {
+      let _ = writeln!(s, "- ほか{}件", findings.parse_errors.len() - MAX_URLS);
+    }
+  }
+  s
+}
+
+/// 記録があれば草稿を`path`に書き出す。書き出したかどうかを返す
+pub async fn write(path: &str) -> Result<bool> {
+  let draft = with_findings(|findings| {
+    if findings.unknown_fields.is_empty() && findings.parse_errors.is_empty() {
+      None
+    } else {
+      Some(render(findings))
+    }
+  });
+  match draft {
+    Some(draft) => {
+      fs::write(path, draft).await?;
+      info!("issue draft: {path}");
+      Ok(true)
+    }
+    None => Ok(false),
+  }
+}
@@ -0,0 +1,14 @@
+//! 取得済みの裁判例の一覧ファイルを読み込んで検索するためのライブラリ
+//!
+//! C ABIの関数も公開しているので、共有ライブラリとしてビルドしてRやJuliaなどから利用できる。
+//! 詳しくは[`ffi`]を参照。
+//!
+//! 裁判年月日の比較や西暦・元号の変換には[`date::DateExt`]を使える。
+//! 一覧ファイルの展開と形式の判別は[`codec`]にまとめてあり、`listup_precedent`本体もこれを使って読む。
+//! 下流のツールのテストデータには、匿名化したサンプルの裁判例を返す[`examples::sample_data`]を使える。
+
+pub mod codec;
+pub mod date;
+pub mod examples;
+pub mod ffi;
+pub mod index;
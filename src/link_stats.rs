@@ -0,0 +1,60 @@
+//! 一覧ページを順に見ていく間に、同じ判例が複数のページに載った回数の計測
+//!
+//! 取得中に新しい判例が登録されると一覧の並びがずれ、前のページに載っていた判例が次のページにも載ったり、
+//! 逆にどのページにも載らずに取りこぼしたりする。ページごとに新しく見つかった件数と前のページにも載っていた件数を
+//! `page_links`イベントとして流し、`run_summary.json`の`unique_links`・`duplicate_links`に集計する。
+
+use crate::events::Events;
+use serde_json::json;
+use std::collections::HashMap;
+use tracing::*;
+
+#[derive(Debug, Default)]
+pub struct LinkTracker {
+  /// `lawsuit_id`ごとの最初に載っていたページ
+  first_page: HashMap<String, usize>,
+}
+
+impl LinkTracker {
+  pub fn new() -> Self {
+    LinkTracker::default()
+  }
+
+  /// `page_num`のページに載っていた判例を記録し、`page_links`イベントを流す
+  pub fn observe<'a>(
+    &mut self,
+    events: &Events,
+    page_num: usize,
+    lawsuit_ids: impl IntoIterator<Item = &'a str>,
+  ) {
+    let mut links = 0;
+    let mut new_links = 0;
+    let mut duplicate_links = 0;
+    for lawsuit_id in lawsuit_ids {
+      links += 1;
+      match self.first_page.get(lawsuit_id) {
+        None => {
+          self.first_page.insert(lawsuit_id.to_string(), page_num);
+          new_links += 1;
+        }
+        Some(first) if *first != page_num => {
+          debug!("duplicate link: {lawsuit_id} (page {first} and {page_num})");
+          duplicate_links += 1;
+        }
+        Some(_) => {}
+      }
+    }
+    if duplicate_links > 0 {
+      warn!("{duplicate_links} link(s) on page {page_num} already appeared on earlier pages");
+    }
+    events.emit(
+      "page_links",
+      json!({
+        "page": page_num,
+        "links": links,
+        "new_links": new_links,
+        "duplicate_links": duplicate_links,
+      }),
+    );
+  }
+}
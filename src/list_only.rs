@@ -0,0 +1,147 @@
+//! 詳細ページに行かず、一覧ページに表示される情報だけを集める`--list-only`モード
+//!
+//! 1ページで10件分の事件番号・裁判年月日・裁判所名・詳細ページのリンクが分かるので、
+//! 詳細ページとPDFを取得するよりずっと少ないリクエストで全体像を把握できる。
+//! 一覧ページの行の文字列から読み取るので、読み取れなかった項目は`null`になる。
+
+use crate::{
+  events::Events, fetch::Fetcher, link_stats::LinkTracker, output::ValueLst, shutdown, Args,
+  COURTS_DOMEIN, RECENT_LIST_PATH,
+};
+use anyhow::{anyhow, Result};
+use jplaw_data_types::{law::Date, precedent::TrialType};
+use regex::Regex;
+use scraper::{Html, Selector};
+use serde::Serialize;
+use serde_json::json;
+use std::sync::OnceLock;
+use tracing::*;
+
+/// 一覧ページの1行から読み取った判例の情報
+#[derive(Serialize)]
+pub struct ListEntry {
+  pub lawsuit_id: String,
+  pub trial_type: TrialType,
+  pub case_number: Option<String>,
+  pub date: Option<Date>,
+  pub court_name: Option<String>,
+  pub detail_page_link: String,
+}
+
+fn case_number_re() -> &'static Regex {
+  static RE: OnceLock<Regex> = OnceLock::new();
+  RE.get_or_init(|| Regex::new(r"(昭和|平成|令和)(\d+|元)[(（][^)）]+[)）]\d+").unwrap())
+}
+
+fn date_re() -> &'static Regex {
+  static RE: OnceLock<Regex> = OnceLock::new();
+  RE.get_or_init(|| Regex::new(r"(昭和|平成|令和)(\d+|元)年\d+月\d+日").unwrap())
+}
+
+fn court_name_re() -> &'static Regex {
+  static RE: OnceLock<Regex> = OnceLock::new();
+  RE.get_or_init(|| Regex::new(r"[^\s　]*裁判所[^\s　]*").unwrap())
+}
+
+/// 一覧ページの詳細ページへのリンクを含む行から、判例の情報を読み取る
+pub async fn parse_list_page(html: &str) -> Result<Vec<ListEntry>> {
+  let document = Html::parse_document(html);
+  let row_selector = Selector::parse("tr").unwrap();
+  let link_selector = Selector::parse("a[href*=\"/app/hanrei_jp/detail\"]").unwrap();
+  let mut entries: Vec<ListEntry> = Vec::new();
+  for row in document.select(&row_selector) {
+    let Some(link) = row
+      .select(&link_selector)
+      .next()
+      .and_then(|a| a.value().attr("href"))
+    else {
+      continue;
+    };
+    let detail_page_link = if link.starts_with("http") {
+      link.to_string()
+    } else {
+      format!("{COURTS_DOMEIN}{link}")
+    };
+    if entries
+      .iter()
+      .any(|e| e.detail_page_link == detail_page_link)
+    {
+      continue;
+    }
+    let text = row.text().collect::<Vec<_>>().join(" ");
+    let date = match date_re().find(&text) {
+      Some(m) => crate::parse_date_era_str(m.as_str()).await.ok(),
+      None => None,
+    };
+    entries.push(ListEntry {
+      lawsuit_id: crate::get_lawsuit_id(&detail_page_link).await?,
+      trial_type: crate::trial_type_from_link(&detail_page_link)?,
+      case_number: case_number_re().find(&text).map(|m| m.as_str().to_string()),
+      date,
+      court_name: court_name_re().find(&text).map(|m| m.as_str().to_string()),
+      detail_page_link,
+    });
+  }
+  Ok(entries)
+}
+
+/// 一覧ページだけを順に取得して、読み取った判例の情報を`--index`の一覧ファイルに書き出す
+///
+/// 期間検索では判例が載っていないページに来るまで次のページに進む
+pub async fn crawl(args: &Args, fetcher: &Fetcher, events: &Events) -> Result<()> {
+  let mut file = ValueLst::create(&args.index, args.index_format).await?;
+  events.emit("run_started", json!({ "list_only": true }));
+  let date_range = if args.recent {
+    None
+  } else {
+    let start = args
+      .start
+      .as_deref()
+      .ok_or_else(|| anyhow!("--startが指定されていません"))?;
+    let end = args
+      .end
+      .as_deref()
+      .ok_or_else(|| anyhow!("--endが指定されていません"))?;
+    Some((
+      crate::parse_date(start).await?,
+      crate::parse_date(end).await?,
+    ))
+  };
+  let mut tracker = LinkTracker::new();
+  let mut page_num = 1;
+  loop {
+    if shutdown::requested() {
+      warn!("interrupted at page {page_num}");
+      events.emit("interrupted", json!({ "page": page_num }));
+      break;
+    }
+    events.emit("page_started", json!({ "page": page_num }));
+    let html = match &date_range {
+      Some((start_date, end_date)) => {
+        crate::get_reqest(fetcher, start_date, end_date, page_num).await?
+      }
+      None => {
+        fetcher
+          .get_text(&format!("{COURTS_DOMEIN}{RECENT_LIST_PATH}"))
+          .await?
+      }
+    };
+    let entries = parse_list_page(&html).await?;
+    info!("page {}: {} entries", page_num, entries.len());
+    tracker.observe(
+      events,
+      page_num,
+      entries.iter().map(|e| e.lawsuit_id.as_str()),
+    );
+    for entry in &entries {
+      file.write(&serde_json::to_value(entry)?).await?;
+    }
+    if entries.is_empty() || date_range.is_none() {
+      break;
+    }
+    page_num += 1;
+  }
+  file.flush().await?;
+  crate::compress::compress_file(&args.index).await?;
+  Ok(())
+}
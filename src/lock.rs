@@ -0,0 +1,65 @@
+//! 同じ出力フォルダに対して複数のインスタンスが同時に書き出すのを防ぐロックファイル
+//!
+//! cronの実行が重なると一覧ファイルが壊れるため、出力フォルダにロックファイルを置いてOSのファイルロックを取る。
+//! プロセスが落ちた場合もロックはOSが解放するので、残ったロックファイルが次の実行を妨げることはない。
+
+use anyhow::{anyhow, Result};
+use fs2::FileExt;
+use std::{
+  fs::{self, File, OpenOptions},
+  io::{Read, Seek, Write},
+  path::{Path, PathBuf},
+};
+use tracing::*;
+
+const LOCK_FILE_NAME: &str = ".listup_precedent.lock";
+
+/// 保持している間ロックを取り続ける。dropするとロックを解放してロックファイルを消す
+pub struct OutputLock {
+  file: Option<File>,
+  path: PathBuf,
+}
+
+impl OutputLock {
+  pub fn acquire(output: &str) -> Result<Self> {
+    fs::create_dir_all(output)?;
+    let path = Path::new(output).join(LOCK_FILE_NAME);
+    let mut file = OpenOptions::new()
+      .read(true)
+      .write(true)
+      .create(true)
+      .truncate(false)
+      .open(&path)?;
+    if file.try_lock_exclusive().is_err() {
+      let mut holder = String::new();
+      let _ = file.read_to_string(&mut holder);
+      return Err(anyhow!(
+        "出力フォルダ{}は別のインスタンス（{}）が使用中です。終了するのを待ってから実行してください（ロックファイル：{}）",
+        output,
+        holder.trim(),
+        path.display()
+      ));
+    }
+    file.set_len(0)?;
+    file.rewind()?;
+    write!(file, "pid {}", std::process::id())?;
+    file.flush()?;
+    info!("lock: {}", path.display());
+    Ok(OutputLock {
+      file: Some(file),
+      path,
+    })
+  }
+}
+
+impl Drop for OutputLock {
+  fn drop(&mut self) {
+    // Windowsでは開いているファイルを消せないので、先に閉じる
+    if let Some(file) = self.file.take() {
+      let _ = FileExt::unlock(&file);
+    }
+    if let Err(e) = fs::remove_file(&self.path) {
+      warn!("failed to remove lock file {}: {e}", self.path.display());
+    }
+  }
+}
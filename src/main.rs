@@ -20,6 +20,8 @@
 //! - `--output`オプションにはその生成した裁判例の情報を書き出すフォルダのpathを与えます。
 //! - `--index`オプションには裁判例情報の一覧を書き出すJSONファイルのpathを与えます。
 //!
+//! そのほかのオプションやサブコマンドの使い方はREADMEを参照してください。
+//!
 //! # 生成される情報
 //!
 //! 以下のフィールドを持つオブジェクトの配列が生成されます。
@@ -61,31 +63,138 @@
 //! - case_gis: string 裁判要旨
 //! - ref_law: string 参照条文
 //!
+//! 各裁判例のJSONには、このほかに`uuid`・`_meta`などのこのツールが付けるフィールドも書き出します。
+//!
 //!
 //! ---
 //! [MIT License](https://github.com/japanese-law-analysis/listup_precedent/blob/master/LICENSE)
 //! (c) 2023 Naoki Kaneko (a.k.a. "puripuri2100")
 //!
 
+// offline-onlyはネットワークに接続しないビルドを保証するためのものなので、onlineと一緒には有効にできない
+#[cfg(all(feature = "offline-only", feature = "online"))]
+compile_error!("offline-onlyのfeatureは`--no-default-features`と併用してください（onlineと同時には有効にできません）");
+
+mod akoma_ntoso;
+mod archive;
+#[cfg(feature = "arrow")]
+mod arrow_index;
+mod audit;
+mod bundle;
+mod canary;
+mod case_mark;
+mod charset;
+mod checkpoint;
+mod chunk;
+mod circuit_breaker;
+mod ckan;
+mod compat;
+mod compress;
+mod conditional;
+mod contents_file;
+mod court_stats;
+mod csv_export;
+mod dataset;
+mod diff_text;
+mod disk;
+#[cfg(feature = "arrow")]
+mod duckdb_export;
+mod en_summary;
+mod es_export;
+mod events;
+mod exclude;
+mod explore;
+mod fetch;
+mod filename_template;
+mod furigana;
+#[cfg(feature = "graphql")]
+mod graphql;
+mod holding;
+mod issue_draft;
+mod link_stats;
+mod list_only;
+mod lock;
+mod meili_export;
+mod meta;
+mod notify;
+mod object_output;
+mod orthography;
+mod output;
+mod output_writer;
+mod pages;
+#[cfg(feature = "arrow")]
+mod parquet_export;
+mod partition;
+mod pdf_store;
+mod pdf_workers;
+mod permissions;
+mod pipeline;
+mod postprocess;
+mod preset;
+mod protobuf;
+mod raw;
+mod record;
+mod ref_law;
+mod related;
+mod repair;
+mod response_cache;
+mod retry_failed;
+mod retry_queue;
+mod robots;
+mod rpc;
+mod schedule;
+mod schema;
+mod shutdown;
+#[cfg(feature = "sqlite")]
+mod sqlite_index;
+mod stable_id;
+mod stats_report;
+mod summary;
+mod text_encoding;
+mod throttle;
+mod warnings;
+mod watch;
+
 use anyhow::{anyhow, Result};
-use clap::Parser;
+use archive::PdfFallback;
+use audit::AuditLog;
+use checkpoint::Checkpoint;
+use circuit_breaker::CircuitBreaker;
+use clap::{Parser, Subcommand};
+use compat::CompatVersion;
+use conditional::ConditionalCache;
+use disk::DiskCheckPolicy;
+use events::Events;
+use exclude::ExcludeAction;
+use fetch::{BodyLimits, ClientOptions, Fetcher};
+use furigana::FuriganaMode;
 use japanese_law_xml_schema::law::Era;
-use jplaw_data_types::{
-  law::Date,
-  listup::{PrecedentData, PrecedentInfo},
-  precedent::TrialType,
-};
-use jplaw_io::{flush_file_value_lst, gen_file_value_lst, init_logger, write_value_lst};
-use jplaw_pdf2text::{clean_up, pdf_bytes_to_text};
+use jplaw_data_types::{law::Date, listup::PrecedentData, precedent::TrialType};
+use jplaw_io::init_logger;
+use lock::OutputLock;
+use meta::RecordMeta;
+use output::{IndexFormat, IndexWriter, OverwritePolicy, RecordFormat};
+use pipeline::ListPages;
 use regex::Regex;
+use response_cache::ResponseCache;
+use retry_queue::{OnError, RetryQueue};
+use robots::RobotsPolicy;
 use scraper::{Html, Selector};
-use tokio::{self, fs::*, io::AsyncWriteExt};
+use serde_json::json;
+use std::{
+  collections::HashMap,
+  time::{Duration, Instant},
+};
+use throttle::Throttle;
 use tokio_stream::StreamExt;
 use tracing::*;
 use url::Url;
 
 const COURTS_DOMEIN: &str = "https://www.courts.go.jp";
 
+/// 「最近の裁判例」一覧ページ
+const RECENT_LIST_PATH: &str = "/app/hanrei_jp/list2";
+
 async fn era_to_uri_encode(era: &Era) -> String {
   match era {
     Era::Showa => "%E6%98%AD%E5%92%8C".to_string(),
@@ -168,20 +277,68 @@ async fn parse_date_era_str(str: &str) -> Result<Date> {
   })
 }
 
-async fn get_reqest(start_date: &Date, end_date: &Date, page: usize) -> Result<String> {
+async fn get_reqest(
+  fetcher: &Fetcher,
+  start_date: &Date,
+  end_date: &Date,
+  page: usize,
+) -> Result<String> {
   // https://www.courts.go.jp/app/hanrei_jp/list1?page={page}&sort=1&filter[judgeDateMode]=2&filter[judgeGengoFrom]={}&filter[judgeYearFrom]={}&filter[judgeMonthFrom]={}&filter[judgeDayFrom]={}&filter[judgeGengoTo]={}&filter[judgeYearTo]={}&filter[judgeMonthTo]={}&filter[judgeDayTo]={}
   let url_str = format!("{COURTS_DOMEIN}/app/hanrei_jp/list1?page={page}&sort=1&filter%5BjudgeDateMode%5D=2&filter%5BjudgeGengoFrom%5D={}&filter%5BjudgeYearFrom%5D={}&filter%5BjudgeMonthFrom%5D={}&filter%5BjudgeDayFrom%5D={}&filter%5BjudgeGengoTo%5D={}&filter%5BjudgeYearTo%5D={}&filter%5BjudgeMonthTo%5D={}&filter%5BjudgeDayTo%5D={}", era_to_uri_encode(&start_date.era).await, start_date.year, start_date.month.unwrap_or_default(), start_date.day.unwrap_or_default(), era_to_uri_encode(&end_date.era).await, end_date.year, end_date.month.unwrap_or_default(), end_date.day.unwrap_or_default());
-  let body = reqwest::get(url_str).await?.text().await?;
+  let body = fetcher.get_text(&url_str).await?;
   Ok(body)
 }
 
-async fn get_pdf_text(pdf_link: &str) -> Result<String> {
-  let bytes = reqwest::get(pdf_link).await?.bytes().await?;
-  let text = pdf_bytes_to_text(&bytes)?;
-  let text = clean_up(&text);
+/// `use_validators`が真でPDFが前回から更新されていなければ`None`を返す
+///
+/// `fallback`が与えられていてPDFが404のときはアーカイブサービスから取得する
+async fn download_pdf(
+  fetcher: &Fetcher,
+  pdf_link: &str,
+  use_validators: bool,
+  fallback: Option<PdfFallback>,
+) -> Result<Option<Vec<u8>>> {
+  match fetcher.get_pdf_if_modified(pdf_link, use_validators).await {
+    Ok(bytes) => Ok(bytes),
+    Err(e) => match fallback {
+      Some(fallback) if fetch::is_not_found(&e) => {
+        let archive_url = fallback.archive_url(pdf_link);
+        info!("pdf not found, try archive: {}", &archive_url);
+        let bytes = fetcher
+          .get_pdf_if_modified(&archive_url, false)
+          .await?
+          .ok_or_else(|| anyhow!("条件付きでないリクエストに304が返された：{archive_url}"))?;
+        Ok(Some(bytes))
+      }
+      _ => Err(e),
+    },
+  }
+}
+
+/// PDFから本文を抽出する。かかった時間を`meta`に記録する
+///
+/// 抽出はCPUを使い続けるので、その間も他の判例の取得が進むように`--pdf-workers`のワーカースレッドで行う
+async fn extract_pdf_text(bytes: Vec<u8>, meta: &mut RecordMeta) -> Result<String> {
+  let extract_start = Instant::now();
+  let text = pdf_workers::extract_text(bytes).await?;
+  meta.pdf_extract_millis = Some(meta::to_millis(extract_start.elapsed()));
   Ok(text)
 }
 
+/// PDFをダウンロードして本文を抽出する
+async fn get_pdf_text(
+  fetcher: &Fetcher,
+  pdf_link: &str,
+  use_validators: bool,
+  fallback: Option<PdfFallback>,
+  meta: &mut RecordMeta,
+) -> Result<Option<String>> {
+  match download_pdf(fetcher, pdf_link, use_validators, fallback).await? {
+    Some(bytes) => Ok(Some(extract_pdf_text(bytes, meta).await?)),
+    None => Ok(None),
+  }
+}
+
 async fn get_lawsuit_id(url_str: &str) -> Result<String> {
   let url = Url::parse(url_str)?;
   let mut querys = url.query_pairs();
@@ -189,50 +346,1538 @@ async fn get_lawsuit_id(url_str: &str) -> Result<String> {
   Ok(id.to_string())
 }
 
+/// 詳細ページのリンク（`/app/hanrei_jp/detail2?id=...`など）の数字から裁判の種類を判定する
+fn trial_type_from_link(link: &str) -> Result<TrialType> {
+  let link_re = Regex::new(r"[^\d]+(?P<type_number>\d).*").unwrap();
+  let trial_type = match link_re
+    .captures(link)
+    .ok_or_else(|| anyhow!("年号付き日付のパースに失敗"))?
+    .name("type_number")
+    .ok_or_else(|| anyhow!("リンクが想定外の形をしている"))?
+    .as_str()
+    .parse::<usize>()?
+  {
+    2 => TrialType::SupremeCourt,
+    3 => TrialType::HighCourt,
+    4 => TrialType::LowerCourt,
+    5 => TrialType::AdministrativeCase,
+    6 => TrialType::LaborCase,
+    7 => TrialType::IPCase,
+    _ => unreachable!(),
+  };
+  Ok(trial_type)
+}
+
 fn remove_line_break(str: &str) -> String {
   str.lines().map(|s| s.trim()).collect::<String>()
 }
 
-async fn write_data(output: &str, filename: &str, data: &PrecedentData) -> Result<()> {
-  let mut buf = File::create(format!("{output}/{filename}.json")).await?;
-  let s = serde_json::to_string_pretty(&data)?;
-  buf.write_all(s.as_bytes()).await?;
-  buf.flush().await?;
-  Ok(())
+/// 詳細ページのHTMLから判例の情報を取り出す（`contents`は`None`のまま）
+async fn parse_detail_page(
+  detail_page_html: &str,
+  trial_type: TrialType,
+  lawsuit_id: String,
+  detail_page_link: String,
+) -> Result<PrecedentData> {
+  let detail_document = Html::parse_document(detail_page_html);
+  let info_selector =
+    Selector::parse("div.module-search-page-table-parts-result-detail > dl").unwrap();
+  let mut date_str = String::new();
+  let mut case_number = String::new();
+  let mut case_name = String::new();
+  let mut court_name = String::new();
+  let mut right_type = None;
+  let mut lawsuit_type = None;
+  let mut result_type = None;
+  let mut result = None;
+  let mut article_info = None;
+  let mut original_court_name = None;
+  let mut original_case_number = None;
+  let mut original_result = None;
+  let mut original_date = None;
+  let mut field = None;
+  let mut gist = None;
+  let mut case_gist = None;
+  let mut ref_law = None;
+  let mut full_pdf_link = String::new();
+  let mut info_stream = tokio_stream::iter(detail_document.select(&info_selector));
+  while let Some(info_element) = info_stream.next().await {
+    let dt_selector = Selector::parse("dt").unwrap();
+    let dd_text_selector = Selector::parse("dd > p").unwrap();
+    let dd_link_selector = Selector::parse("dd > ul > li > a").unwrap();
+    let dt_text = info_element
+      .select(&dt_selector)
+      .next()
+      .unwrap()
+      .text()
+      .collect::<String>()
+      .trim()
+      .to_string();
+    match &*dt_text {
+      "事件番号" => {
+        let text = info_element
+          .select(&dd_text_selector)
+          .next()
+          .unwrap()
+          .text()
+          .collect::<String>()
+          .trim()
+          .to_string();
+        case_number = text;
+      }
+      "事件名" => {
+        let text = info_element
+          .select(&dd_text_selector)
+          .next()
+          .unwrap()
+          .text()
+          .collect::<String>()
+          .trim()
+          .to_string();
+        case_name = text;
+      }
+      "裁判年月日" => {
+        let text = info_element
+          .select(&dd_text_selector)
+          .next()
+          .unwrap()
+          .text()
+          .collect::<String>()
+          .trim()
+          .to_string();
+        date_str = text;
+      }
+      "裁判所名" | "裁判所名・部" | "法廷名" => {
+        let text = info_element
+          .select(&dd_text_selector)
+          .next()
+          .unwrap()
+          .text()
+          .collect::<String>()
+          .trim()
+          .to_string();
+        court_name = remove_line_break(&text);
+      }
+      "権利種別" => {
+        let text = info_element
+          .select(&dd_text_selector)
+          .next()
+          .unwrap()
+          .text()
+          .collect::<String>()
+          .trim()
+          .to_string();
+        if !text.is_empty() {
+          right_type = Some(text);
+        }
+      }
+      "訴訟類型" => {
+        let text = info_element
+          .select(&dd_text_selector)
+          .next()
+          .unwrap()
+          .text()
+          .collect::<String>()
+          .trim()
+          .to_string();
+        if !text.is_empty() {
+          lawsuit_type = Some(text);
+        }
+      }
+      "裁判種別" => {
+        let text = info_element
+          .select(&dd_text_selector)
+          .next()
+          .unwrap()
+          .text()
+          .collect::<String>()
+          .trim()
+          .to_string();
+        if !text.is_empty() {
+          result_type = Some(text);
+        }
+      }
+      "結果" => {
+        let text = info_element
+          .select(&dd_text_selector)
+          .next()
+          .unwrap()
+          .text()
+          .collect::<String>()
+          .trim()
+          .to_string();
+        if !text.is_empty() {
+          result = Some(text);
+        }
+      }
+      "判例集等巻・号・頁" | "高裁判例集登載巻・号・頁" => {
+        let text = info_element
+          .select(&dd_text_selector)
+          .next()
+          .unwrap()
+          .text()
+          .collect::<String>()
+          .trim()
+          .to_string();
+        if !text.is_empty() {
+          article_info = Some(text);
+        }
+      }
+      "原審裁判所名" => {
+        let text = info_element
+          .select(&dd_text_selector)
+          .next()
+          .unwrap()
+          .text()
+          .collect::<String>()
+          .trim()
+          .to_string();
+        if !text.is_empty() {
+          original_court_name = Some(text);
+        }
+      }
+      "原審事件番号" => {
+        let text = info_element
+          .select(&dd_text_selector)
+          .next()
+          .unwrap()
+          .text()
+          .collect::<String>()
+          .trim()
+          .to_string();
+        if !text.is_empty() {
+          original_case_number = Some(text);
+        }
+      }
+      "原審結果" => {
+        let text = info_element
+          .select(&dd_text_selector)
+          .next()
+          .unwrap()
+          .text()
+          .collect::<String>()
+          .trim()
+          .to_string();
+        if !text.is_empty() {
+          original_result = Some(text);
+        }
+      }
+      "原審裁判年月日" => {
+        let text = info_element
+          .select(&dd_text_selector)
+          .next()
+          .unwrap()
+          .text()
+          .collect::<String>()
+          .trim()
+          .to_string();
+        if !text.is_empty() {
+          let date = parse_date_era_str(&text).await?;
+          original_date = Some(date);
+        }
+      }
+      "分野" => {
+        let text = info_element
+          .select(&dd_text_selector)
+          .next()
+          .unwrap()
+          .text()
+          .collect::<String>()
+          .trim()
+          .to_string();
+        if !text.is_empty() {
+          field = Some(text);
+        }
+      }
+      "判示事項の要旨" | "判示事項" => {
+        let text = info_element
+          .select(&dd_text_selector)
+          .next()
+          .unwrap()
+          .text()
+          .collect::<String>()
+          .trim()
+          .to_string();
+        if !text.is_empty() {
+          gist = Some(text);
+        }
+      }
+      "裁判要旨" => {
+        let text = info_element
+          .select(&dd_text_selector)
+          .next()
+          .unwrap()
+          .text()
+          .collect::<String>()
+          .trim()
+          .to_string();
+        if !text.is_empty() {
+          case_gist = Some(text);
+        }
+      }
+      "参照法条" => {
+        let text = info_element
+          .select(&dd_text_selector)
+          .next()
+          .unwrap()
+          .text()
+          .collect::<String>()
+          .trim()
+          .to_string();
+        if !text.is_empty() {
+          ref_law = Some(text);
+        }
+      }
+      "全文" => {
+        let link = info_element
+          .select(&dd_link_selector)
+          .next()
+          .unwrap()
+          .value()
+          .attr("href")
+          .expect("a属性はhrefを持っているはず");
+        full_pdf_link = format!("{COURTS_DOMEIN}{link}");
+      }
+      _ => {
+        info!("!!! OTHER: {}", &dt_text);
+        issue_draft::record_unknown_field(&dt_text, &detail_page_link);
+      }
+    }
+  }
+  let date = parse_date_era_str(date_str.trim()).await?;
+  Ok(PrecedentData {
+    trial_type,
+    date,
+    case_number,
+    case_name,
+    court_name,
+    right_type,
+    lawsuit_type,
+    result_type,
+    result,
+    article_info,
+    original_court_name,
+    original_case_number,
+    original_result,
+    original_date,
+    field,
+    gist,
+    case_gist,
+    ref_law,
+    lawsuit_id,
+    detail_page_link,
+    contents: None,
+    full_pdf_link,
+  })
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+  /// 前回までの実行で失敗したものだけを取得し直し、成功したものを既存の出力と一覧にマージする
+  RetryFailed {
+    /// 失敗を記録したファイル（省略時は`--failed-queue`）
+    #[clap(long)]
+    failed: Option<String>,
+  },
+  /// 出力フォルダを監視し、裁判例のファイルが手で追加・削除されたら一覧と検索用のインデックスを作り直す
+  Watch {
+    /// 出力フォルダを調べる間隔（秒）
+    #[clap(long, default_value_t = 5)]
+    interval: u64,
+  },
+  /// `--save-pdf`で残したPDFが、裁判例のJSONの`pdf_sha256`と一致するかを確かめる
+  VerifyPdfs,
+  /// 裁判例ごとのファイルか一覧ファイルの項目の形式を、JSON Schema（または`.proto`ファイル）として標準出力に書く
+  Schema {
+    /// スキーマを書き出す対象
+    #[clap(long, value_enum, default_value = "data")]
+    target: schema::SchemaTarget,
+  },
+  /// 既存の一覧と裁判例のJSONから、同じ日・同じ裁判所で事件番号が似ている裁判例同士を`related`フィールドで結びつける
+  LinkRelated,
+  /// `--audit-log`に記録したURLを同じ順序で取得し直し、記録と結果が食い違ったものを報告する
+  Replay {
+    /// 再現したい実行の監査ログ
+    #[clap(long)]
+    audit_log: String,
+  },
+  /// 閉じられていない一覧ファイルを閉じ直し、壊れた裁判例のJSONを取得し直す対象として`--failed-queue`に記録する
+  Repair,
+  /// `--skip-pdf-over`で後回しにした大きなPDFの判例を取得し、既存の出力と一覧にマージする
+  LargePdfs {
+    /// 後回しにした判例を記録したファイル（省略時は`--large-pdfs`）
+    #[clap(long)]
+    list: Option<String>,
+  },
+  /// 既存の一覧と裁判例のJSONから、選んだ列を並べたCSVを書き出す
+  ExportCsv {
+    /// 書き出すCSVファイル
+    #[clap(long, default_value = "list.csv")]
+    csv: String,
+    /// 出力する列（カンマ区切り）。省略時は事件番号・事件名・裁判年月日などの主な列
+    #[clap(long, value_delimiter = ',')]
+    columns: Vec<String>,
+  },
+  /// 既存の一覧と裁判例のJSONを、西暦の年ごとのパーティションに分けたParquetのデータセットにする
+  #[cfg(feature = "arrow")]
+  ExportParquet {
+    /// 書き出すディレクトリ
+    #[clap(long, default_value = "parquet")]
+    dir: String,
+  },
+  /// 既存の一覧と裁判例のJSONを、Arrow IPC（Feather v2）形式のデータセットにする
+  #[cfg(feature = "arrow")]
+  ExportArrow {
+    /// 書き出すファイル
+    #[clap(long, default_value = "precedents.arrow")]
+    path: String,
+  },
+  /// 既存の一覧と裁判例のJSONを、裁判例ごとのAkoma Ntoso（LegalDocML）の判決文書にする
+  ExportAkomaNtoso {
+    /// 書き出すディレクトリ
+    #[clap(long, default_value = "akn")]
+    dir: String,
+  },
+  /// 既知の判例を数件だけ取得して期待値と比べ、ページの構造が変わっていないか確かめる
+  Canary {
+    /// 判例の詳細ページのリンクと期待値を並べたJSONファイル
+    #[clap(long, default_value = "canary.json")]
+    fixtures: String,
+    /// 比べずに、現在のページから解析した値で期待値を書き直す
+    #[clap(long)]
+    update: bool,
+    /// サーバーにリクエストを送らず、ライブラリのサンプルのデータが解析結果と一致するかだけを確かめる
+    #[clap(long, conflicts_with = "update")]
+    sample: bool,
+  },
+  /// 一覧ページ・詳細ページ・PDFを取得して`--cache-dir`に保存するだけで、解析や書き出しはしない
+  FetchRaw {
+    /// 取得した判例のリンクを書き出すファイル
+    #[clap(long, default_value = "raw_links.jsonl")]
+    links: String,
+  },
+  /// `fetch-raw`で`--cache-dir`に保存したものだけから、リクエストを送らずに解析して書き出す
+  ParseRaw {
+    /// `fetch-raw`が書き出した判例のリンクのファイル
+    #[clap(long, default_value = "raw_links.jsonl")]
+    links: String,
+  },
+  /// 既存の一覧と裁判例のJSONを、DuckDBで読み込むParquetとビューを作るSQLにする
+  #[cfg(feature = "arrow")]
+  ExportDuckdb {
+    /// 書き出すディレクトリ
+    #[clap(long, default_value = "duckdb")]
+    dir: String,
+  },
+  /// 既存の一覧と裁判例のJSONを読み込み、GraphQLのAPIとして提供する
+  #[cfg(feature = "graphql")]
+  ServeGraphql {
+    /// 待ち受けるアドレス
+    #[clap(long, default_value = "127.0.0.1:8000")]
+    addr: String,
+  },
+  /// 既存の一覧と裁判例のJSONを、`lawsuit_id`を主キーにしてMeilisearchのインデックスに登録する
+  ExportMeili {
+    /// MeilisearchのURL。APIキーは環境変数`LISTUP_MEILI_KEY`から読む
+    #[clap(long, default_value = "http://localhost:7700")]
+    host: String,
+    /// 登録するインデックスの名前
+    #[clap(long, default_value = "precedents")]
+    meili_index: String,
+    /// 1回のリクエストで登録する件数
+    #[clap(long, default_value_t = 1000)]
+    batch_size: usize,
+  },
+  /// 本文などを含めず、件数・分布・フィールドの充足率などの統計だけを共有用のJSONとMarkdownにまとめる
+  ExportStats {
+    /// 書き出すJSONファイル
+    #[clap(long, default_value = "stats.json")]
+    json: String,
+    /// 書き出すMarkdownファイル
+    #[clap(long, default_value = "stats.md")]
+    markdown: String,
+  },
+  /// 既存の一覧と裁判例のJSONを、Elasticsearch・OpenSearchのインデックスに`_bulk`APIで登録する
+  ExportEs {
+    /// ElasticsearchのURL
+    #[clap(long, default_value = "http://localhost:9200")]
+    url: String,
+    /// 登録するインデックスの名前。無ければkuromojiを使うマッピングで作る
+    #[clap(long, default_value = "precedents")]
+    es_index: String,
+    /// 1回の`_bulk`のリクエストで登録する件数
+    #[clap(long, default_value_t = 500)]
+    batch_size: usize,
+    /// 認証のユーザー名。パスワードは環境変数`LISTUP_ES_PASSWORD`から読む
+    #[clap(long)]
+    user: Option<String>,
+  },
 }
 
-#[derive(Parser, Debug)]
-#[clap(author, version, about, long_about = None)]
+#[derive(Parser, Debug, Clone)]
+#[clap(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 struct Args {
-  /// 解析結果を出力するJSONファイルへのpath
+  #[clap(subcommand)]
+  command: Option<Command>,
+  /// 解析結果を出力するJSONファイルへのpath。`s3://bucket/prefix`とするとS3互換のオブジェクトストレージに書き出す
   #[clap(short, long)]
   output: String,
+  /// `--output`にオブジェクトストレージを指定したときに、ローカルで出力フォルダの代わりに使うフォルダ
+  #[clap(long)]
+  staging_dir: Option<String>,
   /// 一覧を出力するJSONファイル名
   #[clap(short, long)]
   index: String,
+  /// 一覧ファイルの形式。`jsonl`では1行に1件ずつ書き出す
+  #[clap(long, value_enum, default_value = "json")]
+  index_format: IndexFormat,
+  /// 裁判例ごとのファイル名のテンプレート（`{trial_type}_{date}_{case_number}`など）。省略時は従来の名前
+  #[clap(long)]
+  filename_template: Option<String>,
+  /// 裁判例ごとのファイルを出力フォルダの下の`2021/SupremeCourt/`のようなサブフォルダに振り分ける
+  #[clap(long, value_enum, value_delimiter = ',')]
+  partition_by: Vec<partition::PartitionKey>,
+  /// 裁判例ごとのファイルを出力フォルダに作らず、一覧ファイルなどと合わせてこのアーカイブ（`.tar`・`.tar.gz`・`.tar.zst`・`.zip`）にまとめる
+  #[clap(long)]
+  archive: Option<String>,
+  /// `export-csv`・`--court-stats`などで書き出すCSVの文字コード
+  #[clap(long, value_enum, default_value = "utf8")]
+  output_encoding: text_encoding::OutputEncoding,
+  /// 裁判例ごとのファイルと一覧ファイルを圧縮して書き出す。拡張子に`.zst`・`.gz`が付く
+  #[clap(long, value_enum, default_value = "none")]
+  compress: compress::Compression,
+  /// 裁判例ごとのファイルの形式。`msgpack`・`cbor`・`chunks`・`protobuf`では拡張子もそれぞれ`.msgpack`・`.cbor`・`.chunks.jsonl`・`.pb`になる
+  #[clap(long, value_enum, default_value = "json")]
+  format: RecordFormat,
+  /// `--format chunks`で本文を区切る塊の文字数
+  #[clap(long, default_value_t = 1000)]
+  chunk_size: usize,
+  /// `--format chunks`で隣り合う塊を重ねる文字数
+  #[clap(long, default_value_t = 100)]
+  chunk_overlap: usize,
+  /// PDFから抽出した本文を裁判例のJSONに含めず、このフォルダ（出力フォルダからの相対パス）に`.txt`として書き出す
+  #[clap(long)]
+  contents_dir: Option<String>,
+  /// `--contents-dir`の本文をgzipで圧縮して`.txt.gz`にする
+  #[clap(long, requires = "contents_dir")]
+  contents_gzip: bool,
+  /// 裁判例ごとのJSONファイル（と`--deterministic`でのJSONの配列の一覧ファイル）の書き方
+  #[clap(long, value_enum, default_value = "pretty")]
+  json_style: output::JsonStyle,
+  /// 裁判例ごとのファイルを作らず、1件1行のJSON（NDJSON）として標準出力に流す。ログは標準エラーに出す
+  #[clap(long, conflicts_with_all = ["archive", "contents_dir", "rpc"])]
+  stdout: bool,
+  /// 裁判例の書き出し先。`--output s3://…`では`s3`、`--stdout`では`stdout`になる
+  #[clap(
+    long,
+    value_enum,
+    default_value = "json-dir",
+    conflicts_with = "stdout"
+  )]
+  writer: output_writer::OutputBackend,
+  /// `--writer jsonl`で、1つのファイルがこのサイズ（`1GB`のように単位を付けられる）を超える前に次のファイルに切り替える
+  #[clap(long, value_parser = parse_size, conflicts_with = "stdout")]
+  max_file_size: Option<u64>,
+  /// 同じ範囲の取得から同じバイト列を書き出す。一覧の項目を`lawsuit_id`の順に並べ、`_meta`から取得日時・時間・再試行回数を除く
+  #[clap(long)]
+  deterministic: bool,
   /// 取得したい判例の日時の開始 yyyy/mm/dd形式で記述
-  #[clap(short, long)]
-  start: String,
+  #[clap(short, long, required_unless_present_any = ["recent", "rpc", "since_id", "preset"])]
+  start: Option<String>,
   /// 取得したい判例の日時の終了 yyyy/mm/dd形式で記述
-  #[clap(short, long)]
-  end: String,
+  #[clap(short, long, required_unless_present_any = ["recent", "rpc", "since_id", "preset"])]
+  end: Option<String>,
+  /// 期間検索ではなく「最近の裁判例」一覧ページに載っている判例だけを取得する
+  #[clap(long, conflicts_with_all = ["start", "end"])]
+  recent: bool,
+  /// 期間検索ではなく、`lawsuit_id`がこの値から`--until-id`までの判例を詳細ページから直接取得する
+  #[clap(long, requires = "until_id", conflicts_with_all = ["start", "end", "recent"])]
+  since_id: Option<u64>,
+  /// `--since-id`で取得する`lawsuit_id`の範囲の終わり（この値を含む）
+  #[clap(long, requires = "since_id")]
+  until_id: Option<u64>,
+  /// `--preset-file`に保存した名前付きの取得条件を使う
+  #[clap(long, conflicts_with_all = ["start", "end", "recent", "since_id", "save_preset"])]
+  preset: Option<String>,
+  /// 指定した取得条件をこの名前で`--preset-file`に保存してから実行する
+  #[clap(long)]
+  save_preset: Option<String>,
+  /// 「分 時 日 月 曜日」のcron式（例：`"0 3 * * *"`）を与えると、終了するまでその時刻ごとに取得を繰り返す
+  #[clap(long, conflicts_with_all = ["rpc", "resume"])]
+  cron: Option<schedule::CronSchedule>,
+  /// 最高裁判所の判例に英訳ページがあれば、そのリンクと英文要旨を`en_summary`として取り込む
+  #[clap(long)]
+  en_summary: bool,
+  /// 書き出す前の各判例に適用する後処理のステップを並べたJSONファイル
+  #[clap(long)]
+  postprocess: Option<String>,
+  /// 本文をPDFのページごとに分けた配列を`pages`フィールドにも書き出す
+  #[clap(long)]
+  pages: bool,
+  /// 本文を行の折り返しや空白に左右されない正規形にして`{ファイル名}.diff.txt`にも書き出す
+  #[clap(long)]
+  diff_text: bool,
+  /// 裁判所名がこの正規表現にマッチする判例を取得対象から除外する
+  #[clap(long)]
+  exclude_court_regex: Option<Regex>,
+  /// 事件名がこの正規表現にマッチする判例を取得対象から除外する
+  #[clap(long)]
+  exclude_case_name_regex: Option<Regex>,
+  /// 除外した判例を書き出さない（`drop`）か、書き出して`excluded`フィールドを付ける（`flag`）か
+  #[clap(long, value_enum, default_value = "drop")]
+  exclude_action: ExcludeAction,
+  /// 名前付きの取得条件を保存するJSONファイル
+  #[clap(long, default_value = "presets.json")]
+  preset_file: String,
+  /// `--since-id`から`--until-id`までの詳細ページの存在を確かめるだけの探索モードにして、見つかったIDをこのファイルに追記する
+  #[clap(long, requires = "since_id")]
+  explore: Option<String>,
+  /// 詳細ページに行かず、一覧ページに表示される事件番号・裁判年月日・裁判所名・リンクだけを`--index`に書き出す
+  #[clap(long, conflicts_with_all = ["since_id", "rpc"])]
+  list_only: bool,
+  /// 探索モードでのリクエスト間隔の下限（ミリ秒）
+  #[clap(long, default_value = "5000")]
+  explore_sleep_time: u64,
+  /// 詳細ページとPDFを並行して取得する件数。リクエストの間隔は並行数によらず`--sleep-time`などの設定に従う
+  #[clap(long, default_value = "1")]
+  jobs: usize,
+  /// PDFを並行して取得する件数（省略時は`--jobs`）
+  #[clap(long)]
+  pdf_jobs: Option<usize>,
+  /// PDFから本文を抽出するワーカースレッドの数（省略時はCPUのコア数）
+  #[clap(long)]
+  pdf_workers: Option<usize>,
   /// 一回のrowについてのAPIアクセスが行われるたびにsleepする時間（ミリ秒）
+  ///
+  /// サーバーの応答に応じて`--min-sleep-time`から`--max-sleep-time`の範囲で自動調整される
   #[clap(short, long, default_value = "500")]
   sleep_time: u64,
+  /// 応答が速いときに縮めるsleep時間の下限（ミリ秒）
+  #[clap(long, default_value = "200")]
+  min_sleep_time: u64,
+  /// 応答が遅いときやエラー時に延ばすsleep時間の上限（ミリ秒）
+  #[clap(long, default_value = "30000")]
+  max_sleep_time: u64,
+  /// PDFのリクエストのsleep時間（ミリ秒）
+  ///
+  /// `--pdf-sleep-time`・`--pdf-min-sleep-time`・`--pdf-max-sleep-time`のどれかを与えると、
+  /// PDFのリクエストはHTMLとは別にホストごとに間隔を調整する。省略したものはHTMLと同じ値を使う
+  #[clap(long)]
+  pdf_sleep_time: Option<u64>,
+  /// PDFのリクエストのsleep時間の下限（ミリ秒）
+  #[clap(long)]
+  pdf_min_sleep_time: Option<u64>,
+  /// PDFのリクエストのsleep時間の上限（ミリ秒）
+  #[clap(long)]
+  pdf_max_sleep_time: Option<u64>,
+  /// これを超える応答時間を「遅い」とみなしてsleep時間を延ばす閾値（ミリ秒）
+  #[clap(long, default_value = "3000")]
+  target_latency: u64,
+  /// 旧スキーマに依存する下流ツールのため、指定したバージョンの形式のindexも併せて出力する
+  #[clap(long, value_enum)]
+  compat: Option<CompatVersion>,
+  /// 互換indexを出力するJSONファイル名（省略時は`--index`に`.v1`のような接尾辞を付けたもの）
+  #[clap(long, requires = "compat")]
+  compat_index: Option<String>,
+  /// 一覧をApache Arrow IPC（Feather v2）形式でも書き出すファイル名
+  #[cfg(feature = "arrow")]
+  #[clap(long)]
+  index_arrow: Option<String>,
+  /// 一覧と裁判例のJSONの主な項目をSQLiteのデータベースにも書き出すファイル名
+  #[cfg(feature = "sqlite")]
+  #[clap(long)]
+  index_sqlite: Option<String>,
+  /// `--index-sqlite`のデータベースに、本文・要旨を対象にしたFTS5の全文検索テーブルを作る
+  #[cfg(feature = "sqlite")]
+  #[clap(long, requires = "index_sqlite")]
+  sqlite_fts: bool,
+  /// 裁判所の部・法廷ごとの月次件数を時系列のCSVにして書き出すファイル名
+  #[clap(long)]
+  court_stats: Option<String>,
+  /// 取得が終わったあと、同じ日・同じ裁判所で事件番号が似ている裁判例同士を`related`フィールドで結びつける
+  #[clap(long)]
+  link_related: bool,
+  /// 書き出すファイルに設定するパーミッション（`0644`のような8進数）
+  #[clap(long, value_parser = permissions::parse_mode)]
+  file_mode: Option<u32>,
+  /// 書き出すファイルに設定するグループ（グループ名かGID）
+  #[clap(long)]
+  file_group: Option<String>,
+  /// HTTP/2を使わずにHTTP/1.1だけで接続する
+  #[clap(long)]
+  http1_only: bool,
+  /// 使われていない接続を閉じずに残しておく時間（秒）
+  #[clap(long, default_value = "90")]
+  pool_idle_timeout: u64,
+  /// 使われていない接続を残しておく数の上限
+  #[clap(long, default_value = "4")]
+  pool_max_idle: usize,
+  /// 一時的なエラーで失敗したリクエストを再試行する回数の上限。超えたらその判例の失敗として扱う
+  #[clap(long, default_value = "3")]
+  max_retries: usize,
+  /// 再試行しても失敗したリクエストが何件続いたら取得を一時停止するか
+  #[clap(long, default_value = "5")]
+  breaker_threshold: usize,
+  /// 連続失敗で一時停止するときの停止時間（秒）
+  #[clap(long, default_value = "300")]
+  breaker_cooldown: u64,
+  /// 一時停止を何回繰り返しても回復しなければ中断するか
+  #[clap(long, default_value = "3")]
+  breaker_max_pauses: usize,
+  /// robots.txtのDisallow・Crawl-delayを無視する
+  #[clap(long)]
+  ignore_robots: bool,
+  /// 実行前に出力先の空き容量を確認し、不足しそうなときにどうするか
+  #[clap(long, value_enum, default_value = "warn")]
+  disk_check: DiskCheckPolicy,
+  /// 空き容量の確認に使う1件あたりの平均出力サイズ（KiB）
+  #[clap(long, default_value = "64")]
+  avg_record_size: u64,
+  /// 空き容量の確認に`--save-pdf`で残すPDFの分として足す、1件あたりの平均PDFサイズ（KiB）
+  #[clap(long, default_value = "1024")]
+  avg_pdf_size: u64,
+  /// 出力フォルダに既に裁判例のJSONがあるものは、詳細ページを取得せずに既存のファイルを使う
+  #[clap(long)]
+  skip_existing: bool,
+  /// 既存の裁判例のJSONを書き換えるかどうか。`if-changed`では内容のハッシュを比べて変わったときだけ書き換える
+  #[clap(long, value_enum, default_value = "always")]
+  overwrite: OverwritePolicy,
+  /// 判決文に含まれる「取消（とりけし）」のような括弧書きの読み仮名の扱い
+  #[clap(long, value_enum, default_value = "keep")]
+  furigana: FuriganaMode,
+  /// カタカナ表記の判例について、本文をひらがなに変換したものを`contents_hiragana`フィールドに書き出す
+  #[clap(long)]
+  hiragana_text: bool,
+  /// 判決文のPDFの取得のタイムアウト（秒）
+  #[clap(long)]
+  pdf_timeout: Option<u64>,
+  /// 判決文のPDFの最大サイズ（MiB）。これを超えるPDFは取得しない
+  #[clap(long)]
+  pdf_max_size: Option<u64>,
+  /// 判決文のPDFを取得する前にHEADリクエストでサイズを確かめ、`--pdf-max-size`を超えるものや0バイトのものは取得しない
+  #[clap(long)]
+  pdf_head_check: bool,
+  /// 判決文のPDFをダウンロードせず、`full_pdf_link`だけを記録する（本文の`contents`は取得しない）
+  #[clap(long, conflicts_with = "save_pdf")]
+  no_contents: bool,
+  /// ダウンロードした判決文のPDFを出力フォルダに残す
+  #[clap(long)]
+  save_pdf: bool,
+  /// `--save-pdf`で残すPDFの名前の付け方。`name`は裁判例のファイルと同じ名前、`hash`は内容のSHA-256で`pdfs/`の下に置く
+  #[clap(long, value_enum, default_value = "name")]
+  pdf_layout: pdf_store::PdfLayout,
+  /// 取得した詳細ページのHTMLを、裁判例のファイルと同じ名前（拡張子は`.html`）で出力フォルダに残す
+  #[clap(long)]
+  save_html: bool,
+  /// このサイズ（`20MB`のように単位を付けられる）を超える判決文のPDFは本文を取得せずに`--large-pdfs`に記録して後回しにする
+  #[clap(long, value_parser = parse_size)]
+  skip_pdf_over: Option<u64>,
+  /// `--skip-pdf-over`で後回しにした判例を記録するファイル。`large-pdfs`サブコマンドで後から取得できる
+  #[clap(long, default_value = "large_pdfs.jsonl")]
+  large_pdfs: String,
+  /// 判決文のPDFが404のときに代わりに取得を試みるアーカイブサービス
+  #[clap(long, value_enum)]
+  pdf_fallback: Option<PdfFallback>,
+  /// 詳細ページとPDFのETag・Last-Modifiedを保存するファイル
+  ///
+  /// 指定すると再取得時に条件付きリクエストを送り、更新されていないものは前回の出力を再利用する
+  #[clap(long)]
+  conditional_cache: Option<String>,
+  /// 取得したHTML・PDFをURLごとに保存するディレクトリ
+  ///
+  /// キャッシュにあるURLにはリクエストを送らず保存してある内容を使う。一覧ページだけは毎回取得し直す
+  #[clap(long)]
+  cache_dir: Option<String>,
+  /// 1件の取得に失敗したときに、`--failed-queue`に記録して続ける（`skip`）か、記録して中止する（`fail`）か
+  #[clap(long, value_enum, default_value = "skip")]
+  on_error: OnError,
+  /// 取得に失敗した詳細ページ・PDFを記録するファイル。次回の実行では最初にここに記録されたものを取得し直す
+  #[clap(long, default_value = "failed.jsonl")]
+  failed_queue: String,
+  /// 進捗を保存するチェックポイントファイル。省略時は`--index`のファイル名に`.checkpoint`を付けたもの
+  #[clap(long)]
+  checkpoint: Option<String>,
+  /// チェックポイントファイルに保存された進捗から再開する。既存の一覧ファイルの項目はそのまま残す
+  #[clap(long)]
+  resume: bool,
+  /// 書き出した件数・スキップした件数・失敗した件数・ページ数・所要時間と終了の仕方を書き出すファイル
+  #[clap(long, default_value = "run_summary.json")]
+  summary: String,
+  /// 想定外の項目やパースに失敗したページを見つけたとき、対象URLの一覧を含むGitHub issueの草稿（Markdown）を書き出すファイル
+  #[clap(long)]
+  issue_draft: Option<String>,
+  /// 実行が終わったときに、終了の仕方と件数をこのアドレスにメールで通知する
+  #[clap(long, requires = "smtp_host")]
+  notify_email: Option<String>,
+  /// 通知メールの送信元アドレス（省略時は`--notify-email`と同じ）
+  #[clap(long)]
+  notify_from: Option<String>,
+  /// 通知メールを送るSMTPサーバー。SMTPSで接続する
+  #[clap(long)]
+  smtp_host: Option<String>,
+  /// SMTPサーバーのポート（省略時はSMTPSの465、`--smtp-starttls`では587）
+  #[clap(long)]
+  smtp_port: Option<u16>,
+  /// SMTPSの代わりにSTARTTLSで接続する
+  #[clap(long)]
+  smtp_starttls: bool,
+  /// SMTPの認証のユーザー名。パスワードは環境変数`LISTUP_SMTP_PASSWORD`から読む
+  #[clap(long)]
+  smtp_user: Option<String>,
+  /// 実行がエラー無く終わったときに、データセットのメタデータを登録・更新するCKAN互換のデータカタログのURL
+  #[clap(long, requires = "ckan_dataset")]
+  ckan_url: Option<String>,
+  /// 登録・更新するCKANのデータセットの名前（URLに使う英数字の名前）。APIキーは環境変数`LISTUP_CKAN_API_KEY`から読む
+  #[clap(long)]
+  ckan_dataset: Option<String>,
+  /// CKANのデータセットの表題
+  #[clap(long, default_value = "裁判例一覧")]
+  ckan_title: String,
+  /// データセットを新しく作るときの所属組織
+  #[clap(long)]
+  ckan_owner_org: Option<String>,
+  /// CKANのデータセットのライセンスのID
+  #[clap(long)]
+  ckan_license_id: Option<String>,
+  /// 一覧ファイルを公開しているURL。与えるとこのURLの下の一覧ファイルをリソースとして登録する
+  #[clap(long)]
+  ckan_resource_base_url: Option<String>,
+  /// 取得したHTMLとPDFのURLと結果を、1行1件のJSONとしてこのファイルに追記する
+  #[clap(long)]
+  audit_log: Option<String>,
+  /// 進捗を1行1イベントのJSONとして標準エラーに出力する
+  #[clap(long)]
+  events_json: bool,
+  /// 標準入出力でJSON-RPCのリクエストを受け付けるモードで起動する（GUIのバックエンド向け）
+  #[clap(long, conflicts_with_all = ["start", "end", "recent", "since_id"])]
+  rpc: bool,
+}
+
+/// `20MB`・`512KiB`・`1048576`のようなサイズの指定をバイト数にする。単位は1024倍ごと
+fn parse_size(s: &str) -> Result<u64> {
+  let s = s.trim();
+  let pos = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+  let (num, unit) = s.split_at(pos);
+  let num = num
+    .parse::<u64>()
+    .map_err(|_| anyhow!("サイズの数値が読み取れません：{s}"))?;
+  let scale = match unit.trim().to_ascii_uppercase().as_str() {
+    "" | "B" => 1,
+    "K" | "KB" | "KIB" => 1024,
+    "M" | "MB" | "MIB" => 1024 * 1024,
+    "G" | "GB" | "GIB" => 1024 * 1024 * 1024,
+    _ => return Err(anyhow!("サイズの単位が読み取れません：{s}")),
+  };
+  num
+    .checked_mul(scale)
+    .ok_or_else(|| anyhow!("サイズが大きすぎます：{s}"))
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-  let args = Args::parse();
-  init_logger().await?;
+  let mut args = Args::parse();
+  if let Some(name) = args.preset.clone() {
+    preset::load(&args.preset_file, &name)
+      .await?
+      .apply(&mut args);
+  }
+  if let Some(name) = &args.save_preset {
+    preset::save(&args.preset_file, name, preset::Preset::of(&args)).await?;
+  }
+  if object_output::install(&args.output)? {
+    let staging = args.staging_dir.clone().unwrap_or_else(|| {
+      std::env::temp_dir()
+        .join("listup_precedent")
+        .to_string_lossy()
+        .to_string()
+    });
+    tokio::fs::create_dir_all(&staging).await?;
+    args.output = staging;
+  }
+  permissions::install(args.file_mode, args.file_group.as_deref())?;
+  output::set_record_format(args.format);
+  chunk::install(args.chunk_size, args.chunk_overlap);
+  output::set_json_style(args.json_style);
+  output::set_deterministic(args.deterministic);
+  let backend = match (args.stdout, object_output::is_enabled()) {
+    (true, _) => output_writer::OutputBackend::Stdout,
+    (false, true) => output_writer::OutputBackend::S3,
+    (false, false) => args.writer,
+  };
+  output_writer::install(backend, &args.output, args.max_file_size)?;
+  if (args.skip_existing || args.overwrite != OverwritePolicy::Always)
+    && !output_writer::get().can_read_back()
+  {
+    return Err(anyhow!(
+      "--writer jsonl・--stdoutでは書き出し済みの裁判例を読み戻せないので、--skip-existing・--overwrite never・--overwrite if-changedは使えません"
+    ));
+  }
+  let link_related = args.link_related || matches!(args.command, Some(Command::LinkRelated));
+  if link_related && (backend != output_writer::OutputBackend::JsonDir || args.archive.is_some()) {
+    return Err(anyhow!(
+      "--link-related・link-relatedは出力フォルダの裁判例のファイルを書き換えるので、--writer json-dir（既定）でだけ使えます（--archive・--stdout・--output s3://…とも併用できません）"
+    ));
+  }
+  compress::set_compression(args.compress);
+  if let Some(template) = &args.filename_template {
+    filename_template::install(template)?;
+  }
+  partition::install(args.partition_by.clone());
+  if let Some(dir) = &args.contents_dir {
+    contents_file::install(dir, args.contents_gzip);
+  }
+  text_encoding::set_output_encoding(args.output_encoding);
+  if let Some(path) = &args.postprocess {
+    postprocess::install(path).await?;
+  }
+  pdf_workers::install(
+    args
+      .pdf_workers
+      .unwrap_or_else(pdf_workers::default_workers),
+  );
+  if args.rpc {
+    // 標準出力はJSON-RPCのレスポンス専用にするのでロガーは初期化しない
+    return rpc::serve(&args).await;
+  }
+  if let Some(Command::Schema { target }) = &args.command {
+    // 標準出力にはスキーマだけを書くのでロガーは初期化しない
+    return schema::print(*target);
+  }
+  if args.stdout {
+    // 標準出力は裁判例のNDJSON専用にするので、ログは標準エラーに出す
+    tracing_subscriber::fmt()
+      .with_writer(std::io::stderr)
+      .init();
+  } else {
+    init_logger().await?;
+  }
+  shutdown::install();
+  let events = Events::new(args.events_json);
+  let started = Instant::now();
 
-  let start_date = parse_date(&args.start).await?;
-  let end_date = parse_date(&args.end).await?;
+  let res = match &args.command {
+    Some(Command::RetryFailed { failed }) => {
+      let failed = failed.as_deref().unwrap_or(&args.failed_queue);
+      retry_failed::retry_failed(&args, &events, failed).await
+    }
+    Some(Command::Repair) => repair::repair(&args).await,
+    Some(Command::Replay { audit_log }) => audit::replay(&args, &events, audit_log)
+      .await
+      .map(|differed| info!("[END] replay: {} ({} differed)", audit_log, differed)),
+    Some(Command::LinkRelated) => related::link(&args.index, &args.output)
+      .await
+      .map(|len| info!("[END] link related: {} files", len)),
+    Some(Command::Schema { .. }) => unreachable!("schemaはロガーの初期化の前に処理している"),
+    Some(Command::VerifyPdfs) => pdf_store::verify(&args.index, &args.output)
+      .await
+      .map(|len| info!("[END] verify pdfs: {} files", len)),
+    Some(Command::Watch { interval }) => {
+      watch::watch(&args, Duration::from_secs((*interval).max(1))).await
+    }
+    Some(Command::LargePdfs { list }) => {
+      let list = list.as_deref().unwrap_or(&args.large_pdfs);
+      // 後回しにしたPDFを今度は取得するので、閾値は外す
+      let args = Args {
+        skip_pdf_over: None,
+        ..args.clone()
+      };
+      retry_failed::retry_failed(&args, &events, list).await
+    }
+    Some(Command::ExportCsv { csv, columns }) => {
+      let columns = if columns.is_empty() {
+        csv_export::DEFAULT_COLUMNS
+          .iter()
+          .map(|c| c.to_string())
+          .collect()
+      } else {
+        columns.clone()
+      };
+      csv_export::export(&args, csv, &columns).await
+    }
+    #[cfg(feature = "arrow")]
+    Some(Command::ExportParquet { dir }) => parquet_export::export(&args.index, &args.output, dir)
+      .await
+      .map(|len| info!("[END] export parquet: {} ({} entries)", dir, len)),
+    #[cfg(feature = "arrow")]
+    Some(Command::ExportArrow { path }) => arrow_index::export(&args.index, &args.output, path)
+      .await
+      .map(|len| info!("[END] export arrow: {} ({} entries)", path, len)),
+    Some(Command::ExportAkomaNtoso { dir }) => akoma_ntoso::export(&args.index, &args.output, dir)
+      .await
+      .map(|len| info!("[END] export akoma ntoso: {} ({} entries)", dir, len)),
+    Some(Command::ExportMeili {
+      host,
+      meili_index,
+      batch_size,
+    }) => meili_export::export(&args.index, &args.output, host, meili_index, *batch_size)
+      .await
+      .map(|len| {
+        info!(
+          "[END] export meili: {}/{} ({} documents)",
+          host, meili_index, len
+        )
+      }),
+    Some(Command::ExportStats { json, markdown }) => {
+      stats_report::export(&args.index, &args.output, json, markdown)
+        .await
+        .map(|len| {
+          info!(
+            "[END] export stats: {}, {} ({} entries)",
+            json, markdown, len
+          )
+        })
+    }
+    Some(Command::ExportEs {
+      url,
+      es_index,
+      batch_size,
+      user,
+    }) => es_export::export(
+      &args.index,
+      &args.output,
+      url,
+      es_index,
+      *batch_size,
+      user.as_deref(),
+    )
+    .await
+    .map(|len| info!("[END] export es: {}/{} ({} documents)", url, es_index, len)),
+    Some(Command::Canary { sample: true, .. }) => canary::check_sample().await,
+    Some(Command::Canary {
+      fixtures, update, ..
+    }) => canary::run(&args, fixtures, *update).await,
+    Some(Command::FetchRaw { links }) => raw::fetch_raw(&args, &events, links).await,
+    Some(Command::ParseRaw { links }) => raw::parse_raw(&args, &events, links).await,
+    #[cfg(feature = "arrow")]
+    Some(Command::ExportDuckdb { dir }) => duckdb_export::export(&args.index, &args.output, dir)
+      .await
+      .map(|len| info!("[END] export duckdb: {} ({} entries)", dir, len)),
+    #[cfg(feature = "graphql")]
+    Some(Command::ServeGraphql { addr }) => graphql::serve(&args.index, &args.output, addr)
+      .await
+      .map(|_| info!("[END] serve graphql: {}", addr)),
+    None => match &args.cron {
+      Some(cron) => schedule::run_scheduled(&args, &events, cron).await,
+      None => run(&args, &events).await,
+    },
+  };
+  if let Some(path) = &args.issue_draft {
+    if let Err(e) = issue_draft::write(path).await {
+      warn!("failed to write issue draft: {e:#}");
+    }
+  }
+  match &res {
+    Ok(()) => events.emit("run_finished", json!({})),
+    Err(e) => events.emit(
+      "error",
+      json!({ "message": format!("{e:#}"), "fatal": true }),
+    ),
+  }
+  let mut run_summary = events.summary();
+  run_summary.duration_millis = meta::to_millis(started.elapsed());
+  run_summary.finish(&res, shutdown::requested());
+  match serde_json::to_string_pretty(&run_summary) {
+    Ok(s) => {
+      if let Err(e) = tokio::fs::write(&args.summary, s).await {
+        warn!("failed to write run summary: {e}");
+      }
+    }
+    Err(e) => warn!("failed to serialize run summary: {e}"),
+  }
+  if let Err(e) = notify::send(&args, &run_summary).await {
+    warn!("failed to send notification email: {e:#}");
+  }
+  let crawled = matches!(args.command, None | Some(Command::RetryFailed { .. }));
+  if res.is_ok() && crawled {
+    if let Err(e) = ckan::publish(&args, &run_summary).await {
+      warn!("failed to publish to CKAN: {e:#}");
+    }
+  }
+  if let Err(e) = &res {
+    eprintln!("Error: {e:?}");
+  }
+  if run_summary.exit_code != 0 {
+    std::process::exit(run_summary.exit_code);
+  }
+  Ok(())
+}
+
+async fn build_fetcher(args: &Args) -> Result<Fetcher> {
+  let throttle = Throttle::new(
+    Duration::from_millis(args.sleep_time),
+    Duration::from_millis(args.min_sleep_time),
+    Duration::from_millis(args.max_sleep_time),
+    Duration::from_millis(args.target_latency),
+  );
+  let breaker = CircuitBreaker::new(
+    args.breaker_threshold,
+    Duration::from_secs(args.breaker_cooldown),
+    args.breaker_max_pauses,
+  );
+  let client_options = ClientOptions {
+    http1_only: args.http1_only,
+    pool_idle_timeout: Duration::from_secs(args.pool_idle_timeout),
+    pool_max_idle_per_host: args.pool_max_idle,
+  };
+  let mut fetcher = Fetcher::new(throttle, breaker, client_options)?;
+  fetcher.set_max_retries(args.max_retries);
+  if args.pdf_sleep_time.is_some()
+    || args.pdf_min_sleep_time.is_some()
+    || args.pdf_max_sleep_time.is_some()
+  {
+    fetcher.set_pdf_throttle(Throttle::new(
+      Duration::from_millis(args.pdf_sleep_time.unwrap_or(args.sleep_time)),
+      Duration::from_millis(args.pdf_min_sleep_time.unwrap_or(args.min_sleep_time)),
+      Duration::from_millis(args.pdf_max_sleep_time.unwrap_or(args.max_sleep_time)),
+      Duration::from_millis(args.target_latency),
+    ));
+  }
+  if args.ignore_robots {
+    warn!("robots.txt policy: ignored by --ignore-robots");
+  } else {
+    let robots_url = format!("{COURTS_DOMEIN}/robots.txt");
+    let robots = match fetcher.get_robots_txt(&robots_url).await? {
+      Some(text) => RobotsPolicy::parse(&text, fetch::USER_AGENT),
+      None => {
+        info!("robots.txt not found: {robots_url}");
+        RobotsPolicy::allow_all()
+      }
+    };
+    robots.log_summary();
+    fetcher.set_robots(&robots_url, robots)?;
+  }
+  fetcher.set_pdf_limits(BodyLimits {
+    timeout: args.pdf_timeout.map(Duration::from_secs),
+    max_size: args.pdf_max_size.map(|mib| mib * 1024 * 1024),
+    head_check: args.pdf_head_check,
+    defer_over: args.skip_pdf_over,
+  });
+  if let Some(path) = &args.audit_log {
+    fetcher.set_audit_log(AuditLog::open(path)?);
+  }
+  if let Some(dir) = &args.cache_dir {
+    info!("response cache: {dir}");
+    fetcher.set_response_cache(ResponseCache::new(dir).await?);
+  }
+  if let Some(path) = &args.conditional_cache {
+    fetcher.set_conditional_cache(ConditionalCache::load(path).await?);
+  }
+  Ok(fetcher)
+}
+
+fn checkpoint_path(args: &Args) -> String {
+  args
+    .checkpoint
+    .clone()
+    .unwrap_or_else(|| format!("{}.checkpoint", args.index))
+}
+
+/// `lawsuit_id`の範囲を順に取得する実行の、最初の`lawsuit_id`と記録を続けるチェックポイント
+///
+/// 処理済みの集合を持たない以前の形式のチェックポイントでは、`last_lawsuit_id`の次から始める
+fn id_range_resume(
+  args: &Args,
+  ids: &std::ops::RangeInclusive<u64>,
+  resume: Option<Checkpoint>,
+) -> (u64, Checkpoint) {
+  match resume {
+    Some(checkpoint) if checkpoint.processed.is_empty() => {
+      let first_id = checkpoint
+        .last_lawsuit_id
+        .as_deref()
+        .and_then(|id| id.parse::<u64>().ok())
+        .map_or(*ids.start(), |last| last + 1);
+      (first_id, checkpoint)
+    }
+    Some(checkpoint) => (*ids.start(), checkpoint),
+    None => (*ids.start(), checkpoint_for(args)),
+  }
+}
 
-  info!("start_date: {}", &args.start);
-  info!("end_date: {}", &args.end);
+/// 今回の取得条件の、まだ何も処理していないチェックポイント
+fn checkpoint_for(args: &Args) -> Checkpoint {
+  Checkpoint {
+    start: args.start.clone(),
+    end: args.end.clone(),
+    recent: args.recent,
+    since_id: args.since_id,
+    until_id: args.until_id,
+    page: 0,
+    last_lawsuit_id: None,
+    processed: Default::default(),
+  }
+}
 
-  let top_html = get_reqest(&start_date, &end_date, 1).await?;
+async fn run(args: &Args, events: &Events) -> Result<()> {
+  let _lock = OutputLock::acquire(&args.output)?;
+  let fetcher = build_fetcher(args).await?;
+  if let Some(path) = &args.archive {
+    bundle::open(path)?;
+  }
+
+  let resume = if args.resume {
+    let path = checkpoint_path(args);
+    match Checkpoint::load(&path).await? {
+      Some(checkpoint) => {
+        checkpoint.ensure_same_range(&checkpoint_for(args))?;
+        info!(
+          "resume from page {} (last lawsuit_id: {:?}, processed: {})",
+          checkpoint.page,
+          checkpoint.last_lawsuit_id,
+          checkpoint.processed.len()
+        );
+        Some(checkpoint)
+      }
+      None => {
+        warn!("checkpoint not found: {path}; starting from the beginning");
+        None
+      }
+    }
+  } else {
+    None
+  };
+
+  // 探索モードでは一覧ファイルには触れない
+  if let (Some(path), Some(since_id), Some(until_id)) =
+    (&args.explore, args.since_id, args.until_id)
+  {
+    explore::explore_id_range(args, &fetcher, events, since_id..=until_id, path, resume).await?;
+    if shutdown::requested() {
+      return Err(anyhow!("中断しました（--resumeで続きから再開できます）"));
+    }
+    Checkpoint::remove(&checkpoint_path(args)).await?;
+    return Ok(());
+  }
+
+  if args.list_only {
+    list_only::crawl(args, &fetcher, events).await?;
+    if shutdown::requested() {
+      return Err(anyhow!("中断しました"));
+    }
+    return Ok(());
+  }
+
+  // 一覧ファイルは開くと作り直されるので、その前に前回の内容を読んでおく
+  let previous_file_names = if args.skip_existing {
+    output::read_file_names(&args.index).await?
+  } else {
+    HashMap::new()
+  };
+  let file_path = &args.output;
+  let mut index_writer = IndexWriter::open(
+    &args.index,
+    args.index_format,
+    args.compat,
+    args.compat_index.as_deref(),
+    resume.is_some(),
+  )
+  .await?;
+  index_writer.set_previous_file_names(previous_file_names);
+  let retry_queue = RetryQueue::new(&args.failed_queue);
+  info!("[START] writing file: {}", &file_path);
+
+  drain_retry_queue(args, &fetcher, events, &index_writer, &retry_queue).await?;
+
+  // 再試行キューの取得し直しの途中で止めた場合は、次回の実行でその続きから取得し直す
+  if !shutdown::requested() {
+    if args.recent {
+      crawl_recent(args, &fetcher, events, &index_writer, &retry_queue, resume).await?;
+    } else if let (Some(since_id), Some(until_id)) = (args.since_id, args.until_id) {
+      crawl_id_range(
+        args,
+        &fetcher,
+        events,
+        &index_writer,
+        &retry_queue,
+        since_id..=until_id,
+        resume,
+      )
+      .await?;
+    } else {
+      crawl_date_range(args, &fetcher, events, &index_writer, &retry_queue, resume).await?;
+    }
+  }
+  if let Some(cache) = fetcher.conditional_cache() {
+    cache.save().await?;
+  }
+  index_writer.flush().await?;
+  write_index_exports(args).await?;
+  info!("[END] write json file");
+  if shutdown::requested() {
+    return Err(anyhow!(
+      "中断しました（一覧ファイルは中断までに取得したものを含む正しいJSONとして書き出し済みです。--resumeで続きから再開できます）"
+    ));
+  }
+  Checkpoint::remove(&checkpoint_path(args)).await?;
+  Ok(())
+}
+
+/// 書き出し終えた一覧から、`--index-arrow`・`--index-sqlite`・`--court-stats`などで指定されたファイルを書き出す
+///
+/// `--compress`が与えられていれば、一覧ファイルはそのあとで圧縮する。
+/// `--archive`が与えられていれば、一覧ファイルとこれらのファイルを加えてアーカイブを閉じる。
+/// `--output`がオブジェクトストレージなら、これらのファイルもアップロードする
+async fn write_index_exports(args: &Args) -> Result<()> {
+  // 出力フォルダの裁判例のファイルを書き換える。json-dir以外の書き出し先では起動時にエラーにしている
+  if args.link_related {
+    related::link(&args.index, &args.output).await?;
+  }
+  #[cfg(feature = "arrow")]
+  if let Some(path) = &args.index_arrow {
+    let len = arrow_index::write(&args.index, path).await?;
+    permissions::apply(path).await?;
+    info!("arrow index: {} ({} entries)", path, len);
+  }
+  #[cfg(feature = "sqlite")]
+  if let Some(path) = &args.index_sqlite {
+    let len = sqlite_index::write(&args.index, &args.output, path, args.sqlite_fts).await?;
+    permissions::apply(path).await?;
+    info!("sqlite index: {} ({} entries)", path, len);
+  }
+  if let Some(path) = &args.court_stats {
+    court_stats::write(&args.index, path).await?;
+    permissions::apply(path).await?;
+    info!("court stats: {}", path);
+  }
+  compress::compress_file(&args.index).await?;
+  let mut bundled = vec![compress::existing_path(&args.index)];
+  if let Some(version) = args.compat {
+    let compat_index = args
+      .compat_index
+      .clone()
+      .unwrap_or_else(|| compat::gen_compat_index_path(&args.index, version));
+    compress::compress_file(&compat_index).await?;
+    bundled.push(compress::existing_path(&compat_index));
+  }
+  #[cfg(feature = "arrow")]
+  bundled.extend(args.index_arrow.clone());
+  #[cfg(feature = "sqlite")]
+  bundled.extend(args.index_sqlite.clone());
+  bundled.extend(args.court_stats.clone());
+  object_output::upload_files(&bundled).await?;
+  bundle::finish(&bundled).await?;
+  Ok(())
+}
+
+/// 前回までに失敗して再試行キューに記録されたものを取得し直す
+async fn drain_retry_queue(
+  args: &Args,
+  fetcher: &Fetcher,
+  events: &Events,
+  index_writer: &IndexWriter,
+  retry_queue: &RetryQueue,
+) -> Result<()> {
+  let queued = retry_queue.take().await?;
+  if queued.is_empty() {
+    return Ok(());
+  }
+  info!("[START] retry failed records: {}", queued.len());
+  // PDFの取得に失敗したものは本文無しでファイルが書き出されているので、既存のファイルがあっても取得し直す
+  let args = &Args {
+    skip_existing: false,
+    ..args.clone()
+  };
+  let mut queued_stream = tokio_stream::iter(queued);
+  while let Some(failed) = queued_stream.next().await {
+    if shutdown::requested() {
+      warn!("interrupted while retrying failed records; the rest will be retried on the next run");
+      return Ok(());
+    }
+    info!("retry: {}", &failed.detail_page_link);
+    record::process_record_or_queue(
+      args,
+      fetcher,
+      events,
+      index_writer,
+      retry_queue,
+      &failed.detail_page_link,
+      None,
+    )
+    .await?;
+  }
+  retry_queue.finish_draining().await?;
+  info!("[END] retry failed records");
+  Ok(())
+}
+
+/// 「最近の裁判例」一覧ページに載っている判例だけを取得する
+async fn crawl_recent(
+  args: &Args,
+  fetcher: &Fetcher,
+  events: &Events,
+  index_writer: &IndexWriter,
+  retry_queue: &RetryQueue,
+  resume: Option<Checkpoint>,
+) -> Result<()> {
+  let url = format!("{COURTS_DOMEIN}{RECENT_LIST_PATH}");
+  info!("recent list: {}", &url);
+  let html = fetcher.get_text(&url).await?;
+  let document = Html::parse_document(&html);
+  let detail_page_link_selector = Selector::parse("a[href*=\"/app/hanrei_jp/detail\"]").unwrap();
+  let mut links = Vec::new();
+  for element in document.select(&detail_page_link_selector) {
+    let link = element
+      .value()
+      .attr("href")
+      .expect("a属性はhrefを持っているはず");
+    let link = if link.starts_with("http") {
+      link.to_string()
+    } else {
+      format!("{COURTS_DOMEIN}{link}")
+    };
+    // 同じ判例へのリンクが事件名と全文PDFなどで複数あることがある
+    if !links.iter().any(|(l, _)| *l == link) {
+      let lawsuit_id = get_lawsuit_id(&link).await?;
+      links.push((link, lawsuit_id));
+    }
+  }
+  events.emit(
+    "run_started",
+    json!({ "recent": true, "total_records": links.len(), "total_pages": 1 }),
+  );
+  disk::check_disk_space(
+    &args.output,
+    links.len(),
+    disk::record_bytes(
+      args.avg_record_size,
+      args.save_pdf.then_some(args.avg_pdf_size),
+    ),
+    args.disk_check,
+  )?;
+  pipeline::run(
+    args,
+    fetcher,
+    events,
+    index_writer,
+    retry_queue,
+    ListPages::Fetched { page_num: 1, links },
+    resume,
+  )
+  .await?;
+  Ok(())
+}
+
+/// `lawsuit_id`から詳細ページのリンクを探す。裁判の種類ごとに詳細ページのpathが違うので、存在するものが見つかるまで順に試す
+async fn find_detail_link(fetcher: &Fetcher, lawsuit_id: u64) -> Result<Option<String>> {
+  let info_selector =
+    Selector::parse("div.module-search-page-table-parts-result-detail > dl").unwrap();
+  for type_number in 2..=7 {
+    let link = format!("{COURTS_DOMEIN}/app/hanrei_jp/detail{type_number}?id={lawsuit_id}");
+    let html = match fetcher.get_text(&link).await {
+      Ok(html) => html,
+      Err(e) if fetch::is_not_found(&e) => continue,
+      Err(e) => return Err(e),
+    };
+    if Html::parse_document(&html)
+      .select(&info_selector)
+      .next()
+      .is_some()
+    {
+      return Ok(Some(link));
+    }
+  }
+  Ok(None)
+}
+
+/// `--since-id`から`--until-id`までの`lawsuit_id`の判例を詳細ページから直接取得する
+async fn crawl_id_range(
+  args: &Args,
+  fetcher: &Fetcher,
+  events: &Events,
+  index_writer: &IndexWriter,
+  retry_queue: &RetryQueue,
+  ids: std::ops::RangeInclusive<u64>,
+  resume: Option<Checkpoint>,
+) -> Result<()> {
+  let (first_id, mut progress) = id_range_resume(args, &ids, resume);
+  events.emit(
+    "run_started",
+    json!({ "since_id": ids.start(), "until_id": ids.end() }),
+  );
+  let checkpoint_path = checkpoint_path(args);
+  let mut id_stream = tokio_stream::iter(first_id..=*ids.end());
+  while let Some(lawsuit_id) = id_stream.next().await {
+    if progress.is_processed(&lawsuit_id.to_string()) {
+      continue;
+    }
+    if shutdown::requested() {
+      warn!("interrupted before id {lawsuit_id}; re-run with --resume to continue");
+      events.emit("interrupted", json!({ "next_id": lawsuit_id }));
+      return Ok(());
+    }
+    match find_detail_link(fetcher, lawsuit_id).await {
+      Ok(Some(detail_page_link)) => {
+        info!("link: {}", &detail_page_link);
+        record::process_record_or_queue(
+          args,
+          fetcher,
+          events,
+          index_writer,
+          retry_queue,
+          &detail_page_link,
+          None,
+        )
+        .await?;
+      }
+      Ok(None) => info!("not found: {lawsuit_id}"),
+      Err(e) if fetch::is_circuit_open(&e) => return Err(e),
+      Err(e) if args.on_error == OnError::Fail => return Err(e),
+      Err(e) => warn!("failed to find detail page of {lawsuit_id}: {e:#}"),
+    }
+    progress.mark_processed(0, &lawsuit_id.to_string());
+    progress.save(&checkpoint_path).await?;
+  }
+  Ok(())
+}
+
+/// 期間検索の結果の1ページに載っている判例のリンクと`lawsuit_id`の組を取得する
+async fn list_date_range_page(
+  fetcher: &Fetcher,
+  start_date: &Date,
+  end_date: &Date,
+  page_num: usize,
+) -> Result<Vec<(String, String)>> {
+  let html = get_reqest(fetcher, start_date, end_date, page_num).await?;
+  info!("html ok");
+  let page_document = Html::parse_document(&html);
+  let detail_page_link_selector = Selector::parse("table > tbody > tr > th > a").unwrap();
+  let mut links = Vec::new();
+  for element in page_document.select(&detail_page_link_selector) {
+    let link = element
+      .value()
+      .attr("href")
+      .expect("a属性はhrefを持っているはず");
+    let detail_page_link = format!("{COURTS_DOMEIN}{link}");
+    let lawsuit_id = get_lawsuit_id(&detail_page_link).await?;
+    links.push((detail_page_link, lawsuit_id));
+  }
+  Ok(links)
+}
+
+/// `--start`から`--end`までの期間検索の結果を全件取得する
+async fn crawl_date_range(
+  args: &Args,
+  fetcher: &Fetcher,
+  events: &Events,
+  index_writer: &IndexWriter,
+  retry_queue: &RetryQueue,
+  resume: Option<Checkpoint>,
+) -> Result<()> {
+  let start = args
+    .start
+    .as_deref()
+    .ok_or_else(|| anyhow!("--startが指定されていません"))?;
+  let end = args
+    .end
+    .as_deref()
+    .ok_or_else(|| anyhow!("--endが指定されていません"))?;
+  let start_date = parse_date(start).await?;
+  let end_date = parse_date(end).await?;
+
+  info!("start_date: {}", start);
+  info!("end_date: {}", end);
+
+  let top_html = get_reqest(fetcher, &start_date, &end_date, 1).await?;
   let top_document = Html::parse_document(&top_html);
   let all_quantity_selector = Selector::parse("div.module-search-page-paging-parts2 > p").unwrap();
   // "64297件中11～20件を表示"のような値になっている
@@ -250,349 +1895,63 @@ async fn main() -> Result<()> {
   } else {
     all_page_quantity + 1
   };
-  let mut stream = tokio_stream::iter(1..=all_page_quantity);
-  let link_re = Regex::new(r"[^\d]+(?P<type_number>\d).*").unwrap();
-  let file_path = &args.output;
-  let mut index_file = gen_file_value_lst(&args.index).await?;
-  info!("[START] writing file: {}", &file_path);
-  while let Some(page_num) = stream.next().await {
-    info!("page_num: {}", page_num);
-    let html = get_reqest(&start_date, &end_date, page_num).await?;
-    info!("html ok");
-    let page_document = Html::parse_document(&html);
-    let detail_page_link_selector = Selector::parse("table > tbody > tr > th > a").unwrap();
-    let mut detail_page_link_stream =
-      tokio_stream::iter(page_document.select(&detail_page_link_selector));
-    while let Some(detail_page_link) = detail_page_link_stream.next().await {
-      let link = detail_page_link
-        .value()
-        .attr("href")
-        .expect("a属性はhrefを持っているはず");
-      info!("link: {}", &link);
-      let trial_type = match link_re
-        .captures(link)
-        .ok_or_else(|| anyhow!("年号付き日付のパースに失敗"))?
-        .name("type_number")
-        .ok_or_else(|| anyhow!("リンクが想定外の形をしている"))?
-        .as_str()
-        .parse::<usize>()?
-      {
-        2 => TrialType::SupremeCourt,
-        3 => TrialType::HighCourt,
-        4 => TrialType::LowerCourt,
-        5 => TrialType::AdministrativeCase,
-        6 => TrialType::LaborCase,
-        7 => TrialType::IPCase,
-        _ => unreachable!(),
-      };
-      let detail_page_link = format!("{COURTS_DOMEIN}{link}");
-      let lawsuit_id = get_lawsuit_id(&detail_page_link).await?;
-      info!("[START] date write: {}", &lawsuit_id);
-      let detail_page_html = reqwest::get(&detail_page_link).await?.text().await?;
-      let detail_document = Html::parse_document(&detail_page_html);
-      let info_selector =
-        Selector::parse("div.module-search-page-table-parts-result-detail > dl").unwrap();
-      let mut date_str = String::new();
-      let mut case_number = String::new();
-      let mut case_name = String::new();
-      let mut court_name = String::new();
-      let mut right_type = None;
-      let mut lawsuit_type = None;
-      let mut result_type = None;
-      let mut result = None;
-      let mut article_info = None;
-      let mut original_court_name = None;
-      let mut original_case_number = None;
-      let mut original_result = None;
-      let mut original_date = None;
-      let mut field = None;
-      let mut gist = None;
-      let mut case_gist = None;
-      let mut ref_law = None;
-      let mut full_pdf_link = String::new();
-      let mut info_stream = tokio_stream::iter(detail_document.select(&info_selector));
-      while let Some(info_element) = info_stream.next().await {
-        let dt_selector = Selector::parse("dt").unwrap();
-        let dd_text_selector = Selector::parse("dd > p").unwrap();
-        let dd_link_selector = Selector::parse("dd > ul > li > a").unwrap();
-        let dt_text = info_element
-          .select(&dt_selector)
-          .next()
-          .unwrap()
-          .text()
-          .collect::<String>()
-          .trim()
-          .to_string();
-        match &*dt_text {
-          "事件番号" => {
-            let text = info_element
-              .select(&dd_text_selector)
-              .next()
-              .unwrap()
-              .text()
-              .collect::<String>()
-              .trim()
-              .to_string();
-            case_number = text;
-          }
-          "事件名" => {
-            let text = info_element
-              .select(&dd_text_selector)
-              .next()
-              .unwrap()
-              .text()
-              .collect::<String>()
-              .trim()
-              .to_string();
-            case_name = text;
-          }
-          "裁判年月日" => {
-            let text = info_element
-              .select(&dd_text_selector)
-              .next()
-              .unwrap()
-              .text()
-              .collect::<String>()
-              .trim()
-              .to_string();
-            date_str = text;
-          }
-          "裁判所名" | "裁判所名・部" | "法廷名" => {
-            let text = info_element
-              .select(&dd_text_selector)
-              .next()
-              .unwrap()
-              .text()
-              .collect::<String>()
-              .trim()
-              .to_string();
-            court_name = remove_line_break(&text);
-          }
-          "権利種別" => {
-            let text = info_element
-              .select(&dd_text_selector)
-              .next()
-              .unwrap()
-              .text()
-              .collect::<String>()
-              .trim()
-              .to_string();
-            if !text.is_empty() {
-              right_type = Some(text);
-            }
-          }
-          "訴訟類型" => {
-            let text = info_element
-              .select(&dd_text_selector)
-              .next()
-              .unwrap()
-              .text()
-              .collect::<String>()
-              .trim()
-              .to_string();
-            if !text.is_empty() {
-              lawsuit_type = Some(text);
-            }
-          }
-          "裁判種別" => {
-            let text = info_element
-              .select(&dd_text_selector)
-              .next()
-              .unwrap()
-              .text()
-              .collect::<String>()
-              .trim()
-              .to_string();
-            if !text.is_empty() {
-              result_type = Some(text);
-            }
-          }
-          "結果" => {
-            let text = info_element
-              .select(&dd_text_selector)
-              .next()
-              .unwrap()
-              .text()
-              .collect::<String>()
-              .trim()
-              .to_string();
-            if !text.is_empty() {
-              result = Some(text);
-            }
-          }
-          "判例集等巻・号・頁" | "高裁判例集登載巻・号・頁" => {
-            let text = info_element
-              .select(&dd_text_selector)
-              .next()
-              .unwrap()
-              .text()
-              .collect::<String>()
-              .trim()
-              .to_string();
-            if !text.is_empty() {
-              article_info = Some(text);
-            }
-          }
-          "原審裁判所名" => {
-            let text = info_element
-              .select(&dd_text_selector)
-              .next()
-              .unwrap()
-              .text()
-              .collect::<String>()
-              .trim()
-              .to_string();
-            if !text.is_empty() {
-              original_court_name = Some(text);
-            }
-          }
-          "原審事件番号" => {
-            let text = info_element
-              .select(&dd_text_selector)
-              .next()
-              .unwrap()
-              .text()
-              .collect::<String>()
-              .trim()
-              .to_string();
-            if !text.is_empty() {
-              original_case_number = Some(text);
-            }
-          }
-          "原審結果" => {
-            let text = info_element
-              .select(&dd_text_selector)
-              .next()
-              .unwrap()
-              .text()
-              .collect::<String>()
-              .trim()
-              .to_string();
-            if !text.is_empty() {
-              original_result = Some(text);
-            }
-          }
-          "原審裁判年月日" => {
-            let text = info_element
-              .select(&dd_text_selector)
-              .next()
-              .unwrap()
-              .text()
-              .collect::<String>()
-              .trim()
-              .to_string();
-            if !text.is_empty() {
-              let date = parse_date_era_str(&text).await?;
-              original_date = Some(date);
-            }
-          }
-          "分野" => {
-            let text = info_element
-              .select(&dd_text_selector)
-              .next()
-              .unwrap()
-              .text()
-              .collect::<String>()
-              .trim()
-              .to_string();
-            if !text.is_empty() {
-              field = Some(text);
-            }
-          }
-          "判示事項の要旨" | "判示事項" => {
-            let text = info_element
-              .select(&dd_text_selector)
-              .next()
-              .unwrap()
-              .text()
-              .collect::<String>()
-              .trim()
-              .to_string();
-            if !text.is_empty() {
-              gist = Some(text);
-            }
-          }
-          "裁判要旨" => {
-            let text = info_element
-              .select(&dd_text_selector)
-              .next()
-              .unwrap()
-              .text()
-              .collect::<String>()
-              .trim()
-              .to_string();
-            if !text.is_empty() {
-              case_gist = Some(text);
-            }
-          }
-          "参照法条" => {
-            let text = info_element
-              .select(&dd_text_selector)
-              .next()
-              .unwrap()
-              .text()
-              .collect::<String>()
-              .trim()
-              .to_string();
-            if !text.is_empty() {
-              ref_law = Some(text);
-            }
-          }
-          "全文" => {
-            let link = info_element
-              .select(&dd_link_selector)
-              .next()
-              .unwrap()
-              .value()
-              .attr("href")
-              .expect("a属性はhrefを持っているはず");
-            full_pdf_link = format!("{COURTS_DOMEIN}{link}");
-          }
-          _ => info!("!!! OTHER: {}", &dt_text),
-        }
-      }
-      let date = parse_date_era_str(date_str.trim()).await?;
-      let precedent_data = PrecedentData {
-        trial_type: trial_type.clone(),
-        date: date.clone(),
-        case_number: case_number.clone(),
-        case_name,
-        court_name,
-        right_type,
-        lawsuit_type,
-        result_type,
-        result,
-        article_info,
-        original_court_name,
-        original_case_number,
-        original_result,
-        original_date,
-        field,
-        gist,
-        case_gist,
-        ref_law,
-        lawsuit_id: lawsuit_id.clone(),
-        detail_page_link,
-        contents: get_pdf_text(&full_pdf_link).await.ok(),
-        full_pdf_link,
-      };
-      let precedent_info = PrecedentInfo {
-        case_number: precedent_data.case_number.clone(),
-        court_name: precedent_data.court_name.clone(),
-        trial_type: precedent_data.trial_type.clone(),
-        date: precedent_data.date.clone(),
-        lawsuit_id: precedent_data.lawsuit_id.clone(),
-      };
-      let file_name = precedent_info.file_name();
-      write_data(&args.output, &file_name, &precedent_data).await?;
-      write_value_lst(&mut index_file, &precedent_info).await?;
-      info!("[END] date write: {}", &lawsuit_id);
-    }
-    // 負荷を抑えるために500ミリ秒待つ
-    info!("sleep");
-    tokio::time::sleep(tokio::time::Duration::from_millis(args.sleep_time)).await;
-  }
-  flush_file_value_lst(&mut index_file).await?;
-  info!("[END] write json file");
+  events.emit(
+    "run_started",
+    json!({
+      "start": start,
+      "end": end,
+      "total_records": all_quantity,
+      "total_pages": all_page_quantity,
+    }),
+  );
+  disk::check_disk_space(
+    &args.output,
+    *all_quantity,
+    disk::record_bytes(
+      args.avg_record_size,
+      args.save_pdf.then_some(args.avg_pdf_size),
+    ),
+    args.disk_check,
+  )?;
+  let first_page = resume.as_ref().map_or(1, |checkpoint| checkpoint.page);
+  pipeline::run(
+    args,
+    fetcher,
+    events,
+    index_writer,
+    retry_queue,
+    ListPages::DateRange {
+      start_date: &start_date,
+      end_date: &end_date,
+      pages: first_page..=all_page_quantity,
+      total_pages: all_page_quantity,
+    },
+    resume,
+  )
+  .await?;
   Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parse_size_units() {
+    assert_eq!(parse_size("1048576").unwrap(), 1048576);
+    assert_eq!(parse_size("512B").unwrap(), 512);
+    assert_eq!(parse_size("512KiB").unwrap(), 512 * 1024);
+    assert_eq!(parse_size("20MB").unwrap(), 20 * 1024 * 1024);
+    assert_eq!(parse_size(" 2 gb ").unwrap(), 2 * 1024 * 1024 * 1024);
+    assert_eq!(parse_size("3m").unwrap(), 3 * 1024 * 1024);
+  }
+
+  #[test]
+  fn parse_size_rejects_invalid() {
+    assert!(parse_size("").is_err());
+    assert!(parse_size("MB").is_err());
+    assert!(parse_size("1.5MB").is_err());
+    assert!(parse_size("10TB").is_err());
+    assert!(parse_size("18446744073709551615GB").is_err());
+  }
+}
@@ -0,0 +1,158 @@
+//! 既存の出力をMeilisearchに登録する`export-meili`サブコマンド
+//!
+//! 一覧の項目に裁判例のJSONを重ねたものを、`lawsuit_id`を主キーにして`--batch-size`件ずつ登録する。
+//! 検索・絞り込み・並べ替えに使うフィールドをインデックスの設定にしておくので、
+//! 登録し終えればMeilisearchの検索画面からすぐに事件名・要旨・本文を検索できる。
+//! APIキーはコマンドライン引数に残らないよう、環境変数`LISTUP_MEILI_KEY`から読む。
+
+use crate::{csv_export, fetch, output};
+use anyhow::{anyhow, Result};
+use serde_json::{json, Map, Value};
+use std::time::Duration;
+use tracing::*;
+
+/// APIキーを読む環境変数
+const KEY_ENV: &str = "LISTUP_MEILI_KEY";
+
+/// 登録したタスクの完了を確かめる間隔
+const TASK_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+fn settings() -> Value {
+  json!({
+    "searchableAttributes": ["case_name", "gist", "case_gist", "contents", "court_name", "case_number", "ref_law"],
+    "filterableAttributes": ["trial_type", "court_name", "year", "lawsuit_type", "result_type", "field"],
+    "sortableAttributes": ["date", "year"],
+  })
+}
+
+struct Client {
+  http: reqwest::Client,
+  host: String,
+  key: Option<String>,
+}
+
+impl Client {
+  fn new(host: &str) -> Result<Self> {
+    Ok(Client {
+      http: reqwest::Client::builder()
+        .user_agent(fetch::USER_AGENT)
+        .build()?,
+      host: host.trim_end_matches('/').to_string(),
+      key: std::env::var(KEY_ENV).ok(),
+    })
+  }
+
+  async fn send(&self, method: reqwest::Method, path: &str, body: Option<Value>) -> Result<Value> {
+    let mut req = self.http.request(method, format!("{}/{path}", self.host));
+    if let Some(key) = &self.key {
+      req = req.bearer_auth(key);
+    }
+    if let Some(body) = body {
+      req = req
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(&body)?);
+    }
+    let res = req.send().await?;
+    let status = res.status();
+    let bytes = res.bytes().await?;
+    if !status.is_success() {
+      return Err(anyhow!(
+        "Meilisearchがエラーを返しました：{path}: {status}: {}",
+        String::from_utf8_lossy(&bytes)
+      ));
+    }
+    Ok(serde_json::from_slice(&bytes)?)
+  }
+
+  /// 登録したタスクの番号
+  fn task_uid(res: &Value) -> Result<u64> {
+    res
+      .get("taskUid")
+      .and_then(Value::as_u64)
+      .ok_or_else(|| anyhow!("Meilisearchの応答にtaskUidがありません：{res}"))
+  }
+
+  /// タスクが終わるまで待つ。失敗していればエラーにする
+  async fn wait_task(&self, uid: u64) -> Result<()> {
+    loop {
+      let task = self
+        .send(reqwest::Method::GET, &format!("tasks/{uid}"), None)
+        .await?;
+      match task.get("status").and_then(Value::as_str) {
+        Some("succeeded") => return Ok(()),
+        Some("failed") | Some("canceled") => {
+          return Err(anyhow!(
+            "Meilisearchのタスク{uid}が失敗しました：{}",
+            task.get("error").unwrap_or(&Value::Null)
+          ))
+        }
+        _ => tokio::time::sleep(TASK_POLL_INTERVAL).await,
+      }
+    }
+  }
+}
+
+/// 一覧の項目に裁判例のJSONを重ねて、登録するドキュメントにする
+async fn document(entry: Value, output: &str) -> Result<Option<Map<String, Value>>> {
+  let Some(mut doc) = output::read_merged_record(entry, output).await? else {
+    return Ok(None);
+  };
+  doc.remove("_meta");
+  if let Some(date) = doc.get("date").and_then(csv_export::date_text) {
+    // 年で絞り込めるように数値の`year`も加える
+    if let Some(year) = date.get(..4).and_then(|y| y.parse::<u32>().ok()) {
+      doc.insert("year".to_string(), year.into());
+    }
+    doc.insert("date".to_string(), date.into());
+  }
+  Ok(Some(doc))
+}
+
+/// `index`の一覧と`output`の裁判例のJSONを、`host`のMeilisearchの`meili_index`に登録する。登録した件数を返す
+pub async fn export(
+  index: &str,
+  output: &str,
+  host: &str,
+  meili_index: &str,
+  batch_size: usize,
+) -> Result<usize> {
+  let client = Client::new(host)?;
+  let res = client
+    .send(
+      reqwest::Method::PATCH,
+      &format!("indexes/{meili_index}/settings"),
+      Some(settings()),
+    )
+    .await?;
+  client.wait_task(Client::task_uid(&res)?).await?;
+  let entries = output::read_value_lst(index).await?;
+  let total = entries.len();
+  let mut sent = 0;
+  let mut last_task = None;
+  for chunk in entries.chunks(batch_size.max(1)) {
+    let mut docs = Vec::with_capacity(chunk.len());
+    for entry in chunk {
+      if let Some(doc) = document(entry.clone(), output).await? {
+        docs.push(Value::Object(doc));
+      }
+    }
+    if docs.is_empty() {
+      continue;
+    }
+    sent += docs.len();
+    let res = client
+      .send(
+        reqwest::Method::POST,
+        &format!("indexes/{meili_index}/documents?primaryKey=lawsuit_id"),
+        Some(Value::Array(docs)),
+      )
+      .await?;
+    last_task = Some(Client::task_uid(&res)?);
+    info!("sent {}/{} documents", sent, total);
+  }
+  // タスクは登録した順に処理されるので、最後のタスクが終われば全件の登録が終わっている
+  if let Some(uid) = last_task {
+    client.wait_task(uid).await?;
+  }
+  Ok(sent)
+}
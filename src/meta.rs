@@ -0,0 +1,57 @@
+//! 遅いページやおかしな挙動のレコードを後から特定するための、レコードごとの処理の記録
+//!
+//! 各レコードのJSONに`_meta`フィールドとして書き出す。
+//! 何か月にもわたって取得を重ねたデータセットでも、各レコードをいつどの版で取得したかを後から確かめられるよう、
+//! 取得日時・このツールの版・裁判例のJSONのスキーマの版も記録する。
+
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use std::time::Duration;
+
+/// 裁判例のJSONのスキーマの版。フィールドの意味や構成を互換でない形で変えたら上げる
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordMeta {
+  /// 詳細ページの取得を始めた日時（UTC、RFC 3339）
+  pub fetched_at: String,
+  /// 書き出したこのツールの版
+  pub scraper_version: &'static str,
+  /// 裁判例のJSONのスキーマの版
+  pub schema_version: u32,
+  /// 詳細ページとPDFの取得からファイルの書き出しまでにかかった時間（ミリ秒）
+  pub elapsed_millis: u64,
+  /// 取得中にリクエストを再試行した回数
+  pub retries: usize,
+  /// PDFからテキストを抽出するのにかかった時間（ミリ秒）
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub pdf_extract_millis: Option<u64>,
+  /// `_meta`を除いた内容のSHA-256。`--overwrite if-changed`で書き換えるかどうかの判定に使う
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub content_hash: Option<String>,
+}
+
+impl RecordMeta {
+  /// 取得日時を今にした記録を作る
+  pub fn new() -> Self {
+    RecordMeta {
+      fetched_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+      scraper_version: env!("CARGO_PKG_VERSION"),
+      schema_version: SCHEMA_VERSION,
+      elapsed_millis: 0,
+      retries: 0,
+      pdf_extract_millis: None,
+      content_hash: None,
+    }
+  }
+}
+
+impl Default for RecordMeta {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+pub fn to_millis(d: Duration) -> u64 {
+  d.as_millis().try_into().unwrap_or(u64::MAX)
+}
@@ -0,0 +1,89 @@
+//! 実行の終了をSMTPでメール通知する
+//!
+//! `--notify-email`を与えると、実行が終わったときに終了の仕方と件数を`run_summary.json`と同じ内容で送る。
+//! SMTPの認証のパスワードはコマンドライン引数に残らないよう、環境変数`LISTUP_SMTP_PASSWORD`から読む。
+//! online featureを無効にしたビルドでは送らない。
+
+use crate::{summary::RunSummary, Args};
+#[cfg(feature = "online")]
+use anyhow::anyhow;
+use anyhow::Result;
+#[cfg(feature = "online")]
+use lettre::{
+  message::header::ContentType, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+  AsyncTransport, Message, Tokio1Executor,
+};
+
+/// SMTPの認証のパスワードを読む環境変数
+#[cfg(feature = "online")]
+const PASSWORD_ENV: &str = "LISTUP_SMTP_PASSWORD";
+
+/// 取得した範囲を表す文字列
+pub fn range_text(args: &Args) -> String {
+  match (&args.start, &args.end) {
+    (Some(start), Some(end)) => format!("{start} - {end}"),
+    _ if args.recent => "recent".to_string(),
+    _ => match (args.since_id, args.until_id) {
+      (Some(since), Some(until)) => format!("id {since} - {until}"),
+      _ => "-".to_string(),
+    },
+  }
+}
+
+#[cfg(feature = "online")]
+fn body(args: &Args, summary: &RunSummary) -> Result<String> {
+  Ok(format!(
+    "status: {}\nexit code: {}\nrange: {}\noutput: {}\nindex: {}\n\n{}\n",
+    summary.status,
+    summary.exit_code,
+    range_text(args),
+    args.output,
+    args.index,
+    serde_json::to_string_pretty(summary)?
+  ))
+}
+
+/// `args.notify_email`の宛先に実行の結果を送る
+#[cfg(feature = "online")]
+pub async fn send(args: &Args, summary: &RunSummary) -> Result<()> {
+  let Some(to) = &args.notify_email else {
+    return Ok(());
+  };
+  let host = args
+    .smtp_host
+    .as_deref()
+    .ok_or_else(|| anyhow!("--notify-emailには--smtp-hostが必要です"))?;
+  let from = args.notify_from.as_deref().unwrap_or(to);
+  let message = Message::builder()
+    .from(from.parse()?)
+    .to(to.parse()?)
+    .subject(format!(
+      "[listup_precedent] {} ({} written, {} failed)",
+      summary.status, summary.records_written, summary.records_failed
+    ))
+    .header(ContentType::TEXT_PLAIN)
+    .body(body(args, summary)?)?;
+  let mut transport = if args.smtp_starttls {
+    AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+  } else {
+    AsyncSmtpTransport::<Tokio1Executor>::relay(host)?
+  };
+  if let Some(port) = args.smtp_port {
+    transport = transport.port(port);
+  }
+  if let Some(user) = &args.smtp_user {
+    let password = std::env::var(PASSWORD_ENV)
+      .map_err(|_| anyhow!("SMTPのパスワードを環境変数{PASSWORD_ENV}に設定してください"))?;
+    transport = transport.credentials(Credentials::new(user.clone(), password));
+  }
+  transport.build().send(message).await?;
+  Ok(())
+}
+
+#[cfg(not(feature = "online"))]
+pub async fn send(args: &Args, _summary: &RunSummary) -> Result<()> {
+  if args.notify_email.is_some() {
+    tracing::warn!("--notify-email is ignored: built without the online feature");
+  }
+  Ok(())
+}
@@ -0,0 +1,143 @@
+//! `--output s3://bucket/prefix`による、S3互換のオブジェクトストレージへの書き出し
+//!
+//! 裁判例ごとのファイルは書き出すたびに`prefix/{ファイル名}.json`のオブジェクトとしてアップロードし、
+//! 一覧ファイルなどは実行の最後にファイル名をそのままキーにしてアップロードする。
+//! 出力フォルダの代わりにローカルには`--staging-dir`を使うので、状態を持たないコンテナでも動かせる。
+//!
+//! 認証情報・リージョン・エンドポイントは`AWS_ACCESS_KEY_ID`・`AWS_SECRET_ACCESS_KEY`・`AWS_REGION`・`AWS_ENDPOINT`などの環境変数から読む。
+//! s3 featureを無効にしたビルドでは`s3://`の出力先をエラーにする。
+
+use anyhow::{anyhow, Result};
+#[cfg(feature = "s3")]
+use object_store::{aws::AmazonS3Builder, path::Path as ObjectPath, ObjectStore};
+#[cfg(feature = "s3")]
+use std::{path::Path, sync::OnceLock};
+#[cfg(feature = "s3")]
+use tracing::*;
+
+#[cfg(feature = "s3")]
+struct Remote {
+  store: Box<dyn ObjectStore>,
+  /// 表示用の`s3://bucket/prefix`
+  url: String,
+  prefix: String,
+}
+
+#[cfg(feature = "s3")]
+static REMOTE: OnceLock<Remote> = OnceLock::new();
+
+/// `s3://bucket/prefix`をバケット名とキーの前置きに分ける
+fn parse_s3_url(output: &str) -> Option<(&str, &str)> {
+  let rest = output.strip_prefix("s3://")?;
+  match rest.split_once('/') {
+    Some((bucket, prefix)) => Some((bucket, prefix.trim_end_matches('/'))),
+    None => Some((rest, "")),
+  }
+}
+
+/// `output`が`s3://`で始まればオブジェクトストレージへの書き出しを設定して`true`を返す
+#[cfg(feature = "s3")]
+pub fn install(output: &str) -> Result<bool> {
+  let Some((bucket, prefix)) = parse_s3_url(output) else {
+    return Ok(false);
+  };
+  if bucket.is_empty() {
+    return Err(anyhow!("バケット名がありません：{output}"));
+  }
+  let store = AmazonS3Builder::from_env()
+    .with_bucket_name(bucket)
+    .build()?;
+  let _ = REMOTE.set(Remote {
+    store: Box::new(store),
+    url: output.trim_end_matches('/').to_string(),
+    prefix: prefix.to_string(),
+  });
+  info!("object storage output: {output}");
+  Ok(true)
+}
+
+/// オブジェクトストレージに書き出しているかどうか
+#[cfg(feature = "s3")]
+pub fn is_enabled() -> bool {
+  REMOTE.get().is_some()
+}
+
+#[cfg(feature = "s3")]
+fn key(remote: &Remote, name: &str) -> ObjectPath {
+  if remote.prefix.is_empty() {
+    ObjectPath::from(name)
+  } else {
+    ObjectPath::from(format!("{}/{name}", remote.prefix))
+  }
+}
+
+/// `name`のオブジェクトとしてアップロードする
+#[cfg(feature = "s3")]
+pub async fn put(name: &str, bytes: Vec<u8>) -> Result<()> {
+  let remote = REMOTE
+    .get()
+    .ok_or_else(|| anyhow!("オブジェクトストレージが設定されていません"))?;
+  remote.store.put(&key(remote, name), bytes.into()).await?;
+  debug!("uploaded: {}/{name}", remote.url);
+  Ok(())
+}
+
+/// `name`のオブジェクトをダウンロードする。無ければ`None`
+#[cfg(feature = "s3")]
+pub async fn get(name: &str) -> Result<Option<Vec<u8>>> {
+  let remote = REMOTE
+    .get()
+    .ok_or_else(|| anyhow!("オブジェクトストレージが設定されていません"))?;
+  match remote.store.get(&key(remote, name)).await {
+    Ok(res) => Ok(Some(res.bytes().await?.to_vec())),
+    Err(object_store::Error::NotFound { .. }) => Ok(None),
+    Err(e) => Err(e.into()),
+  }
+}
+
+/// ローカルに書き出した`files`（一覧ファイルなど）を、ファイル名をキーにしてアップロードする。設定されていなければ何もしない
+#[cfg(feature = "s3")]
+pub async fn upload_files(files: &[String]) -> Result<()> {
+  if !is_enabled() {
+    return Ok(());
+  }
+  for path in files.iter().filter(|p| Path::new(p).exists()) {
+    let name = Path::new(path)
+      .file_name()
+      .map_or_else(|| path.clone(), |n| n.to_string_lossy().to_string());
+    put(&name, tokio::fs::read(path).await?).await?;
+    info!("uploaded: {}/{}", REMOTE.get().map_or("", |r| &r.url), name);
+  }
+  Ok(())
+}
+
+/// オブジェクトストレージを扱わないビルドでは、`s3://`の出力先をエラーにする
+#[cfg(not(feature = "s3"))]
+pub fn install(output: &str) -> Result<bool> {
+  match parse_s3_url(output) {
+    Some(_) => Err(anyhow!(
+      "s3://への出力はs3 featureを有効にしたビルドでだけ使えます：{output}"
+    )),
+    None => Ok(false),
+  }
+}
+
+#[cfg(not(feature = "s3"))]
+pub fn is_enabled() -> bool {
+  false
+}
+
+#[cfg(not(feature = "s3"))]
+pub async fn put(_name: &str, _bytes: Vec<u8>) -> Result<()> {
+  Err(anyhow!("オブジェクトストレージが設定されていません"))
+}
+
+#[cfg(not(feature = "s3"))]
+pub async fn get(_name: &str) -> Result<Option<Vec<u8>>> {
+  Err(anyhow!("オブジェクトストレージが設定されていません"))
+}
+
+#[cfg(not(feature = "s3"))]
+pub async fn upload_files(_files: &[String]) -> Result<()> {
+  Ok(())
+}
@@ -0,0 +1,46 @@
+//! 戦前から昭和中期の判例に見られる、カタカナ交じり文の判定とひらがなへの変換
+//!
+//! 本文中のカタカナがひらがなより多ければカタカナ表記の判例とみなす。
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Orthography {
+  /// 漢字カタカナ交じり文
+  Katakana,
+  /// 漢字ひらがな交じり文
+  Modern,
+}
+
+fn is_hiragana(c: char) -> bool {
+  ('\u{3041}'..='\u{3096}').contains(&c)
+}
+
+fn is_katakana(c: char) -> bool {
+  ('\u{30A1}'..='\u{30F6}').contains(&c)
+}
+
+pub fn detect(text: &str) -> Orthography {
+  let hiragana = text.chars().filter(|c| is_hiragana(*c)).count();
+  let katakana = text.chars().filter(|c| is_katakana(*c)).count();
+  if katakana > hiragana {
+    Orthography::Katakana
+  } else {
+    Orthography::Modern
+  }
+}
+
+/// カタカナをひらがなに置き換える。「ヷ」などの対応するひらがなが無い文字はそのまま残す
+pub fn to_hiragana(text: &str) -> String {
+  text
+    .chars()
+    .map(|c| {
+      if is_katakana(c) {
+        char::from_u32(c as u32 - 0x60).unwrap_or(c)
+      } else {
+        c
+      }
+    })
+    .collect()
+}
@@ -0,0 +1,642 @@
+//! 裁判例ごとのJSONファイルと一覧ファイルの書き出し
+//!
+//! `--format`で裁判例ごとのファイルを、`--index-format`で一覧ファイルをMessagePackやCBORでも書き出せる。
+//! 裁判例ごとのファイルはProtocol Buffersでも書き出せる（[`crate::protobuf`]）。
+//! 一覧ファイルはどの形式でも先頭のバイトで見分けて読む。
+//!
+//! 裁判例のファイルも一覧ファイルも`.tmp`を付けたファイルに書いてから名前を変えるので、
+//! 途中で止まったりディスクがいっぱいになったりしても、書きかけのファイルが元の名前で残ることはない。
+//!
+//! JSONのキーはserde_jsonの`Map`が辞書順に並べるので、常に同じ順で書き出される。
+//! `--deterministic`では、一覧の項目も閉じるときに`lawsuit_id`の順に並べ直し、`_meta`から実行ごとに変わる取得日時・時間・再試行回数を除く。
+//! 同じ範囲を2回取得すれば同じバイト列になるので、出力をgitで差分を取って管理できる。
+//!
+//! 裁判例を書き出す先は`--writer`で選ぶ。書き出し先ごとの処理は[`crate::output_writer`]にある。
+
+use crate::bundle;
+use crate::chunk;
+use crate::compat::{self, CompatIndex, CompatVersion};
+use crate::compress;
+use crate::contents_file;
+use crate::meta::RecordMeta;
+use crate::object_output;
+use crate::output_writer;
+use crate::partition;
+use crate::permissions;
+use crate::protobuf;
+use crate::response_cache::to_hex;
+use crate::stable_id;
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use jplaw_data_types::listup::{PrecedentData, PrecedentInfo};
+use jplaw_io::{flush_file_value_lst, gen_file_value_lst, write_value_lst};
+use listup_precedent_lib::codec;
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, sync::OnceLock};
+use tokio::{fs::*, io::AsyncWriteExt, sync::Mutex};
+use tracing::*;
+
+/// 裁判例ごとのファイルの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum RecordFormat {
+  Json,
+  /// MessagePack
+  Msgpack,
+  Cbor,
+  /// 本文を塊に区切り、1行に1つの塊を書くJSON Lines（`--chunk-size`・`--chunk-overlap`）
+  Chunks,
+  /// Protocol Buffers（`proto/precedent.proto`の`Precedent`メッセージ）
+  Protobuf,
+}
+
+static RECORD_FORMAT: OnceLock<RecordFormat> = OnceLock::new();
+
+/// JSONの書き方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum JsonStyle {
+  /// 改行と字下げを入れる
+  Pretty,
+  /// 空白を入れずに1行にする
+  Compact,
+}
+
+impl JsonStyle {
+  pub fn to_vec<T: serde::Serialize>(self, value: &T) -> Result<Vec<u8>> {
+    Ok(match self {
+      JsonStyle::Pretty => serde_json::to_vec_pretty(value)?,
+      JsonStyle::Compact => serde_json::to_vec(value)?,
+    })
+  }
+}
+
+static JSON_STYLE: OnceLock<JsonStyle> = OnceLock::new();
+
+/// 以降に書き出すJSONの書き方を設定する。2回目以降の呼び出しでは何もしない
+pub fn set_json_style(style: JsonStyle) {
+  let _ = JSON_STYLE.set(style);
+}
+
+/// JSONの書き方。設定されていなければ`Pretty`
+pub fn json_style() -> JsonStyle {
+  JSON_STYLE.get().copied().unwrap_or(JsonStyle::Pretty)
+}
+
+static DETERMINISTIC: OnceLock<bool> = OnceLock::new();
+
+/// 同じ入力から同じバイト列を書き出すようにする。2回目以降の呼び出しでは何もしない
+pub fn set_deterministic(deterministic: bool) {
+  let _ = DETERMINISTIC.set(deterministic);
+}
+
+fn deterministic() -> bool {
+  DETERMINISTIC.get().copied().unwrap_or(false)
+}
+
+/// `--deterministic`で`_meta`から除く、実行ごとに変わるフィールド
+const VOLATILE_META_FIELDS: &[&str] = &[
+  "fetched_at",
+  "elapsed_millis",
+  "retries",
+  "pdf_extract_millis",
+];
+
+impl RecordFormat {
+  pub fn extension(self) -> &'static str {
+    match self {
+      RecordFormat::Json => "json",
+      RecordFormat::Msgpack => "msgpack",
+      RecordFormat::Cbor => "cbor",
+      RecordFormat::Chunks => "chunks.jsonl",
+      RecordFormat::Protobuf => "pb",
+    }
+  }
+
+  pub fn encode(self, value: &serde_json::Value) -> Result<Vec<u8>> {
+    match self {
+      RecordFormat::Json => json_style().to_vec(value),
+      RecordFormat::Msgpack => Ok(rmp_serde::to_vec_named(value)?),
+      RecordFormat::Cbor => {
+        let mut buf = Vec::new();
+        ciborium::ser::into_writer(value, &mut buf)?;
+        Ok(buf)
+      }
+      RecordFormat::Chunks => chunk::encode(value),
+      RecordFormat::Protobuf => protobuf::encode(value),
+    }
+  }
+
+  pub fn decode(self, bytes: &[u8]) -> Result<serde_json::Value> {
+    match self {
+      RecordFormat::Json => Ok(serde_json::from_slice(bytes)?),
+      RecordFormat::Msgpack => Ok(rmp_serde::from_slice(bytes)?),
+      RecordFormat::Cbor => Ok(ciborium::de::from_reader(bytes)?),
+      RecordFormat::Chunks => chunk::decode(bytes),
+      RecordFormat::Protobuf => protobuf::decode(bytes),
+    }
+  }
+}
+
+/// 以降に読み書きする裁判例ごとのファイルの形式を設定する。2回目以降の呼び出しでは何もしない
+pub fn set_record_format(format: RecordFormat) {
+  let _ = RECORD_FORMAT.set(format);
+}
+
+/// 裁判例ごとのファイルの形式。設定されていなければJSON
+pub fn record_format() -> RecordFormat {
+  RECORD_FORMAT.get().copied().unwrap_or(RecordFormat::Json)
+}
+
+/// 裁判例ごとのファイルの名前の`{ファイル名}`より後ろの部分（`.json.zst`など）
+pub fn record_suffix() -> String {
+  format!(
+    ".{}{}",
+    record_format().extension(),
+    compress::compression().extension()
+  )
+}
+
+/// 裁判例のJSONを`--format`の形式にし、`--compress`で圧縮する
+pub fn encode_record(value: &serde_json::Value) -> Result<Vec<u8>> {
+  compress::compression().compress(&record_format().encode(value)?)
+}
+
+/// 裁判例ごとのファイルのpath
+pub fn record_path(output: &str, filename: &str) -> String {
+  format!("{output}/{filename}{}", record_suffix())
+}
+
+/// 裁判例ごとのファイルの中身を、圧縮されていれば展開してから読む
+pub fn decode_record_bytes(bytes: Vec<u8>) -> Result<serde_json::Value> {
+  record_format().decode(&compress::decompress(bytes)?)
+}
+
+pub fn data_exists(output: &str, filename: &str) -> bool {
+  std::path::Path::new(&record_path(output, filename)).exists()
+}
+
+/// 裁判例のファイルを、`_meta`などこのツールが付け加えたフィールドも含めて読む
+///
+/// 本文を`--contents-dir`に書き出していれば、`contents_path`のファイルから`contents`に読み戻す
+pub async fn read_record_value(output: &str, filename: &str) -> Result<serde_json::Value> {
+  let bytes = read(record_path(output, filename)).await?;
+  let mut value = decode_record_bytes(bytes)?;
+  if let serde_json::Value::Object(obj) = &mut value {
+    if let (None, Some(reference)) = (
+      obj.get("contents").filter(|v| !v.is_null()),
+      obj.get("contents_path").and_then(|v| v.as_str()),
+    ) {
+      let contents = contents_file::read(output, reference).await?;
+      obj.insert("contents".to_string(), contents.into());
+    }
+  }
+  Ok(value)
+}
+
+/// 一覧の項目に、出力フォルダにあればその裁判例のファイルの内容を重ねる。項目がオブジェクトでなければ`None`
+pub async fn read_merged_record(
+  entry: serde_json::Value,
+  output: &str,
+) -> Result<Option<serde_json::Map<String, serde_json::Value>>> {
+  let serde_json::Value::Object(mut record) = entry else {
+    return Ok(None);
+  };
+  let file_name =
+    serde_json::from_value::<PrecedentInfo>(serde_json::Value::Object(record.clone()))
+      .ok()
+      .map(|info| partition::record_name(&info));
+  if let Some(name) = file_name.filter(|name| data_exists(output, name)) {
+    if let serde_json::Value::Object(data) = read_record_value(output, &name).await? {
+      record.extend(data);
+    }
+  }
+  Ok(Some(record))
+}
+
+pub async fn read_data(output: &str, filename: &str) -> Result<PrecedentData> {
+  let value = read_record_value(output, filename).await?;
+  let data = serde_json::from_value(value)?;
+  Ok(data)
+}
+
+/// 既存の裁判例のJSONを書き換えるかどうか
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OverwritePolicy {
+  /// 常に書き換える
+  Always,
+  /// 既にファイルがあれば書き換えない
+  Never,
+  /// 内容のハッシュが既存のファイルと異なるときだけ書き換える
+  IfChanged,
+}
+
+fn to_record_value(
+  data: &PrecedentData,
+  extra: &serde_json::Map<String, serde_json::Value>,
+) -> Result<serde_json::Value> {
+  let mut value = serde_json::to_value(data)?;
+  if let serde_json::Value::Object(obj) = &mut value {
+    obj.extend(extra.clone());
+  }
+  Ok(value)
+}
+
+fn hash_value(value: &serde_json::Value) -> String {
+  to_hex(&Sha256::digest(value.to_string().as_bytes()))
+}
+
+/// `_meta`を除いた裁判例のJSONの内容のSHA-256
+pub fn content_hash(
+  data: &PrecedentData,
+  extra: &serde_json::Map<String, serde_json::Value>,
+) -> Result<String> {
+  Ok(hash_value(&to_record_value(data, extra)?))
+}
+
+/// 書き出し先に既にある裁判例。`--skip-existing`・`--overwrite`で、書き出し先によらず既存の裁判例を調べるのに使う
+pub async fn existing_record(output: &str, filename: &str) -> Result<Option<serde_json::Value>> {
+  output_writer::get().read_record(output, filename).await
+}
+
+/// 既存の裁判例のJSONの内容のハッシュ。`_meta`に記録が無ければ内容から計算する
+pub fn stored_content_hash(mut value: serde_json::Value) -> Option<String> {
+  let meta = value.as_object_mut()?.remove("_meta");
+  match meta
+    .as_ref()
+    .and_then(|m| m.get("content_hash"))
+    .and_then(|h| h.as_str())
+  {
+    Some(hash) => Some(hash.to_string()),
+    None => Some(hash_value(&value)),
+  }
+}
+
+/// `extra`は`PrecedentData`に無い、このツールが付け加えるフィールド
+pub async fn write_data(
+  output: &str,
+  filename: &str,
+  data: &PrecedentData,
+  meta: &RecordMeta,
+  extra: &serde_json::Map<String, serde_json::Value>,
+) -> Result<()> {
+  let mut value = to_record_value(data, extra)?;
+  if let serde_json::Value::Object(obj) = &mut value {
+    if contents_file::is_enabled() {
+      if let Some(serde_json::Value::String(text)) = obj.remove("contents") {
+        let reference = contents_file::write(output, filename, &text).await?;
+        obj.insert("contents".to_string(), serde_json::Value::Null);
+        obj.insert("contents_path".to_string(), reference.into());
+      }
+    }
+    let mut meta = serde_json::to_value(meta)?;
+    if let (true, serde_json::Value::Object(meta)) = (deterministic(), &mut meta) {
+      for field in VOLATILE_META_FIELDS {
+        meta.remove(*field);
+      }
+    }
+    obj.insert("_meta".to_string(), meta);
+  }
+  // `--archive`では書き出し先によらずアーカイブに収める
+  if bundle::is_open() {
+    let bytes = encode_record(&value)?;
+    return bundle::append(&format!("{filename}{}", record_suffix()), &bytes);
+  }
+  output_writer::get()
+    .write_record(output, filename, &value)
+    .await
+}
+
+/// 裁判例のファイルに添えるファイル（本文の`.txt`やPDFなど）を、出力フォルダからの相対パス`name`に書き出す
+///
+/// `--archive`ではアーカイブに、オブジェクトストレージに出力するときはそこに収める。`name`が絶対パスならそのパスに書く
+pub async fn write_side_file(output: &str, name: &str, bytes: Vec<u8>) -> Result<()> {
+  if bundle::is_open() {
+    return bundle::append(name.trim_start_matches('/'), &bytes);
+  }
+  if object_output::is_enabled() {
+    return object_output::put(name.trim_start_matches('/'), bytes).await;
+  }
+  let path = if std::path::Path::new(name).is_absolute() {
+    name.to_string()
+  } else {
+    format!("{output}/{name}")
+  };
+  if let Some(dir) = std::path::Path::new(&path).parent() {
+    create_dir_all(dir).await?;
+  }
+  write_atomic(&path, &bytes).await?;
+  permissions::apply(&path).await?;
+  Ok(())
+}
+
+/// 書き込み中のファイルのパス。書き終えたら`path`に名前を変える
+pub fn tmp_path(path: &str) -> String {
+  format!("{path}.tmp")
+}
+
+/// `.tmp`を付けたファイルが書き終わってからディスクに書き出し、名前を`path`に変える
+pub async fn commit_tmp(mut file: File, path: &str) -> Result<()> {
+  file.flush().await?;
+  file.sync_all().await?;
+  rename(tmp_path(path), path).await?;
+  Ok(())
+}
+
+/// `bytes`を`.tmp`を付けたファイルに書いてから`path`に名前を変える。失敗したら書きかけのファイルは消す
+pub async fn write_atomic(path: &str, bytes: &[u8]) -> Result<()> {
+  let tmp = tmp_path(path);
+  let res = async {
+    let mut file = File::create(&tmp).await?;
+    file.write_all(bytes).await?;
+    commit_tmp(file, path).await
+  }
+  .await;
+  if res.is_err() {
+    let _ = remove_file(&tmp).await;
+  }
+  res
+}
+
+pub use listup_precedent_lib::codec::{detect_index_format, IndexFormat};
+
+enum ValueLstFile {
+  Json(File),
+  Jsonl(File),
+  Binary(RecordFormat, File),
+}
+
+/// 書き出し中の一覧ファイル
+///
+/// `.tmp`を付けたファイルに書き、`flush`で閉じたときに元の名前に変える
+pub struct ValueLst {
+  path: String,
+  format: IndexFormat,
+  file: Option<ValueLstFile>,
+}
+
+impl ValueLst {
+  pub async fn create(path: &str, format: IndexFormat) -> Result<Self> {
+    let tmp = tmp_path(path);
+    let file = match format {
+      IndexFormat::Json => ValueLstFile::Json(gen_file_value_lst(&tmp).await?),
+      IndexFormat::Jsonl => ValueLstFile::Jsonl(File::create(&tmp).await?),
+      IndexFormat::Msgpack => {
+        ValueLstFile::Binary(RecordFormat::Msgpack, File::create(&tmp).await?)
+      }
+      IndexFormat::Cbor => ValueLstFile::Binary(RecordFormat::Cbor, File::create(&tmp).await?),
+    };
+    Ok(ValueLst {
+      path: path.to_string(),
+      format,
+      file: Some(file),
+    })
+  }
+
+  pub async fn write(&mut self, value: &serde_json::Value) -> Result<()> {
+    match &mut self.file {
+      Some(ValueLstFile::Json(file)) => write_value_lst(file, value).await?,
+      Some(ValueLstFile::Jsonl(file)) => {
+        let mut line = serde_json::to_string(value)?;
+        line.push('\n');
+        file.write_all(line.as_bytes()).await?;
+      }
+      Some(ValueLstFile::Binary(format, file)) => file.write_all(&format.encode(value)?).await?,
+      None => return Err(anyhow!("一覧ファイルは閉じられています：{}", self.path)),
+    }
+    Ok(())
+  }
+
+  /// 一覧ファイルを閉じて元の名前に変える。2回目以降の呼び出しでは何もしない
+  ///
+  /// `--deterministic`では項目を`lawsuit_id`の順に並べ直してから元の名前で書き出す
+  pub async fn flush(&mut self) -> Result<()> {
+    let file = match self.file.take() {
+      Some(ValueLstFile::Json(mut file)) => {
+        flush_file_value_lst(&mut file).await?;
+        file
+      }
+      Some(ValueLstFile::Jsonl(file)) | Some(ValueLstFile::Binary(_, file)) => file,
+      None => return Ok(()),
+    };
+    if deterministic() {
+      let mut file = file;
+      file.flush().await?;
+      drop(file);
+      write_sorted(&self.path, self.format).await?;
+    } else {
+      commit_tmp(file, &self.path).await?;
+    }
+    permissions::apply(&self.path).await?;
+    Ok(())
+  }
+}
+
+/// 一覧の項目を並べる順。`lawsuit_id`を数として比べ、同じものや無いものは項目の内容で比べる
+fn entry_sort_key(value: &serde_json::Value) -> (u64, String, String) {
+  let id = value
+    .get("lawsuit_id")
+    .and_then(|v| v.as_str())
+    .unwrap_or_default();
+  (
+    id.parse().unwrap_or(u64::MAX),
+    id.to_string(),
+    value.to_string(),
+  )
+}
+
+/// 書き終えた`.tmp`の一覧を読み、項目を並べ直して`path`に書き出す
+async fn write_sorted(path: &str, format: IndexFormat) -> Result<()> {
+  let mut values = read_value_lst(&tmp_path(path)).await?;
+  values.sort_by_cached_key(entry_sort_key);
+  let bytes = match format {
+    IndexFormat::Json => json_style().to_vec(&values)?,
+    IndexFormat::Jsonl => {
+      let mut bytes = Vec::new();
+      for value in &values {
+        bytes.extend(serde_json::to_vec(value)?);
+        bytes.push(b'\n');
+      }
+      bytes
+    }
+    IndexFormat::Msgpack | IndexFormat::Cbor => {
+      let record_format = if format == IndexFormat::Msgpack {
+        RecordFormat::Msgpack
+      } else {
+        RecordFormat::Cbor
+      };
+      let mut bytes = Vec::new();
+      for value in &values {
+        bytes.extend(record_format.encode(value)?);
+      }
+      bytes
+    }
+  };
+  write_atomic(path, &bytes).await
+}
+
+/// 前回の実行が一覧ファイルを閉じる前に止まっていれば、その書きかけの`.tmp`のファイル。なければ`path`
+pub fn unfinished_or(path: &str) -> String {
+  let tmp = tmp_path(path);
+  if std::path::Path::new(&tmp).exists() {
+    tmp
+  } else {
+    path.to_string()
+  }
+}
+
+/// 一覧ファイルを読む。`path`が無く圧縮したものがあればそれを展開して読む
+async fn read_index_bytes(path: &str) -> Result<Option<Vec<u8>>> {
+  let path = compress::existing_path(path);
+  if !std::path::Path::new(&path).exists() {
+    return Ok(None);
+  }
+  Ok(Some(compress::decompress(read(&path).await?)?))
+}
+
+/// JSONの配列・JSON Lines・MessagePack・CBORのどの形式の一覧ファイルも、先頭のバイトで見分けて読む
+pub async fn read_value_lst(path: &str) -> Result<Vec<serde_json::Value>> {
+  let Some(bytes) = read_index_bytes(path).await? else {
+    return Ok(Vec::new());
+  };
+  codec::parse_index(&bytes, false)
+}
+
+/// 書き込みの途中で止まって閉じられていない一覧ファイルも、読める項目までを読む
+pub async fn read_value_lst_lenient(path: &str) -> Result<Vec<serde_json::Value>> {
+  let Some(bytes) = read_index_bytes(path).await? else {
+    return Ok(Vec::new());
+  };
+  codec::parse_index(&bytes, true)
+}
+
+/// `new_path`の一覧の項目を`path`の一覧にマージする。`lawsuit_id`が同じ項目は新しいもので置き換える
+pub async fn merge_index(path: &str, new_path: &str, format: IndexFormat) -> Result<()> {
+  let existing = read_value_lst(path).await?;
+  let new = read_value_lst(new_path).await?;
+  let new_ids = new
+    .iter()
+    .filter_map(|v| v.get("lawsuit_id"))
+    .collect::<Vec<_>>();
+  let mut file = ValueLst::create(path, format).await?;
+  for value in existing
+    .iter()
+    .filter(|v| {
+      v.get("lawsuit_id")
+        .map_or(true, |id| !new_ids.contains(&id))
+    })
+    .chain(new.iter())
+  {
+    file.write(value).await?;
+  }
+  file.flush().await?;
+  info!("merged {} entries into {}", new.len(), path);
+  Ok(())
+}
+
+/// 既存の一覧ファイルから、`lawsuit_id`ごとの裁判例のJSONのファイル名を読み出す
+pub async fn read_file_names(index: &str) -> Result<HashMap<String, String>> {
+  let mut file_names = HashMap::new();
+  for value in read_value_lst_lenient(&unfinished_or(index)).await? {
+    let info: PrecedentInfo = serde_json::from_value(value)?;
+    file_names.insert(info.lawsuit_id.clone(), partition::record_name(&info));
+  }
+  Ok(file_names)
+}
+
+/// 一覧ファイル（と必要であれば旧スキーマの互換一覧ファイル）への書き出し
+///
+/// 並行して取得した判例を書き込めるよう、ファイルはロックを取ってから書き込む
+pub struct IndexWriter {
+  files: Mutex<IndexFiles>,
+  /// 前回の一覧ファイルにあった`lawsuit_id`ごとのファイル名
+  previous_file_names: HashMap<String, String>,
+}
+
+struct IndexFiles {
+  index_file: ValueLst,
+  compat_index_file: Option<CompatIndex>,
+}
+
+/// 一覧ファイルを作り直す。`keep_existing`のときは既にある項目（前回の書きかけがあればその項目）を書き戻しておく
+async fn open_value_lst(path: &str, keep_existing: bool, format: IndexFormat) -> Result<ValueLst> {
+  let existing = if keep_existing {
+    read_value_lst_lenient(&unfinished_or(path)).await?
+  } else {
+    Vec::new()
+  };
+  let mut file = ValueLst::create(path, format).await?;
+  for value in existing.iter() {
+    file.write(value).await?;
+  }
+  if keep_existing {
+    info!("kept {} entries in {}", existing.len(), path);
+  }
+  Ok(file)
+}
+
+impl IndexWriter {
+  /// `keep_existing`のときは、中断した実行の続きとして既存の一覧の項目を残す
+  ///
+  /// 互換一覧ファイルは旧スキーマに合わせて、元の版と同じ形式で書き出す
+  pub async fn open(
+    index: &str,
+    format: IndexFormat,
+    compat: Option<CompatVersion>,
+    compat_index: Option<&str>,
+    keep_existing: bool,
+  ) -> Result<Self> {
+    let index_file = open_value_lst(index, keep_existing, format).await?;
+    let compat_index_file = match compat {
+      Some(version) => {
+        let path = compat_index
+          .map(|s| s.to_string())
+          .unwrap_or_else(|| compat::gen_compat_index_path(index, version));
+        info!("compat index ({}): {}", version.suffix(), &path);
+        Some(CompatIndex::open(&path, keep_existing).await?)
+      }
+      None => None,
+    };
+    Ok(IndexWriter {
+      files: Mutex::new(IndexFiles {
+        index_file,
+        compat_index_file,
+      }),
+      previous_file_names: HashMap::new(),
+    })
+  }
+
+  pub fn set_previous_file_names(&mut self, file_names: HashMap<String, String>) {
+    self.previous_file_names = file_names;
+  }
+
+  pub fn previous_file_name(&self, lawsuit_id: &str) -> Option<&str> {
+    self.previous_file_names.get(lawsuit_id).map(|s| s.as_str())
+  }
+
+  pub async fn write(&self, info: &PrecedentInfo, data: &PrecedentData) -> Result<()> {
+    let mut value = serde_json::to_value(info)?;
+    if let serde_json::Value::Object(obj) = &mut value {
+      obj.insert("uuid".to_string(), stable_id::uuid(data).to_string().into());
+      if partition::is_enabled() {
+        obj.insert(
+          "path".to_string(),
+          format!("{}{}", partition::record_name(info), record_suffix()).into(),
+        );
+      }
+    }
+    let mut files = self.files.lock().await;
+    files.index_file.write(&value).await?;
+    if let Some(file) = &mut files.compat_index_file {
+      file.write(info).await?;
+    }
+    output_writer::get().write_index_entry(&value).await
+  }
+
+  pub async fn flush(&self) -> Result<()> {
+    let mut files = self.files.lock().await;
+    files.index_file.flush().await?;
+    if let Some(file) = &mut files.compat_index_file {
+      file.flush().await?;
+    }
+    output_writer::get().flush().await
+  }
+}
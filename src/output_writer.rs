@@ -0,0 +1,487 @@
+//! 裁判例の書き出し先を切り替えるための`OutputWriter`
+//!
+//! 取得の処理は書き出し先を知らずに[`get`]の`OutputWriter`へ1件ずつ渡すだけなので、
+//! 書き出し先を増やすときは実装を足して`--writer`の選択肢に加えればよい。
+//!
+//! - `json-dir`：出力フォルダに裁判例ごとのファイルを作る（既定）
+//! - `jsonl`：出力フォルダの`records.jsonl`に1件1行で追記する。`--max-file-size`を与えると、
+//!   その大きさを超える前に`records-00000.jsonl`・`records-00001.jsonl`…と次のファイルに切り替える
+//! - `sqlite`：出力フォルダの`records.sqlite`の`records`テーブルに書き、一覧の項目も`index_entries`テーブルに書く（sqlite feature）。
+//!   全文検索のテーブルは作らないので、全文検索には`--index-sqlite`と`--sqlite-fts`を使う
+//! - `s3`：`--output s3://…`のオブジェクトストレージにアップロードする（`--output`から自動で選ばれる。s3 feature）
+//! - `stdout`：1件1行のJSONとして標準出力に流す（`--stdout`）
+//!
+//! どの書き出し先でも一覧ファイルは`--index`に書き出す。`--archive`を与えたときはアーカイブへの追記が優先される。
+//! `--skip-existing`・`--overwrite never`・`--overwrite if-changed`は書き出し済みの裁判例を[`OutputWriter::read_record`]で調べるので、
+//! 読み戻せない`jsonl`・`stdout`では使えない。
+
+use crate::{object_output, output, partition, permissions};
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use futures::future::BoxFuture;
+use serde_json::Value;
+use std::{
+  fs::{File, OpenOptions},
+  io::Write,
+  sync::{Arc, Mutex, OnceLock},
+};
+use tracing::*;
+
+/// 裁判例の書き出し先
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputBackend {
+  JsonDir,
+  Jsonl,
+  #[cfg(feature = "sqlite")]
+  Sqlite,
+  S3,
+  Stdout,
+}
+
+pub trait OutputWriter: Send + Sync {
+  /// 1件の裁判例を書き出す。`value`は`_meta`などを付け加えたあとのJSON
+  fn write_record<'a>(
+    &'a self,
+    output: &'a str,
+    file_name: &'a str,
+    value: &'a Value,
+  ) -> BoxFuture<'a, Result<()>>;
+
+  /// 書き出し済みの裁判例を`_meta`なども含めて読む。書き出していなければ`None`
+  fn read_record<'a>(
+    &'a self,
+    _output: &'a str,
+    _file_name: &'a str,
+  ) -> BoxFuture<'a, Result<Option<Value>>> {
+    Box::pin(async {
+      Err(anyhow!(
+        "この書き出し先からは書き出し済みの裁判例を読み戻せません"
+      ))
+    })
+  }
+
+  /// [`OutputWriter::read_record`]で書き出し済みの裁判例を読み戻せるかどうか
+  fn can_read_back(&self) -> bool {
+    false
+  }
+
+  /// 一覧ファイルに書いた項目を、書き出し先にも残す。一覧ファイルだけで足りる書き出し先では何もしない
+  fn write_index_entry<'a>(&'a self, _entry: &'a Value) -> BoxFuture<'a, Result<()>> {
+    Box::pin(async { Ok(()) })
+  }
+
+  /// 一覧ファイルを閉じるときに呼ばれる。何度呼ばれてもよい
+  fn flush(&self) -> BoxFuture<'_, Result<()>> {
+    Box::pin(async { Ok(()) })
+  }
+}
+
+static WRITER: OnceLock<Box<dyn OutputWriter>> = OnceLock::new();
+
+/// 以降の書き出し先を設定する。2回目以降の呼び出しでは何もしない
+///
+/// `max_file_size`は`jsonl`でだけ使える
+pub fn install(backend: OutputBackend, output: &str, max_file_size: Option<u64>) -> Result<()> {
+  if max_file_size.is_some() && backend != OutputBackend::Jsonl {
+    return Err(anyhow!("--max-file-sizeは--writer jsonlでだけ使えます"));
+  }
+  let writer: Box<dyn OutputWriter> = match backend {
+    OutputBackend::JsonDir => Box::new(JsonDir),
+    OutputBackend::Jsonl => Box::new(Jsonl::new(output, max_file_size)),
+    #[cfg(feature = "sqlite")]
+    OutputBackend::Sqlite => Box::new(Sqlite {
+      path: format!("{output}/records.sqlite"),
+      conn: Arc::new(Mutex::new(None)),
+    }),
+    OutputBackend::S3 if !object_output::is_enabled() => {
+      return Err(anyhow!(
+        "--writer s3には--outputにs3://で始まるURLを指定してください"
+      ));
+    }
+    OutputBackend::S3 => Box::new(S3),
+    OutputBackend::Stdout => Box::new(Stdout),
+  };
+  let _ = WRITER.set(writer);
+  Ok(())
+}
+
+/// 設定された書き出し先。設定されていなければ`json-dir`
+pub fn get() -> &'static dyn OutputWriter {
+  WRITER.get_or_init(|| Box::new(JsonDir)).as_ref()
+}
+
+/// 出力フォルダに裁判例ごとのファイルを作る
+struct JsonDir;
+
+impl OutputWriter for JsonDir {
+  fn write_record<'a>(
+    &'a self,
+    output: &'a str,
+    file_name: &'a str,
+    value: &'a Value,
+  ) -> BoxFuture<'a, Result<()>> {
+    Box::pin(async move {
+      let bytes = output::encode_record(value)?;
+      let path = output::record_path(output, file_name);
+      if partition::is_enabled() {
+        if let Some(dir) = std::path::Path::new(&path).parent() {
+          tokio::fs::create_dir_all(dir).await?;
+        }
+      }
+      output::write_atomic(&path, &bytes).await?;
+      permissions::apply(&path).await?;
+      Ok(())
+    })
+  }
+
+  fn read_record<'a>(
+    &'a self,
+    output: &'a str,
+    file_name: &'a str,
+  ) -> BoxFuture<'a, Result<Option<Value>>> {
+    Box::pin(async move {
+      if !output::data_exists(output, file_name) {
+        return Ok(None);
+      }
+      Ok(Some(output::read_record_value(output, file_name).await?))
+    })
+  }
+
+  fn can_read_back(&self) -> bool {
+    true
+  }
+}
+
+/// 出力フォルダのJSON Linesファイルに追記する
+///
+/// ファイルへの書き込みと`sync_all`は待ちが生じるので、tokioのランタイムを止めないよう`spawn_blocking`で実行する
+struct Jsonl {
+  files: Arc<JsonlFiles>,
+}
+
+struct JsonlFiles {
+  output: String,
+  max_file_size: Option<u64>,
+  state: Mutex<JsonlState>,
+}
+
+#[derive(Default)]
+struct JsonlState {
+  /// 書き込み中のファイルと、その番号と大きさ
+  current: Option<(File, usize, u64)>,
+  /// 書き終えてパーミッションをまだ設定していないファイル
+  finished: Vec<String>,
+}
+
+impl Jsonl {
+  fn new(output: &str, max_file_size: Option<u64>) -> Self {
+    Jsonl {
+      files: Arc::new(JsonlFiles {
+        output: output.to_string(),
+        max_file_size,
+        state: Mutex::new(JsonlState::default()),
+      }),
+    }
+  }
+}
+
+impl JsonlFiles {
+  fn path(&self, part: usize) -> String {
+    match self.max_file_size {
+      Some(_) => format!("{}/records-{part:05}.jsonl", self.output),
+      None => format!("{}/records.jsonl", self.output),
+    }
+  }
+
+  /// 前回の実行の続きになるよう、既にある最後のファイルに追記する
+  fn open(&self, part: usize) -> Result<(File, usize, u64)> {
+    let mut part = part;
+    while self.max_file_size.is_some() && std::path::Path::new(&self.path(part + 1)).exists() {
+      part += 1;
+    }
+    let file = OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(self.path(part))?;
+    let size = file.metadata()?.len();
+    Ok((file, part, size))
+  }
+
+  fn write_line(&self, line: &str) -> Result<()> {
+    let mut state = self.state.lock().unwrap();
+    if state.current.is_none() {
+      state.current = Some(self.open(0)?);
+    }
+    let (part, size) = state
+      .current
+      .as_ref()
+      .map(|(_, part, size)| (*part, *size))
+      .unwrap_or_default();
+    // 1行だけで上限を超えるときは、空のファイルにそのまま書く
+    if let Some(max) = self.max_file_size {
+      if size > 0 && size + line.len() as u64 > max {
+        let next = part + 1;
+        if let Some((mut file, _, _)) = state.current.take() {
+          file.flush()?;
+          file.sync_all()?;
+          state.finished.push(self.path(part));
+        }
+        state.current = Some(self.open(next)?);
+        debug!("jsonl rotated: {}", self.path(next));
+      }
+    }
+    if let Some((file, _, size)) = state.current.as_mut() {
+      file.write_all(line.as_bytes())?;
+      *size += line.len() as u64;
+    }
+    Ok(())
+  }
+
+  /// 書き込み中のファイルをディスクに書き出し、パーミッションを設定していないファイルを返す
+  fn sync(&self) -> Result<Vec<String>> {
+    let mut state = self.state.lock().unwrap();
+    let mut paths = std::mem::take(&mut state.finished);
+    if let Some((file, part, _)) = state.current.as_mut() {
+      file.flush()?;
+      file.sync_all()?;
+      paths.push(self.path(*part));
+    }
+    Ok(paths)
+  }
+}
+
+impl OutputWriter for Jsonl {
+  fn write_record<'a>(
+    &'a self,
+    _output: &'a str,
+    _file_name: &'a str,
+    value: &'a Value,
+  ) -> BoxFuture<'a, Result<()>> {
+    Box::pin(async move {
+      let line = format!("{value}\n");
+      let files = Arc::clone(&self.files);
+      tokio::task::spawn_blocking(move || files.write_line(&line)).await?
+    })
+  }
+
+  fn flush(&self) -> BoxFuture<'_, Result<()>> {
+    Box::pin(async move {
+      let files = Arc::clone(&self.files);
+      let paths = tokio::task::spawn_blocking(move || files.sync()).await??;
+      for path in paths {
+        permissions::apply(&path).await?;
+      }
+      Ok(())
+    })
+  }
+}
+
+/// 出力フォルダのSQLiteデータベースに書く
+///
+/// rusqliteの呼び出しは待ちが生じるので、tokioのランタイムを止めないよう`spawn_blocking`で実行する
+#[cfg(feature = "sqlite")]
+struct Sqlite {
+  path: String,
+  conn: Arc<Mutex<Option<rusqlite::Connection>>>,
+}
+
+#[cfg(feature = "sqlite")]
+impl Sqlite {
+  async fn with_conn<T: Send + 'static>(
+    &self,
+    f: impl FnOnce(&rusqlite::Connection) -> Result<T> + Send + 'static,
+  ) -> Result<T> {
+    let path = self.path.clone();
+    let conn = Arc::clone(&self.conn);
+    tokio::task::spawn_blocking(move || {
+      let mut conn = conn.lock().unwrap();
+      if conn.is_none() {
+        let c = rusqlite::Connection::open(&path)?;
+        c.execute_batch(
+          "CREATE TABLE IF NOT EXISTS records (file_name TEXT PRIMARY KEY, lawsuit_id TEXT, data TEXT NOT NULL);
+           CREATE TABLE IF NOT EXISTS index_entries (lawsuit_id TEXT PRIMARY KEY, entry TEXT NOT NULL);",
+        )?;
+        *conn = Some(c);
+      }
+      f(conn.as_ref().unwrap())
+    })
+    .await?
+  }
+}
+
+#[cfg(feature = "sqlite")]
+impl OutputWriter for Sqlite {
+  fn write_record<'a>(
+    &'a self,
+    _output: &'a str,
+    file_name: &'a str,
+    value: &'a Value,
+  ) -> BoxFuture<'a, Result<()>> {
+    Box::pin(async move {
+      let file_name = file_name.to_string();
+      let lawsuit_id = value
+        .get("lawsuit_id")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+      let data = value.to_string();
+      self
+        .with_conn(move |conn| {
+          conn.execute(
+            "INSERT OR REPLACE INTO records VALUES (?1, ?2, ?3)",
+            rusqlite::params![file_name, lawsuit_id, data],
+          )?;
+          Ok(())
+        })
+        .await
+    })
+  }
+
+  fn read_record<'a>(
+    &'a self,
+    _output: &'a str,
+    file_name: &'a str,
+  ) -> BoxFuture<'a, Result<Option<Value>>> {
+    Box::pin(async move {
+      let file_name = file_name.to_string();
+      let data = self
+        .with_conn(move |conn| {
+          use rusqlite::OptionalExtension;
+          Ok(
+            conn
+              .query_row(
+                "SELECT data FROM records WHERE file_name = ?1",
+                [file_name],
+                |row| row.get::<_, String>(0),
+              )
+              .optional()?,
+          )
+        })
+        .await?;
+      Ok(
+        data
+          .map(|data| serde_json::from_str::<Value>(&data))
+          .transpose()?,
+      )
+    })
+  }
+
+  fn can_read_back(&self) -> bool {
+    true
+  }
+
+  fn write_index_entry<'a>(&'a self, entry: &'a Value) -> BoxFuture<'a, Result<()>> {
+    Box::pin(async move {
+      let lawsuit_id = entry
+        .get("lawsuit_id")
+        .and_then(Value::as_str)
+        .map(str::to_string);
+      let entry = entry.to_string();
+      self
+        .with_conn(move |conn| {
+          conn.execute(
+            "INSERT OR REPLACE INTO index_entries VALUES (?1, ?2)",
+            rusqlite::params![lawsuit_id, entry],
+          )?;
+          Ok(())
+        })
+        .await
+    })
+  }
+
+  fn flush(&self) -> BoxFuture<'_, Result<()>> {
+    Box::pin(async move {
+      // 書き込み中の接続があればその終わりを待つことになるので、ここもブロッキング用のスレッドで確かめる
+      let conn = Arc::clone(&self.conn);
+      let opened = tokio::task::spawn_blocking(move || conn.lock().unwrap().is_some()).await?;
+      if opened {
+        permissions::apply(&self.path).await?;
+      }
+      Ok(())
+    })
+  }
+}
+
+/// オブジェクトストレージにアップロードする
+struct S3;
+
+impl OutputWriter for S3 {
+  fn write_record<'a>(
+    &'a self,
+    _output: &'a str,
+    file_name: &'a str,
+    value: &'a Value,
+  ) -> BoxFuture<'a, Result<()>> {
+    Box::pin(async move {
+      let bytes = output::encode_record(value)?;
+      object_output::put(&format!("{file_name}{}", output::record_suffix()), bytes).await
+    })
+  }
+
+  fn read_record<'a>(
+    &'a self,
+    _output: &'a str,
+    file_name: &'a str,
+  ) -> BoxFuture<'a, Result<Option<Value>>> {
+    Box::pin(async move {
+      let bytes = object_output::get(&format!("{file_name}{}", output::record_suffix())).await?;
+      bytes.map(output::decode_record_bytes).transpose()
+    })
+  }
+
+  fn can_read_back(&self) -> bool {
+    true
+  }
+}
+
+/// 1件1行のJSONとして標準出力に流す
+struct Stdout;
+
+impl OutputWriter for Stdout {
+  fn write_record<'a>(
+    &'a self,
+    _output: &'a str,
+    _file_name: &'a str,
+    value: &'a Value,
+  ) -> BoxFuture<'a, Result<()>> {
+    Box::pin(async move {
+      // パイプの読み手が遅いと書き込みが待たされるので、tokioのランタイムの外で書く
+      let line = format!("{value}\n");
+      tokio::task::spawn_blocking(move || {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(line.as_bytes())?;
+        stdout.flush()?;
+        Ok::<_, anyhow::Error>(())
+      })
+      .await?
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[tokio::test]
+  async fn jsonl_rotates_before_exceeding_max_file_size() {
+    let dir = std::env::temp_dir().join(format!("listup_precedent_jsonl_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let output = dir.to_str().unwrap().to_string();
+    let value = json!({ "lawsuit_id": "12345", "case_name": "損害賠償請求事件" });
+    let line_len = format!("{value}\n").len() as u64;
+    // 2行ちょうどで上限になるので、3行目で次のファイルに切り替わる
+    let writer = Jsonl::new(&output, Some(line_len * 2));
+    for _ in 0..3 {
+      writer.write_record(&output, "12345", &value).await.unwrap();
+    }
+    writer.flush().await.unwrap();
+
+    let first = std::fs::metadata(dir.join("records-00000.jsonl")).unwrap();
+    let second = std::fs::metadata(dir.join("records-00001.jsonl")).unwrap();
+    assert_eq!(first.len(), line_len * 2);
+    assert_eq!(second.len(), line_len);
+    assert!(!dir.join("records-00002.jsonl").exists());
+    assert!(!dir.join("records.jsonl").exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+  }
+}
@@ -0,0 +1,31 @@
+//! 判決文の本文をPDFのページごとに分けた配列
+//!
+//! `--pages`を与えると、各裁判例のJSONに`pages`フィールドとして書き出す。`pages[0]`が1ページ目になる。
+//! 抽出した本文に改ページ（`\x0c`）があればそこで分け、無ければ判決文の各ページの末尾にある
+//! `- 1 -`のようなページ番号だけの行で分ける。ページ番号の行はそのページの最後の行として残す。
+
+use crate::diff_text;
+
+/// 本文をページごとに分ける。区切りが見つからなければ本文全体を1ページとする
+pub fn split(text: &str) -> Vec<String> {
+  if text.contains('\x0c') {
+    return text
+      .split('\x0c')
+      .filter(|page| !page.trim().is_empty())
+      .map(|page| page.to_string())
+      .collect();
+  }
+  let mut pages = Vec::new();
+  let mut page = String::new();
+  for line in text.lines() {
+    page.push_str(line);
+    page.push('\n');
+    if diff_text::page_number_line().is_match(line.trim()) {
+      pages.push(std::mem::take(&mut page));
+    }
+  }
+  if !page.trim().is_empty() {
+    pages.push(page);
+  }
+  pages
+}
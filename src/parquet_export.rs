@@ -0,0 +1,37 @@
+//! 既存の出力をParquetのデータセットにする`export-parquet`サブコマンド
+//!
+//! 一覧の列（[`crate::arrow_index`]と同じもの）に裁判例のJSONの事件名・要旨・本文などの列を加え、
+//! 裁判年月日の西暦の年ごとに`{dir}/year=2023/part-0.parquet`のようなHive形式のパーティションに分けて書き出す。
+//! pandas・Polars・Sparkでディレクトリごとそのまま読み込める。
+
+use crate::{arrow_index, compat, dataset, permissions};
+use anyhow::Result;
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+use std::{collections::BTreeMap, fs::File};
+use tracing::*;
+
+/// `index`の一覧と`output`の裁判例のJSONを、`dir`に年ごとに分けたParquetで書き出す。書き出した件数を返す
+pub async fn export(index: &str, output: &str, dir: &str) -> Result<usize> {
+  let rows = dataset::load_dataset(index, output).await?;
+  let mut by_year = BTreeMap::<usize, Vec<_>>::new();
+  for row in &rows {
+    let year = compat::era_to_ad_year(&row.info.date.era, row.info.date.year);
+    by_year.entry(year).or_default().push(row);
+  }
+  let props = WriterProperties::builder()
+    .set_compression(Compression::SNAPPY)
+    .build();
+  for (year, rows) in by_year {
+    let partition = format!("{dir}/year={year}");
+    tokio::fs::create_dir_all(&partition).await?;
+    let path = format!("{partition}/part-0.parquet");
+    let batch = arrow_index::to_dataset_batch(&rows)?;
+    let mut writer =
+      ArrowWriter::try_new(File::create(&path)?, batch.schema(), Some(props.clone()))?;
+    writer.write(&batch)?;
+    writer.close()?;
+    permissions::apply(&path).await?;
+    info!("parquet: {} ({} entries)", &path, rows.len());
+  }
+  Ok(rows.len())
+}
@@ -0,0 +1,53 @@
+//! `--partition-by`による、出力フォルダの裁判年（西暦）・裁判の種類ごとのサブフォルダへの振り分け
+//!
+//! 6万件を超えるファイルを1つのフォルダに置くと扱いにくいファイルシステムが多いので、
+//! `--partition-by year,trial_type`では裁判例ごとのファイルを`output/2021/SupremeCourt/…`のように置く。
+//! 裁判例のファイル名（`--filename-template`で変えられる）はサブフォルダを含めた出力フォルダからの相対パスとして扱い、一覧の各項目にも`path`として書く。
+
+use crate::compat;
+use clap::ValueEnum;
+use jplaw_data_types::listup::PrecedentInfo;
+use serde_json::Value;
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PartitionKey {
+  /// 裁判年月日の西暦の年
+  Year,
+  /// 裁判の種類（`SupremeCourt`など）
+  TrialType,
+}
+
+static PARTITION_BY: OnceLock<Vec<PartitionKey>> = OnceLock::new();
+
+/// 以降に書き出す裁判例のファイルの振り分け方を設定する。2回目以降の呼び出しでは何もしない
+pub fn install(keys: Vec<PartitionKey>) {
+  let _ = PARTITION_BY.set(keys);
+}
+
+/// サブフォルダに振り分けているかどうか
+pub fn is_enabled() -> bool {
+  PARTITION_BY.get().is_some_and(|keys| !keys.is_empty())
+}
+
+fn segment(key: PartitionKey, info: &PrecedentInfo) -> String {
+  match key {
+    PartitionKey::Year => compat::era_to_ad_year(&info.date.era, info.date.year).to_string(),
+    PartitionKey::TrialType => match serde_json::to_value(&info.trial_type) {
+      Ok(Value::String(s)) => s,
+      _ => format!("{:?}", info.trial_type),
+    },
+  }
+}
+
+/// 裁判例のファイルの、出力フォルダからの拡張子を除いた相対パス
+pub fn record_name(info: &PrecedentInfo) -> String {
+  let mut parts = PARTITION_BY
+    .get()
+    .into_iter()
+    .flatten()
+    .map(|key| segment(*key, info))
+    .collect::<Vec<_>>();
+  parts.push(crate::filename_template::file_name(info));
+  parts.join("/")
+}
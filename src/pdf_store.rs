@@ -0,0 +1,130 @@
+//! 判決文のPDFのSHA-256による記録と、内容のハッシュで名前を付けた保存
+//!
+//! ダウンロードしたPDFのSHA-256は常に`pdf_sha256`として裁判例のJSONに書く。
+//! 前回の出力と同じ`full_pdf_link`なのにハッシュが変わっていれば、裁判所がPDFを差し替えたものとして警告し、
+//! 前回のハッシュを`pdf_previous_sha256`に残す。
+//!
+//! `--save-pdf`で残すPDFは、`--pdf-layout hash`では`pdfs/ab/abcdef….pdf`のように内容のハッシュで名前を付ける。
+//! 同じ内容のPDFは1つのファイルにまとまり、`verify-pdfs`サブコマンドでファイルが壊れていないかを確かめられる。
+//! どちらの名前の付け方でも、保存したファイルの出力フォルダからの相対パスを`pdf_path`に書く。
+
+use crate::{output, response_cache::to_hex};
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::{
+  collections::HashSet,
+  path::Path,
+  sync::{Mutex, OnceLock},
+};
+use tracing::*;
+
+/// `--save-pdf`で残すPDFの名前の付け方
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PdfLayout {
+  /// 裁判例のファイルと同じ名前にする
+  Name,
+  /// 内容のSHA-256で名前を付け、同じ内容のPDFを1つにまとめる
+  Hash,
+}
+
+/// PDFを置く出力フォルダの中のフォルダ（`--pdf-layout hash`）
+const PDF_DIR: &str = "pdfs";
+
+/// この実行で保存したPDFのハッシュ。アーカイブなどに同じ内容を2回収めないために使う
+fn saved() -> &'static Mutex<HashSet<String>> {
+  static SAVED: OnceLock<Mutex<HashSet<String>>> = OnceLock::new();
+  SAVED.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+  to_hex(&Sha256::digest(bytes))
+}
+
+/// 出力フォルダからの、ハッシュで名前を付けたPDFの相対パス
+fn hash_path(hash: &str) -> String {
+  format!("{PDF_DIR}/{}/{hash}.pdf", &hash[..2])
+}
+
+/// PDFを保存し、出力フォルダからの相対パスを返す。`Hash`では同じ内容のPDFが既にあれば書き出さない
+pub async fn save(
+  output: &str,
+  layout: PdfLayout,
+  file_name: &str,
+  hash: &str,
+  bytes: &[u8],
+) -> Result<String> {
+  let path = match layout {
+    PdfLayout::Name => format!("{file_name}.pdf"),
+    PdfLayout::Hash => hash_path(hash),
+  };
+  if layout == PdfLayout::Hash {
+    let first = saved().lock().unwrap().insert(hash.to_string());
+    if !first || Path::new(&format!("{output}/{path}")).exists() {
+      debug!("pdf already stored: {path}");
+      return Ok(path);
+    }
+  }
+  output::write_side_file(output, &path, bytes.to_vec()).await?;
+  Ok(path)
+}
+
+/// 前回の出力の同じ判例とPDFのハッシュを比べ、差し替えられていれば`extra`に前回のハッシュを書く
+pub async fn check_replaced(
+  output: &str,
+  file_name: &str,
+  full_pdf_link: &str,
+  hash: &str,
+  extra: &mut Map<String, Value>,
+) {
+  if !output::data_exists(output, file_name) {
+    return;
+  }
+  let Ok(previous) = output::read_record_value(output, file_name).await else {
+    return;
+  };
+  let same_link = previous.get("full_pdf_link").and_then(Value::as_str) == Some(full_pdf_link);
+  match previous.get("pdf_sha256").and_then(Value::as_str) {
+    Some(previous_hash) if same_link && previous_hash != hash => {
+      warn!("pdf replaced: {full_pdf_link} ({previous_hash} -> {hash})");
+      extra.insert("pdf_previous_sha256".to_string(), previous_hash.into());
+    }
+    _ => {}
+  }
+}
+
+/// `index`の一覧の裁判例のうち`pdf_path`に保存したPDFを読み、`pdf_sha256`と内容が一致するか確かめる。確かめた件数を返す
+pub async fn verify(index: &str, output: &str) -> Result<usize> {
+  let mut checked = 0;
+  let mut broken = 0;
+  for entry in output::read_value_lst(index).await? {
+    let Some(record) = output::read_merged_record(entry, output).await? else {
+      continue;
+    };
+    let (Some(path), Some(expected)) = (
+      record.get("pdf_path").and_then(Value::as_str),
+      record.get("pdf_sha256").and_then(Value::as_str),
+    ) else {
+      continue;
+    };
+    checked += 1;
+    match tokio::fs::read(format!("{output}/{path}")).await {
+      Ok(bytes) if sha256_hex(&bytes) == expected => {}
+      Ok(_) => {
+        warn!("pdf checksum mismatch: {path}");
+        broken += 1;
+      }
+      Err(e) => {
+        warn!("pdf not readable: {path}: {e}");
+        broken += 1;
+      }
+    }
+  }
+  if broken > 0 {
+    return Err(anyhow!(
+      "{checked}件のうち{broken}件のPDFが見つからないか、SHA-256が一致しません"
+    ));
+  }
+  Ok(checked)
+}
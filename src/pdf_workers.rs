@@ -0,0 +1,109 @@
+//! ダウンロードしたPDFから本文を抽出する専用のワーカースレッド
+//!
+//! `--pdf-workers`本のスレッドが抽出待ちのPDFのキューから順に取り出して抽出する。
+//! 数百ページある判決文の抽出に時間がかかっても、他のワーカーが後ろのPDFの抽出を続け、
+//! 非同期のランタイムのスレッドも塞がないので、取得は止まらずに進む。
+
+use anyhow::{anyhow, Result};
+#[cfg(feature = "online")]
+use jplaw_pdf2text::{clean_up, pdf_bytes_to_text};
+use std::{
+  panic::{self, AssertUnwindSafe},
+  sync::{Arc, Mutex, OnceLock},
+  thread,
+};
+use tokio::sync::{mpsc, oneshot};
+use tracing::*;
+
+struct Job {
+  bytes: Vec<u8>,
+  reply: oneshot::Sender<Result<String>>,
+}
+
+static QUEUE: OnceLock<mpsc::Sender<Job>> = OnceLock::new();
+
+#[cfg(feature = "online")]
+fn extract(bytes: &[u8]) -> Result<String> {
+  let text = pdf_bytes_to_text(bytes)?;
+  Ok(clean_up(&text))
+}
+
+/// PDFを扱わないビルドでは、本文を取得できなかった判例として書き出す
+#[cfg(not(feature = "online"))]
+fn extract(_bytes: &[u8]) -> Result<String> {
+  Err(anyhow!(
+    "PDFからの本文の抽出はonline featureを有効にしたビルドでだけ使えます"
+  ))
+}
+
+/// 壊れたPDFで抽出の処理がpanicしても、ワーカーを終わらせずにその判例のエラーとして返す
+fn catch_panic(f: impl FnOnce() -> Result<String>) -> Result<String> {
+  panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+    let message = payload
+      .downcast_ref::<&str>()
+      .map(|s| s.to_string())
+      .or_else(|| payload.downcast_ref::<String>().cloned())
+      .unwrap_or_default();
+    Err(anyhow!("PDFからの本文の抽出が異常終了しました：{message}"))
+  })
+}
+
+/// `workers`本のワーカースレッドを起動する。2回目以降の呼び出しでは何もしない
+pub fn install(workers: usize) {
+  let workers = workers.max(1);
+  let (tx, rx) = mpsc::channel::<Job>(workers);
+  if QUEUE.set(tx).is_err() {
+    return;
+  }
+  let rx = Arc::new(Mutex::new(rx));
+  for i in 0..workers {
+    let rx = Arc::clone(&rx);
+    let spawned = thread::Builder::new()
+      .name(format!("pdf-worker-{i}"))
+      .spawn(move || loop {
+        let job = rx.lock().unwrap().blocking_recv();
+        let Some(job) = job else {
+          break;
+        };
+        // 受け取る側が中断などで先に終わっていても気にしない
+        let _ = job.reply.send(catch_panic(|| extract(&job.bytes)));
+      });
+    if let Err(e) = spawned {
+      warn!("failed to spawn pdf worker {i}: {e}");
+    }
+  }
+  info!("pdf workers: {workers}");
+}
+
+/// 既定のワーカー数（CPUのコア数）
+pub fn default_workers() -> usize {
+  thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// PDFから本文を抽出する。ワーカーが起動していなければブロッキング用のスレッドで抽出する
+pub async fn extract_text(bytes: Vec<u8>) -> Result<String> {
+  let Some(queue) = QUEUE.get() else {
+    return tokio::task::spawn_blocking(move || extract(&bytes)).await?;
+  };
+  let (reply, rx) = oneshot::channel();
+  queue
+    .send(Job { bytes, reply })
+    .await
+    .map_err(|_| anyhow!("PDFのワーカーが終了しています"))?;
+  rx.await
+    .map_err(|_| anyhow!("PDFのワーカーが抽出の途中で終了しました"))?
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn panic_becomes_error() {
+    let e = catch_panic(|| panic!("broken xref")).unwrap_err();
+    assert!(e.to_string().contains("broken xref"));
+    let e = catch_panic(|| panic!("{} pages", 3)).unwrap_err();
+    assert!(e.to_string().contains("3 pages"));
+    assert_eq!(catch_panic(|| Ok("本文".to_string())).unwrap(), "本文");
+  }
+}
@@ -0,0 +1,77 @@
+//! 共有サーバーで後続の処理を行うユーザーが読めるよう、出力ファイルのパーミッションとグループを設定する
+//!
+//! `--file-mode`・`--file-group`を与えると、裁判例のJSON・一覧ファイルなどを書き出すたびに設定する。
+//! どちらもUnix系のOSでだけ効き、それ以外では何もしない。
+
+use anyhow::{anyhow, Context, Result};
+use std::sync::OnceLock;
+
+#[derive(Debug, Clone, Copy)]
+struct FileOwnership {
+  mode: Option<u32>,
+  gid: Option<u32>,
+}
+
+static OWNERSHIP: OnceLock<FileOwnership> = OnceLock::new();
+
+/// `0644`のような8進数のパーミッションを読む
+pub fn parse_mode(s: &str) -> Result<u32> {
+  let mode = u32::from_str_radix(s.trim(), 8)
+    .map_err(|_| anyhow!("パーミッションは0644のような8進数で指定してください：{s}"))?;
+  if mode > 0o7777 {
+    return Err(anyhow!("パーミッションが範囲外です：{s}"));
+  }
+  Ok(mode)
+}
+
+/// グループ名かGIDをGIDにする。グループ名は`/etc/group`から引く
+fn resolve_gid(group: &str) -> Result<u32> {
+  if let Ok(gid) = group.parse::<u32>() {
+    return Ok(gid);
+  }
+  let groups = std::fs::read_to_string("/etc/group").context("/etc/groupを読めません")?;
+  groups
+    .lines()
+    .filter_map(|line| {
+      let mut fields = line.split(':');
+      let name = fields.next()?;
+      let gid = fields.nth(1)?.parse::<u32>().ok()?;
+      Some((name, gid))
+    })
+    .find(|(name, _)| *name == group)
+    .map(|(_, gid)| gid)
+    .ok_or_else(|| anyhow!("グループが見つかりません：{group}"))
+}
+
+/// 以降に書き出すファイルに設定するパーミッションとグループを登録する。2回目以降の呼び出しでは何もしない
+pub fn install(mode: Option<u32>, group: Option<&str>) -> Result<()> {
+  let gid = group.map(resolve_gid).transpose()?;
+  let _ = OWNERSHIP.set(FileOwnership { mode, gid });
+  Ok(())
+}
+
+/// 書き出したファイルに、登録されたパーミッションとグループを設定する
+pub async fn apply(path: &str) -> Result<()> {
+  let Some(ownership) = OWNERSHIP.get().copied() else {
+    return Ok(());
+  };
+  #[cfg(unix)]
+  {
+    use std::os::unix::fs::PermissionsExt;
+    if let Some(gid) = ownership.gid {
+      std::os::unix::fs::chown(path, None, Some(gid))
+        .with_context(|| format!("グループを変更できません：{path}"))?;
+    }
+    // グループを変えるとsetgidビットが落ちることがあるので、パーミッションは後から設定する
+    if let Some(mode) = ownership.mode {
+      tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .await
+        .with_context(|| format!("パーミッションを変更できません：{path}"))?;
+    }
+  }
+  #[cfg(not(unix))]
+  if ownership.mode.is_some() || ownership.gid.is_some() {
+    tracing::debug!("file mode and group are ignored on this platform: {path}");
+  }
+  Ok(())
+}
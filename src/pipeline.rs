@@ -0,0 +1,392 @@
+//! 一覧ページ → 詳細ページ → PDFのダウンロード → 本文の抽出 → 書き出しの各段階を別々に進めるパイプライン
+//!
+//! 段階の間は容量`--jobs`の`tokio::sync::mpsc`のチャネルでつなぐ。後ろの段階が詰まったら前の段階は送れずに待つので、
+//! 取得したまま書き出されていない判例がメモリに溜まり続けることはない。
+//! 詳細ページの段階では`--jobs`件、PDFの段階では`--pdf-jobs`件（省略時は`--jobs`）まで並行して取得し、
+//! 本文の抽出は`--pdf-workers`件まで並行して行う。
+//! リクエストの間隔は`Fetcher`がホストごとに制御するので、並行数を増やしてもサーバーへの負荷の上限は変わらない。
+//!
+//! チェックポイントには書き出した判例の`lawsuit_id`を集合として記録し、再開するときは集合に含まれる判例を飛ばす。
+//! 書き出す順が一覧ページの順と異なっても、集合で判定するので正しく再開できる。
+//!
+//! 期間検索の一覧ページは、前のページの判例を流している間に次のページを先読みする。
+
+use crate::{
+  checkpoint::{self, Checkpoint},
+  events::Events,
+  fetch::Fetcher,
+  link_stats::LinkTracker,
+  output::IndexWriter,
+  record::{self, Record},
+  retry_queue::RetryQueue,
+  shutdown, Args,
+};
+use anyhow::Result;
+use futures::StreamExt;
+use jplaw_data_types::law::Date;
+use serde_json::json;
+use std::ops::RangeInclusive;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+use tokio_stream::wrappers::ReceiverStream;
+use tracing::*;
+
+/// パイプラインに流す一覧ページ
+pub enum ListPages<'a> {
+  /// 取得済みの1ページ分の判例（リンクと`lawsuit_id`の組）
+  Fetched {
+    page_num: usize,
+    links: Vec<(String, String)>,
+  },
+  /// 期間検索の結果のページ
+  DateRange {
+    start_date: &'a Date,
+    end_date: &'a Date,
+    pages: RangeInclusive<usize>,
+    total_pages: usize,
+  },
+}
+
+/// 段階の間で受け渡すもの
+enum Item<T> {
+  Record {
+    page_num: usize,
+    detail_page_link: String,
+    lawsuit_id: String,
+    record: T,
+  },
+  /// そのページの判例をすべて送り終えた
+  PageDone(usize),
+  /// 中断が要求されたので、これより後の判例は取得しない
+  Interrupted {
+    page_num: usize,
+    detail_page_link: String,
+  },
+}
+
+/// 一覧ページの判例をパイプラインで取得して書き出す。中断した場合は`false`を返す
+///
+/// `resume`が与えられたら、そこに記録された処理済みの判例は飛ばし、続けて処理したものを記録していく
+pub async fn run(
+  args: &Args,
+  fetcher: &Fetcher,
+  events: &Events,
+  index_writer: &IndexWriter,
+  retry_queue: &RetryQueue,
+  pages: ListPages<'_>,
+  resume: Option<Checkpoint>,
+) -> Result<bool> {
+  let progress = resume.unwrap_or_else(|| crate::checkpoint_for(args));
+  // 処理済みの集合を持たない以前の形式のチェックポイントでは、最初のページの`last_lawsuit_id`までを飛ばす
+  let skip_until = progress
+    .processed
+    .is_empty()
+    .then(|| progress.last_lawsuit_id.clone())
+    .flatten();
+  let capacity = args.jobs.max(1);
+  let (link_tx, link_rx) = channel(capacity);
+  let (detail_tx, detail_rx) = channel(capacity);
+  let (pdf_tx, pdf_rx) = channel(capacity);
+  let (contents_tx, contents_rx) = channel(capacity);
+  let (_, _, _, _, completed) = tokio::try_join!(
+    list_stage(fetcher, events, pages, &progress, skip_until, link_tx),
+    detail_stage(args, fetcher, index_writer, link_rx, detail_tx),
+    download_stage(args, fetcher, detail_rx, pdf_tx),
+    extract_stage(args, pdf_rx, contents_tx),
+    write_stage(
+      args,
+      fetcher,
+      events,
+      index_writer,
+      retry_queue,
+      progress.clone(),
+      contents_rx
+    ),
+  )?;
+  Ok(completed)
+}
+
+/// 一覧ページを取得して、載っている判例のリンクを順に送る
+async fn list_stage(
+  fetcher: &Fetcher,
+  events: &Events,
+  pages: ListPages<'_>,
+  progress: &Checkpoint,
+  mut skip_until: Option<String>,
+  tx: Sender<Item<()>>,
+) -> Result<()> {
+  let mut tracker = LinkTracker::new();
+  let mut send_page = |page_num: usize, mut links: Vec<(String, String)>| {
+    tracker.observe(events, page_num, links.iter().map(|(_, id)| id.as_str()));
+    if let Some(last) = skip_until.take() {
+      if !checkpoint::skip_processed(&mut links, &last) {
+        warn!("lawsuit_id in the checkpoint not found on page {page_num}: {last}; fetching the whole page again");
+      }
+    }
+    let skipped = checkpoint::remove_processed(&mut links, &progress.processed);
+    if skipped > 0 {
+      info!("skip {skipped} processed record(s) on page {page_num}");
+    }
+    let tx = tx.clone();
+    async move {
+      for (detail_page_link, lawsuit_id) in links {
+        // 中断が要求されたあとは新しい判例を流さない
+        if shutdown::requested() {
+          let _ = tx
+            .send(Item::Interrupted {
+              page_num,
+              detail_page_link,
+            })
+            .await;
+          return false;
+        }
+        let item = Item::Record {
+          page_num,
+          detail_page_link,
+          lawsuit_id,
+          record: (),
+        };
+        if tx.send(item).await.is_err() {
+          return false;
+        }
+      }
+      tx.send(Item::PageDone(page_num)).await.is_ok()
+    }
+  };
+  match pages {
+    ListPages::Fetched { page_num, links } => {
+      send_page(page_num, links).await;
+    }
+    ListPages::DateRange {
+      start_date,
+      end_date,
+      pages,
+      total_pages,
+    } => {
+      let fetch_page = |page_num: usize| async move {
+        let links = crate::list_date_range_page(fetcher, start_date, end_date, page_num).await?;
+        Ok::<_, anyhow::Error>((page_num, links))
+      };
+      let mut pages = pages;
+      let mut next = match pages.next() {
+        Some(page_num) => Some(fetch_page(page_num).await?),
+        None => None,
+      };
+      while let Some((page_num, links)) = next.take() {
+        info!("page_num: {}", page_num);
+        events.emit(
+          "page_started",
+          json!({ "page": page_num, "total_pages": total_pages }),
+        );
+        // このページの判例を流している間に次のページを先に取得しておく。
+        // リクエストの間隔はFetcherが制御するので、先読みしてもリクエストの頻度の上限は変わらない
+        let prefetch = async {
+          match pages.next() {
+            Some(page_num) => fetch_page(page_num).await.map(Some),
+            None => Ok(None),
+          }
+        };
+        let (sent, prefetched) = tokio::join!(send_page(page_num, links), prefetch);
+        if !sent {
+          break;
+        }
+        next = prefetched?;
+      }
+    }
+  }
+  Ok(())
+}
+
+/// 詳細ページを`--jobs`件まで並行して取得し、受け取った順に送る
+async fn detail_stage(
+  args: &Args,
+  fetcher: &Fetcher,
+  index_writer: &IndexWriter,
+  rx: Receiver<Item<()>>,
+  tx: Sender<Item<Result<Record>>>,
+) -> Result<()> {
+  let mut details = ReceiverStream::new(rx)
+    .map(|item| async move {
+      match item {
+        Item::Record {
+          page_num,
+          detail_page_link,
+          lawsuit_id,
+          record: (),
+        } => {
+          // チャネルに残っていた判例も、中断が要求されたあとは取得を始めない
+          if shutdown::requested() {
+            return Item::Interrupted {
+              page_num,
+              detail_page_link,
+            };
+          }
+          info!("link: {}", &detail_page_link);
+          let record = record::fetch_detail(args, fetcher, index_writer, &detail_page_link).await;
+          Item::Record {
+            page_num,
+            detail_page_link,
+            lawsuit_id,
+            record,
+          }
+        }
+        Item::PageDone(page_num) => Item::PageDone(page_num),
+        Item::Interrupted {
+          page_num,
+          detail_page_link,
+        } => Item::Interrupted {
+          page_num,
+          detail_page_link,
+        },
+      }
+    })
+    .buffered(args.jobs.max(1));
+  while let Some(item) = details.next().await {
+    let interrupted = matches!(item, Item::Interrupted { .. });
+    if tx.send(item).await.is_err() || interrupted {
+      break;
+    }
+  }
+  Ok(())
+}
+
+/// PDFを`--jobs`件まで並行してダウンロードし、受け取った順に送る
+async fn download_stage(
+  args: &Args,
+  fetcher: &Fetcher,
+  rx: Receiver<Item<Result<Record>>>,
+  tx: Sender<Item<Result<Record>>>,
+) -> Result<()> {
+  let mut downloads = ReceiverStream::new(rx)
+    .map(|item| async move {
+      match item {
+        Item::Record {
+          page_num,
+          detail_page_link,
+          lawsuit_id,
+          record: Ok(mut record),
+        } => {
+          let record = record::download_contents(args, fetcher, &mut record)
+            .await
+            .map(|()| record);
+          Item::Record {
+            page_num,
+            detail_page_link,
+            lawsuit_id,
+            record,
+          }
+        }
+        item => item,
+      }
+    })
+    .buffered(args.pdf_jobs.unwrap_or(args.jobs).max(1));
+  while let Some(item) = downloads.next().await {
+    if tx.send(item).await.is_err() {
+      break;
+    }
+  }
+  Ok(())
+}
+
+/// ダウンロードしたPDFの本文を`--pdf-workers`件まで並行して抽出し、受け取った順に送る
+async fn extract_stage(
+  args: &Args,
+  rx: Receiver<Item<Result<Record>>>,
+  tx: Sender<Item<Result<Record>>>,
+) -> Result<()> {
+  let workers = args
+    .pdf_workers
+    .unwrap_or_else(crate::pdf_workers::default_workers)
+    .max(1);
+  let mut contents = ReceiverStream::new(rx)
+    .map(|item| async move {
+      match item {
+        Item::Record {
+          page_num,
+          detail_page_link,
+          lawsuit_id,
+          record: Ok(mut record),
+        } => {
+          let record = record::extract_contents(args, &mut record)
+            .await
+            .map(|()| record);
+          Item::Record {
+            page_num,
+            detail_page_link,
+            lawsuit_id,
+            record,
+          }
+        }
+        item => item,
+      }
+    })
+    .buffered(workers);
+  while let Some(item) = contents.next().await {
+    if tx.send(item).await.is_err() {
+      break;
+    }
+  }
+  Ok(())
+}
+
+/// 判例を受け取った順に書き出し、処理した`lawsuit_id`をチェックポイントに加えて保存する。中断した場合は`false`を返す
+async fn write_stage(
+  args: &Args,
+  fetcher: &Fetcher,
+  events: &Events,
+  index_writer: &IndexWriter,
+  retry_queue: &RetryQueue,
+  mut progress: Checkpoint,
+  mut rx: Receiver<Item<Result<Record>>>,
+) -> Result<bool> {
+  let checkpoint_path = crate::checkpoint_path(args);
+  while let Some(item) = rx.recv().await {
+    match item {
+      Item::Record {
+        page_num,
+        detail_page_link,
+        lawsuit_id,
+        record,
+      } => {
+        let result = match record {
+          Ok(record) => record::write_record(args, fetcher, index_writer, record).await,
+          Err(e) => Err(e),
+        };
+        record::report(
+          args,
+          events,
+          retry_queue,
+          &detail_page_link,
+          Some(page_num),
+          result,
+        )
+        .await?;
+        progress.mark_processed(page_num, &lawsuit_id);
+        progress.save(&checkpoint_path).await?;
+      }
+      Item::PageDone(page_num) => {
+        progress.mark_page_done(page_num);
+        progress.save(&checkpoint_path).await?;
+        if let Some(cache) = fetcher.conditional_cache() {
+          cache.save().await?;
+        }
+        // 負荷を抑えるためのsleepはFetcherが各リクエストの前に行う
+        info!("current sleep time: {}ms", fetcher.current_delay_millis());
+      }
+      Item::Interrupted {
+        page_num,
+        detail_page_link,
+      } => {
+        warn!(
+          "interrupted at page {} (next: {}); re-run with --resume to continue",
+          page_num, &detail_page_link
+        );
+        events.emit(
+          "interrupted",
+          json!({ "page": page_num, "next_link": &detail_page_link }),
+        );
+        return Ok(false);
+      }
+    }
+  }
+  Ok(true)
+}
@@ -0,0 +1,174 @@
+//! 設定ファイルで宣言した後処理のステップを、書き出す前の各判例に順に適用する
+//!
+//! `--postprocess pipeline.json`で次のようなファイルを与える。ステップは書いた順に適用され、
+//! 正規化→構造化→分類→出力の整形のような組み合わせを利用者ごとに選べる。
+//!
+//! ```json
+//! {
+//!   "steps": [
+//!     { "step": "replace", "field": "contents", "pattern": "[ 　]+", "replacement": " " },
+//!     { "step": "furigana", "mode": "remove" },
+//!     { "step": "pages" },
+//!     { "step": "classify", "field": "case_name", "target": "category",
+//!       "rules": [{ "pattern": "損害賠償", "label": "damages" }], "default": "other" },
+//!     { "step": "remove", "fields": ["contents_hiragana"] }
+//!   ]
+//! }
+//! ```
+//!
+//! 各ステップは判例のデータと`uuid`などの付け加える項目を合わせた1つのオブジェクトに対して働く。
+//! 文字列でない値や存在しない項目は対象にしない。
+
+use crate::{
+  furigana::{self, FuriganaMode},
+  orthography, pages,
+};
+use anyhow::{anyhow, Context, Result};
+use jplaw_data_types::listup::PrecedentData;
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+use serde_json::{Map, Value};
+use std::sync::OnceLock;
+
+fn de_regex<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Regex, D::Error> {
+  let pattern = String::deserialize(deserializer)?;
+  Regex::new(&pattern).map_err(serde::de::Error::custom)
+}
+
+/// 分類の規則。`pattern`にマッチしたら`label`を付ける
+#[derive(Debug, Clone, Deserialize)]
+pub struct Rule {
+  #[serde(deserialize_with = "de_regex")]
+  pattern: Regex,
+  label: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Step {
+  /// `field`の`pattern`にマッチする部分を`replacement`に置き換える
+  Replace {
+    field: String,
+    #[serde(deserialize_with = "de_regex")]
+    pattern: Regex,
+    replacement: String,
+  },
+  /// 本文の括弧書きの読み仮名を取り除く。`extract`では取り除いたものを`readings`に書き出す
+  Furigana { mode: FuriganaMode },
+  /// 本文をページごとに分けた配列を`pages`に書き出す
+  Pages,
+  /// カタカナ表記の判例の本文をひらがなにしたものを`contents_hiragana`に書き出す
+  Hiragana,
+  /// `field`に最初にマッチした規則のラベルを`target`に書き出す。どれにもマッチしなければ`default`
+  Classify {
+    field: String,
+    target: String,
+    rules: Vec<Rule>,
+    #[serde(default)]
+    default: Option<String>,
+  },
+  /// 書き出さない項目。判例のデータの必須の項目は取り除けない
+  Remove { fields: Vec<String> },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Pipeline {
+  steps: Vec<Step>,
+}
+
+static PIPELINE: OnceLock<Pipeline> = OnceLock::new();
+
+/// `path`の設定ファイルを読んで、以降に書き出す判例に適用する後処理として登録する
+pub async fn install(path: &str) -> Result<()> {
+  let s = tokio::fs::read_to_string(path)
+    .await
+    .with_context(|| format!("後処理の設定ファイルを読めません：{path}"))?;
+  let pipeline: Pipeline = serde_json::from_str(&s)
+    .with_context(|| format!("後処理の設定ファイルが正しくありません：{path}"))?;
+  let _ = PIPELINE.set(pipeline);
+  Ok(())
+}
+
+fn str_field<'a>(record: &'a Map<String, Value>, field: &str) -> Option<&'a str> {
+  record.get(field).and_then(|v| v.as_str())
+}
+
+fn apply_step(step: &Step, record: &mut Map<String, Value>) -> Result<()> {
+  match step {
+    Step::Replace {
+      field,
+      pattern,
+      replacement,
+    } => {
+      if let Some(text) = str_field(record, field) {
+        let replaced = pattern.replace_all(text, replacement.as_str()).into_owned();
+        record.insert(field.clone(), replaced.into());
+      }
+    }
+    Step::Furigana { mode } => {
+      if let Some(text) = str_field(record, "contents") {
+        let (text, readings) = furigana::normalize(text, *mode);
+        record.insert("contents".to_string(), text.into());
+        if *mode == FuriganaMode::Extract {
+          record.insert("readings".to_string(), serde_json::to_value(readings)?);
+        }
+      }
+    }
+    Step::Pages => {
+      if let Some(text) = str_field(record, "contents") {
+        let pages = pages::split(text);
+        record.insert("pages".to_string(), serde_json::to_value(pages)?);
+      }
+    }
+    Step::Hiragana => {
+      if let Some(text) = str_field(record, "contents") {
+        if orthography::detect(text) == orthography::Orthography::Katakana {
+          let hiragana = orthography::to_hiragana(text);
+          record.insert("contents_hiragana".to_string(), hiragana.into());
+        }
+      }
+    }
+    Step::Classify {
+      field,
+      target,
+      rules,
+      default,
+    } => {
+      let label = str_field(record, field)
+        .and_then(|text| rules.iter().find(|rule| rule.pattern.is_match(text)))
+        .map(|rule| rule.label.clone())
+        .or_else(|| default.clone());
+      if let Some(label) = label {
+        record.insert(target.clone(), label.into());
+      }
+    }
+    Step::Remove { fields } => {
+      for field in fields {
+        record.remove(field);
+      }
+    }
+  }
+  Ok(())
+}
+
+/// 登録された後処理を判例のデータと付け加える項目に適用する。登録されていなければ何もしない
+pub fn apply(data: &mut PrecedentData, extra: &mut Map<String, Value>) -> Result<()> {
+  let Some(pipeline) = PIPELINE.get() else {
+    return Ok(());
+  };
+  let Value::Object(mut record) = serde_json::to_value(&*data)? else {
+    return Err(anyhow!("判例のデータがオブジェクトになりませんでした"));
+  };
+  let data_keys = record.keys().cloned().collect::<Vec<_>>();
+  record.extend(std::mem::take(extra));
+  for step in &pipeline.steps {
+    apply_step(step, &mut record)?;
+  }
+  let (data_fields, extra_fields): (Map<String, Value>, Map<String, Value>) = record
+    .into_iter()
+    .partition(|(key, _)| data_keys.contains(key));
+  *data = serde_json::from_value(Value::Object(data_fields))
+    .context("後処理で判例のデータの必須の項目が取り除かれたか、型が変わりました")?;
+  *extra = extra_fields;
+  Ok(())
+}
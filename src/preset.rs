@@ -0,0 +1,90 @@
+//! よく使う取得条件に名前を付けて保存しておき、`--preset`で呼び出すための仕組み
+//!
+//! プリセットファイルは名前から取得条件への対応を持つJSONで、リポジトリに置いてチームで共有できる。
+//!
+//! ```json
+//! {
+//!   "ip-cases-2020s": { "start": "2020/01/01", "end": "2029/12/31" },
+//!   "daily": { "recent": true }
+//! }
+//! ```
+
+use crate::Args;
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::Path};
+use tokio::fs;
+
+/// 名前を付けて保存する取得条件
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Preset {
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub start: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub end: Option<String>,
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub recent: bool,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub since_id: Option<u64>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub until_id: Option<u64>,
+}
+
+impl Preset {
+  /// コマンドラインで指定された取得条件
+  pub fn of(args: &Args) -> Self {
+    Preset {
+      start: args.start.clone(),
+      end: args.end.clone(),
+      recent: args.recent,
+      since_id: args.since_id,
+      until_id: args.until_id,
+    }
+  }
+
+  /// 取得条件を`args`に設定する
+  pub fn apply(&self, args: &mut Args) {
+    args.start = self.start.clone();
+    args.end = self.end.clone();
+    args.recent = self.recent;
+    args.since_id = self.since_id;
+    args.until_id = self.until_id;
+  }
+
+  fn is_empty(&self) -> bool {
+    *self == Preset::default()
+  }
+}
+
+async fn load_all(path: &str) -> Result<BTreeMap<String, Preset>> {
+  if !Path::new(path).exists() {
+    return Ok(BTreeMap::new());
+  }
+  let s = fs::read_to_string(path).await?;
+  serde_json::from_str(&s)
+    .map_err(|e| anyhow!("プリセットファイル{path}を読み込めませんでした：{e}"))
+}
+
+/// `path`のプリセットファイルから`name`のプリセットを読み込む
+pub async fn load(path: &str, name: &str) -> Result<Preset> {
+  let mut presets = load_all(path).await?;
+  presets.remove(name).ok_or_else(|| {
+    anyhow!(
+      "プリセット{name}が{path}にありません（保存されているもの：{}）",
+      presets.keys().cloned().collect::<Vec<_>>().join(", ")
+    )
+  })
+}
+
+/// `path`のプリセットファイルに`name`のプリセットを保存する。同じ名前のものがあれば置き換える
+pub async fn save(path: &str, name: &str, preset: Preset) -> Result<()> {
+  if preset.is_empty() {
+    return Err(anyhow!(
+      "保存する取得条件（--start・--end・--recent・--since-id・--until-id）が指定されていません"
+    ));
+  }
+  let mut presets = load_all(path).await?;
+  presets.insert(name.to_string(), preset);
+  fs::write(path, serde_json::to_string_pretty(&presets)?).await?;
+  Ok(())
+}
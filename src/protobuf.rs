@@ -0,0 +1,377 @@
+//! `--format protobuf`で裁判例ごとのファイルをProtocol Buffersで書き出す
+//!
+//! 形式は`proto/precedent.proto`の`Precedent`メッセージで、`schema --target proto`で出力できる。
+//! `PrecedentData`のフィールドはそれぞれ型の付いたフィールドにし、それ以外の`uuid`・`_meta`などは
+//! `extra_json`にJSONのオブジェクトとしてまとめるので、読み戻せば元のJSONと同じ内容になる。
+
+use anyhow::{anyhow, Result};
+use prost::Message;
+use serde_json::{json, Map, Value};
+
+/// `proto/precedent.proto`の内容
+pub const PROTO: &str = include_str!("../proto/precedent.proto");
+
+/// `proto/precedent.proto`の`Date`。`era`は[`ERAS`]のいずれかで、それ以外は書き出しでも読み込みでもエラーにする
+#[derive(Clone, PartialEq, Message)]
+pub struct Date {
+  #[prost(string, tag = "1")]
+  pub era: String,
+  #[prost(uint32, tag = "2")]
+  pub year: u32,
+  #[prost(uint32, optional, tag = "3")]
+  pub month: Option<u32>,
+  #[prost(uint32, optional, tag = "4")]
+  pub day: Option<u32>,
+}
+
+/// `proto/precedent.proto`の`Precedent`。`.proto`とタグ・型が食い違わないことはテストで確かめる
+#[derive(Clone, PartialEq, Message)]
+pub struct Precedent {
+  #[prost(string, tag = "1")]
+  pub trial_type: String,
+  #[prost(message, optional, tag = "2")]
+  pub date: Option<Date>,
+  #[prost(string, tag = "3")]
+  pub case_number: String,
+  #[prost(string, tag = "4")]
+  pub case_name: String,
+  #[prost(string, tag = "5")]
+  pub court_name: String,
+  #[prost(string, optional, tag = "6")]
+  pub right_type: Option<String>,
+  #[prost(string, optional, tag = "7")]
+  pub lawsuit_type: Option<String>,
+  #[prost(string, optional, tag = "8")]
+  pub result_type: Option<String>,
+  #[prost(string, optional, tag = "9")]
+  pub result: Option<String>,
+  #[prost(string, optional, tag = "10")]
+  pub article_info: Option<String>,
+  #[prost(string, optional, tag = "11")]
+  pub original_court_name: Option<String>,
+  #[prost(string, optional, tag = "12")]
+  pub original_case_number: Option<String>,
+  #[prost(string, optional, tag = "13")]
+  pub original_result: Option<String>,
+  #[prost(message, optional, tag = "14")]
+  pub original_date: Option<Date>,
+  #[prost(string, optional, tag = "15")]
+  pub field: Option<String>,
+  #[prost(string, optional, tag = "16")]
+  pub gist: Option<String>,
+  #[prost(string, optional, tag = "17")]
+  pub case_gist: Option<String>,
+  #[prost(string, optional, tag = "18")]
+  pub ref_law: Option<String>,
+  #[prost(string, tag = "19")]
+  pub lawsuit_id: String,
+  #[prost(string, tag = "20")]
+  pub detail_page_link: String,
+  #[prost(string, optional, tag = "21")]
+  pub contents: Option<String>,
+  #[prost(string, tag = "22")]
+  pub full_pdf_link: String,
+  #[prost(string, tag = "23")]
+  pub extra_json: String,
+}
+
+/// `Date.era`に書ける元号。裁判所のホームページの裁判例はこの3つのいずれかになる
+const ERAS: [&str; 3] = ["Showa", "Heisei", "Reiwa"];
+
+fn check_era(era: &str) -> Result<()> {
+  if ERAS.contains(&era) {
+    Ok(())
+  } else {
+    Err(anyhow!("扱えない元号です：{era}"))
+  }
+}
+
+/// 文字列のフィールドを取り出す。文字列でない値はJSONとして文字列にする
+fn take_opt_str(obj: &mut Map<String, Value>, key: &str) -> Option<String> {
+  match obj.remove(key)? {
+    Value::Null => None,
+    Value::String(s) => Some(s),
+    v => Some(v.to_string()),
+  }
+}
+
+fn take_str(obj: &mut Map<String, Value>, key: &str) -> String {
+  take_opt_str(obj, key).unwrap_or_default()
+}
+
+fn take_date(obj: &mut Map<String, Value>, key: &str) -> Result<Option<Date>> {
+  let Some(date) = obj.remove(key).filter(|v| !v.is_null()) else {
+    return Ok(None);
+  };
+  let number = |k: &str| date.get(k).and_then(Value::as_u64).map(|n| n as u32);
+  let era = date
+    .get("era")
+    .and_then(Value::as_str)
+    .ok_or_else(|| anyhow!("{key}に元号がありません：{date}"))?;
+  check_era(era)?;
+  Ok(Some(Date {
+    era: era.to_string(),
+    year: number("year").ok_or_else(|| anyhow!("{key}に年がありません：{date}"))?,
+    month: number("month"),
+    day: number("day"),
+  }))
+}
+
+fn date_value(date: Option<Date>) -> Result<Value> {
+  let Some(date) = date else {
+    return Ok(Value::Null);
+  };
+  check_era(&date.era)?;
+  Ok(json!({
+    "era": date.era,
+    "year": date.year,
+    "month": date.month,
+    "day": date.day,
+  }))
+}
+
+/// 裁判例のJSONを`Precedent`メッセージにする
+pub fn encode(value: &Value) -> Result<Vec<u8>> {
+  let mut obj = value
+    .as_object()
+    .cloned()
+    .ok_or_else(|| anyhow!("裁判例のJSONがオブジェクトではありません"))?;
+  let mut message = Precedent {
+    trial_type: take_str(&mut obj, "trial_type"),
+    date: take_date(&mut obj, "date")?,
+    case_number: take_str(&mut obj, "case_number"),
+    case_name: take_str(&mut obj, "case_name"),
+    court_name: take_str(&mut obj, "court_name"),
+    right_type: take_opt_str(&mut obj, "right_type"),
+    lawsuit_type: take_opt_str(&mut obj, "lawsuit_type"),
+    result_type: take_opt_str(&mut obj, "result_type"),
+    result: take_opt_str(&mut obj, "result"),
+    article_info: take_opt_str(&mut obj, "article_info"),
+    original_court_name: take_opt_str(&mut obj, "original_court_name"),
+    original_case_number: take_opt_str(&mut obj, "original_case_number"),
+    original_result: take_opt_str(&mut obj, "original_result"),
+    original_date: take_date(&mut obj, "original_date")?,
+    field: take_opt_str(&mut obj, "field"),
+    gist: take_opt_str(&mut obj, "gist"),
+    case_gist: take_opt_str(&mut obj, "case_gist"),
+    ref_law: take_opt_str(&mut obj, "ref_law"),
+    lawsuit_id: take_str(&mut obj, "lawsuit_id"),
+    detail_page_link: take_str(&mut obj, "detail_page_link"),
+    contents: take_opt_str(&mut obj, "contents"),
+    full_pdf_link: take_str(&mut obj, "full_pdf_link"),
+    extra_json: String::new(),
+  };
+  if !obj.is_empty() {
+    message.extra_json = Value::Object(obj).to_string();
+  }
+  Ok(message.encode_to_vec())
+}
+
+/// `Precedent`メッセージを裁判例のJSONに戻す
+pub fn decode(bytes: &[u8]) -> Result<Value> {
+  let message = Precedent::decode(bytes)?;
+  let mut obj: Map<String, Value> = if message.extra_json.is_empty() {
+    Map::new()
+  } else {
+    serde_json::from_str(&message.extra_json)?
+  };
+  let fields = [
+    ("trial_type", Value::from(message.trial_type)),
+    ("date", date_value(message.date)?),
+    ("case_number", message.case_number.into()),
+    ("case_name", message.case_name.into()),
+    ("court_name", message.court_name.into()),
+    ("right_type", message.right_type.into()),
+    ("lawsuit_type", message.lawsuit_type.into()),
+    ("result_type", message.result_type.into()),
+    ("result", message.result.into()),
+    ("article_info", message.article_info.into()),
+    ("original_court_name", message.original_court_name.into()),
+    ("original_case_number", message.original_case_number.into()),
+    ("original_result", message.original_result.into()),
+    ("original_date", date_value(message.original_date)?),
+    ("field", message.field.into()),
+    ("gist", message.gist.into()),
+    ("case_gist", message.case_gist.into()),
+    ("ref_law", message.ref_law.into()),
+    ("lawsuit_id", message.lawsuit_id.into()),
+    ("detail_page_link", message.detail_page_link.into()),
+    ("contents", message.contents.into()),
+    ("full_pdf_link", message.full_pdf_link.into()),
+  ];
+  for (key, value) in fields {
+    obj.insert(key.to_string(), value);
+  }
+  Ok(Value::Object(obj))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use regex::Regex;
+  use std::collections::BTreeMap;
+
+  /// `.proto`の`message`ごとの、フィールド名から（型、`optional`か、タグ）
+  fn proto_fields() -> BTreeMap<String, BTreeMap<String, (String, bool, u32)>> {
+    let message_re = Regex::new(r"^message (\w+) \{$").unwrap();
+    let field_re = Regex::new(r"^(optional )?(\w+) (\w+) = (\d+);$").unwrap();
+    let mut messages = BTreeMap::new();
+    let mut current = None;
+    for line in PROTO.lines().map(str::trim) {
+      if let Some(caps) = message_re.captures(line) {
+        current = Some(caps[1].to_string());
+      } else if line == "}" {
+        current = None;
+      } else if let (Some(message), Some(caps)) = (&current, field_re.captures(line)) {
+        messages
+          .entry(message.clone())
+          .or_insert_with(BTreeMap::new)
+          .insert(
+            caps[3].to_string(),
+            (
+              caps[2].to_string(),
+              caps.get(1).is_some(),
+              caps[4].parse().unwrap(),
+            ),
+          );
+      }
+    }
+    messages
+  }
+
+  fn read_varint(bytes: &[u8], pos: &mut usize) -> u64 {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+      let b = bytes[*pos];
+      *pos += 1;
+      value |= u64::from(b & 0x7f) << shift;
+      if b & 0x80 == 0 {
+        return value;
+      }
+      shift += 7;
+    }
+  }
+
+  /// エンコードされたメッセージの最上位のフィールドの（タグ、ワイヤ型）
+  fn wire_keys(bytes: &[u8]) -> Vec<(u32, u64)> {
+    let mut keys = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+      let key = read_varint(bytes, &mut pos);
+      let wire_type = key & 7;
+      match wire_type {
+        0 => {
+          read_varint(bytes, &mut pos);
+        }
+        2 => {
+          let len = read_varint(bytes, &mut pos) as usize;
+          pos += len;
+        }
+        _ => panic!("想定していないワイヤ型：{wire_type}"),
+      }
+      keys.push(((key >> 3) as u32, wire_type));
+    }
+    keys
+  }
+
+  fn wire_type_of(ty: &str) -> u64 {
+    match ty {
+      "uint32" => 0,
+      _ => 2,
+    }
+  }
+
+  /// すべてのフィールドに値があり、`uuid`・`_meta`も付いた裁判例のJSON
+  fn sample_value() -> Value {
+    json!({
+      "trial_type": "SupremeCourt",
+      "date": { "era": "Reiwa", "year": 4, "month": 3, "day": 24 },
+      "case_number": "令和3(受)9999",
+      "case_name": "損害賠償請求事件",
+      "court_name": "最高裁判所第一小法廷",
+      "right_type": "民事",
+      "lawsuit_type": "通常訴訟",
+      "result_type": "判決",
+      "result": "棄却",
+      "article_info": "民集　第76巻3号999頁",
+      "original_court_name": "東京高等裁判所",
+      "original_case_number": "令和2(ネ)9999",
+      "original_result": "棄却",
+      "original_date": { "era": "Heisei", "year": 31, "month": 4, "day": null },
+      "field": "民事",
+      "gist": "甲が乙に対して負う信義則上の説明義務の範囲",
+      "case_gist": "甲は，乙に対して重要な事項を説明すべき信義則上の義務を負う。",
+      "ref_law": "民法1条2項",
+      "lawsuit_id": "99999",
+      "detail_page_link": "/app/hanrei_jp/detail2?id=99999",
+      "contents": "主文\n本件上告を棄却する。",
+      "full_pdf_link": "https://www.courts.go.jp/app/files/hanrei_jp/999/099999_hanrei.pdf",
+      "uuid": "00000000-0000-5000-8000-000000000000",
+      "_meta": { "schema_version": 1, "retries": 0 },
+    })
+  }
+
+  #[test]
+  fn precedent_matches_proto() {
+    let fields = &proto_fields()["Precedent"];
+    let value = sample_value();
+    let obj = value.as_object().unwrap();
+    let mut keys = wire_keys(&encode(&value).unwrap());
+    keys.sort();
+    let mut expected = fields
+      .values()
+      .map(|(ty, _, tag)| (*tag, wire_type_of(ty)))
+      .collect::<Vec<_>>();
+    expected.sort();
+    assert_eq!(keys, expected);
+
+    for (name, (ty, optional, tag)) in fields {
+      if name == "extra_json" {
+        continue;
+      }
+      let field = obj
+        .get(name)
+        .unwrap_or_else(|| panic!("{name}がJSONにありません"));
+      let bytes = encode(&json!({ name.as_str(): field })).unwrap();
+      assert_eq!(wire_keys(&bytes), vec![(*tag, wire_type_of(ty))], "{name}");
+      if ty == "string" {
+        // `optional`のフィールドだけが空文字列を書き出す
+        let bytes = encode(&json!({ name.as_str(): "" })).unwrap();
+        assert_eq!(!bytes.is_empty(), *optional, "{name}");
+      }
+    }
+  }
+
+  #[test]
+  fn date_matches_proto() {
+    let fields = &proto_fields()["Date"];
+    let date = Date {
+      era: "Reiwa".to_string(),
+      year: 4,
+      month: Some(3),
+      day: Some(24),
+    };
+    let mut keys = wire_keys(&date.encode_to_vec());
+    keys.sort();
+    let mut expected = fields
+      .values()
+      .map(|(ty, _, tag)| (*tag, wire_type_of(ty)))
+      .collect::<Vec<_>>();
+    expected.sort();
+    assert_eq!(keys, expected);
+  }
+
+  #[test]
+  fn round_trip_restores_json() {
+    let value = sample_value();
+    assert_eq!(decode(&encode(&value).unwrap()).unwrap(), value);
+  }
+
+  #[test]
+  fn rejects_unknown_era() {
+    let mut value = sample_value();
+    value["date"]["era"] = json!("Meiji");
+    assert!(encode(&value).is_err());
+  }
+}
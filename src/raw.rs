@@ -0,0 +1,134 @@
+//! 取得と解析を分けて行う`fetch-raw`・`parse-raw`サブコマンド
+//!
+//! `fetch-raw`は一覧ページ・詳細ページ・判決文のPDFを取得して`--cache-dir`にそのまま保存するだけで、
+//! PDFからの本文の抽出や裁判例のファイルの書き出しはしない。取得した判例のリンクは`--links`のファイルに一覧にする。
+//! `parse-raw`は`--links`の判例を`--cache-dir`に保存したものだけから解析し、通常の実行と同じく出力フォルダと一覧ファイルに書き出す。
+//! サーバーにはリクエストを送らないので、パーサーを直したあとに何度でもやり直せる。
+
+use crate::{
+  events::Events, fetch, list_only, lock::OutputLock, output, output::IndexWriter,
+  retry_queue::RetryQueue, shutdown, Args,
+};
+use anyhow::{anyhow, Result};
+use serde_json::json;
+use tracing::*;
+
+fn require_cache_dir(args: &Args) -> Result<()> {
+  if args.cache_dir.is_none() {
+    return Err(anyhow!(
+      "fetch-raw・parse-rawには--cache-dirを指定してください"
+    ));
+  }
+  Ok(())
+}
+
+/// `links`の一覧ファイルから詳細ページのリンクを読む
+async fn read_links(links: &str) -> Result<Vec<String>> {
+  let entries = output::read_value_lst(links).await?;
+  Ok(
+    entries
+      .iter()
+      .filter_map(|e| e.get("detail_page_link").and_then(|l| l.as_str()))
+      .map(|l| l.to_string())
+      .collect(),
+  )
+}
+
+/// 詳細ページと判決文のPDFを取得してキャッシュに保存する
+async fn fetch_one(args: &Args, fetcher: &fetch::Fetcher, detail_page_link: &str) -> Result<()> {
+  let html = fetcher.get_text(detail_page_link).await?;
+  let data = crate::parse_detail_page(
+    &html,
+    crate::trial_type_from_link(detail_page_link)?,
+    crate::get_lawsuit_id(detail_page_link).await?,
+    detail_page_link.to_string(),
+  )
+  .await?;
+  crate::download_pdf(fetcher, &data.full_pdf_link, false, args.pdf_fallback).await?;
+  Ok(())
+}
+
+/// 一覧ページから判例のリンクを`links`に書き出し、それぞれの詳細ページとPDFをキャッシュに保存する
+pub async fn fetch_raw(args: &Args, events: &Events, links: &str) -> Result<()> {
+  require_cache_dir(args)?;
+  let fetcher = crate::build_fetcher(args).await?;
+  let list_args = Args {
+    index: links.to_string(),
+    ..args.clone()
+  };
+  list_only::crawl(&list_args, &fetcher, events).await?;
+  let links = read_links(links).await?;
+  info!("[START] fetch raw: {} records", links.len());
+  let mut failed = 0;
+  for (i, link) in links.iter().enumerate() {
+    if shutdown::requested() {
+      warn!("interrupted at {}/{}", i, links.len());
+      events.emit("interrupted", json!({ "next_link": link }));
+      break;
+    }
+    match fetch_one(args, &fetcher, link).await {
+      Ok(()) => info!("fetched: {link}"),
+      Err(e) if fetch::is_circuit_open(&e) => return Err(e),
+      Err(e) => {
+        failed += 1;
+        warn!("failed to fetch raw: {link}: {e:#}");
+        events.emit(
+          "error",
+          json!({ "message": format!("{e:#}"), "fatal": false, "detail_page_link": link }),
+        );
+      }
+    }
+  }
+  info!(
+    "[END] fetch raw: {} records ({} failed)",
+    links.len(),
+    failed
+  );
+  Ok(())
+}
+
+/// `links`の判例を、キャッシュに保存したものだけから解析して書き出す
+pub async fn parse_raw(args: &Args, events: &Events, links: &str) -> Result<()> {
+  require_cache_dir(args)?;
+  let _lock = OutputLock::acquire(&args.output)?;
+  // リクエストを送らないのでrobots.txtも取得しない
+  let mut fetcher = crate::build_fetcher(&Args {
+    ignore_robots: true,
+    ..args.clone()
+  })
+  .await?;
+  fetcher.set_cache_only();
+  let links = read_links(links).await?;
+  let index_writer = IndexWriter::open(
+    &args.index,
+    args.index_format,
+    args.compat,
+    args.compat_index.as_deref(),
+    false,
+  )
+  .await?;
+  let retry_queue = RetryQueue::new(&args.failed_queue);
+  events.emit("run_started", json!({ "parse_raw": links.len() }));
+  info!("[START] parse raw: {} records", links.len());
+  for link in &links {
+    if shutdown::requested() {
+      warn!("interrupted before {link}");
+      events.emit("interrupted", json!({ "next_link": link }));
+      break;
+    }
+    crate::record::process_record_or_queue(
+      args,
+      &fetcher,
+      events,
+      &index_writer,
+      &retry_queue,
+      link,
+      None,
+    )
+    .await?;
+  }
+  index_writer.flush().await?;
+  crate::write_index_exports(args).await?;
+  info!("[END] parse raw");
+  Ok(())
+}
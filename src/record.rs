@@ -0,0 +1,596 @@
+//! 1件の判例を取得して書き出す処理
+//!
+//! 詳細ページの取得と解析（[`fetch_detail`]）、PDFのダウンロード（[`download_contents`]）、
+//! 本文の抽出（[`extract_contents`]）、書き出し（[`write_record`]）の段階に分かれている。
+//! 期間検索と「最近の裁判例」の取得では、[`crate::pipeline`]がそれぞれの段階を別々に進める。
+
+use crate::{
+  case_mark, diff_text, en_summary,
+  events::Events,
+  exclude::{self, ExcludeAction},
+  fetch::{self, Fetcher, Unavailable},
+  furigana::{self, FuriganaMode},
+  holding, issue_draft,
+  meta::{self, RecordMeta},
+  orthography,
+  output::{self, IndexWriter, OverwritePolicy},
+  pages, partition, pdf_store, postprocess, ref_law,
+  retry_queue::{FailedRecord, FailureKind, OnError, RetryQueue},
+  stable_id,
+  summary::ParseFailure,
+  warnings, Args,
+};
+use anyhow::Result;
+use jplaw_data_types::listup::{PrecedentData, PrecedentInfo};
+use serde_json::{json, Map, Value};
+use std::time::Instant;
+use tracing::*;
+
+pub fn precedent_info_of(data: &PrecedentData) -> PrecedentInfo {
+  PrecedentInfo {
+    case_number: data.case_number.clone(),
+    court_name: data.court_name.clone(),
+    trial_type: data.trial_type.clone(),
+    date: data.date.clone(),
+    lawsuit_id: data.lawsuit_id.clone(),
+  }
+}
+
+/// 1件の判例を取得した結果
+pub struct RecordOutcome {
+  pub lawsuit_id: String,
+  pub file_name: String,
+  /// PDFの取得に失敗した場合はそのエラー（レコード自体は本文無しで書き出されている）
+  pub pdf_error: Option<anyhow::Error>,
+  pub full_pdf_link: String,
+  /// 既存のファイルがあったか、除外のパターンにマッチしたので書き出さなかった
+  pub skipped: bool,
+}
+
+/// 詳細ページを取得した結果の種類
+enum DetailState {
+  /// 既存のファイルがあったので取得しなかった
+  Skipped { file_name: String },
+  /// `--exclude-action drop`で除外したので書き出さない
+  Excluded,
+  /// 詳細ページが前回から更新されていないので前回の出力をそのまま使う
+  Unchanged,
+  /// 詳細ページを取得して解析した
+  Parsed {
+    /// PDFが更新されていなかったときに使う前回の本文
+    previous_contents: Option<String>,
+    /// PDFが更新されていなかったときに引き継ぐ、前回の出力の[`PDF_DERIVED_FIELDS`]
+    previous_pdf_fields: Map<String, Value>,
+  },
+}
+
+/// 判決文のPDFとその本文から作るフィールド。PDFを取得し直さなかったときは前回の出力から引き継ぐ
+const PDF_DERIVED_FIELDS: &[&str] = &[
+  "pdf_sha256",
+  "pdf_previous_sha256",
+  "pdf_path",
+  "orthography",
+  "pages",
+  "readings",
+  "contents_hiragana",
+];
+
+/// 書き出すまでの途中の段階にある判例
+pub struct Record {
+  detail_page_link: String,
+  lawsuit_id: String,
+  data: PrecedentData,
+  /// 判例のデータに加えて書き出す項目
+  extra: Map<String, Value>,
+  meta: RecordMeta,
+  state: DetailState,
+  started: Instant,
+  /// ダウンロードしたまま本文を抽出していないPDF
+  pdf: Option<Vec<u8>>,
+  pdf_error: Option<anyhow::Error>,
+}
+
+/// 詳細ページを取得して解析する
+pub async fn fetch_detail(
+  args: &Args,
+  fetcher: &Fetcher,
+  index_writer: &IndexWriter,
+  detail_page_link: &str,
+) -> Result<Record> {
+  let trial_type = crate::trial_type_from_link(detail_page_link)?;
+  let lawsuit_id = crate::get_lawsuit_id(detail_page_link).await?;
+  let started = Instant::now();
+  let record = |data, extra, state| Record {
+    detail_page_link: detail_page_link.to_string(),
+    lawsuit_id: lawsuit_id.clone(),
+    data,
+    extra,
+    meta: RecordMeta::new(),
+    state,
+    started,
+    pdf: None,
+    pdf_error: None,
+  };
+  if args.skip_existing {
+    // ファイル名は詳細ページの内容から決まるので、前回の一覧か条件付きリクエストのキャッシュから引く
+    let file_name = index_writer
+      .previous_file_name(&lawsuit_id)
+      .map(|s| s.to_string())
+      .or_else(|| {
+        fetcher
+          .conditional_cache()
+          .and_then(|c| c.get(detail_page_link))
+          .and_then(|v| v.file_name)
+      });
+    let existing = match &file_name {
+      Some(name) => output::existing_record(&args.output, name).await?,
+      None => None,
+    };
+    if let (Some(file_name), Some(existing)) = (file_name, existing) {
+      info!("skip existing: {}", &lawsuit_id);
+      let precedent_data = serde_json::from_value(existing)?;
+      return Ok(record(
+        precedent_data,
+        Map::new(),
+        DetailState::Skipped { file_name },
+      ));
+    }
+  }
+  info!("[START] date write: {}", &lawsuit_id);
+  // 前回の出力が残っていれば、更新されていないページ・PDFは前回の内容を再利用する
+  let previous_value = match fetcher
+    .conditional_cache()
+    .and_then(|c| c.get(detail_page_link))
+    .and_then(|v| v.file_name)
+  {
+    Some(name) => output::read_record_value(&args.output, &name).await.ok(),
+    None => None,
+  };
+  let previous = previous_value
+    .clone()
+    .and_then(|value| serde_json::from_value::<PrecedentData>(value).ok());
+  let detail_page_html = fetcher
+    .get_text_if_modified(detail_page_link, previous.is_some())
+    .await?;
+  match (detail_page_html, previous) {
+    (None, Some(previous)) => Ok(record(previous, Map::new(), DetailState::Unchanged)),
+    (Some(detail_page_html), previous) => {
+      let precedent_data = crate::parse_detail_page(
+        &detail_page_html,
+        trial_type,
+        lawsuit_id.clone(),
+        detail_page_link.to_string(),
+      )
+      .await
+      .inspect_err(|e| issue_draft::record_parse_error(detail_page_link, e))
+      .map_err(|e| e.context(ParseFailure(detail_page_link.to_string())))?;
+      if args.save_html {
+        // レイアウトの変更や解析のバグを見つけたときに、取得し直さずに解析し直せるよう残しておく
+        let file_name = partition::record_name(&precedent_info_of(&precedent_data));
+        output::write_side_file(
+          &args.output,
+          &format!("{file_name}.html"),
+          detail_page_html.into_bytes(),
+        )
+        .await?;
+      }
+      let mut extra = Map::new();
+      extra.insert(
+        "uuid".to_string(),
+        stable_id::uuid(&precedent_data).to_string().into(),
+      );
+      if let Some(mark) = case_mark::find(&precedent_data.case_number) {
+        if let Some(meaning) = mark.meaning {
+          extra.insert("case_mark_meaning".to_string(), meaning.into());
+        }
+        extra.insert("case_mark".to_string(), mark.mark.into());
+      }
+      if let Some(holding) = precedent_data
+        .case_gist
+        .as_deref()
+        .and_then(holding::extract)
+      {
+        extra.insert("holding".to_string(), holding.into());
+      }
+      if let Some(text) = &precedent_data.ref_law {
+        extra.insert(
+          "ref_law_links".to_string(),
+          serde_json::to_value(ref_law::links(text))?,
+        );
+      }
+      if let Some(excluded) = exclude::check(args, &precedent_data) {
+        info!(
+          "excluded: {}: {} matches {}",
+          &lawsuit_id, excluded.field, excluded.pattern
+        );
+        match args.exclude_action {
+          ExcludeAction::Drop => {
+            return Ok(record(precedent_data, Map::new(), DetailState::Excluded))
+          }
+          ExcludeAction::Flag => {
+            extra.insert("excluded".to_string(), serde_json::to_value(excluded)?);
+          }
+        }
+      }
+      if args.en_summary {
+        match en_summary::find(fetcher, &precedent_data).await {
+          Ok(Some(summary)) => {
+            extra.insert("en_summary".to_string(), serde_json::to_value(summary)?);
+          }
+          Ok(None) => {}
+          Err(e) if fetch::is_circuit_open(&e) => return Err(e),
+          Err(e) => warn!("failed to get english summary: {}: {:#}", &lawsuit_id, e),
+        }
+      }
+      let same_pdf = previous
+        .as_ref()
+        .is_some_and(|p| p.full_pdf_link == precedent_data.full_pdf_link);
+      let previous_contents = previous.filter(|_| same_pdf).and_then(|p| p.contents);
+      let previous_pdf_fields = match previous_value {
+        Some(Value::Object(obj)) if same_pdf => obj
+          .into_iter()
+          .filter(|(k, _)| PDF_DERIVED_FIELDS.contains(&k.as_str()))
+          .collect(),
+        _ => Map::new(),
+      };
+      Ok(record(
+        precedent_data,
+        extra,
+        DetailState::Parsed {
+          previous_contents,
+          previous_pdf_fields,
+        },
+      ))
+    }
+    (None, None) => unreachable!("条件付きリクエストは前回の出力があるときだけ送る"),
+  }
+}
+
+/// 判決文のPDFをダウンロードする。PDFが前回から更新されていなければ前回の本文を使う
+///
+/// サーキットブレーカーが回復を諦めた場合以外の失敗は、レコードに記録して本文無しで書き出す
+pub async fn download_contents(args: &Args, fetcher: &Fetcher, record: &mut Record) -> Result<()> {
+  let DetailState::Parsed {
+    previous_contents,
+    previous_pdf_fields,
+  } = &mut record.state
+  else {
+    return Ok(());
+  };
+  if args.no_contents {
+    // 本文を取得しない実行で前回の本文を消さないよう、前回の本文があればそのまま残す
+    record.data.contents = previous_contents.take();
+    record.extra.append(previous_pdf_fields);
+    if record.data.contents.is_none() {
+      record.extra.insert(
+        "contents_unavailable".to_string(),
+        json!({ "reason": "skipped", "message": "--no-contentsのため本文を取得していません" }),
+      );
+    }
+    return Ok(());
+  }
+  match crate::download_pdf(
+    fetcher,
+    &record.data.full_pdf_link,
+    previous_contents.is_some(),
+    args.pdf_fallback,
+  )
+  .await
+  {
+    Ok(Some(bytes)) => {
+      let hash = pdf_store::sha256_hex(&bytes);
+      let file_name = partition::record_name(&precedent_info_of(&record.data));
+      pdf_store::check_replaced(
+        &args.output,
+        &file_name,
+        &record.data.full_pdf_link,
+        &hash,
+        &mut record.extra,
+      )
+      .await;
+      if args.save_pdf {
+        // 後から別の方法で本文を抽出し直せるよう、PDFを残す
+        let path =
+          pdf_store::save(&args.output, args.pdf_layout, &file_name, &hash, &bytes).await?;
+        record.extra.insert("pdf_path".to_string(), path.into());
+      }
+      record.extra.insert("pdf_sha256".to_string(), hash.into());
+      record.pdf = Some(bytes)
+    }
+    Ok(None) => {
+      // PDFが更新されていないので、本文とそこから作ったフィールドは前回のものを使う
+      record.data.contents = previous_contents.take();
+      record.extra.append(previous_pdf_fields);
+    }
+    Err(e) if fetch::is_circuit_open(&e) => return Err(e),
+    Err(e) if fetch::unavailable(&e).is_some() => {
+      let unavailable = fetch::unavailable(&e).unwrap();
+      warn!(
+        "pdf contents unavailable: {}: {}",
+        &record.lawsuit_id, unavailable
+      );
+      if let Unavailable::Deferred { .. } = unavailable {
+        let deferred = FailedRecord::new(
+          FailureKind::LargePdf,
+          &record.data.full_pdf_link,
+          &record.detail_page_link,
+          Some(&record.lawsuit_id),
+          &e,
+        );
+        RetryQueue::new(&args.large_pdfs).push(&deferred).await?;
+      }
+      record.extra.insert(
+        "contents_unavailable".to_string(),
+        json!({ "reason": unavailable.reason(), "message": unavailable.to_string() }),
+      );
+    }
+    Err(e) => record.pdf_error = Some(e),
+  }
+  Ok(())
+}
+
+/// ダウンロードしたPDFから本文を抽出し、ふりがな・表記の処理をする
+pub async fn extract_contents(args: &Args, record: &mut Record) -> Result<()> {
+  let Some(bytes) = record.pdf.take() else {
+    return Ok(());
+  };
+  let text = match crate::extract_pdf_text(bytes, &mut record.meta).await {
+    Ok(text) => text,
+    Err(e) => {
+      record.pdf_error = Some(e);
+      return Ok(());
+    }
+  };
+  let (text, readings) = furigana::normalize(&text, args.furigana);
+  if args.furigana == FuriganaMode::Extract {
+    record
+      .extra
+      .insert("readings".to_string(), serde_json::to_value(readings)?);
+  }
+  if args.pages {
+    record.extra.insert(
+      "pages".to_string(),
+      serde_json::to_value(pages::split(&text))?,
+    );
+  }
+  let written_in = orthography::detect(&text);
+  record
+    .extra
+    .insert("orthography".to_string(), serde_json::to_value(written_in)?);
+  if args.hiragana_text && written_in == orthography::Orthography::Katakana {
+    record.extra.insert(
+      "contents_hiragana".to_string(),
+      orthography::to_hiragana(&text).into(),
+    );
+  }
+  record.data.contents = Some(text);
+  Ok(())
+}
+
+/// 判例のファイルを書き出し、一覧に追加する
+pub async fn write_record(
+  args: &Args,
+  fetcher: &Fetcher,
+  index_writer: &IndexWriter,
+  mut record: Record,
+) -> Result<RecordOutcome> {
+  if matches!(record.state, DetailState::Parsed { .. }) {
+    postprocess::apply(&mut record.data, &mut record.extra)?;
+  }
+  let precedent_info = precedent_info_of(&record.data);
+  let file_name = match &record.state {
+    DetailState::Skipped { file_name } => {
+      index_writer.write(&precedent_info, &record.data).await?;
+      return Ok(RecordOutcome {
+        lawsuit_id: record.lawsuit_id,
+        file_name: file_name.clone(),
+        pdf_error: None,
+        full_pdf_link: record.data.full_pdf_link,
+        skipped: true,
+      });
+    }
+    DetailState::Excluded => {
+      return Ok(RecordOutcome {
+        lawsuit_id: record.lawsuit_id,
+        file_name: partition::record_name(&precedent_info),
+        pdf_error: None,
+        full_pdf_link: record.data.full_pdf_link,
+        skipped: true,
+      });
+    }
+    DetailState::Unchanged => {
+      info!("unchanged: {}", &record.lawsuit_id);
+      partition::record_name(&precedent_info)
+    }
+    DetailState::Parsed { .. } => {
+      let file_name = partition::record_name(&precedent_info);
+      record.meta.retries =
+        fetcher.take_retries(&[&record.detail_page_link, &record.data.full_pdf_link]);
+      record.meta.elapsed_millis = meta::to_millis(record.started.elapsed());
+      let warnings = warnings::collect(&record.data, &record.extra, record.pdf_error.as_ref());
+      if !warnings.is_empty() {
+        record
+          .extra
+          .insert("warnings".to_string(), serde_json::to_value(warnings)?);
+      }
+      let content_hash = output::content_hash(&record.data, &record.extra)?;
+      let overwrite = match args.overwrite {
+        OverwritePolicy::Always => true,
+        policy => match output::existing_record(&args.output, &file_name).await? {
+          None => true,
+          Some(_) if policy == OverwritePolicy::Never => false,
+          Some(existing) => {
+            output::stored_content_hash(existing).as_deref() != Some(content_hash.as_str())
+          }
+        },
+      };
+      if overwrite {
+        record.meta.content_hash = Some(content_hash);
+        output::write_data(
+          &args.output,
+          &file_name,
+          &record.data,
+          &record.meta,
+          &record.extra,
+        )
+        .await?;
+        if let (true, Some(contents)) = (args.diff_text, &record.data.contents) {
+          diff_text::write(&args.output, &file_name, contents).await?;
+        }
+      } else {
+        info!("not overwritten: {}", &file_name);
+      }
+      file_name
+    }
+  };
+  if let Some(cache) = fetcher.conditional_cache() {
+    cache.set_file_name(&record.detail_page_link, &file_name);
+  }
+  index_writer.write(&precedent_info, &record.data).await?;
+  info!("[END] date write: {}", &record.lawsuit_id);
+  Ok(RecordOutcome {
+    lawsuit_id: record.lawsuit_id,
+    file_name,
+    pdf_error: record.pdf_error,
+    full_pdf_link: record.data.full_pdf_link,
+    skipped: false,
+  })
+}
+
+/// 1件の判例の取得から書き出しまでを順に行う
+pub async fn process_record(
+  args: &Args,
+  fetcher: &Fetcher,
+  index_writer: &IndexWriter,
+  detail_page_link: &str,
+) -> Result<RecordOutcome> {
+  let mut record = fetch_detail(args, fetcher, index_writer, detail_page_link).await?;
+  download_contents(args, fetcher, &mut record).await?;
+  extract_contents(args, &mut record).await?;
+  write_record(args, fetcher, index_writer, record).await
+}
+
+/// 1件の判例を取得して書き出す。失敗したら再試行キューに記録して、`--on-error skip`なら続ける
+///
+/// サーキットブレーカーが回復を諦めた場合だけはエラーを返して実行を中断する
+pub async fn process_record_or_queue(
+  args: &Args,
+  fetcher: &Fetcher,
+  events: &Events,
+  index_writer: &IndexWriter,
+  retry_queue: &RetryQueue,
+  detail_page_link: &str,
+  page_num: Option<usize>,
+) -> Result<()> {
+  let result = process_record(args, fetcher, index_writer, detail_page_link).await;
+  report(
+    args,
+    events,
+    retry_queue,
+    detail_page_link,
+    page_num,
+    result,
+  )
+  .await
+}
+
+/// 1件の判例を取得した結果をイベントとして出力し、失敗していたら再試行キューに記録する
+pub async fn report(
+  args: &Args,
+  events: &Events,
+  retry_queue: &RetryQueue,
+  detail_page_link: &str,
+  page_num: Option<usize>,
+  result: Result<RecordOutcome>,
+) -> Result<()> {
+  match result {
+    Ok(outcome) if outcome.skipped => {
+      events.emit(
+        "record_skipped",
+        json!({ "page": page_num, "lawsuit_id": &outcome.lawsuit_id, "file_name": &outcome.file_name }),
+      );
+      Ok(())
+    }
+    Ok(outcome) => {
+      events.emit(
+        "record_written",
+        json!({ "page": page_num, "lawsuit_id": &outcome.lawsuit_id, "file_name": &outcome.file_name }),
+      );
+      if let Some(e) = outcome.pdf_error {
+        warn!("failed to get pdf text: {}: {}", &outcome.lawsuit_id, e);
+        events.emit(
+          "error",
+          json!({
+            "message": format!("{e:#}"),
+            "fatal": false,
+            "lawsuit_id": &outcome.lawsuit_id,
+            "url": &outcome.full_pdf_link,
+          }),
+        );
+        let failed = FailedRecord::new(
+          FailureKind::Pdf,
+          &outcome.full_pdf_link,
+          detail_page_link,
+          Some(&outcome.lawsuit_id),
+          &e,
+        );
+        retry_queue.push(&failed).await?;
+        if args.on_error == OnError::Fail {
+          return Err(e.context(format!("PDFの取得に失敗しました：{}", &outcome.lawsuit_id)));
+        }
+      }
+      Ok(())
+    }
+    Err(e) if fetch::is_circuit_open(&e) => Err(e),
+    Err(e) if fetch::unavailable(&e).is_some() => {
+      // サイト側の問題で取得できないものは、解析の失敗とは分けて取得不可として記録し、取得を続ける
+      let reason = fetch::unavailable(&e).unwrap().reason();
+      warn!("unreachable record: {}: {:#}", detail_page_link, e);
+      events.emit(
+        "error",
+        json!({
+          "message": format!("{e:#}"),
+          "fatal": false,
+          "url": detail_page_link,
+          "unreachable": reason,
+        }),
+      );
+      let lawsuit_id = crate::get_lawsuit_id(detail_page_link).await.ok();
+      let failed = FailedRecord::new(
+        FailureKind::Unreachable,
+        detail_page_link,
+        detail_page_link,
+        lawsuit_id.as_deref(),
+        &e,
+      );
+      retry_queue.push(&failed).await?;
+      Ok(())
+    }
+    Err(e) => {
+      warn!("failed to get record: {}: {:#}", detail_page_link, e);
+      events.emit(
+        "error",
+        json!({
+          "message": format!("{e:#}"),
+          "fatal": false,
+          "url": detail_page_link,
+        }),
+      );
+      let lawsuit_id = crate::get_lawsuit_id(detail_page_link).await.ok();
+      let failed = FailedRecord::new(
+        FailureKind::Detail,
+        detail_page_link,
+        detail_page_link,
+        lawsuit_id.as_deref(),
+        &e,
+      );
+      retry_queue.push(&failed).await?;
+      if args.on_error == OnError::Fail {
+        return Err(e.context(format!(
+          "詳細ページの取得に失敗しました：{detail_page_link}"
+        )));
+      }
+      Ok(())
+    }
+  }
+}
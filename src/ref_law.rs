@@ -0,0 +1,110 @@
+//! 参照法条（`ref_law`）の各条文に対応するe-Gov法令検索のURLの生成
+//!
+//! 「民法709条，710条」のように法令名が省略された条文は直前の法令のものとみなす。
+//! 法令IDの分かっている法令は条文へのリンクを、それ以外は法令名での検索結果へのリンクを生成する。
+
+use regex::Regex;
+use serde::Serialize;
+use url::Url;
+
+const EGOV_LAW_URL: &str = "https://laws.e-gov.go.jp/law";
+const EGOV_SEARCH_URL: &str = "https://laws.e-gov.go.jp/search";
+
+/// 判例でよく参照される法令の法令ID
+const LAW_IDS: &[(&str, &str)] = &[
+  ("日本国憲法", "321CONSTITUTION"),
+  ("憲法", "321CONSTITUTION"),
+  ("民法", "129AC0000000089"),
+  ("商法", "132AC0000000048"),
+  ("刑法", "140AC0000000045"),
+  ("民事訴訟法", "408AC0000000109"),
+  ("刑事訴訟法", "323AC0000000131"),
+  ("会社法", "417AC0000000086"),
+  ("行政事件訴訟法", "337AC0000000139"),
+  ("国家賠償法", "322AC0000000125"),
+  ("行政手続法", "405AC0000000088"),
+  ("地方自治法", "322AC0000000067"),
+  ("労働基準法", "322AC0000000049"),
+  ("労働組合法", "324AC0000000174"),
+  ("労働契約法", "419AC0000000128"),
+  ("特許法", "334AC0000000121"),
+  ("実用新案法", "334AC0000000123"),
+  ("意匠法", "334AC0000000125"),
+  ("商標法", "334AC0000000127"),
+  ("著作権法", "345AC0000000048"),
+  ("不正競争防止法", "405AC0000000047"),
+  ("破産法", "416AC0000000075"),
+  ("民事執行法", "354AC0000000004"),
+  ("民事保全法", "401AC0000000091"),
+  ("借地借家法", "403AC0000000090"),
+  ("所得税法", "340AC0000000033"),
+  ("法人税法", "340AC0000000034"),
+  ("国税通則法", "337AC0000000066"),
+  ("少年法", "323AC0000000168"),
+  ("道路交通法", "335AC0000000105"),
+  ("公職選挙法", "325AC0000000100"),
+];
+
+/// 参照法条の1つの条文
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RefLawLink {
+  pub law: String,
+  /// 「709」や「3の2」のような条番号
+  pub article: String,
+  pub url: String,
+}
+
+fn to_ascii_digits(s: &str) -> String {
+  s.chars()
+    .map(|c| match c {
+      '０'..='９' => char::from_u32(c as u32 - '０' as u32 + '0' as u32).unwrap_or(c),
+      _ => c,
+    })
+    .collect()
+}
+
+fn law_url(law: &str, article: &str, branch: Option<&str>) -> String {
+  match LAW_IDS.iter().find(|(name, _)| *name == law) {
+    Some((_, id)) => {
+      let anchor = match branch {
+        Some(branch) => format!("Mp-At_{article}_{branch}"),
+        None => format!("Mp-At_{article}"),
+      };
+      format!("{EGOV_LAW_URL}/{id}#{anchor}")
+    }
+    None => Url::parse_with_params(EGOV_SEARCH_URL, &[("keyword", law)])
+      .map(|u| u.to_string())
+      .unwrap_or_else(|_| EGOV_SEARCH_URL.to_string()),
+  }
+}
+
+/// 参照法条の文字列から条文ごとのリンクを生成する。条番号の読み取れない部分は無視する
+pub fn links(ref_law: &str) -> Vec<RefLawLink> {
+  let re =
+    Regex::new(r"^(?P<law>.*?)(?:（[^）]*）)?第?(?P<article>\d+)条(?:の(?P<branch>\d+))?").unwrap();
+  let mut links = Vec::new();
+  let mut current_law: Option<String> = None;
+  for part in to_ascii_digits(ref_law).split(['，', '、', ',', '\n', ' ', '　']) {
+    let Some(caps) = re.captures(part.trim()) else {
+      continue;
+    };
+    let law = caps["law"].trim();
+    if !law.is_empty() {
+      current_law = Some(law.to_string());
+    }
+    let Some(law) = &current_law else {
+      continue;
+    };
+    let branch = caps.name("branch").map(|m| m.as_str());
+    let article = match branch {
+      Some(branch) => format!("{}の{}", &caps["article"], branch),
+      None => caps["article"].to_string(),
+    };
+    links.push(RefLawLink {
+      law: law.clone(),
+      url: law_url(law, &caps["article"], branch),
+      article,
+    });
+  }
+  links
+}
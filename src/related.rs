@@ -0,0 +1,141 @@
+//! 同じ日に同じ裁判所で言い渡された関連事件を相互に結びつける`related`フィールド
+//!
+//! 一覧ファイルの項目を裁判年月日と裁判所名でまとめ、その中で事件番号が似ているもの同士を関連事件とする。
+//! 事件番号が似ているとは、`令和3(受)1234`の`令和3(受)`のような年と事件記号の部分が同じで、
+//! 番号の差が[`MAX_NUMBER_DISTANCE`]以内であることをいう。併合された事件のように事件番号が複数書かれていれば、そのどれかが似ていればよい。
+//!
+//! 取得がすべて終わったあとに出力フォルダの裁判例のファイルを書き換えるので、1回の実行の中で言い渡しが別々に取得された事件も結びつく。
+//! 関連事件が無くなった裁判例からは`related`フィールドを取り除く。
+
+use crate::{output, partition, permissions};
+use anyhow::Result;
+use jplaw_data_types::listup::PrecedentInfo;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+  collections::{BTreeMap, HashMap},
+  sync::OnceLock,
+};
+use tracing::*;
+
+/// 関連事件とみなす事件番号の番号部分の差の上限
+pub const MAX_NUMBER_DISTANCE: u64 = 10;
+
+/// 関連事件へのリンク
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+struct Related {
+  lawsuit_id: String,
+  case_number: String,
+}
+
+fn case_number_re() -> &'static Regex {
+  static RE: OnceLock<Regex> = OnceLock::new();
+  RE.get_or_init(|| Regex::new(r"([^\s,，、]+?[(（][^)）]+[)）])\s*(\d+)").unwrap())
+}
+
+/// 事件番号を、年と事件記号の部分と番号の組に分ける。併合された事件では複数の組になる
+fn case_number_parts(case_number: &str) -> Vec<(String, u64)> {
+  case_number_re()
+    .captures_iter(case_number)
+    .filter_map(|caps| {
+      let prefix = caps[1].replace('（', "(").replace('）', ")");
+      Some((prefix, caps[2].parse().ok()?))
+    })
+    .collect()
+}
+
+fn is_similar(a: &[(String, u64)], b: &[(String, u64)]) -> bool {
+  a.iter().any(|(prefix_a, number_a)| {
+    b.iter().any(|(prefix_b, number_b)| {
+      prefix_a == prefix_b && number_a.abs_diff(*number_b) <= MAX_NUMBER_DISTANCE
+    })
+  })
+}
+
+/// 一覧の項目から、`lawsuit_id`ごとの関連事件を求める
+fn find_related(infos: &[PrecedentInfo]) -> Result<HashMap<String, Vec<Related>>> {
+  let mut groups: BTreeMap<(String, String), Vec<&PrecedentInfo>> = BTreeMap::new();
+  for info in infos {
+    let date = serde_json::to_string(&info.date)?;
+    groups
+      .entry((date, info.court_name.clone()))
+      .or_default()
+      .push(info);
+  }
+  let mut related: HashMap<String, Vec<Related>> = HashMap::new();
+  for group in groups.values().filter(|g| g.len() > 1) {
+    let parts = group
+      .iter()
+      .map(|info| case_number_parts(&info.case_number))
+      .collect::<Vec<_>>();
+    for (i, a) in group.iter().enumerate() {
+      for (j, b) in group.iter().enumerate() {
+        if i == j || a.lawsuit_id == b.lawsuit_id || !is_similar(&parts[i], &parts[j]) {
+          continue;
+        }
+        related
+          .entry(a.lawsuit_id.clone())
+          .or_default()
+          .push(Related {
+            lawsuit_id: b.lawsuit_id.clone(),
+            case_number: b.case_number.clone(),
+          });
+      }
+    }
+  }
+  for links in related.values_mut() {
+    links.sort_by(|a, b| a.lawsuit_id.cmp(&b.lawsuit_id));
+  }
+  Ok(related)
+}
+
+/// 裁判例のファイルの`related`を書き換える。変わらなければ書き出さず、書き換えたかどうかを返す
+///
+/// `--contents-dir`の本文を読み戻さないよう、ファイルの中身をそのまま読んで書き換える
+async fn rewrite(output: &str, file_name: &str, related: Option<&Vec<Related>>) -> Result<bool> {
+  let path = output::record_path(output, file_name);
+  let mut value = output::decode_record_bytes(tokio::fs::read(&path).await?)?;
+  let Value::Object(obj) = &mut value else {
+    return Ok(false);
+  };
+  let new = related.map(serde_json::to_value).transpose()?;
+  if obj.get("related") == new.as_ref() {
+    return Ok(false);
+  }
+  match new {
+    Some(new) => obj.insert("related".to_string(), new),
+    None => obj.remove("related"),
+  };
+  output::write_atomic(&path, &output::encode_record(&value)?).await?;
+  permissions::apply(&path).await?;
+  Ok(true)
+}
+
+/// `index`の一覧から関連事件を求め、`output`の裁判例のファイルに`related`を書き込む。書き換えたファイルの数を返す
+///
+/// 出力フォルダの裁判例のファイルを直接書き換えるので、`--writer json-dir`でだけ使える
+pub async fn link(index: &str, output: &str) -> Result<usize> {
+  let entries = output::read_value_lst(index).await?;
+  let infos = entries
+    .into_iter()
+    .map(serde_json::from_value::<PrecedentInfo>)
+    .collect::<Result<Vec<_>, _>>()?;
+  let related = find_related(&infos)?;
+  let mut rewritten = 0;
+  for info in &infos {
+    let file_name = partition::record_name(info);
+    if !output::data_exists(output, &file_name) {
+      continue;
+    }
+    if rewrite(output, &file_name, related.get(&info.lawsuit_id)).await? {
+      rewritten += 1;
+    }
+  }
+  info!(
+    "related: {} records have related cases ({} files rewritten)",
+    related.len(),
+    rewritten
+  );
+  Ok(rewritten)
+}
@@ -0,0 +1,145 @@
+//! 過去の実行で壊れた出力を見つけて直す`repair`サブコマンド
+//!
+//! - 閉じられていない・最後の項目が書きかけの一覧ファイルは、読める項目までで閉じ直す
+//! - 読めない裁判例のJSONは`{ファイル名}.json.broken`に移し、詳細ページのリンクが分かれば
+//!   `--failed-queue`に記録して、次回の実行（または`retry-failed`）で取得し直す対象にする
+
+use crate::{
+  compress,
+  lock::OutputLock,
+  output::{self, ValueLst},
+  retry_queue::{FailedRecord, FailureKind, RetryQueue},
+  Args,
+};
+use anyhow::{anyhow, Result};
+use jplaw_data_types::listup::PrecedentData;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tracing::*;
+
+/// 一覧ファイルが壊れていれば読める項目までで書き直す。直したら`true`を返す
+///
+/// `compat`のときは互換一覧ファイルとして元の版と同じ形式で書き直す
+async fn repair_index(path: &str, compat: bool) -> Result<bool> {
+  if !Path::new(path).exists() || output::read_value_lst(path).await.is_ok() {
+    return Ok(false);
+  }
+  let entries = output::read_value_lst_lenient(path).await?;
+  let format = output::detect_index_format(&fs::read(path).await?);
+  fs::copy(path, format!("{path}.broken")).await?;
+  if compat {
+    crate::compat::rewrite(path, &entries).await?;
+  } else {
+    let mut file = ValueLst::create(path, format).await?;
+    for value in &entries {
+      file.write(value).await?;
+    }
+    file.flush().await?;
+  }
+  warn!("repaired index: {} ({} entries kept)", path, entries.len());
+  Ok(true)
+}
+
+/// 壊れた裁判例のJSONの中身から、読める範囲で詳細ページのリンクを探す
+fn find_detail_page_link(s: &str) -> Option<String> {
+  let re = Regex::new(r#""detail_page_link"\s*:\s*"([^"]+)""#).unwrap();
+  re.captures(s).map(|caps| caps[1].to_string())
+}
+
+/// 出力フォルダの`--format`の形式の裁判例のファイル（一覧ファイルを除く）
+///
+/// `--partition-by`で振り分けたサブフォルダの中も探す
+pub async fn record_files(args: &Args) -> Result<Vec<PathBuf>> {
+  let index = Path::new(&args.index).canonicalize().ok();
+  let mut files = Vec::new();
+  let mut dirs = vec![PathBuf::from(&args.output)];
+  while let Some(dir) = dirs.pop() {
+    let mut entries = fs::read_dir(&dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+      let path = entry.path();
+      if entry.file_type().await?.is_dir() {
+        dirs.push(path);
+        continue;
+      }
+      if !path.to_string_lossy().ends_with(&output::record_suffix()) {
+        continue;
+      }
+      if index.is_some() && path.canonicalize().ok() == index {
+        continue;
+      }
+      files.push(path);
+    }
+  }
+  files.sort();
+  Ok(files)
+}
+
+pub async fn repair(args: &Args) -> Result<()> {
+  let _lock = OutputLock::acquire(&args.output)?;
+  let mut repaired_indexes = 0;
+  let mut indexes = vec![(args.index.clone(), false)];
+  if let Some(version) = args.compat {
+    indexes.push((
+      args
+        .compat_index
+        .clone()
+        .unwrap_or_else(|| crate::compat::gen_compat_index_path(&args.index, version)),
+      true,
+    ));
+  }
+  for (index, compat) in &indexes {
+    if repair_index(index, *compat).await? {
+      repaired_indexes += 1;
+    }
+  }
+
+  let retry_queue = RetryQueue::new(&args.failed_queue);
+  let mut broken = 0;
+  let mut queued = 0;
+  for path in record_files(args).await? {
+    let raw = fs::read(&path).await?;
+    // 圧縮されたファイルが途中で切れていれば展開できないので、そのまま扱う
+    let bytes = compress::decompress(raw.clone()).unwrap_or(raw);
+    let readable = output::record_format()
+      .decode(&bytes)
+      .ok()
+      .and_then(|value| serde_json::from_value::<PrecedentData>(value).ok());
+    if readable.is_some() {
+      continue;
+    }
+    let s = String::from_utf8_lossy(&bytes);
+    // 一覧ファイルの書式のJSONは裁判例のJSONではないので触らない
+    if s.trim_start().starts_with('[') {
+      continue;
+    }
+    broken += 1;
+    let path_str = path.to_string_lossy().to_string();
+    let broken_path = format!("{path_str}.broken");
+    fs::rename(&path, &broken_path).await?;
+    match find_detail_page_link(&s) {
+      Some(link) => {
+        warn!("broken record: {} (queued for refetch: {})", path_str, link);
+        let lawsuit_id = crate::get_lawsuit_id(&link).await.ok();
+        let failed = FailedRecord::new(
+          FailureKind::Corrupt,
+          &path_str,
+          &link,
+          lawsuit_id.as_deref(),
+          &anyhow!("壊れたJSONを{broken_path}に移しました"),
+        );
+        retry_queue.push(&failed).await?;
+        queued += 1;
+      }
+      None => warn!(
+        "broken record: {} (detail page link not found; moved to {})",
+        path_str, broken_path
+      ),
+    }
+  }
+  info!(
+    "[END] repair: {} index(es) repaired, {} broken record(s), {} queued for refetch",
+    repaired_indexes, broken, queued
+  );
+  Ok(())
+}
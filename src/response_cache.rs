@@ -0,0 +1,82 @@
+//! 取得したHTML・PDFをURLごとにそのまま保存しておくキャッシュディレクトリ
+//!
+//! パーサーを直したあとの再実行などで、裁判所のホームページに負荷をかけずに全件を処理し直せるようにする。
+//!
+//! 一覧ページ（[`is_listing`]）も保存するが、新しい判例を見落とさないよう、ネットワークに接続する実行ではキャッシュから読まずに毎回取得する。
+//! 保存した一覧ページを使うのは、`parse-raw`とonline featureを無効にしたビルドのようにリクエストを送らない実行だけである。
+
+use crate::output;
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use tokio::fs;
+use url::Url;
+
+/// 判例の一覧ページのパス。載る判例が日々変わる
+const LISTING_PATHS: &[&str] = &[
+  "/app/hanrei_jp/list1",
+  "/app/hanrei_jp/list2",
+  "/app/hanrei_en/list",
+];
+
+/// 検索結果や「最近の裁判例」などの一覧ページのURLかどうか
+pub fn is_listing(url: &str) -> bool {
+  Url::parse(url).is_ok_and(|url| LISTING_PATHS.contains(&url.path()))
+}
+
+pub struct ResponseCache {
+  dir: PathBuf,
+}
+
+/// バイト列を小文字の16進数の文字列にする
+pub fn to_hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+impl ResponseCache {
+  pub async fn new(dir: &str) -> Result<Self> {
+    fs::create_dir_all(dir).await?;
+    Ok(ResponseCache {
+      dir: PathBuf::from(dir),
+    })
+  }
+
+  /// URLのSHA-256の先頭2文字をサブディレクトリ名にして、1つのディレクトリにファイルが集中しないようにする
+  fn path(&self, url: &str) -> PathBuf {
+    let hash = to_hex(&Sha256::digest(url.as_bytes()));
+    self.dir.join(&hash[..2]).join(hash)
+  }
+
+  pub async fn read(&self, url: &str) -> Option<Vec<u8>> {
+    fs::read(self.path(url)).await.ok()
+  }
+
+  /// 書きかけのファイルがキャッシュとして読まれないよう、書き終えてから名前を変える
+  pub async fn write(&self, url: &str, bytes: &[u8]) -> Result<()> {
+    let path = self.path(url);
+    if let Some(parent) = path.parent() {
+      fs::create_dir_all(parent).await?;
+    }
+    output::write_atomic(&path.to_string_lossy(), bytes).await
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn listing_pages() {
+    assert!(is_listing(
+      "https://www.courts.go.jp/app/hanrei_jp/list1?page=2&sort=1"
+    ));
+    assert!(is_listing("https://www.courts.go.jp/app/hanrei_jp/list2"));
+    assert!(is_listing("https://www.courts.go.jp/app/hanrei_en/list"));
+    assert!(!is_listing(
+      "https://www.courts.go.jp/app/hanrei_jp/detail2?id=99999"
+    ));
+    assert!(!is_listing(
+      "https://www.courts.go.jp/app/files/hanrei_jp/999/099999_hanrei.pdf"
+    ));
+  }
+}
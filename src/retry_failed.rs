@@ -0,0 +1,58 @@
+//! `retry-failed`サブコマンド
+//!
+//! 前回までの実行で失敗したものだけを取得し直し、成功したものを既存の出力と一覧にマージする。
+//! 取得し直したものの一覧はいったん一時ファイルに書き出し、最後に既存の一覧とマージする。
+
+use crate::{
+  compat, events::Events, lock::OutputLock, output::IndexWriter, retry_queue::RetryQueue, Args,
+};
+use anyhow::Result;
+use serde_json::json;
+use tokio::fs;
+use tracing::*;
+
+fn tmp_path(path: &str) -> String {
+  format!("{path}.retry.tmp")
+}
+
+pub async fn retry_failed(args: &Args, events: &Events, failed: &str) -> Result<()> {
+  let _lock = OutputLock::acquire(&args.output)?;
+  let fetcher = crate::build_fetcher(args).await?;
+  if let Some(path) = &args.archive {
+    crate::bundle::open(path)?;
+  }
+  let retry_queue = RetryQueue::new(failed);
+
+  let compat_index = args.compat.map(|version| {
+    args
+      .compat_index
+      .clone()
+      .unwrap_or_else(|| compat::gen_compat_index_path(&args.index, version))
+  });
+  let tmp_index = tmp_path(&args.index);
+  let tmp_compat_index = compat_index.as_deref().map(tmp_path);
+  let index_writer = IndexWriter::open(
+    &tmp_index,
+    args.index_format,
+    args.compat,
+    tmp_compat_index.as_deref(),
+    false,
+  )
+  .await?;
+  events.emit("run_started", json!({ "retry_failed": failed }));
+  crate::drain_retry_queue(args, &fetcher, events, &index_writer, &retry_queue).await?;
+  if let Some(cache) = fetcher.conditional_cache() {
+    cache.save().await?;
+  }
+  index_writer.flush().await?;
+
+  crate::output::merge_index(&args.index, &tmp_index, args.index_format).await?;
+  fs::remove_file(&tmp_index).await?;
+  if let (Some(compat_index), Some(tmp_compat_index)) = (&compat_index, &tmp_compat_index) {
+    compat::merge_index(compat_index, tmp_compat_index).await?;
+    fs::remove_file(tmp_compat_index).await?;
+  }
+  crate::write_index_exports(args).await?;
+  info!("[END] retry failed records");
+  Ok(())
+}
@@ -0,0 +1,144 @@
+//! 取得に失敗した詳細ページ・PDFを記録しておき、次回の実行で最初に取得し直すためのキュー
+//!
+//! キューは1行1件のJSON Lines形式のファイルで、失敗するたびに追記する。
+//! 次回の実行では`failed.jsonl.draining`のような名前に移してから読み込み、
+//! 取得し直し終えたら削除する。途中で落ちても両方のファイルを読み込むので記録は失われない。
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::{
+  path::Path,
+  time::{SystemTime, UNIX_EPOCH},
+};
+use tokio::{fs, io::AsyncWriteExt};
+
+/// 1件の取得に失敗したときの動作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OnError {
+  /// キューに記録して次の判例の取得を続ける
+  Skip,
+  /// キューに記録して実行を中止する
+  Fail,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FailureKind {
+  /// 詳細ページの取得・解析に失敗した
+  Detail,
+  /// 判決文のPDFの取得・テキスト抽出に失敗した
+  Pdf,
+  /// 判決文のPDFが`--skip-pdf-over`を超えるので後回しにした
+  LargePdf,
+  /// 書き出した裁判例のJSONが壊れていた（`repair`サブコマンドで記録する）
+  Corrupt,
+  /// リダイレクトが循環したり検索のトップページに戻されたりして、詳細ページを取得できなかった
+  Unreachable,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FailedRecord {
+  pub kind: FailureKind,
+  /// 失敗したURL
+  pub url: String,
+  /// 取得し直すときに使う詳細ページのURL
+  pub detail_page_link: String,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub lawsuit_id: Option<String>,
+  pub error: String,
+  /// 失敗した時刻（UNIX時間、秒）
+  pub failed_at: u64,
+}
+
+impl FailedRecord {
+  pub fn new(
+    kind: FailureKind,
+    url: &str,
+    detail_page_link: &str,
+    lawsuit_id: Option<&str>,
+    error: &anyhow::Error,
+  ) -> Self {
+    let failed_at = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|d| d.as_secs())
+      .unwrap_or_default();
+    FailedRecord {
+      kind,
+      url: url.to_string(),
+      detail_page_link: detail_page_link.to_string(),
+      lawsuit_id: lawsuit_id.map(|s| s.to_string()),
+      error: format!("{error:#}"),
+      failed_at,
+    }
+  }
+}
+
+pub struct RetryQueue {
+  path: String,
+}
+
+fn draining_path(path: &str) -> String {
+  format!("{path}.draining")
+}
+
+async fn read_records(path: &str) -> Result<Vec<FailedRecord>> {
+  if !Path::new(path).exists() {
+    return Ok(Vec::new());
+  }
+  let s = fs::read_to_string(path).await?;
+  let mut records = Vec::new();
+  for line in s.lines().filter(|l| !l.trim().is_empty()) {
+    records.push(serde_json::from_str(line)?);
+  }
+  Ok(records)
+}
+
+impl RetryQueue {
+  pub fn new(path: &str) -> Self {
+    RetryQueue {
+      path: path.to_string(),
+    }
+  }
+
+  /// 前回までに失敗したものを取り出す。同じ詳細ページは1回だけ取得し直せば良いのでまとめる
+  pub async fn take(&self) -> Result<Vec<FailedRecord>> {
+    let draining = draining_path(&self.path);
+    let mut records = read_records(&draining).await?;
+    if Path::new(&self.path).exists() {
+      records.extend(read_records(&self.path).await?);
+      let s = records
+        .iter()
+        .map(serde_json::to_string)
+        .collect::<Result<Vec<_>, _>>()?
+        .join("\n");
+      fs::write(&draining, s).await?;
+      fs::remove_file(&self.path).await?;
+    }
+    let mut seen = std::collections::HashSet::new();
+    records.retain(|r| seen.insert(r.detail_page_link.clone()));
+    Ok(records)
+  }
+
+  /// 取り出したものの取得し直しが終わったことを記録する
+  pub async fn finish_draining(&self) -> Result<()> {
+    let draining = draining_path(&self.path);
+    if Path::new(&draining).exists() {
+      fs::remove_file(draining).await?;
+    }
+    Ok(())
+  }
+
+  pub async fn push(&self, record: &FailedRecord) -> Result<()> {
+    let mut file = fs::OpenOptions::new()
+      .create(true)
+      .append(true)
+      .open(&self.path)
+      .await?;
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+    file.write_all(line.as_bytes()).await?;
+    file.flush().await?;
+    Ok(())
+  }
+}
@@ -0,0 +1,212 @@
+//! robots.txtのDisallow・Allow・Crawl-delayの解釈
+
+use regex::Regex;
+use std::time::Duration;
+use tracing::*;
+
+#[derive(Debug, Clone)]
+struct Rule {
+  allow: bool,
+  pattern: String,
+  re: Regex,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Group {
+  agents: Vec<String>,
+  rules: Vec<Rule>,
+  crawl_delay: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RobotsPolicy {
+  rules: Vec<Rule>,
+  crawl_delay: Option<Duration>,
+}
+
+/// `*`と末尾の`$`を解釈してpathにマッチする正規表現を作る
+fn pattern_to_regex(pattern: &str) -> Option<Regex> {
+  let (pattern, anchored) = match pattern.strip_suffix('$') {
+    Some(p) => (p, true),
+    None => (pattern, false),
+  };
+  let body = pattern
+    .split('*')
+    .map(regex::escape)
+    .collect::<Vec<_>>()
+    .join(".*");
+  let re = if anchored {
+    format!("^{body}$")
+  } else {
+    format!("^{body}")
+  };
+  Regex::new(&re).ok()
+}
+
+impl RobotsPolicy {
+  /// 何も制限しないポリシー
+  pub fn allow_all() -> Self {
+    RobotsPolicy::default()
+  }
+
+  /// robots.txtの内容から`user_agent`に適用されるポリシーを作る
+  ///
+  /// `user_agent`を含む名前のグループがあればそれを、無ければ`*`のグループを使う
+  pub fn parse(text: &str, user_agent: &str) -> Self {
+    let mut groups: Vec<Group> = Vec::new();
+    let mut reading_agents = false;
+    for line in text.lines() {
+      let line = line.split('#').next().unwrap_or_default().trim();
+      let Some((key, value)) = line.split_once(':') else {
+        continue;
+      };
+      let key = key.trim().to_ascii_lowercase();
+      let value = value.trim();
+      match &*key {
+        "user-agent" => {
+          if !reading_agents {
+            groups.push(Group::default());
+            reading_agents = true;
+          }
+          if let Some(group) = groups.last_mut() {
+            group.agents.push(value.to_ascii_lowercase());
+          }
+        }
+        "allow" | "disallow" => {
+          reading_agents = false;
+          let Some(group) = groups.last_mut() else {
+            continue;
+          };
+          // 空のDisallowは「すべて許可」の意味なので規則として扱わない
+          if value.is_empty() {
+            continue;
+          }
+          if let Some(re) = pattern_to_regex(value) {
+            group.rules.push(Rule {
+              allow: key == "allow",
+              pattern: value.to_string(),
+              re,
+            });
+          }
+        }
+        "crawl-delay" => {
+          reading_agents = false;
+          if let (Some(group), Ok(secs)) = (groups.last_mut(), value.parse::<f64>()) {
+            if secs.is_finite() && secs >= 0.0 {
+              group.crawl_delay = Some(Duration::from_secs_f64(secs));
+            }
+          }
+        }
+        _ => reading_agents = false,
+      }
+    }
+    let user_agent = user_agent.to_ascii_lowercase();
+    let group = groups
+      .iter()
+      .find(|g| {
+        g.agents
+          .iter()
+          .any(|a| a != "*" && !a.is_empty() && user_agent.contains(a.as_str()))
+      })
+      .or_else(|| groups.iter().find(|g| g.agents.iter().any(|a| a == "*")));
+    match group {
+      Some(group) => RobotsPolicy {
+        rules: group.rules.clone(),
+        crawl_delay: group.crawl_delay,
+      },
+      None => RobotsPolicy::allow_all(),
+    }
+  }
+
+  pub fn crawl_delay(&self) -> Option<Duration> {
+    self.crawl_delay
+  }
+
+  /// 最も長くマッチした規則に従う。同じ長さならAllowを優先する
+  pub fn is_allowed(&self, path: &str) -> bool {
+    let mut best: Option<&Rule> = None;
+    for rule in self.rules.iter().filter(|r| r.re.is_match(path)) {
+      best = match best {
+        Some(b)
+          if b.pattern.len() > rule.pattern.len()
+            || (b.pattern.len() == rule.pattern.len() && b.allow) =>
+        {
+          Some(b)
+        }
+        _ => Some(rule),
+      };
+    }
+    best.map(|r| r.allow).unwrap_or(true)
+  }
+
+  /// 適用したポリシーを監査用にログへ出す
+  pub fn log_summary(&self) {
+    info!(
+      "robots.txt policy: {} rule(s), crawl-delay: {}",
+      self.rules.len(),
+      self
+        .crawl_delay
+        .map(|d| format!("{}ms", d.as_millis()))
+        .unwrap_or_else(|| "none".to_string())
+    );
+    for rule in &self.rules {
+      info!(
+        "robots.txt rule: {} {}",
+        if rule.allow { "Allow" } else { "Disallow" },
+        rule.pattern
+      );
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const ROBOTS: &str = "\
+User-agent: Googlebot
+Disallow: /
+
+User-agent: *
+Disallow: /app/hanrei_jp/
+Allow: /app/hanrei_jp/detail2
+Disallow: /*.pdf$
+Crawl-delay: 2.5 # 秒
+";
+
+  #[test]
+  fn longest_match_wins() {
+    let policy = RobotsPolicy::parse(ROBOTS, "listup_precedent/0.1");
+    assert!(!policy.is_allowed("/app/hanrei_jp/list1?page=1"));
+    assert!(policy.is_allowed("/app/hanrei_jp/detail2?id=99999"));
+    assert!(policy.is_allowed("/app/search"));
+    assert_eq!(policy.crawl_delay(), Some(Duration::from_millis(2500)));
+  }
+
+  #[test]
+  fn wildcard_and_end_anchor() {
+    let policy = RobotsPolicy::parse(ROBOTS, "listup_precedent/0.1");
+    assert!(!policy.is_allowed("/app/files/hanrei_jp/999/099999_hanrei.pdf"));
+    assert!(policy.is_allowed("/app/files/hanrei_jp/999/099999_hanrei.pdf?download=1"));
+  }
+
+  #[test]
+  fn allow_wins_ties() {
+    let policy = RobotsPolicy::parse("User-agent: *\nDisallow: /a\nAllow: /a\n", "bot");
+    assert!(policy.is_allowed("/a"));
+  }
+
+  #[test]
+  fn picks_group_by_user_agent() {
+    let policy = RobotsPolicy::parse(ROBOTS, "Mozilla/5.0 (compatible; Googlebot/2.1)");
+    assert!(!policy.is_allowed("/app/search"));
+    assert_eq!(policy.crawl_delay(), None);
+  }
+
+  #[test]
+  fn empty_disallow_allows_everything() {
+    let policy = RobotsPolicy::parse("User-agent: *\nDisallow:\n", "bot");
+    assert!(policy.is_allowed("/app/hanrei_jp/list1"));
+    assert!(RobotsPolicy::parse("", "bot").is_allowed("/"));
+  }
+}
@@ -0,0 +1,141 @@
+//! GUIフロントエンドのバックエンドとして使うための、標準入出力上のJSON-RPC 2.0インタフェース
+//!
+//! 1行に1つのリクエストを標準入力から読み、1行に1つのレスポンスを標準出力に書く。
+//! 取得中の進捗は`event`メソッドの通知として流れる。
+//!
+//! | メソッド | パラメータ | 結果 |
+//! |---|---|---|
+//! | `version` | なし | `{"version": "..."}` |
+//! | `crawl` | `{"start": "yyyy/mm/dd", "end": "yyyy/mm/dd"}`または`{"recent": true}` | `null` |
+//! | `get_record` | `{"detail_page_link": "..."}` | 判例の情報（ファイルには書き出さない） |
+//! | `shutdown` | なし | `null`（レスポンスを返したあと終了する） |
+
+use crate::{events::Events, meta::RecordMeta, Args};
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::io::Write;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tracing::*;
+
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const SERVER_ERROR: i64 = -32000;
+
+fn write_line(value: &Value) {
+  let mut stdout = std::io::stdout().lock();
+  let _ = writeln!(stdout, "{value}");
+  let _ = stdout.flush();
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Value {
+  json!({
+    "jsonrpc": "2.0",
+    "id": id,
+    "error": { "code": code, "message": message },
+  })
+}
+
+fn str_param<'a>(params: &'a Value, name: &str) -> Option<&'a str> {
+  params.get(name).and_then(|v| v.as_str())
+}
+
+async fn crawl(args: &Args, params: &Value) -> Result<Value, (i64, String)> {
+  let mut args = args.clone();
+  if params.get("recent").and_then(|v| v.as_bool()) == Some(true) {
+    args.recent = true;
+    args.start = None;
+    args.end = None;
+  } else {
+    match (str_param(params, "start"), str_param(params, "end")) {
+      (Some(start), Some(end)) => {
+        args.recent = false;
+        args.start = Some(start.to_string());
+        args.end = Some(end.to_string());
+      }
+      _ => {
+        return Err((
+          INVALID_PARAMS,
+          "startとend、またはrecentを指定してください".to_string(),
+        ))
+      }
+    }
+  }
+  crate::run(&args, &Events::rpc())
+    .await
+    .map_err(|e| (SERVER_ERROR, format!("{e:#}")))?;
+  Ok(Value::Null)
+}
+
+async fn get_record(args: &Args, params: &Value) -> Result<Value, (i64, String)> {
+  let detail_page_link = str_param(params, "detail_page_link").ok_or_else(|| {
+    (
+      INVALID_PARAMS,
+      "detail_page_linkを指定してください".to_string(),
+    )
+  })?;
+  let fetch = async {
+    let fetcher = crate::build_fetcher(args).await?;
+    let trial_type = crate::trial_type_from_link(detail_page_link)?;
+    let lawsuit_id = crate::get_lawsuit_id(detail_page_link).await?;
+    let html = fetcher.get_text(detail_page_link).await?;
+    let mut data =
+      crate::parse_detail_page(&html, trial_type, lawsuit_id, detail_page_link.to_string()).await?;
+    let mut meta = RecordMeta::new();
+    data.contents = crate::get_pdf_text(
+      &fetcher,
+      &data.full_pdf_link,
+      false,
+      args.pdf_fallback,
+      &mut meta,
+    )
+    .await?
+    .map(|text| crate::furigana::normalize(&text, args.furigana).0);
+    Ok::<_, anyhow::Error>(serde_json::to_value(&data)?)
+  };
+  fetch.await.map_err(|e| (SERVER_ERROR, format!("{e:#}")))
+}
+
+/// 標準入力が閉じられるか`shutdown`が呼ばれるまでリクエストを処理し続ける
+pub async fn serve(args: &Args) -> Result<()> {
+  let mut lines = BufReader::new(tokio::io::stdin()).lines();
+  while let Some(line) = lines.next_line().await? {
+    if line.trim().is_empty() {
+      continue;
+    }
+    let request: Value = match serde_json::from_str(&line) {
+      Ok(v) => v,
+      Err(e) => {
+        write_line(&error_response(Value::Null, PARSE_ERROR, &e.to_string()));
+        continue;
+      }
+    };
+    let id = request.get("id").cloned().unwrap_or(Value::Null);
+    let Some(method) = request.get("method").and_then(|v| v.as_str()) else {
+      write_line(&error_response(id, INVALID_REQUEST, "methodがありません"));
+      continue;
+    };
+    let params = request.get("params").cloned().unwrap_or(Value::Null);
+    info!("rpc: {method}");
+    let result = match method {
+      "version" => Ok(json!({ "version": env!("CARGO_PKG_VERSION") })),
+      "crawl" => crawl(args, &params).await,
+      "get_record" => get_record(args, &params).await,
+      "shutdown" => Ok(Value::Null),
+      _ => Err((METHOD_NOT_FOUND, format!("未知のメソッドです：{method}"))),
+    };
+    // idの無いリクエストは通知なのでレスポンスを返さない
+    if request.get("id").is_some() {
+      let response = match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err((code, message)) => error_response(id, code, &message),
+      };
+      write_line(&response);
+    }
+    if method == "shutdown" {
+      break;
+    }
+  }
+  Ok(())
+}
@@ -0,0 +1,256 @@
+//! `--cron`で与えたcron式に従って、外部のcronなしで定期的に取得を繰り返す仕組み
+//!
+//! cron式は「分 時 日 月 曜日」の5つのフィールドで、それぞれ`*`・`3`・`1-5`・`*/15`・`1,15`の形とその組み合わせを書ける。
+//! 曜日は0（日曜日）から7（日曜日）で与える。日と曜日の両方が`*`以外のときは、どちらかに当てはまる日に実行する。
+//! 時刻はローカルタイムで解釈する。
+
+use crate::{events::Events, shutdown, Args};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Duration, Local, Timelike};
+use serde_json::json;
+use std::str::FromStr;
+use tracing::*;
+
+/// 次の実行時刻を探す範囲（分）。2月29日だけに当てはまる式も見つけられるよう5年分にする
+const SEARCH_LIMIT_MINUTES: i64 = 5 * 366 * 24 * 60;
+
+/// 1つのフィールドで当てはまる値の一覧
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Field {
+  values: Vec<bool>,
+  /// `*`で与えられた（日と曜日の組み合わせ方の判定に使う）
+  any: bool,
+}
+
+impl Field {
+  fn parse(s: &str, min: u32, max: u32) -> Result<Self> {
+    let mut values = vec![false; max as usize + 1];
+    for part in s.split(',') {
+      let (range, step) = match part.split_once('/') {
+        Some((range, step)) => (range, step.parse::<u32>()?),
+        None => (part, 1),
+      };
+      if step == 0 {
+        return Err(anyhow!("cron式の間隔に0は使えません：{part}"));
+      }
+      let (from, to) = match range {
+        "*" => (min, max),
+        _ => match range.split_once('-') {
+          Some((from, to)) => (from.parse()?, to.parse()?),
+          None if step == 1 => (range.parse()?, range.parse()?),
+          None => (range.parse()?, max),
+        },
+      };
+      if from < min || to > max || from > to {
+        return Err(anyhow!(
+          "cron式の値が範囲（{min}-{max}）の外にあります：{part}"
+        ));
+      }
+      for v in (from..=to).step_by(step as usize) {
+        values[v as usize] = true;
+      }
+    }
+    Ok(Field {
+      values,
+      any: s == "*",
+    })
+  }
+
+  fn matches(&self, v: u32) -> bool {
+    self.values.get(v as usize).copied().unwrap_or(false)
+  }
+}
+
+/// 5つのフィールドからなるcron式
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSchedule {
+  expr: String,
+  minute: Field,
+  hour: Field,
+  day: Field,
+  month: Field,
+  weekday: Field,
+}
+
+impl FromStr for CronSchedule {
+  type Err = anyhow::Error;
+
+  fn from_str(s: &str) -> Result<Self> {
+    let fields = s.split_whitespace().collect::<Vec<_>>();
+    let [minute, hour, day, month, weekday] = fields[..] else {
+      return Err(anyhow!(
+        "cron式は「分 時 日 月 曜日」の5つのフィールドで与えてください：{s}"
+      ));
+    };
+    let mut weekday = Field::parse(weekday, 0, 7)?;
+    // 7も日曜日として扱う
+    if weekday.values[7] {
+      weekday.values[0] = true;
+    }
+    Ok(CronSchedule {
+      expr: s.to_string(),
+      minute: Field::parse(minute, 0, 59)?,
+      hour: Field::parse(hour, 0, 23)?,
+      day: Field::parse(day, 1, 31)?,
+      month: Field::parse(month, 1, 12)?,
+      weekday,
+    })
+  }
+}
+
+impl CronSchedule {
+  fn matches_day(&self, t: &DateTime<Local>) -> bool {
+    let day = self.day.matches(t.day());
+    let weekday = self.weekday.matches(t.weekday().num_days_from_sunday());
+    match (self.day.any, self.weekday.any) {
+      (false, false) => day || weekday,
+      _ => day && weekday,
+    }
+  }
+
+  /// `after`より後で最初に当てはまる時刻
+  pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+    let start = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+    let mut t = start;
+    while t - start < Duration::minutes(SEARCH_LIMIT_MINUTES) {
+      if !self.month.matches(t.month()) || !self.matches_day(&t) {
+        // 次の日の0時0分まで飛ばす
+        t = t + Duration::minutes(i64::from(24 * 60 - t.hour() * 60 - t.minute()));
+        continue;
+      }
+      if self.hour.matches(t.hour()) && self.minute.matches(t.minute()) {
+        return Some(t);
+      }
+      t = t + Duration::minutes(1);
+    }
+    None
+  }
+}
+
+/// 終了が要求されるまで、`schedule`の時刻になるたびに取得を実行する
+///
+/// 1回の実行が失敗しても、記録して次の時刻を待つ
+pub async fn run_scheduled(args: &Args, events: &Events, schedule: &CronSchedule) -> Result<()> {
+  loop {
+    let next = schedule
+      .next_after(Local::now())
+      .ok_or_else(|| anyhow!("cron式に当てはまる時刻がありません：{}", schedule.expr))?;
+    info!("next run: {} ({})", next.to_rfc3339(), schedule.expr);
+    events.emit(
+      "schedule_waiting",
+      json!({ "next_run": next.to_rfc3339(), "cron": &schedule.expr }),
+    );
+    while Local::now() < next {
+      if shutdown::requested() {
+        return Ok(());
+      }
+      let rest = (next - Local::now()).to_std().unwrap_or_default();
+      tokio::time::sleep(rest.min(std::time::Duration::from_secs(1))).await;
+    }
+    match crate::run(args, events).await {
+      Ok(()) => info!("scheduled run finished"),
+      Err(e) if shutdown::requested() => return Err(e),
+      Err(e) => {
+        warn!("scheduled run failed: {e:#}");
+        events.emit(
+          "error",
+          json!({ "message": format!("{e:#}"), "fatal": false }),
+        );
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::TimeZone;
+
+  fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Local> {
+    Local.with_ymd_and_hms(y, m, d, h, min, 0).single().unwrap()
+  }
+
+  fn values(field: &Field) -> Vec<u32> {
+    (0..field.values.len() as u32)
+      .filter(|v| field.matches(*v))
+      .collect()
+  }
+
+  #[test]
+  fn parses_steps_ranges_and_lists() {
+    assert_eq!(
+      values(&Field::parse("*/15", 0, 59).unwrap()),
+      [0, 15, 30, 45]
+    );
+    assert_eq!(values(&Field::parse("1-5", 0, 7).unwrap()), [1, 2, 3, 4, 5]);
+    assert_eq!(
+      values(&Field::parse("10-20/5", 0, 59).unwrap()),
+      [10, 15, 20]
+    );
+    assert_eq!(values(&Field::parse("50/5", 0, 59).unwrap()), [50, 55]);
+    assert_eq!(values(&Field::parse("1,15", 1, 31).unwrap()), [1, 15]);
+    assert!(Field::parse("*", 0, 23).unwrap().any);
+    assert!(!Field::parse("*/2", 0, 23).unwrap().any);
+  }
+
+  #[test]
+  fn rejects_invalid_fields() {
+    assert!(Field::parse("*/0", 0, 59).is_err());
+    assert!(Field::parse("60", 0, 59).is_err());
+    assert!(Field::parse("5-3", 0, 59).is_err());
+    assert!(Field::parse("0", 1, 31).is_err());
+    assert!("0 0 * *".parse::<CronSchedule>().is_err());
+  }
+
+  #[test]
+  fn treats_weekday_7_as_sunday() {
+    let schedule = "0 9 * * 7".parse::<CronSchedule>().unwrap();
+    // 2024-03-01は金曜日
+    assert_eq!(
+      schedule.next_after(at(2024, 3, 1, 10, 0)),
+      Some(at(2024, 3, 3, 9, 0))
+    );
+  }
+
+  #[test]
+  fn next_after_is_strictly_later() {
+    let schedule = "*/15 * * * *".parse::<CronSchedule>().unwrap();
+    assert_eq!(
+      schedule.next_after(at(2024, 3, 1, 10, 15)),
+      Some(at(2024, 3, 1, 10, 30))
+    );
+    assert_eq!(
+      schedule.next_after(at(2024, 3, 1, 23, 50)),
+      Some(at(2024, 3, 2, 0, 0))
+    );
+  }
+
+  #[test]
+  fn day_and_weekday_are_ored_when_both_restricted() {
+    // 13日か金曜日
+    let schedule = "0 0 13 * 5".parse::<CronSchedule>().unwrap();
+    assert_eq!(
+      schedule.next_after(at(2024, 3, 1, 10, 0)),
+      Some(at(2024, 3, 8, 0, 0))
+    );
+    assert_eq!(
+      schedule.next_after(at(2024, 3, 8, 0, 0)),
+      Some(at(2024, 3, 13, 0, 0))
+    );
+    // 曜日が`*`なら日だけで決まる
+    let schedule = "0 0 13 * *".parse::<CronSchedule>().unwrap();
+    assert_eq!(
+      schedule.next_after(at(2024, 3, 1, 10, 0)),
+      Some(at(2024, 3, 13, 0, 0))
+    );
+  }
+
+  #[test]
+  fn finds_leap_day() {
+    let schedule = "0 0 29 2 *".parse::<CronSchedule>().unwrap();
+    assert_eq!(
+      schedule.next_after(at(2024, 3, 1, 0, 0)),
+      Some(at(2028, 2, 29, 0, 0))
+    );
+  }
+}
@@ -0,0 +1,166 @@
+//! 出力の形式をJSON Schemaとして書き出す`schema`サブコマンド
+//!
+//! `PrecedentData`・`PrecedentInfo`は`jplaw_data_types`の型で`JsonSchema`を実装していないので、
+//! 同じ形の型をここに用意してschemarsでスキーマを生成する。
+//! 元の型にフィールドが増えたり減ったりすると[`check_fields`]がコンパイルできなくなるので、食い違ったまま公開されることはない。
+//!
+//! このツールが付け加える`uuid`・`_meta`などのフィールドは含めず、追加のフィールドは許すスキーマにする。
+
+// スキーマを生成するためだけの型なので、値は作らない
+#![allow(dead_code)]
+
+use anyhow::Result;
+use clap::ValueEnum;
+use jplaw_data_types::listup;
+use schemars::{schema_for, JsonSchema};
+use std::io::Write;
+
+/// スキーマを書き出す型
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SchemaTarget {
+  /// 裁判例ごとのファイル
+  Data,
+  /// 一覧ファイルの項目
+  Info,
+  /// `--format protobuf`の`.proto`ファイル（JSON Schemaではない）
+  Proto,
+}
+
+/// 裁判の種類
+#[derive(JsonSchema)]
+enum TrialType {
+  SupremeCourt,
+  HighCourt,
+  LowerCourt,
+  AdministrativeCase,
+  LaborCase,
+  IPCase,
+}
+
+/// 元号
+#[derive(JsonSchema)]
+enum Era {
+  Showa,
+  Heisei,
+  Reiwa,
+}
+
+/// 元号付きの日付
+#[derive(JsonSchema)]
+struct Date {
+  era: Era,
+  /// その元号の何年か
+  year: usize,
+  month: Option<usize>,
+  day: Option<usize>,
+}
+
+/// 一覧ファイルの項目
+#[derive(JsonSchema)]
+struct PrecedentInfo {
+  /// 事件に振られているID
+  lawsuit_id: String,
+  trial_type: TrialType,
+  /// 裁判年月日
+  date: Date,
+  /// 事件番号
+  case_number: String,
+  /// 裁判所・部・法廷名
+  court_name: String,
+}
+
+/// 裁判例ごとのファイル
+#[derive(JsonSchema)]
+struct PrecedentData {
+  trial_type: TrialType,
+  /// 裁判年月日
+  date: Date,
+  /// 事件番号
+  case_number: String,
+  /// 事件名
+  case_name: String,
+  /// 裁判所・部・法廷名
+  court_name: String,
+  /// 争われた対象の権利の種別
+  right_type: Option<String>,
+  /// 訴訟類型
+  lawsuit_type: Option<String>,
+  /// 判決の種別
+  result_type: Option<String>,
+  /// 結果
+  result: Option<String>,
+  /// 判例集等巻・号・頁
+  article_info: Option<String>,
+  /// 原審裁判所名
+  original_court_name: Option<String>,
+  /// 原審事件番号
+  original_case_number: Option<String>,
+  /// 原審結果
+  original_result: Option<String>,
+  /// 原審裁判年月日
+  original_date: Option<Date>,
+  /// 分野
+  field: Option<String>,
+  /// 判示事項の要旨
+  gist: Option<String>,
+  /// 裁判要旨
+  case_gist: Option<String>,
+  /// 参照法条
+  ref_law: Option<String>,
+  /// 事件に振られているID
+  lawsuit_id: String,
+  /// 詳細が載っているページのリンク
+  detail_page_link: String,
+  /// 判決文の本文
+  contents: Option<String>,
+  /// 判決文全文のPDFのリンク
+  full_pdf_link: String,
+}
+
+/// 元の型のフィールドをすべて取り出す。フィールドの構成が変わるとここがコンパイルできなくなる
+fn check_fields(data: listup::PrecedentData, info: listup::PrecedentInfo) {
+  let listup::PrecedentData {
+    trial_type: _,
+    date: _,
+    case_number: _,
+    case_name: _,
+    court_name: _,
+    right_type: _,
+    lawsuit_type: _,
+    result_type: _,
+    result: _,
+    article_info: _,
+    original_court_name: _,
+    original_case_number: _,
+    original_result: _,
+    original_date: _,
+    field: _,
+    gist: _,
+    case_gist: _,
+    ref_law: _,
+    lawsuit_id: _,
+    detail_page_link: _,
+    contents: _,
+    full_pdf_link: _,
+  } = data;
+  let listup::PrecedentInfo {
+    lawsuit_id: _,
+    trial_type: _,
+    date: _,
+    case_number: _,
+    court_name: _,
+  } = info;
+}
+
+/// `target`のJSON Schema（`proto`では`.proto`ファイル）を標準出力に書く
+pub fn print(target: SchemaTarget) -> Result<()> {
+  let text = match target {
+    SchemaTarget::Data => serde_json::to_string_pretty(&schema_for!(PrecedentData))?,
+    SchemaTarget::Info => serde_json::to_string_pretty(&schema_for!(PrecedentInfo))?,
+    SchemaTarget::Proto => crate::protobuf::PROTO.trim_end().to_string(),
+  };
+  let mut stdout = std::io::stdout().lock();
+  writeln!(stdout, "{text}")?;
+  stdout.flush()?;
+  Ok(())
+}
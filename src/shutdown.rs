@@ -0,0 +1,49 @@
+//! Ctrl-C（SIGINT）・SIGTERMを受け取ったときに、処理中のレコードを書き終えてから一覧を閉じて終了するための仕組み
+//!
+//! 1回目のシグナルでは終了の要求を記録するだけで、取得のループの側が区切りの良いところで止まる。
+//! 2回目のシグナルを受け取ったらその場で終了する。
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use tracing::*;
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+/// 終了が要求されているかどうか
+pub fn requested() -> bool {
+  REQUESTED.load(Ordering::SeqCst)
+}
+
+#[cfg(unix)]
+async fn wait_signal() {
+  use tokio::signal::unix::{signal, SignalKind};
+  match signal(SignalKind::terminate()) {
+    Ok(mut sigterm) => {
+      tokio::select! {
+        _ = tokio::signal::ctrl_c() => {}
+        _ = sigterm.recv() => {}
+      }
+    }
+    Err(_) => {
+      let _ = tokio::signal::ctrl_c().await;
+    }
+  }
+}
+
+#[cfg(not(unix))]
+async fn wait_signal() {
+  let _ = tokio::signal::ctrl_c().await;
+}
+
+/// シグナルを待ち受けるタスクを起動する
+pub fn install() {
+  tokio::spawn(async {
+    wait_signal().await;
+    warn!(
+      "shutdown requested: finishing the current record (press Ctrl-C again to abort immediately)"
+    );
+    REQUESTED.store(true, Ordering::SeqCst);
+    wait_signal().await;
+    error!("aborted");
+    std::process::exit(130);
+  });
+}
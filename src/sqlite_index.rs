@@ -0,0 +1,155 @@
+//! 一覧と裁判例のJSONのSQLiteデータベースへの出力
+//!
+//! 一覧ファイルの各項目を`precedents`テーブルの1行にし、出力フォルダに裁判例のJSONがあれば要旨や本文の列も埋める。
+//! `--sqlite-fts`を与えると、`contents`・`gist`・`case_gist`を対象にしたFTS5の仮想テーブル`precedents_fts`も作る。
+//! 日本語は単語の間に空白が無いので、トークナイザには3文字ずつに区切る`trigram`を使う。
+//! 全文検索のテーブルを作るのはこの`--index-sqlite`のデータベースだけで、`--writer sqlite`の`records.sqlite`には作らない。
+//!
+//! ```sql
+//! SELECT p.case_number, p.case_name FROM precedents_fts f
+//!   JOIN precedents p ON p.rowid = f.rowid
+//!   WHERE precedents_fts MATCH '信義則上の義務';
+//! ```
+
+use crate::{compat, output};
+use anyhow::Result;
+use jplaw_data_types::listup::{PrecedentData, PrecedentInfo};
+use rusqlite::{params, Connection};
+use serde_json::Value;
+use std::path::Path;
+use tracing::*;
+
+const CREATE_TABLE: &str = "CREATE TABLE precedents (
+  lawsuit_id TEXT PRIMARY KEY,
+  uuid TEXT,
+  case_number TEXT NOT NULL,
+  case_name TEXT,
+  court_name TEXT NOT NULL,
+  trial_type TEXT NOT NULL,
+  date TEXT NOT NULL,
+  lawsuit_type TEXT,
+  result_type TEXT,
+  result TEXT,
+  field TEXT,
+  gist TEXT,
+  case_gist TEXT,
+  ref_law TEXT,
+  detail_page_link TEXT,
+  full_pdf_link TEXT,
+  contents TEXT
+)";
+
+/// 本文を持たずに`precedents`の列を参照する外部コンテンツのFTS5テーブル
+const CREATE_FTS: &str = "CREATE VIRTUAL TABLE precedents_fts USING fts5(
+  contents, gist, case_gist,
+  content = 'precedents', content_rowid = 'rowid', tokenize = 'trigram'
+)";
+
+/// 文字列のフィールドはそのまま、それ以外はJSONとして文字列にする
+fn field_text(value: &Value) -> String {
+  match value {
+    Value::String(s) => s.clone(),
+    v => v.to_string(),
+  }
+}
+
+/// 裁判年月日を西暦の`yyyy-mm-dd`（月日が無ければその部分を省く）にする
+fn date_text(info: &PrecedentInfo) -> String {
+  let year = compat::era_to_ad_year(&info.date.era, info.date.year);
+  match (info.date.month, info.date.day) {
+    (Some(month), Some(day)) => format!("{year:04}-{month:02}-{day:02}"),
+    (Some(month), None) => format!("{year:04}-{month:02}"),
+    _ => format!("{year:04}"),
+  }
+}
+
+/// 一覧の項目と、出力フォルダにあればその裁判例のデータ
+type Row = (Value, PrecedentInfo, Option<PrecedentData>);
+
+/// `index`の一覧ファイルと`output`の裁判例のJSONを、`path`のSQLiteデータベースに書き出す。書き出した件数を返す
+///
+/// 既存のデータベースは作り直す。途中で失敗しても既存のデータベースが残るよう、`.tmp`を付けたファイルに作ってから名前を変える。
+/// rusqliteの呼び出しは待ちが生じるので、tokioのランタイムを止めないよう`spawn_blocking`で実行する
+pub async fn write(index: &str, output: &str, path: &str, fts: bool) -> Result<usize> {
+  let entries = output::read_value_lst(index).await?;
+  let mut rows = Vec::with_capacity(entries.len());
+  let mut missing = 0;
+  for entry in entries {
+    let info: PrecedentInfo = serde_json::from_value(entry.clone())?;
+    let file_name = crate::partition::record_name(&info);
+    let data: Option<PrecedentData> = if output::data_exists(output, &file_name) {
+      Some(output::read_data(output, &file_name).await?)
+    } else {
+      missing += 1;
+      None
+    };
+    rows.push((entry, info, data));
+  }
+  if missing > 0 {
+    warn!("{missing} record file(s) not found; only index fields were written to sqlite");
+  }
+
+  let len = rows.len();
+  let path = path.to_string();
+  tokio::task::spawn_blocking(move || {
+    let tmp = output::tmp_path(&path);
+    if Path::new(&tmp).exists() {
+      std::fs::remove_file(&tmp)?;
+    }
+    match build(&rows, &tmp, fts) {
+      Ok(()) => {
+        std::fs::rename(&tmp, &path)?;
+        Ok(())
+      }
+      Err(e) => {
+        let _ = std::fs::remove_file(&tmp);
+        Err(e)
+      }
+    }
+  })
+  .await??;
+  Ok(len)
+}
+
+/// `rows`を`path`の新しいデータベースに書く
+fn build(rows: &[Row], path: &str, fts: bool) -> Result<()> {
+  let mut conn = Connection::open(path)?;
+  let tx = conn.transaction()?;
+  tx.execute(CREATE_TABLE, [])?;
+  {
+    let mut insert = tx.prepare(
+      "INSERT OR REPLACE INTO precedents VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+    )?;
+    for (entry, info, data) in rows {
+      let data = data.as_ref();
+      insert.execute(params![
+        &info.lawsuit_id,
+        entry.get("uuid").and_then(|v| v.as_str()),
+        &info.case_number,
+        data.map(|d| &d.case_name),
+        &info.court_name,
+        field_text(&serde_json::to_value(&info.trial_type)?),
+        date_text(info),
+        data.and_then(|d| d.lawsuit_type.as_ref()),
+        data.and_then(|d| d.result_type.as_ref()),
+        data.and_then(|d| d.result.as_ref()),
+        data.and_then(|d| d.field.as_ref()),
+        data.and_then(|d| d.gist.as_ref()),
+        data.and_then(|d| d.case_gist.as_ref()),
+        data.and_then(|d| d.ref_law.as_ref()),
+        data.map(|d| &d.detail_page_link),
+        data.map(|d| &d.full_pdf_link),
+        data.and_then(|d| d.contents.as_ref()),
+      ])?;
+    }
+  }
+  if fts {
+    tx.execute(CREATE_FTS, [])?;
+    tx.execute(
+      "INSERT INTO precedents_fts(precedents_fts) VALUES ('rebuild')",
+      [],
+    )?;
+  }
+  tx.commit()?;
+  Ok(())
+}
@@ -0,0 +1,39 @@
+//! サイトの都合で変わりうる`lawsuit_id`の代わりに、データ統合の主キーとして使える安定したUUID
+//!
+//! 事件番号・裁判年月日（西暦）・裁判所名を正規化してつなげたものから、UUID v5を導出する。
+//! 同じ判決は何度取得しても同じUUIDになる。
+
+use crate::compat::era_to_ad_year;
+use jplaw_data_types::listup::PrecedentData;
+use uuid::Uuid;
+
+/// UUIDの名前空間。`https://www.courts.go.jp/`をURLの名前空間で変換したもの
+fn namespace() -> Uuid {
+  Uuid::new_v5(&Uuid::NAMESPACE_URL, b"https://www.courts.go.jp/")
+}
+
+/// 全角の英数字を半角にし、空白を取り除く
+pub fn normalize(s: &str) -> String {
+  s.chars()
+    .filter(|c| !c.is_whitespace())
+    .map(|c| match c {
+      '０'..='９' | 'Ａ'..='Ｚ' | 'ａ'..='ｚ' => {
+        char::from_u32(c as u32 - 0xFEE0).unwrap_or(c)
+      }
+      _ => c,
+    })
+    .collect()
+}
+
+pub fn uuid(data: &PrecedentData) -> Uuid {
+  let date = &data.date;
+  let name = format!(
+    "{}|{:04}-{:02}-{:02}|{}",
+    normalize(&data.case_number),
+    era_to_ad_year(&date.era, date.year),
+    date.month.unwrap_or(0),
+    date.day.unwrap_or(0),
+    normalize(&data.court_name)
+  );
+  Uuid::new_v5(&namespace(), name.as_bytes())
+}
@@ -0,0 +1,209 @@
+//! 本文を含めず統計だけをまとめた共有用のレポートを書き出す`export-stats`サブコマンド
+//!
+//! データ本体を配布できない相手にも取得状況を伝えられるよう、件数・年や裁判所ごとの分布・フィールドの充足率・
+//! 本文の長さの分布だけをJSONとMarkdownに書き出す。事件名・事件番号・本文などの個々の判例を特定できる値は含めない。
+
+use crate::{compat, output, permissions};
+use anyhow::Result;
+use jplaw_data_types::{law::Date, listup::PrecedentInfo};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use tokio::fs;
+
+/// 分布を数えるフィールド
+const DISTRIBUTION_FIELDS: &[&str] = &[
+  "trial_type",
+  "court_name",
+  "lawsuit_type",
+  "result_type",
+  "field",
+];
+
+/// 本文の文字数の分布
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LengthStats {
+  pub count: usize,
+  pub min: usize,
+  pub p25: usize,
+  pub median: usize,
+  pub p75: usize,
+  pub p90: usize,
+  pub max: usize,
+  pub mean: f64,
+}
+
+impl LengthStats {
+  fn of(mut lengths: Vec<usize>) -> Self {
+    if lengths.is_empty() {
+      return LengthStats::default();
+    }
+    lengths.sort_unstable();
+    let at = |q: f64| lengths[((lengths.len() - 1) as f64 * q).round() as usize];
+    LengthStats {
+      count: lengths.len(),
+      min: lengths[0],
+      p25: at(0.25),
+      median: at(0.5),
+      p75: at(0.75),
+      p90: at(0.9),
+      max: lengths[lengths.len() - 1],
+      mean: lengths.iter().sum::<usize>() as f64 / lengths.len() as f64,
+    }
+  }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StatsReport {
+  /// 一覧の件数
+  pub records: usize,
+  /// 出力フォルダに裁判例のファイルがあった件数
+  pub record_files: usize,
+  /// 裁判年月日の西暦の年ごとの件数
+  pub by_year: BTreeMap<String, usize>,
+  /// フィールドの値ごとの件数
+  pub distributions: BTreeMap<String, BTreeMap<String, usize>>,
+  /// フィールドごとの、値が空でない裁判例のファイルの割合
+  pub field_coverage: BTreeMap<String, f64>,
+  /// 本文の文字数の分布
+  pub contents_length: LengthStats,
+  /// 警告の種類ごとの件数
+  pub warnings: BTreeMap<String, usize>,
+}
+
+fn is_filled(value: &Value) -> bool {
+  match value {
+    Value::Null => false,
+    Value::String(s) => !s.trim().is_empty(),
+    Value::Array(a) => !a.is_empty(),
+    Value::Object(o) => !o.is_empty(),
+    _ => true,
+  }
+}
+
+fn label(value: Option<&Value>) -> String {
+  match value {
+    None | Some(Value::Null) => "(none)".to_string(),
+    Some(Value::String(s)) => s.trim().to_string(),
+    Some(v) => v.to_string(),
+  }
+}
+
+/// `index`の一覧と`output`の裁判例のファイルから統計をまとめる
+pub async fn collect(index: &str, output: &str) -> Result<StatsReport> {
+  let entries = output::read_value_lst(index).await?;
+  let mut report = StatsReport {
+    records: entries.len(),
+    ..Default::default()
+  };
+  let mut filled: BTreeMap<String, usize> = BTreeMap::new();
+  let mut lengths = Vec::new();
+  for entry in entries {
+    let Value::Object(mut record) = entry else {
+      continue;
+    };
+    if let Some(date) = record.get("date") {
+      let year = serde_json::from_value::<Date>(date.clone())
+        .map(|d| compat::era_to_ad_year(&d.era, d.year).to_string())
+        .unwrap_or_else(|_| "(unknown)".to_string());
+      *report.by_year.entry(year).or_default() += 1;
+    }
+    let file_name = serde_json::from_value::<PrecedentInfo>(Value::Object(record.clone()))
+      .ok()
+      .map(|info| crate::partition::record_name(&info));
+    if let Some(name) = file_name.filter(|name| output::data_exists(output, name)) {
+      if let Value::Object(data) = output::read_record_value(output, &name).await? {
+        report.record_files += 1;
+        for (key, value) in &data {
+          if !key.starts_with('_') {
+            *filled.entry(key.clone()).or_default() += usize::from(is_filled(value));
+          }
+        }
+        if let Some(contents) = data.get("contents").and_then(Value::as_str) {
+          lengths.push(contents.chars().count());
+        }
+        for warning in data
+          .get("warnings")
+          .and_then(Value::as_array)
+          .into_iter()
+          .flatten()
+        {
+          *report
+            .warnings
+            .entry(label(warning.get("kind")))
+            .or_default() += 1;
+        }
+        record.extend(data);
+      }
+    }
+    for field in DISTRIBUTION_FIELDS {
+      *report
+        .distributions
+        .entry(field.to_string())
+        .or_default()
+        .entry(label(record.get(*field)))
+        .or_default() += 1;
+    }
+  }
+  if report.record_files > 0 {
+    report.field_coverage = filled
+      .into_iter()
+      .map(|(key, n)| (key, n as f64 / report.record_files as f64))
+      .collect();
+  }
+  report.contents_length = LengthStats::of(lengths);
+  Ok(report)
+}
+
+/// Markdownの表の1つの値にする
+fn md_cell(s: &str) -> String {
+  s.replace('|', "\\|")
+}
+
+fn markdown(report: &StatsReport) -> String {
+  let mut md = String::from("# listup_precedent stats\n\n");
+  md.push_str(&format!(
+    "- records: {}\n- record files: {}\n\n",
+    report.records, report.record_files
+  ));
+  md.push_str("## by year\n\n| year | count |\n| --- | ---: |\n");
+  for (year, count) in &report.by_year {
+    md.push_str(&format!("| {} | {} |\n", md_cell(year), count));
+  }
+  for (field, counts) in &report.distributions {
+    md.push_str(&format!(
+      "\n## {field}\n\n| value | count |\n| --- | ---: |\n"
+    ));
+    let mut counts = counts.iter().collect::<Vec<_>>();
+    counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    for (value, count) in counts {
+      md.push_str(&format!("| {} | {} |\n", md_cell(value), count));
+    }
+  }
+  md.push_str("\n## field coverage\n\n| field | filled |\n| --- | ---: |\n");
+  for (field, ratio) in &report.field_coverage {
+    md.push_str(&format!("| {} | {:.1}% |\n", md_cell(field), ratio * 100.0));
+  }
+  let l = &report.contents_length;
+  md.push_str(&format!(
+    "\n## contents length (chars)\n\n| count | min | p25 | median | p75 | p90 | max | mean |\n| ---: | ---: | ---: | ---: | ---: | ---: | ---: | ---: |\n| {} | {} | {} | {} | {} | {} | {} | {:.0} |\n",
+    l.count, l.min, l.p25, l.median, l.p75, l.p90, l.max, l.mean
+  ));
+  if !report.warnings.is_empty() {
+    md.push_str("\n## warnings\n\n| kind | count |\n| --- | ---: |\n");
+    for (kind, count) in &report.warnings {
+      md.push_str(&format!("| {} | {} |\n", md_cell(kind), count));
+    }
+  }
+  md
+}
+
+/// 統計をまとめて`json`と`markdown`に書き出す。一覧の件数を返す
+pub async fn export(index: &str, output: &str, json: &str, markdown_path: &str) -> Result<usize> {
+  let report = collect(index, output).await?;
+  fs::write(json, serde_json::to_string_pretty(&report)?).await?;
+  permissions::apply(json).await?;
+  fs::write(markdown_path, markdown(&report)).await?;
+  permissions::apply(markdown_path).await?;
+  Ok(report.records)
+}
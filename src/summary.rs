@@ -0,0 +1,68 @@
+//! 実行の最後に書き出す集計（`run_summary.json`）と、終了の仕方に応じた終了コード
+//!
+//! 件数は`Events`に流れたイベントから数える。
+
+use crate::fetch::CircuitOpen;
+use serde::Serialize;
+
+/// 取得を終えたが、取得に失敗した判例があった
+pub const EXIT_COMPLETED_WITH_FAILURES: i32 = 3;
+/// ネットワークのエラーやサーキットブレーカーで中止した
+pub const EXIT_NETWORK_ABORT: i32 = 4;
+/// ページの解析に失敗して中止した
+pub const EXIT_PARSE_ABORT: i32 = 5;
+/// Ctrl-C・SIGTERMで中断した
+pub const EXIT_INTERRUPTED: i32 = 130;
+
+/// ページの解析に失敗したことを表すエラーの文脈
+#[derive(Debug)]
+pub struct ParseFailure(pub String);
+
+impl std::fmt::Display for ParseFailure {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    write!(f, "ページの解析に失敗しました：{}", self.0)
+  }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct RunSummary {
+  pub records_written: usize,
+  pub records_skipped: usize,
+  pub records_failed: usize,
+  pub pages: usize,
+  /// 一覧ページに載っていた判例の数（複数のページに載ったものは1件と数える）
+  pub unique_links: usize,
+  /// 前のページにも載っていた判例が、後のページに再び載った回数
+  pub duplicate_links: usize,
+  pub duration_millis: u64,
+  /// `completed`・`completed_with_failures`・`network_abort`・`parse_abort`・`interrupted`・`error`のいずれか
+  pub status: String,
+  pub exit_code: i32,
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub error: Option<String>,
+}
+
+impl RunSummary {
+  /// 実行の結果から`status`と`exit_code`を決める
+  pub fn finish(&mut self, res: &anyhow::Result<()>, interrupted: bool) {
+    let (status, exit_code) = match res {
+      Ok(()) if self.records_failed > 0 => {
+        ("completed_with_failures", EXIT_COMPLETED_WITH_FAILURES)
+      }
+      Ok(()) => ("completed", 0),
+      Err(_) if interrupted => ("interrupted", EXIT_INTERRUPTED),
+      Err(e) if e.downcast_ref::<ParseFailure>().is_some() => ("parse_abort", EXIT_PARSE_ABORT),
+      Err(e)
+        if e
+          .chain()
+          .any(|c| c.is::<CircuitOpen>() || c.is::<reqwest::Error>()) =>
+      {
+        ("network_abort", EXIT_NETWORK_ABORT)
+      }
+      Err(_) => ("error", 1),
+    };
+    self.status = status.to_string();
+    self.exit_code = exit_code;
+    self.error = res.as_ref().err().map(|e| format!("{e:#}"));
+  }
+}
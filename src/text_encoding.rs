@@ -0,0 +1,72 @@
+//! `--output-encoding`による、CSVなどのテキストの出力ファイルの文字コードの指定
+//!
+//! UTF-8しか読めない古いシステムに渡すために、Shift_JIS・EUC-JPでも書き出せるようにする。
+//! これらの文字コードに無い文字（旧字体の一部や丸数字以外の記号など）は`&#12345;`の形の数値文字参照になる。
+
+use anyhow::Result;
+use clap::ValueEnum;
+use encoding_rs::{Encoding, EUC_JP, SHIFT_JIS};
+use std::sync::OnceLock;
+use tracing::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputEncoding {
+  Utf8,
+  #[clap(name = "shift_jis")]
+  ShiftJis,
+  #[clap(name = "euc-jp")]
+  EucJp,
+}
+
+static OUTPUT_ENCODING: OnceLock<OutputEncoding> = OnceLock::new();
+
+/// 以降に書き出すテキストの文字コードを設定する。2回目以降の呼び出しでは何もしない
+pub fn set_output_encoding(encoding: OutputEncoding) {
+  let _ = OUTPUT_ENCODING.set(encoding);
+}
+
+pub fn output_encoding() -> OutputEncoding {
+  OUTPUT_ENCODING
+    .get()
+    .copied()
+    .unwrap_or(OutputEncoding::Utf8)
+}
+
+impl OutputEncoding {
+  fn encoding(self) -> Option<&'static Encoding> {
+    match self {
+      OutputEncoding::Utf8 => None,
+      OutputEncoding::ShiftJis => Some(SHIFT_JIS),
+      OutputEncoding::EucJp => Some(EUC_JP),
+    }
+  }
+}
+
+/// `text`を設定された文字コードにする。`path`は表せない文字があったときの警告に使う
+pub fn encode(text: &str, path: &str) -> Vec<u8> {
+  match output_encoding().encoding() {
+    None => text.as_bytes().to_vec(),
+    Some(encoding) => {
+      let (bytes, _, had_unmappable) = encoding.encode(text);
+      if had_unmappable {
+        warn!(
+          "{} cannot represent some characters; written as numeric character references: {}",
+          encoding.name(),
+          path
+        );
+      }
+      bytes.into_owned()
+    }
+  }
+}
+
+/// 設定された文字コードがUTF-8かどうか
+pub fn is_utf8() -> bool {
+  output_encoding() == OutputEncoding::Utf8
+}
+
+/// `text`を設定された文字コードで`path`に書き出す
+pub async fn write(path: &str, text: &str) -> Result<()> {
+  tokio::fs::write(path, encode(text, path)).await?;
+  Ok(())
+}
@@ -0,0 +1,219 @@
+//! サーバーの応答時間とエラー率に応じてリクエスト間隔を自動調整する仕組み
+//!
+//! 応答が遅い・429や5xxが返る・タイムアウトするときは間隔を広げ、応答が速いときは下限の範囲内で間隔を狭める。
+//! 間隔はホストごとに独立して調整し、PDFには`--pdf-sleep-time`などでHTMLとは別の間隔を設定できる。
+
+use std::{
+  collections::HashMap,
+  time::{Duration, Instant},
+};
+use tracing::*;
+
+/// 応答時間の指数移動平均を取るときの直近の値の重み
+const LATENCY_EWMA_WEIGHT: f64 = 0.3;
+
+/// 失敗したときに間隔を広げる倍率
+const BACKOFF_ON_ERROR: f64 = 2.0;
+
+/// 応答が遅かったときに間隔を広げる倍率
+const BACKOFF_ON_SLOW: f64 = 1.5;
+
+/// 応答が速かったときに間隔を狭める倍率
+const SPEEDUP_ON_FAST: f64 = 0.9;
+
+#[derive(Debug, Clone)]
+pub struct Throttle {
+  /// 現在のリクエスト間隔
+  delay: Duration,
+  /// リクエスト間隔の下限
+  min_delay: Duration,
+  /// リクエスト間隔の上限
+  max_delay: Duration,
+  /// これを超える応答時間を「遅い」とみなす閾値
+  target_latency: Duration,
+  /// 応答時間の指数移動平均
+  latency_ewma: Option<Duration>,
+  /// 直前のリクエストが完了した時刻
+  last_request: Option<Instant>,
+  /// 並行して送るリクエストのために予約済みの、次にリクエストを送ってよい時刻
+  next_slot: Option<Instant>,
+}
+
+impl Throttle {
+  pub fn new(
+    initial_delay: Duration,
+    min_delay: Duration,
+    max_delay: Duration,
+    target_latency: Duration,
+  ) -> Self {
+    let max_delay = max_delay.max(min_delay);
+    Throttle {
+      delay: initial_delay.clamp(min_delay, max_delay),
+      min_delay,
+      max_delay,
+      target_latency,
+      latency_ewma: None,
+      last_request: None,
+      next_slot: None,
+    }
+  }
+
+  /// リクエスト間隔の下限を引き上げる（robots.txtのCrawl-delayなど）
+  pub fn raise_min_delay(&mut self, min_delay: Duration) {
+    if min_delay > self.min_delay {
+      self.min_delay = min_delay;
+      self.max_delay = self.max_delay.max(min_delay);
+      self.delay = self.delay.max(min_delay);
+    }
+  }
+
+  /// 現在のリクエスト間隔
+  pub fn delay(&self) -> Duration {
+    self.delay
+  }
+
+  /// 次のリクエストを送る時刻を予約し、それまでに待つべき時間を返す
+  ///
+  /// 並行して呼ばれても、リクエストの送信が現在の間隔ずつずれるようにする
+  pub fn reserve(&mut self) -> Duration {
+    let now = Instant::now();
+    let start = [
+      Some(now),
+      self.last_request.map(|last| last + self.delay),
+      self.next_slot,
+    ]
+    .into_iter()
+    .flatten()
+    .max()
+    .unwrap_or(now);
+    self.next_slot = Some(start + self.delay);
+    start - now
+  }
+
+  /// リクエストの結果を記録し、リクエスト間隔を調整する
+  ///
+  /// `overloaded`はサーバーが混んでいることを示す失敗（429・5xx・タイムアウト）のときに真にする。
+  /// 404のようにページが無いだけの失敗では間隔を広げない
+  pub fn record(&mut self, latency: Duration, overloaded: bool) {
+    self.last_request = Some(Instant::now());
+    let ewma = match self.latency_ewma {
+      Some(prev) => prev.mul_f64(1.0 - LATENCY_EWMA_WEIGHT) + latency.mul_f64(LATENCY_EWMA_WEIGHT),
+      None => latency,
+    };
+    self.latency_ewma = Some(ewma);
+    let factor = if overloaded {
+      BACKOFF_ON_ERROR
+    } else if ewma > self.target_latency {
+      BACKOFF_ON_SLOW
+    } else {
+      SPEEDUP_ON_FAST
+    };
+    let new_delay = self
+      .delay
+      .max(Duration::from_millis(1))
+      .mul_f64(factor)
+      .clamp(self.min_delay, self.max_delay);
+    if new_delay != self.delay {
+      debug!(
+        "throttle: {}ms -> {}ms (latency_ewma: {}ms, overloaded: {})",
+        self.delay.as_millis(),
+        new_delay.as_millis(),
+        ewma.as_millis(),
+        overloaded
+      );
+    }
+    self.delay = new_delay;
+  }
+}
+
+/// リクエストの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RequestKind {
+  Html,
+  Pdf,
+}
+
+/// ホストとリクエストの種類ごとの`Throttle`
+///
+/// 初めてのホストには設定どおりの`Throttle`を作る。PDFの設定が無ければPDFもHTMLと同じ`Throttle`に従う
+#[derive(Debug)]
+pub struct HostThrottles {
+  html: Throttle,
+  pdf: Option<Throttle>,
+  by_host: HashMap<(RequestKind, String), Throttle>,
+}
+
+impl HostThrottles {
+  pub fn new(html: Throttle) -> Self {
+    HostThrottles {
+      html,
+      pdf: None,
+      by_host: HashMap::new(),
+    }
+  }
+
+  /// PDFのリクエストに、HTMLとは別の`throttle`の設定を使う
+  pub fn set_pdf(&mut self, throttle: Throttle) {
+    self.pdf = Some(throttle);
+  }
+
+  /// `host`への`kind`のリクエストの`Throttle`
+  pub fn get(&mut self, kind: RequestKind, host: &str) -> &mut Throttle {
+    let (kind, template) = match (kind, &self.pdf) {
+      (RequestKind::Pdf, Some(pdf)) => (RequestKind::Pdf, pdf),
+      _ => (RequestKind::Html, &self.html),
+    };
+    let template = template.clone();
+    self
+      .by_host
+      .entry((kind, host.to_string()))
+      .or_insert(template)
+  }
+
+  /// すべてのホストのリクエスト間隔の下限を引き上げる
+  pub fn raise_min_delay(&mut self, min_delay: Duration) {
+    self.html.raise_min_delay(min_delay);
+    if let Some(pdf) = &mut self.pdf {
+      pdf.raise_min_delay(min_delay);
+    }
+    for throttle in self.by_host.values_mut() {
+      throttle.raise_min_delay(min_delay);
+    }
+  }
+
+  /// ホストごとのリクエスト間隔のうち最も長いもの
+  pub fn max_delay(&self) -> Duration {
+    self
+      .by_host
+      .values()
+      .map(|t| t.delay())
+      .max()
+      .unwrap_or_else(|| self.html.delay())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn throttle() -> Throttle {
+    Throttle::new(
+      Duration::from_millis(1000),
+      Duration::from_millis(100),
+      Duration::from_secs(60),
+      Duration::from_secs(5),
+    )
+  }
+
+  #[test]
+  fn backs_off_only_when_overloaded() {
+    let mut t = throttle();
+    t.record(Duration::from_millis(200), true);
+    assert_eq!(t.delay(), Duration::from_millis(2000));
+
+    // ページが無いだけの失敗は速い応答として扱う
+    let mut t = throttle();
+    t.record(Duration::from_millis(200), false);
+    assert!(t.delay() < Duration::from_millis(1000));
+  }
+}
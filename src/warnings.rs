@@ -0,0 +1,53 @@
+//! 書き出しはできたが完全ではない判例に付ける警告
+//!
+//! 警告があれば各裁判例のJSONに`warnings`フィールドとして書き出す。
+//! `warnings`が無い判例だけを選べば、下流では必須の項目と本文がそろった判例だけを扱える。
+
+use jplaw_data_types::listup::PrecedentData;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Warning {
+  /// 必須の項目が空だった
+  MissingField { field: &'static str },
+  /// 本文を取得できなかった
+  NoContents { reason: String },
+}
+
+/// 判例のデータと付け加える項目から警告を集める。`pdf_error`はPDFの取得に失敗したときのエラー
+pub fn collect(
+  data: &PrecedentData,
+  extra: &Map<String, Value>,
+  pdf_error: Option<&anyhow::Error>,
+) -> Vec<Warning> {
+  let mut warnings = [
+    ("case_number", &data.case_number),
+    ("case_name", &data.case_name),
+    ("court_name", &data.court_name),
+    ("full_pdf_link", &data.full_pdf_link),
+  ]
+  .into_iter()
+  .filter(|(_, value)| value.trim().is_empty())
+  .map(|(field, _)| Warning::MissingField { field })
+  .collect::<Vec<_>>();
+  // `--no-contents`で取得しなかった本文は警告しない
+  let skipped = extra
+    .get("contents_unavailable")
+    .and_then(|u| u.get("reason"))
+    .is_some_and(|r| r == "skipped");
+  if data.contents.is_none() && !skipped {
+    let reason = match (pdf_error, extra.get("contents_unavailable")) {
+      (Some(e), _) => format!("{e:#}"),
+      (None, Some(unavailable)) => unavailable
+        .get("reason")
+        .and_then(|r| r.as_str())
+        .unwrap_or_default()
+        .to_string(),
+      (None, None) => "no_pdf".to_string(),
+    };
+    warnings.push(Warning::NoContents { reason });
+  }
+  warnings
+}
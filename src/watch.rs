@@ -0,0 +1,101 @@
+//! 出力フォルダを監視し、裁判例のファイルが手で追加・削除・編集されたら一覧と検索用のインデックスを作り直す`watch`サブコマンド
+//!
+//! `--interval`秒ごとに出力フォルダの裁判例のファイルの名前・大きさ・更新日時を調べ、
+//! 前回作り直したときから変わっていて、かつ1回分の間隔のあいだ変化が止まっていれば作り直す。
+//! 一覧は出力フォルダにある裁判例のファイルだけから作るので、ファイルを消した裁判例は一覧からも消える。
+//! `--index-sqlite`・`--index-arrow`・`--court-stats`も与えておけば、あわせて作り直す。
+//! 取得の実行が出力フォルダのロックを持っている間は作り直さず、次の確認まで待つ。
+
+use crate::{
+  compat,
+  lock::OutputLock,
+  output::{self, IndexWriter},
+  record, repair, shutdown, Args,
+};
+use anyhow::Result;
+use jplaw_data_types::listup::PrecedentData;
+use std::{path::PathBuf, time::Duration, time::SystemTime};
+use tokio::fs;
+use tracing::*;
+
+/// 裁判例のファイルの名前・大きさ・更新日時
+type Snapshot = Vec<(PathBuf, u64, Option<SystemTime>)>;
+
+async fn snapshot(args: &Args) -> Result<Snapshot> {
+  let mut files = Vec::new();
+  for path in repair::record_files(args).await? {
+    // 調べている間に消されたファイルは無かったことにする
+    let Ok(metadata) = fs::metadata(&path).await else {
+      continue;
+    };
+    files.push((path, metadata.len(), metadata.modified().ok()));
+  }
+  Ok(files)
+}
+
+/// 出力フォルダの裁判例のファイルから一覧を作り直す。一覧に載せた件数を返す
+async fn reindex(args: &Args, files: &Snapshot) -> Result<usize> {
+  let compat_index = args.compat.map(|version| {
+    args
+      .compat_index
+      .clone()
+      .unwrap_or_else(|| compat::gen_compat_index_path(&args.index, version))
+  });
+  let index_writer = IndexWriter::open(
+    &args.index,
+    args.index_format,
+    args.compat,
+    compat_index.as_deref(),
+    false,
+  )
+  .await?;
+  let mut len = 0;
+  for (path, _, _) in files {
+    let data = match fs::read(path)
+      .await
+      .map_err(anyhow::Error::from)
+      .and_then(|bytes| {
+        let value = output::decode_record_bytes(bytes)?;
+        Ok(serde_json::from_value::<PrecedentData>(value)?)
+      }) {
+      Ok(data) => data,
+      Err(e) => {
+        warn!("skipped unreadable record: {}: {e:#}", path.display());
+        continue;
+      }
+    };
+    index_writer
+      .write(&record::precedent_info_of(&data), &data)
+      .await?;
+    len += 1;
+  }
+  index_writer.flush().await?;
+  crate::write_index_exports(args).await?;
+  Ok(len)
+}
+
+/// 終了が要求されるまで出力フォルダを監視し、変化があれば一覧を作り直す
+pub async fn watch(args: &Args, interval: Duration) -> Result<()> {
+  info!("[START] watch: {}", args.output);
+  let mut indexed: Option<Snapshot> = None;
+  let mut previous = snapshot(args).await?;
+  while !shutdown::requested() {
+    tokio::time::sleep(interval).await;
+    let current = snapshot(args).await?;
+    // 変化が続いている間は書き込みの途中かもしれないので待つ
+    let settled = current == previous;
+    previous = current;
+    if !settled || indexed.as_ref() == Some(&previous) {
+      continue;
+    }
+    let Ok(_lock) = OutputLock::acquire(&args.output) else {
+      info!("output is locked by another instance; reindex postponed");
+      continue;
+    };
+    let len = reindex(args, &previous).await?;
+    info!("reindexed: {} ({} entries)", args.index, len);
+    indexed = Some(previous.clone());
+  }
+  info!("[END] watch");
+  Ok(())
+}